@@ -34,6 +34,8 @@ formatting = function (hljs) {
     "window",
     "concat",
     "union",
+    "intersect",
+    "remove",
     "prql",
   ];
   const BUILTIN_FUNCTIONS = ["switch", "in", "as"];
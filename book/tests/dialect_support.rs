@@ -0,0 +1,126 @@
+#![cfg(not(target_family = "wasm"))]
+/// This test:
+/// - Compiles a representative query of each feature in [FEATURES] for every
+///   target.
+/// - Records whether the target supports the feature, emulates it (and warns
+///   about the emulation), or rejects it.
+/// - Writes the matrix as JSON to `src/dialect-support.json`, for rendering
+///   the dialect support tables of the book, and snapshots it, so a change of
+///   the support of a feature shows up in review.
+use std::fs;
+
+use insta::assert_snapshot;
+use prql_compiler::{compile_with_notes, sql};
+use serde_json::{json, Map, Value};
+
+/// Features, each with a query that uses it and as little else as possible.
+const FEATURES: &[(&str, &str)] = &[
+    ("take", "from employees | take 10"),
+    ("take with offset", "from employees | sort id | take 11..20"),
+    (
+        "take a percentage",
+        "from employees | sort salary | take 10%",
+    ),
+    ("sample rows", "from events | sample 1000"),
+    ("sample a percentage", "from events | sample 10%"),
+    (
+        "full join",
+        "from orders | join side:full customers [orders.customer_id == customers.id]",
+    ),
+    (
+        "right join",
+        "from orders | join side:right customers [orders.customer_id == customers.id]",
+    ),
+    (
+        "append",
+        "from employees | select [id] | append (from managers | select [id])",
+    ),
+    (
+        "intersect",
+        "from employees | select [id] | intersect (from managers | select [id])",
+    ),
+    (
+        "remove",
+        "from employees | select [id] | remove (from managers | select [id])",
+    ),
+    (
+        "group with rollup",
+        "from sales | group region rollup:true (aggregate [n = count])",
+    ),
+    (
+        "first row of each group",
+        "from employees | group department (sort hired_at | take 1)",
+    ),
+    (
+        "window function without sort",
+        "from employees | derive prev_salary = lag 1 salary",
+    ),
+    ("sort with nulls", "from orders | sort nulls:last [amount]"),
+    ("exclude columns", "from employees | select ![password]"),
+    (
+        "quantified comparison",
+        "from employees | filter salary > all (from benchmarks | select p99)",
+    ),
+    (
+        "f-string",
+        r#"from employees | derive name = f"{first_name} {last_name}""#,
+    ),
+    (
+        "average",
+        "from employees | aggregate [avg_salary = average salary]",
+    ),
+    (
+        "format_date",
+        r#"from orders | derive ym = format_date order_date "%Y-%m""#,
+    ),
+    (
+        "string functions",
+        "from employees | derive [name = upper (trim first_name), n = length name, initial = substring 1 1 last_name]",
+    ),
+    (
+        "loop",
+        "from ([[n], [1]]) | loop (filter n < 4 | select n = n + 1)",
+    ),
+    ("date literal", "from orders | filter created_at > @2020-01-01"),
+    (
+        "regex",
+        r#"from employees | filter last_name ~= "^Smith""#,
+    ),
+];
+
+#[test]
+fn dialect_support() {
+    let json = serde_json::to_string_pretty(&support_matrix()).unwrap();
+
+    fs::write("src/dialect-support.json", format!("{json}\n")).unwrap();
+
+    assert_snapshot!("dialect_support", json);
+}
+
+fn support_matrix() -> Value {
+    let targets = sql::Target::all();
+
+    let features = FEATURES.iter().map(|(name, prql)| {
+        let support: Map<_, _> = (targets.iter())
+            .map(|target| (target.to_string(), json!(support_of(prql, target))))
+            .collect();
+        json!({ "name": name, "prql": prql, "support": support })
+    });
+
+    json!({
+        "targets": targets.iter().map(|t| t.to_string()).collect::<Vec<_>>(),
+        "features": features.collect::<Vec<_>>(),
+    })
+}
+
+/// Whether the target supports the query, emulates a part of it or cannot
+/// express it at all.
+fn support_of(prql: &str, target: &sql::Target) -> &'static str {
+    let options = (sql::Options::default().no_signature()).with_target(target.clone());
+    match compile_with_notes(prql, Some(options)) {
+        // warnings that are not specific to the target are about the query
+        Ok((_, notes)) if notes.warnings.iter().any(|w| w.target.is_some()) => "emulated",
+        Ok(_) => "supported",
+        Err(_) => "error",
+    }
+}
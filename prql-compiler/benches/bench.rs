@@ -21,8 +21,23 @@ cfg_if::cfg_if! {
             compile(CONTENT, None)
         }
 
+        // checks for cancellation, which should not be measurably slower
+        fn compile_query_cancellable() -> Result<String, ErrorMessages> {
+            let cancellation = Cancellation::with_timeout(std::time::Duration::from_secs(60));
+            compile(CONTENT, sql::Options::default().with_cancellation(cancellation).some())
+        }
+
+        // only parses, so should be much faster than compiling
+        fn check_query() -> Result<(), Vec<Diagnostic>> {
+            check(CONTENT)
+        }
+
         fn criterion_benchmark(c: &mut Criterion) {
             c.bench_function("variables-query", |b| b.iter(compile_query));
+            c.bench_function("variables-query-cancellable", |b| {
+                b.iter(compile_query_cancellable)
+            });
+            c.bench_function("variables-check", |b| b.iter(check_query));
         }
 
         criterion_group!(benches, criterion_benchmark);
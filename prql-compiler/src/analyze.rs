@@ -0,0 +1,246 @@
+//! Cheap statistics of a query, for tooling that needs to estimate the cost
+//! of compiling many queries without compiling them.
+//!
+//! The query is resolved into RQ, so transforms are counted after
+//! normalization (i.e. `derive` and `select` both produce `compute`s) and
+//! functions of the standard library are reported after user functions have
+//! been inlined. User functions and s-strings are looked up in PL instead,
+//! because they are gone from RQ or also come from the standard library.
+//! Nothing is translated into SQL.
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::ast::pl::{self, fold::AstFold, Stmt, StmtKind, TableExternRef};
+use crate::ast::rq::{self, fold_transform, Query, RelationKind, RqFold, Transform};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Analysis {
+    /// Number of transforms of each kind, over all pipelines of the query.
+    pub transforms: BTreeMap<String, usize>,
+
+    /// Functions of the standard library that are called, without the `std.`
+    /// prefix.
+    pub std_functions: BTreeSet<String>,
+
+    /// Functions declared in the query that are referenced.
+    pub user_functions: BTreeSet<String>,
+
+    /// Names of the tables that the query reads from.
+    pub source_tables: BTreeSet<String>,
+
+    pub uses_window_functions: bool,
+
+    pub uses_s_strings: bool,
+
+    /// Number of queries (CTEs and the main query) that the query is split
+    /// into when translating to SQL.
+    pub atomic_queries: usize,
+}
+
+/// Resolves the statements and analyzes the resulting query.
+pub fn analyze_statements(statements: Vec<Stmt>) -> Result<Analysis> {
+    let collector = PlCollector::collect(&statements)?;
+    let user_functions = referenced_functions(&statements, &collector);
+
+    let query = crate::semantic::resolve(statements)?;
+
+    let mut analysis = Analysis {
+        user_functions,
+        uses_s_strings: collector.has_s_strings,
+        ..Analysis::default()
+    };
+    analysis.source_tables = source_tables(&query);
+
+    let query = analysis.fold_query(query)?;
+    analysis.atomic_queries = crate::sql::count_atomic_queries(query)?;
+
+    Ok(analysis)
+}
+
+/// Names of function declarations that are referenced by other statements or
+/// declarations.
+fn referenced_functions(statements: &[Stmt], collector: &PlCollector) -> BTreeSet<String> {
+    (statements.iter())
+        .filter_map(|s| s.kind.as_func_def())
+        .map(|f| &f.name)
+        .filter(|name| collector.names.contains(*name))
+        .cloned()
+        .collect()
+}
+
+fn source_tables(query: &Query) -> BTreeSet<String> {
+    (query.tables.iter())
+        .filter_map(|decl| match &decl.relation.kind {
+            RelationKind::ExternRef(TableExternRef::LocalTable(name)) => Some(name.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+impl RqFold for Analysis {
+    fn fold_transform(&mut self, transform: Transform) -> Result<Transform> {
+        let kind = transform.as_ref().to_lowercase();
+        *self.transforms.entry(kind).or_default() += 1;
+
+        if let Transform::Compute(compute) = &transform {
+            self.uses_window_functions |= compute.window.is_some();
+        }
+
+        fold_transform(self, transform)
+    }
+
+    fn fold_expr_kind(&mut self, kind: rq::ExprKind) -> Result<rq::ExprKind> {
+        if let rq::ExprKind::BuiltInFunction { name, .. } = &kind {
+            let name = name.strip_prefix("std.").unwrap_or(name);
+            self.std_functions.insert(name.to_string());
+        }
+
+        rq::fold_expr_kind(self, kind)
+    }
+}
+
+/// Collects names that are referenced and whether s-strings are used, over
+/// statements of the query. Names of the declarations themselves are skipped,
+/// so a function is not referenced just by being declared.
+#[derive(Default)]
+struct PlCollector {
+    names: HashSet<String>,
+    has_s_strings: bool,
+}
+
+impl PlCollector {
+    fn collect(statements: &[Stmt]) -> Result<Self> {
+        let mut collector = PlCollector::default();
+        for stmt in statements {
+            if let StmtKind::FuncDef(func_def) = &stmt.kind {
+                collector.fold_expr(*func_def.body.clone())?;
+            } else {
+                collector.fold_stmt(stmt.clone())?;
+            }
+        }
+        Ok(collector)
+    }
+}
+
+impl AstFold for PlCollector {
+    fn fold_expr_kind(&mut self, kind: pl::ExprKind) -> Result<pl::ExprKind> {
+        match &kind {
+            pl::ExprKind::Ident(ident) if ident.path.is_empty() => {
+                self.names.insert(ident.name.clone());
+            }
+            pl::ExprKind::SString(_) => self.has_s_strings = true,
+            _ => {}
+        }
+
+        pl::fold::fold_expr_kind(self, kind)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::assert_yaml_snapshot;
+
+    use super::*;
+    use crate::parser::parse;
+
+    fn analyze(prql: &str) -> Analysis {
+        analyze_statements(parse(prql).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_window_and_user_function() {
+        assert_yaml_snapshot!(analyze(r#"
+        func add_bonus salary -> salary + 100
+
+        from employees
+        filter department == "sales"
+        sort hired_at
+        derive [gross = add_bonus salary, prev = lag 1 salary]
+        "#), @r###"
+        ---
+        transforms:
+          compute: 2
+          filter: 1
+          from: 1
+          select: 1
+          sort: 1
+        std_functions:
+          - lag
+        user_functions:
+          - add_bonus
+        source_tables:
+          - employees
+        uses_window_functions: true
+        uses_s_strings: false
+        atomic_queries: 1
+        "###);
+    }
+
+    #[test]
+    fn test_aggregation_after_take() {
+        assert_yaml_snapshot!(analyze(r#"
+        from orders
+        derive [year = s"EXTRACT(YEAR FROM {order_date})"]
+        take 1000
+        group year (
+          aggregate [total = sum amount, n = count]
+        )
+        sort year
+        "#), @r###"
+        ---
+        transforms:
+          aggregate: 1
+          compute: 3
+          from: 1
+          select: 1
+          sort: 1
+          take: 1
+        std_functions:
+          - count
+          - sum
+        user_functions: []
+        source_tables:
+          - orders
+        uses_window_functions: false
+        uses_s_strings: true
+        atomic_queries: 2
+        "###);
+    }
+
+    #[test]
+    fn test_nested_tables() {
+        assert_yaml_snapshot!(analyze(r#"
+        table high_earners = (
+          from employees
+          filter salary > 100000
+        )
+
+        table managers = (
+          from high_earners
+          join departments [==department_id]
+          select [high_earners.name, departments.title]
+        )
+
+        from managers
+        take 10
+        "#), @r###"
+        ---
+        transforms:
+          filter: 1
+          from: 3
+          join: 1
+          select: 4
+          take: 1
+        std_functions: []
+        user_functions: []
+        source_tables:
+          - departments
+          - employees
+        uses_window_functions: false
+        uses_s_strings: false
+        atomic_queries: 3
+        "###);
+    }
+}
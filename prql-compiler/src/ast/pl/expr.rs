@@ -34,6 +34,11 @@ pub struct Expr {
     #[serde(skip)]
     pub needs_window: bool,
 
+    /// Set for calls of ranking functions with `over:()`, which ignore the sort
+    /// of the pipeline.
+    #[serde(skip)]
+    pub ignores_sort: bool,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub alias: Option<String>,
 }
@@ -64,6 +69,12 @@ pub enum ExprKind {
         name: String,
         args: Vec<Expr>,
     },
+    /// `all (...)` or `any (...)` on the right of a comparison.
+    /// The pipeline must produce a single column.
+    Quantified {
+        quantifier: Quantifier,
+        pipeline: Box<Expr>,
+    },
 }
 
 impl ExprKind {
@@ -101,6 +112,8 @@ pub enum BinOp {
     Gte,
     #[strum(to_string = "<=")]
     Lte,
+    #[strum(to_string = "~=")]
+    RegexSearch,
     #[strum(to_string = "and")]
     And,
     #[strum(to_string = "or")]
@@ -119,6 +132,16 @@ pub enum UnOp {
     EqSelf,
 }
 
+#[derive(
+    Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, strum::Display, strum::EnumString,
+)]
+pub enum Quantifier {
+    #[strum(to_string = "all")]
+    All,
+    #[strum(to_string = "any")]
+    Any,
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ListItem(pub Expr);
 
@@ -268,17 +291,32 @@ pub enum TransformKind {
     Select {
         assigns: Vec<Expr>,
     },
+    /// Keeps all columns, except the listed ones, as in `select ![a, b]`.
+    Exclude {
+        columns: Vec<Expr>,
+    },
     Filter {
         filter: Box<Expr>,
     },
     Aggregate {
         assigns: Vec<Expr>,
+        #[serde(default, skip_serializing_if = "GroupingKind::is_plain")]
+        grouping: GroupingKind,
     },
     Sort {
         by: Vec<ColumnSort<Expr>>,
     },
     Take {
         range: Range,
+        /// Whether the end of the range is a percentage of rows.
+        #[serde(default, skip_serializing_if = "is_false")]
+        percent: bool,
+    },
+    /// A random sample of rows, either a number of them or a percentage.
+    Sample {
+        size: i64,
+        #[serde(default, skip_serializing_if = "is_false")]
+        percent: bool,
     },
     Join {
         side: JoinSide,
@@ -288,6 +326,8 @@ pub enum TransformKind {
     Group {
         by: Vec<Expr>,
         pipeline: Box<Expr>,
+        #[serde(default, skip_serializing_if = "GroupingKind::is_plain")]
+        grouping: GroupingKind,
     },
     Window {
         kind: WindowKind,
@@ -295,6 +335,13 @@ pub enum TransformKind {
         pipeline: Box<Expr>,
     },
     Concat(Box<Expr>),
+    Intersect(Box<Expr>),
+    Remove(Box<Expr>),
+    Unique,
+    /// Applies the pipeline to the relation repeatedly, each time to the rows
+    /// produced by the previous iteration, and appends the results, until no
+    /// more rows are produced.
+    Loop(Box<Expr>),
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -309,12 +356,43 @@ pub enum TableExternRef {
     // TODO: add other sources such as files, URLs
 }
 
+/// How the keys of a group are combined into grouping sets of an aggregation.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum GroupingKind {
+    /// A single grouping set of all keys.
+    Plain,
+    /// Grouping sets of all prefixes of the keys, down to the grand total.
+    Rollup,
+    /// Grouping sets of all subsets of the keys.
+    Cube,
+    /// Explicit grouping sets, each listing positions of keys.
+    Sets(Vec<Vec<usize>>),
+}
+
+impl Default for GroupingKind {
+    fn default() -> Self {
+        GroupingKind::Plain
+    }
+}
+
+impl GroupingKind {
+    pub fn is_plain(&self) -> bool {
+        matches!(self, GroupingKind::Plain)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum JoinSide {
     Inner,
     Left,
     Right,
     Full,
+    /// Rows of the left relation that have a match in the right relation.
+    /// Columns of the right relation are not added to the frame.
+    Semi,
+    /// Rows of the left relation that have no match in the right relation.
+    /// Columns of the right relation are not added to the frame.
+    Anti,
 }
 
 impl Expr {
@@ -367,6 +445,7 @@ impl From<ExprKind> for Expr {
             target_id: None,
             ty: None,
             needs_window: false,
+            ignores_sort: false,
             alias: None,
         }
     }
@@ -539,6 +618,13 @@ impl Display for Expr {
             ExprKind::BuiltInFunction { .. } => {
                 f.write_str("<built-in>")?;
             }
+            ExprKind::Quantified {
+                quantifier,
+                pipeline,
+            } => match pipeline.kind {
+                ExprKind::Pipeline(_) => write!(f, "{quantifier} {pipeline}")?,
+                _ => write!(f, "{quantifier} ({pipeline})")?,
+            },
         }
 
         Ok(())
@@ -561,3 +647,7 @@ fn display_interpolation(
     f.write_char('"')?;
     Ok(())
 }
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
@@ -40,6 +40,13 @@ pub trait AstFold {
         Ok(TableDef {
             name: table.name,
             value: Box::new(self.fold_expr(*table.value)?),
+            materialized: table.materialized,
+        })
+    }
+    fn fold_var_def(&mut self, var_def: VarDef) -> Result<VarDef> {
+        Ok(VarDef {
+            name: var_def.name,
+            value: Box::new(self.fold_expr(*var_def.value)?),
         })
     }
     fn fold_pipeline(&mut self, pipeline: Pipeline) -> Result<Pipeline> {
@@ -111,6 +118,13 @@ pub fn fold_expr_kind<T: ?Sized + AstFold>(fold: &mut T, expr_kind: ExprKind) ->
             name,
             args: fold.fold_exprs(args)?,
         },
+        Quantified {
+            quantifier,
+            pipeline,
+        } => Quantified {
+            quantifier,
+            pipeline: Box::new(fold.fold_expr(*pipeline)?),
+        },
 
         // None of these capture variables, so we don't need to fold them.
         Literal(_) => expr_kind,
@@ -122,8 +136,9 @@ pub fn fold_stmt_kind<T: ?Sized + AstFold>(fold: &mut T, stmt_kind: StmtKind) ->
     Ok(match stmt_kind {
         FuncDef(func) => FuncDef(fold.fold_func_def(func)?),
         TableDef(table) => TableDef(fold.fold_table(table)?),
+        VarDef(var_def) => VarDef(fold.fold_var_def(var_def)?),
         Main(expr) => Main(Box::new(fold.fold_expr(*expr)?)),
-        QueryDef(_) => stmt_kind,
+        QueryDef(_) | Expect(_) => stmt_kind,
     })
 }
 
@@ -190,6 +205,7 @@ pub fn fold_column_sort<T: ?Sized + AstFold>(
     Ok(ColumnSort {
         direction: sort_column.direction,
         column: fold.fold_expr(sort_column.column)?,
+        nulls: sort_column.nulls,
     })
 }
 
@@ -230,11 +246,15 @@ pub fn fold_transform_kind<T: ?Sized + AstFold>(
         Select { assigns } => Select {
             assigns: fold.fold_exprs(assigns)?,
         },
+        Exclude { columns } => Exclude {
+            columns: fold.fold_exprs(columns)?,
+        },
         Filter { filter } => Filter {
             filter: Box::new(fold.fold_expr(*filter)?),
         },
-        Aggregate { assigns } => Aggregate {
+        Aggregate { assigns, grouping } => Aggregate {
             assigns: fold.fold_exprs(assigns)?,
+            grouping,
         },
         Sort { by } => Sort {
             by: by
@@ -242,18 +262,29 @@ pub fn fold_transform_kind<T: ?Sized + AstFold>(
                 .map(|s| fold_column_sort(fold, s))
                 .try_collect()?,
         },
-        Take { range } => Take {
+        Take { range, percent } => Take {
             range: fold_range(fold, range)?,
+            percent,
         },
+        Sample { size, percent } => Sample { size, percent },
         Join { side, with, filter } => Join {
             side,
             with: Box::new(fold.fold_expr(*with)?),
             filter: Box::new(fold.fold_expr(*filter)?),
         },
         Concat(bottom) => Concat(Box::new(fold.fold_expr(*bottom)?)),
-        Group { by, pipeline } => Group {
+        Intersect(bottom) => Intersect(Box::new(fold.fold_expr(*bottom)?)),
+        Remove(bottom) => Remove(Box::new(fold.fold_expr(*bottom)?)),
+        Unique => Unique,
+        Loop(pipeline) => Loop(Box::new(fold.fold_expr(*pipeline)?)),
+        Group {
+            by,
+            pipeline,
+            grouping,
+        } => Group {
             by: fold.fold_exprs(by)?,
             pipeline: Box::new(fold.fold_expr(*pipeline)?),
+            grouping,
         },
         Window {
             kind,
@@ -286,6 +317,7 @@ pub fn fold_func_def<T: ?Sized + AstFold>(fold: &mut T, func_def: FuncDef) -> Re
         named_params: fold_func_param(fold, func_def.named_params)?,
         body: Box::new(fold.fold_expr(*func_def.body)?),
         return_ty: func_def.return_ty,
+        is_override: func_def.is_override,
     })
 }
 
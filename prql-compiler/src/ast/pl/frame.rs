@@ -13,6 +13,13 @@ pub struct Frame {
     pub columns: Vec<FrameColumn>,
 
     pub inputs: Vec<FrameInput>,
+
+    /// Columns of inputs of a join that are merged into the column of the
+    /// same name of the other input, which the join condition requires them
+    /// to be equal to. For example `salaries.emp_no` after
+    /// `join salaries [==emp_no]`. Unqualified names refer to the other column.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merged: Vec<Ident>,
 }
 
 #[derive(Clone, Eq, Debug, PartialEq, Serialize, Deserialize)]
@@ -46,6 +53,10 @@ pub enum FrameColumn {
 pub struct ColumnSort<T = Expr> {
     pub direction: SortDirection,
     pub column: T,
+
+    /// Placement of NULLs. When not set, it is left to the database.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nulls: Option<NullsOrder>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -54,6 +65,12 @@ pub enum SortDirection {
     Desc,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum NullsOrder {
+    First,
+    Last,
+}
+
 impl Default for SortDirection {
     fn default() -> Self {
         SortDirection::Asc
@@ -101,6 +118,24 @@ impl Frame {
         }
     }
 
+    /// Renames inputs of the frame, so all of its columns can be referenced
+    /// as `alias.column`. Used for relations that are given an alias.
+    pub fn rename(&mut self, alias: &str) {
+        self.merged.clear();
+        for input in &mut self.inputs {
+            input.name = alias.to_string();
+        }
+        for column in &mut self.columns {
+            match column {
+                FrameColumn::Wildcard { input_name } => *input_name = alias.to_string(),
+                FrameColumn::Single {
+                    name: Some(name), ..
+                } => name.path = vec![alias.to_string()],
+                FrameColumn::Single { name: None, .. } => {}
+            }
+        }
+    }
+
     pub fn find_input(&self, input_name: &str) -> Option<&FrameInput> {
         self.inputs.iter().find(|i| i.name == input_name)
     }
@@ -1,4 +1,5 @@
 use anyhow::anyhow;
+use itertools::Itertools;
 use std::fmt::Display;
 
 use enum_as_inner::EnumAsInner;
@@ -15,6 +16,7 @@ pub enum Literal {
     Time(String),
     Timestamp(String),
     ValueAndUnit(ValueAndUnit),
+    Relation(RelationLiteral),
 }
 
 // Compound units, such as "2 days 3 hours" can be represented as `2days + 3hours`
@@ -24,6 +26,14 @@ pub struct ValueAndUnit {
     pub unit: String, // Could be an enum IntervalType,
 }
 
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct RelationLiteral {
+    /// Column names
+    pub columns: Vec<String>,
+    /// Row-oriented data
+    pub rows: Vec<Vec<Literal>>,
+}
+
 impl From<Literal> for anyhow::Error {
     fn from(item: Literal) -> Self {
         anyhow!("Failed to convert `{item}`")
@@ -79,6 +89,14 @@ impl Display for Literal {
             Literal::ValueAndUnit(i) => {
                 write!(f, "{}{}", i.n, i.unit)?;
             }
+
+            Literal::Relation(rel) => {
+                write!(f, "[[{}]", rel.columns.join(", "))?;
+                for row in &rel.rows {
+                    write!(f, ", [{}]", row.iter().join(", "))?;
+                }
+                f.write_str("]")?;
+            }
         }
         Ok(())
     }
@@ -27,7 +27,9 @@ pub enum StmtKind {
     QueryDef(QueryDef),
     FuncDef(FuncDef),
     TableDef(TableDef),
+    VarDef(VarDef),
     Main(Box<Expr>),
+    Expect(Expect),
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize, Default)]
@@ -45,6 +47,14 @@ pub struct FuncDef {
     pub named_params: Vec<FuncParam>,      // named expr
     pub body: Box<Expr>,
     pub return_ty: Option<Ty>,
+
+    /// Declared with `override`, which means it deliberately shadows a std function.
+    #[serde(skip_serializing_if = "is_false", default)]
+    pub is_override: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -61,6 +71,25 @@ pub struct FuncParam {
 pub struct TableDef {
     pub name: String,
     pub value: Box<Expr>,
+
+    /// Set by `@materialized` (true) or `@not_materialized` (false).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub materialized: Option<bool>,
+}
+
+/// Declaration of a constant. Its value is folded at compile time and
+/// substituted wherever it is referenced.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct VarDef {
+    pub name: String,
+    pub value: Box<Expr>,
+}
+
+/// Columns that the main pipeline is expected to produce, as in
+/// `expect columns [a, b]`. Checked after resolution; the order is not checked.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Expect {
+    pub columns: Vec<String>,
 }
 
 impl From<StmtKind> for Stmt {
@@ -119,13 +148,24 @@ impl Display for StmtKind {
                 let pipeline = &table.value;
                 match &pipeline.kind {
                     ExprKind::FuncCall(_) => {
-                        write!(f, "table {} = (\n  {pipeline}\n)\n\n", table.name)?;
+                        write!(f, "table {} = (\n  {pipeline}\n)", table.name)?;
                     }
 
                     _ => {
-                        write!(f, "table {} = {pipeline}\n\n", table.name)?;
+                        write!(f, "table {} = {pipeline}", table.name)?;
                     }
                 };
+                match table.materialized {
+                    Some(true) => write!(f, " @materialized\n\n")?,
+                    Some(false) => write!(f, " @not_materialized\n\n")?,
+                    None => write!(f, "\n\n")?,
+                }
+            }
+            StmtKind::VarDef(var_def) => {
+                write!(f, "let {} = {}\n\n", var_def.name, var_def.value)?;
+            }
+            StmtKind::Expect(expect) => {
+                write!(f, "expect columns [{}]\n\n", expect.columns.join(", "))?;
             }
         }
         Ok(())
@@ -134,6 +174,9 @@ impl Display for StmtKind {
 
 impl Display for FuncDef {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_override {
+            write!(f, "override ")?;
+        }
         write!(f, "func {}", self.name)?;
         for arg in &self.positional_params {
             write!(f, " {}", arg.name)?;
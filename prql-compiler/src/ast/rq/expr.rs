@@ -1,8 +1,8 @@
 use enum_as_inner::EnumAsInner;
 use serde::{Deserialize, Serialize};
 
-use super::super::pl::{BinOp, InterpolateItem, Literal, SwitchCase};
-use super::CId;
+use super::super::pl::{BinOp, InterpolateItem, Literal, Quantifier, SwitchCase};
+use super::{CId, TableRef};
 use crate::error::Span;
 
 /// Analogous to [crate::ast::pl::Expr], but with less kinds.
@@ -32,6 +32,11 @@ pub enum ExprKind {
         name: String,
         args: Vec<Expr>,
     },
+    /// Right side of a comparison with every value of a single-column relation.
+    Quantified {
+        quantifier: Quantifier,
+        relation: TableRef,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -91,6 +91,7 @@ pub fn fold_table<F: ?Sized + RqFold>(fold: &mut F, t: TableDecl) -> Result<Tabl
         id: t.id,
         name: t.name,
         relation: fold.fold_relation(t.relation)?,
+        materialized: t.materialized,
     })
 }
 
@@ -101,6 +102,7 @@ pub fn fold_relation<F: ?Sized + RqFold>(
     Ok(Relation {
         kind: fold.fold_relation_kind(relation.kind)?,
         columns: relation.columns,
+        span: relation.span,
     })
 }
 
@@ -168,26 +170,37 @@ pub fn fold_transform<T: ?Sized + RqFold>(
         From(tid) => From(fold.fold_table_ref(tid)?),
 
         Compute(compute) => Compute(fold.fold_compute(compute)?),
-        Aggregate { partition, compute } => Aggregate {
+        Aggregate {
+            partition,
+            compute,
+            grouping,
+        } => Aggregate {
             partition: fold_cids(fold, partition)?,
             compute: fold_cids(fold, compute)?,
+            grouping,
         },
 
         Select(ids) => Select(fold_cids(fold, ids)?),
+        Exclude(ids) => Exclude(fold_cids(fold, ids)?),
         Filter(i) => Filter(fold.fold_expr(i)?),
         Sort(sorts) => Sort(fold_column_sorts(fold, sorts)?),
         Take(take) => Take(super::Take {
             partition: fold_cids(fold, take.partition)?,
             sort: fold_column_sorts(fold, take.sort)?,
             range: take.range,
+            percent: take.percent,
         }),
+        Sample(sample) => Sample(sample),
         Join { side, with, filter } => Join {
             side,
             with: fold.fold_table_ref(with)?,
             filter: fold.fold_expr(filter)?,
         },
         Concat(bottom) => Concat(fold.fold_table_ref(bottom)?),
+        Intersect(bottom) => Intersect(fold.fold_table_ref(bottom)?),
+        Remove(bottom) => Remove(fold.fold_table_ref(bottom)?),
         Unique => Unique,
+        Loop(pipeline) => Loop(fold.fold_transforms(pipeline)?),
     };
     Ok(transform)
 }
@@ -202,6 +215,7 @@ fn fold_column_sorts<T: ?Sized + RqFold>(
             Ok(ColumnSort {
                 column: fold.fold_cid(s.column)?,
                 direction: s.direction,
+                nulls: s.nulls,
             })
         })
         .try_collect()
@@ -233,6 +247,13 @@ pub fn fold_expr_kind<F: ?Sized + RqFold>(fold: &mut F, kind: ExprKind) -> Resul
             name,
             args: args.into_iter().map(|a| fold.fold_expr(a)).try_collect()?,
         },
+        ExprKind::Quantified {
+            quantifier,
+            relation,
+        } => ExprKind::Quantified {
+            quantifier,
+            relation: fold.fold_table_ref(relation)?,
+        },
 
         ExprKind::Literal(_) => kind,
     })
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use itertools::Itertools;
+
+use super::{Expr, Query, Relation, RqFold, Transform};
+use crate::utils::fnv1a;
+
+impl Query {
+    /// A hash of the structure of the query, suitable for use as a cache key.
+    ///
+    /// It is computed from the serialized form of the query without spans, so
+    /// it does not change when only formatting or comments of the source
+    /// change. It is stable across compiler releases, as long as the RQ of the
+    /// query does not change.
+    pub fn hash(&self) -> u64 {
+        let query = SpanRemover.fold_query(self.clone()).unwrap();
+
+        // keys of `other` are sorted, so the order of a HashMap does not leak in
+        let other = query.def.other.iter().sorted().collect_vec();
+        let canonical = (&query.def.version, other, &query.tables, &query.relation);
+        let json = serde_json::to_vec(&canonical).unwrap();
+
+        fnv1a(&json)
+    }
+}
+
+struct SpanRemover;
+
+impl RqFold for SpanRemover {
+    fn fold_relation(&mut self, relation: Relation) -> Result<Relation> {
+        let mut relation = super::fold_relation(self, relation)?;
+        relation.span = None;
+        Ok(relation)
+    }
+
+    fn fold_expr(&mut self, mut expr: Expr) -> Result<Expr> {
+        expr.kind = self.fold_expr_kind(expr.kind)?;
+        expr.span = None;
+        Ok(expr)
+    }
+
+    fn fold_transform(&mut self, transform: Transform) -> Result<Transform> {
+        let mut transform = super::fold_transform(self, transform)?;
+        if let Transform::Sample(sample) = &mut transform {
+            sample.span = None;
+        }
+        Ok(transform)
+    }
+}
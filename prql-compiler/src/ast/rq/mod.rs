@@ -4,6 +4,7 @@
 
 mod expr;
 mod fold;
+mod hash;
 mod ids;
 mod transform;
 mod utils;
@@ -19,6 +20,9 @@ use serde::{Deserialize, Serialize};
 
 use super::pl::{ColumnSort, QueryDef, Range, WindowFrame};
 use super::pl::{InterpolateItem, TableExternRef};
+use crate::error::Span;
+
+pub use super::pl::RelationLiteral;
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Query {
@@ -35,6 +39,10 @@ pub struct Relation {
     /// Column definitions.
     /// This is the interface of the table that can be referenced from other tables.
     pub columns: Vec<RelationColumn>,
+
+    /// Span of the PRQL source of the relation, if it is a literal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize, EnumAsInner)]
@@ -65,6 +73,12 @@ pub struct TableDecl {
 
     /// Table's contents.
     pub relation: Relation,
+
+    /// Whether the CTE should be materialized (`AS MATERIALIZED`) or inlined
+    /// (`AS NOT MATERIALIZED`). None leaves the choice to the database.
+    /// Ignored on targets that don't support the hint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub materialized: Option<bool>,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
@@ -79,13 +93,3 @@ pub struct TableRef {
     /// Given name of this table (table alias)
     pub name: Option<String>,
 }
-
-#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
-pub struct RelationLiteral {
-    /// Column names
-    pub columns: Vec<String>,
-    /// Row-oriented data
-    // TODO: this should be generic, so it can contain any type (but at least
-    // numbers)
-    pub rows: Vec<Vec<String>>,
-}
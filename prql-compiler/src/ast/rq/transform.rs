@@ -1,8 +1,8 @@
 use enum_as_inner::EnumAsInner;
 use serde::{Deserialize, Serialize};
 
-use crate::ast::pl::JoinSide;
 use crate::ast::pl::{ColumnSort, Range, WindowFrame};
+use crate::ast::pl::{GroupingKind, JoinSide};
 
 use super::*;
 
@@ -12,20 +12,38 @@ pub enum Transform {
     From(TableRef),
     Compute(Compute),
     Select(Vec<CId>),
+    /// Columns of tables with unknown columns that are excluded from the
+    /// wildcards of the table, as in `SELECT * EXCLUDE (a, b)`. The columns
+    /// are not in the following [Transform::Select].
+    Exclude(Vec<CId>),
     Filter(Expr),
     Aggregate {
         partition: Vec<CId>,
         compute: Vec<CId>,
+        #[serde(default, skip_serializing_if = "GroupingKind::is_plain")]
+        grouping: GroupingKind,
     },
     Sort(Vec<ColumnSort<CId>>),
     Take(Take),
+    /// A random sample of rows, as `TABLESAMPLE` on the table of the preceding
+    /// [Transform::From].
+    Sample(Sample),
     Join {
         side: JoinSide,
         with: TableRef,
         filter: Expr,
     },
     Concat(TableRef),
+    /// Rows of the frame that are also in the table (INTERSECT).
+    Intersect(TableRef),
+    /// Rows of the frame that are not in the table (EXCEPT).
+    Remove(TableRef),
     Unique,
+    /// Appends the rows of the pipeline applied to the frame, repeatedly, each
+    /// time to the rows appended by the previous iteration. The pipeline
+    /// starts from columns of the preceding frame, and ends with a
+    /// [Transform::Select] of columns that correspond to them by position.
+    Loop(Vec<Transform>),
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -33,6 +51,22 @@ pub struct Take {
     pub range: Range<Expr>,
     pub partition: Vec<CId>,
     pub sort: Vec<ColumnSort<CId>>,
+
+    /// Whether the end of the range is a percentage of rows, as in `take 10%`.
+    #[serde(skip_serializing_if = "is_false", default)]
+    pub percent: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub size: i64,
+
+    /// Whether the size is a percentage of rows, as in `sample 10%`.
+    #[serde(skip_serializing_if = "is_false", default)]
+    pub percent: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
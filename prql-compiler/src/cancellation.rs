@@ -0,0 +1,85 @@
+//! Cancellation of long-running compilations.
+//!
+//! Compilation checks for cancellation at coarse-grained points: for each
+//! transform during resolution, for each split of a pipeline into atomic
+//! queries and for each CTE during codegen.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+#[cfg(not(target_family = "wasm"))]
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::error::{Error, ErrorCode, Reason};
+
+/// A handle for aborting a compilation, either explicitly from another thread
+/// or after a deadline. Clones share the same state.
+///
+/// Deadlines are not available on wasm, where `wasm32-unknown-unknown` has no
+/// clock.
+#[derive(Debug, Clone, Default)]
+pub struct Cancellation {
+    cancelled: Arc<AtomicBool>,
+    #[cfg(not(target_family = "wasm"))]
+    deadline: Option<Instant>,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the compilation when `timeout` elapses, measured from now.
+    #[cfg(not(target_family = "wasm"))]
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self::with_deadline(Instant::now() + timeout)
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    pub fn with_deadline(deadline: Instant) -> Self {
+        Cancellation {
+            deadline: Some(deadline),
+            ..Self::default()
+        }
+    }
+
+    /// Cancels the compilation (and all other compilations using a clone of
+    /// this handle).
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.is_past_deadline()
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn is_past_deadline(&self) -> bool {
+        self.deadline.map_or(false, |d| Instant::now() >= d)
+    }
+
+    #[cfg(target_family = "wasm")]
+    fn is_past_deadline(&self) -> bool {
+        false
+    }
+
+    pub(crate) fn check(&self) -> Result<()> {
+        if !self.is_cancelled() {
+            return Ok(());
+        }
+
+        let reason = if self.cancelled.load(Ordering::Relaxed) {
+            "compilation cancelled"
+        } else {
+            "compilation timed out"
+        };
+        Err(Error::new(Reason::Simple(reason.to_string()))
+            .with_code(ErrorCode::Cancelled)
+            .into())
+    }
+}
+
+/// Checks an optional cancellation.
+pub(crate) fn check(cancellation: Option<&Cancellation>) -> Result<()> {
+    cancellation.map_or(Ok(()), Cancellation::check)
+}
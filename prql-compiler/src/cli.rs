@@ -11,6 +11,7 @@ use std::{
 use crate::semantic::{self, reporting::*};
 
 use crate::parser;
+use crate::sql::{self, Options, Target};
 use crate::{ast::pl::Frame, pl_to_prql};
 use crate::{
     compile,
@@ -36,7 +37,14 @@ pub enum Cli {
     Resolve(CommandIO),
 
     /// Transpiles to SQL
-    Compile(CommandIO),
+    Compile {
+        #[clap(flatten)]
+        io: CommandIO,
+
+        /// Target dialect, overriding the one in the query header
+        #[clap(short, long, value_parser = Target::from_name)]
+        target: Option<Target>,
+    },
 }
 
 #[derive(clap::Args, Default)]
@@ -86,11 +94,26 @@ impl Cli {
                 let stmts = parser::parse(source)?;
                 let (stmts, context) = semantic::resolve_only(stmts, None)?;
 
+                // notes about normalizations and warnings, if the query compiles
+                let notes = semantic::lower(stmts.clone(), context.clone())
+                    .and_then(|(query, mut notes)| {
+                        notes.extend(sql::compile_and_note(query, None)?.1);
+                        Ok(notes)
+                    })
+                    .map(|notes| {
+                        let optimizations = notes.optimizations.iter().map(|n| format!("{n}\n"));
+                        let warnings = notes.warnings.iter().map(|w| format!("{w}\n"));
+                        let target = notes.target.iter().map(|t| format!("{t}\n"));
+                        target.chain(optimizations).chain(warnings).join("")
+                    })
+                    .unwrap_or_default();
+
                 let (references, stmts) =
                     label_references(stmts, &context, "".to_string(), source.to_string());
 
                 [
                     references,
+                    notes.into_bytes(),
                     format!("\n{context:#?}\n").into_bytes(),
                     serde_yaml::to_string(&stmts)?.into_bytes(),
                 ]
@@ -113,17 +136,26 @@ impl Cli {
 
                 serde_json::to_string_pretty(&ir)?.into_bytes()
             }
-            Cli::Compile(_) => compile(source, None)
-                .map_or_else(|x| x.to_string(), |x| x)
-                .as_bytes()
-                .to_vec(),
+            Cli::Compile { target, .. } => {
+                let options = target.map(|t| Options::default().with_target(t));
+
+                compile(source, options)
+                    .map_or_else(|x| x.to_string(), |x| x)
+                    .as_bytes()
+                    .to_vec()
+            }
         })
     }
 
     fn read_input(&mut self) -> Result<(String, String)> {
         use Cli::*;
         match self {
-            Parse(io) | Format(io) | Debug(io) | Annotate(io) | Resolve(io) | Compile(io) => {
+            Parse(io)
+            | Format(io)
+            | Debug(io)
+            | Annotate(io)
+            | Resolve(io)
+            | Compile { io, .. } => {
                 // Don't wait without a prompt when running `prql-compiler compile` —
                 // it's confusing whether it's waiting for input or not. This
                 // offers the prompt.
@@ -143,9 +175,12 @@ impl Cli {
     fn write_output(&mut self, data: &[u8]) -> std::io::Result<()> {
         use Cli::*;
         match self {
-            Parse(io) | Format(io) | Debug(io) | Annotate(io) | Resolve(io) | Compile(io) => {
-                io.output.write_all(data)
-            }
+            Parse(io)
+            | Format(io)
+            | Debug(io)
+            | Annotate(io)
+            | Resolve(io)
+            | Compile { io, .. } => io.output.write_all(data),
         }
     }
 }
@@ -241,4 +276,35 @@ group a_column (take 10 | sort b_column | derive [the_number = rank, last = lag
         )
         "###);
     }
+
+    #[test]
+    fn compile_target() {
+        let source = "prql target:sql.sqlite\n\nfrom employees\ntake 10\n";
+        let compile_for = |target| {
+            let cli = Cli::Compile {
+                io: CommandIO::default(),
+                target,
+            };
+            String::from_utf8(Cli::execute(&cli, source).unwrap()).unwrap()
+        };
+
+        // the flag overrides the query header
+        assert!(compile_for(Some(Target::MsSql)).contains("TOP (10)"));
+        assert!(compile_for(None).contains("LIMIT 10"));
+    }
+
+    #[test]
+    fn compile_target_flag() {
+        let cli = Cli::try_parse_from(["prql-compiler", "compile", "--target", "sql.mssql"]);
+        assert!(matches!(
+            cli,
+            Ok(Cli::Compile {
+                target: Some(Target::MsSql),
+                ..
+            })
+        ));
+
+        let cli = Cli::try_parse_from(["prql-compiler", "compile", "--target", "sql.oracle"]);
+        assert!(cli.is_err());
+    }
 }
@@ -0,0 +1,968 @@
+//! Best-effort description of the relation produced by a query.
+//!
+//! Types are inferred from RQ expressions only: we don't know the schema of
+//! the database, so columns from extern tables (and anything derived from them
+//! in a non-obvious way) are reported as [ColumnType::Unknown].
+//!
+//! Columns of a join are the columns of the left relation, followed by the
+//! columns of the right relation. Outer joins pad the columns of their optional
+//! side with NULLs, so these are reported as nullable. The same goes for keys
+//! of a group with `rollup` or `cube`, which are NULL in subtotal rows. Whether
+//! columns of extern tables contain NULLs is not known.
+//!
+//! Columns of an `append` have the common type of the columns of both
+//! relations.
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ast::pl::{BinOp, GroupingKind, InterpolateItem, JoinSide, Literal};
+use crate::ast::rq::{
+    CId, Expr, ExprKind, Query, Relation, RelationColumn, RelationKind, TId, TableDecl, TableRef,
+    Transform, UnOp,
+};
+use crate::notes::Warning;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnDescription {
+    /// Name of the column, if it has one.
+    pub name: Option<String>,
+
+    pub ty: ColumnType,
+
+    /// Whether the column may contain NULLs, i.e. from the optional side of an
+    /// outer join. `None` if it is not known, as for columns of extern tables.
+    pub nullable: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Bool,
+    String,
+    Date,
+    Time,
+    Timestamp,
+    Unknown,
+}
+
+/// Describes columns of the main relation of the query.
+pub fn describe_query(query: &Query) -> Vec<ColumnDescription> {
+    let tables: HashMap<_, _> = query.tables.iter().map(|t| (t.id, t)).collect();
+
+    describe_relation(&query.relation, &tables)
+}
+
+fn describe_relation(
+    relation: &Relation,
+    tables: &HashMap<TId, &TableDecl>,
+) -> Vec<ColumnDescription> {
+    let columns = match &relation.kind {
+        RelationKind::Pipeline(pipeline) => TypeInference::default().of_pipeline(pipeline, tables),
+        RelationKind::Literal(lit) => (0..lit.columns.len())
+            .map(|index| {
+                let values = lit.rows.iter().map(|row| &row[index]);
+                let (nulls, values): (Vec<_>, Vec<_>) =
+                    values.partition(|v| matches!(v, Literal::Null));
+                let ty = unify_all(values.into_iter().map(type_of_literal));
+                (ty, Some(!nulls.is_empty()))
+            })
+            .collect(),
+        RelationKind::ExternRef(_) | RelationKind::SString(_) => Vec::new(),
+    };
+
+    (relation.columns.iter())
+        .enumerate()
+        .filter_map(|(index, col)| match col {
+            RelationColumn::Single(name) => {
+                let column = columns.get(index);
+                Some(ColumnDescription {
+                    name: name.clone(),
+                    ty: column.map_or(ColumnType::Unknown, |c| c.0),
+                    nullable: column.and_then(|c| c.1),
+                })
+            }
+            RelationColumn::Wildcard => None,
+        })
+        .collect()
+}
+
+fn describe_table_ref(
+    table_ref: &TableRef,
+    tables: &HashMap<TId, &TableDecl>,
+) -> Vec<ColumnDescription> {
+    tables
+        .get(&table_ref.source)
+        .map(|decl| describe_relation(&decl.relation, tables))
+        .unwrap_or_default()
+}
+
+/// A column of `append` whose types in the two relations have no common type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ConcatMismatch {
+    /// Position of the column in the relation
+    pub index: usize,
+    pub name: Option<String>,
+    pub top: ColumnType,
+    pub bottom: ColumnType,
+    /// The table that is appended
+    pub appended: TId,
+}
+
+/// Finds columns of `append` in the pipeline of the relation, whose types are
+/// known in both relations, but don't unify. Engines differ in how (and if)
+/// they coerce such columns in UNION.
+pub(crate) fn concat_mismatches(
+    relation: &Relation,
+    tables: &HashMap<TId, &TableDecl>,
+) -> Vec<ConcatMismatch> {
+    let Some(pipeline) = relation.kind.as_pipeline() else {
+        return Vec::new();
+    };
+
+    let mut inference = TypeInference::default();
+    let mut selected: &[CId] = &[];
+    let mut mismatches = Vec::new();
+    for transform in pipeline {
+        match transform {
+            Transform::Select(cids) => selected = cids,
+            Transform::Concat(bottom_ref) => {
+                let described = describe_table_ref(bottom_ref, tables);
+                if described.len() == selected.len() {
+                    for (index, (cid, bottom)) in selected.iter().zip(described).enumerate() {
+                        let top = inference.of_cid(cid);
+                        let known = top != ColumnType::Unknown && bottom.ty != ColumnType::Unknown;
+                        if !known || unify_all([top, bottom.ty]) != ColumnType::Unknown {
+                            continue;
+                        }
+
+                        let name = match relation.columns.get(index) {
+                            Some(RelationColumn::Single(Some(name))) => Some(name.clone()),
+                            _ => bottom.name,
+                        };
+                        mismatches.push(ConcatMismatch {
+                            index,
+                            name,
+                            top,
+                            bottom: bottom.ty,
+                            appended: bottom_ref.source,
+                        });
+                    }
+                }
+                inference.declare_concat(selected, bottom_ref, tables);
+            }
+            _ => {}
+        }
+        inference.declare_transform(transform, tables);
+    }
+    mismatches
+}
+
+/// A column of `append` that is a bare `null` in one of the relations, and
+/// has a known type in the other one. Some engines type such a column by the
+/// `null` alone, or by the first relation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ConcatNull {
+    /// Position of the column in the relation
+    pub index: usize,
+    pub ty: ColumnType,
+    /// The table that is appended, if the `null` is in it
+    pub appended: Option<TId>,
+}
+
+/// Finds columns of `append` in the pipeline of the relation, which are a
+/// `null` in one relation and have a known type in the other.
+pub(crate) fn concat_nulls(
+    relation: &Relation,
+    tables: &HashMap<TId, &TableDecl>,
+) -> Vec<ConcatNull> {
+    let Some(pipeline) = relation.kind.as_pipeline() else {
+        return Vec::new();
+    };
+
+    let mut inference = TypeInference::default();
+    let mut selected: &[CId] = &[];
+    let mut nulls = Vec::new();
+    for transform in pipeline {
+        match transform {
+            Transform::Select(cids) => selected = cids,
+            Transform::Concat(bottom_ref) => {
+                let described = describe_table_ref(bottom_ref, tables);
+                let bottom_nulls = (tables.get(&bottom_ref.source))
+                    .map(|decl| null_columns(&decl.relation, tables))
+                    .unwrap_or_default();
+
+                if described.len() == selected.len() {
+                    for (index, (cid, bottom)) in selected.iter().zip(described).enumerate() {
+                        let top = inference.of_cid(cid);
+                        let null = if inference.nulls.contains(cid) {
+                            (bottom.ty != ColumnType::Unknown).then_some((bottom.ty, None))
+                        } else if bottom_nulls.get(index) == Some(&true) {
+                            (top != ColumnType::Unknown).then_some((top, Some(bottom_ref.source)))
+                        } else {
+                            None
+                        };
+                        if let Some((ty, appended)) = null {
+                            nulls.push(ConcatNull {
+                                index,
+                                ty,
+                                appended,
+                            });
+                        }
+                    }
+                }
+                inference.declare_concat(selected, bottom_ref, tables);
+            }
+            _ => {}
+        }
+        inference.declare_transform(transform, tables);
+    }
+    nulls
+}
+
+/// Whether each of the output columns of the relation is a bare `null`.
+fn null_columns(relation: &Relation, tables: &HashMap<TId, &TableDecl>) -> Vec<bool> {
+    let Some(pipeline) = relation.kind.as_pipeline() else {
+        return Vec::new();
+    };
+
+    let mut inference = TypeInference::default();
+    let mut selected: &[CId] = &[];
+    for transform in pipeline {
+        if let Transform::Select(cids) = transform {
+            selected = cids;
+        }
+        inference.declare_transform(transform, tables);
+    }
+    (selected.iter())
+        .map(|cid| inference.nulls.contains(cid))
+        .collect()
+}
+
+/// Finds join conditions and `in` lists that compare values of obviously
+/// different types, i.e. a string key of a relation literal with an integer
+/// column. Engines with implicit casts may accept these, so they are only
+/// warnings.
+pub(crate) fn comparison_type_mismatches(query: &Query) -> Vec<Warning> {
+    let tables: HashMap<_, _> = query.tables.iter().map(|t| (t.id, t)).collect();
+
+    let relations = query.tables.iter().map(|t| &t.relation);
+    let pipelines = relations
+        .chain(Some(&query.relation))
+        .filter_map(|r| r.kind.as_pipeline());
+
+    let mut warnings = Vec::new();
+    for pipeline in pipelines {
+        let mut inference = TypeInference::default();
+        for transform in pipeline {
+            inference.declare_transform(transform, &tables);
+
+            let expr = match transform {
+                Transform::Join { filter, .. } => {
+                    warnings.extend(inference.mismatches_of_condition(filter));
+                    filter
+                }
+                Transform::Filter(filter) => filter,
+                Transform::Compute(compute) => &compute.expr,
+                _ => continue,
+            };
+            warnings.extend(inference.mismatches_of_in_lists(expr));
+        }
+    }
+    warnings
+}
+
+/// Inferred types of the columns of all relations of a query, by their ids.
+#[derive(Default)]
+pub(crate) struct ColumnTypes(TypeInference);
+
+impl ColumnTypes {
+    pub fn of_expr(&self, expr: &Expr) -> ColumnType {
+        self.0.of_expr(expr)
+    }
+
+    /// Declares a column that has the type of another one, i.e. a column of
+    /// a CTE that the other column is materialized in.
+    pub fn redirect(&mut self, from: &CId, to: CId) {
+        let ty = self.0.of_cid(from);
+        self.0.columns.insert(to, ty);
+    }
+}
+
+/// Infers types of the columns of the main relation and of the relations of
+/// tables of the query.
+pub(crate) fn column_types(query: &Query) -> ColumnTypes {
+    let tables: HashMap<_, _> = query.tables.iter().map(|t| (t.id, t)).collect();
+
+    let relations = query.tables.iter().map(|t| &t.relation);
+    let pipelines = relations
+        .chain(Some(&query.relation))
+        .filter_map(|r| r.kind.as_pipeline());
+
+    let mut inference = TypeInference::default();
+    for pipeline in pipelines {
+        inference.of_pipeline(pipeline, &tables);
+    }
+    ColumnTypes(inference)
+}
+
+#[derive(Default)]
+struct TypeInference {
+    columns: HashMap<CId, ColumnType>,
+
+    /// Whether columns may contain NULLs, if it is known
+    nullable: HashMap<CId, bool>,
+
+    /// Names of columns of table instances
+    names: HashMap<CId, String>,
+
+    /// Columns that are a bare `null`, whose type is not known
+    nulls: HashSet<CId>,
+}
+
+impl TypeInference {
+    /// Returns types and nullability of the output columns of the pipeline.
+    fn of_pipeline(
+        &mut self,
+        pipeline: &[Transform],
+        tables: &HashMap<TId, &TableDecl>,
+    ) -> Vec<(ColumnType, Option<bool>)> {
+        let mut selected: &[CId] = &[];
+        for transform in pipeline {
+            match transform {
+                Transform::Select(cids) => selected = cids,
+                Transform::Concat(bottom) => self.declare_concat(selected, bottom, tables),
+                _ => {}
+            }
+            self.declare_transform(transform, tables);
+        }
+        (selected.iter())
+            .map(|cid| (self.of_cid(cid), self.nullable_of_cid(cid)))
+            .collect()
+    }
+
+    /// Columns of `append` get the common type of both relations.
+    fn declare_concat(
+        &mut self,
+        top: &[CId],
+        bottom: &TableRef,
+        tables: &HashMap<TId, &TableDecl>,
+    ) {
+        let bottom = describe_table_ref(bottom, tables);
+        if bottom.len() != top.len() {
+            return;
+        }
+
+        for (cid, bottom) in top.iter().zip(bottom) {
+            let ty = unify_all([self.of_cid(cid), bottom.ty]);
+            self.columns.insert(*cid, ty);
+            self.set_nullable(
+                *cid,
+                any_nullable([self.nullable_of_cid(cid), bottom.nullable]),
+            );
+        }
+    }
+
+    /// Declares columns that the transform introduces.
+    fn declare_transform(&mut self, transform: &Transform, tables: &HashMap<TId, &TableDecl>) {
+        match transform {
+            Transform::From(table_ref) => {
+                self.declare_table_ref(table_ref, tables);
+            }
+            Transform::Join { side, with, .. } => {
+                let left: Vec<_> = self.columns.keys().cloned().collect();
+                self.declare_table_ref(with, tables);
+
+                if let JoinSide::Left | JoinSide::Full = side {
+                    self.nullable
+                        .extend(with.columns.iter().map(|(_, cid)| (*cid, true)));
+                }
+                if let JoinSide::Right | JoinSide::Full = side {
+                    self.nullable
+                        .extend(left.into_iter().map(|cid| (cid, true)));
+                }
+            }
+            Transform::Compute(compute) => {
+                let ty = self.of_expr(&compute.expr);
+                self.columns.insert(compute.id, ty);
+
+                let nullable = self.is_nullable(&compute.expr);
+                self.set_nullable(compute.id, nullable);
+                if let ExprKind::Literal(Literal::Null) = compute.expr.kind {
+                    self.nulls.insert(compute.id);
+                }
+            }
+            Transform::Aggregate {
+                partition,
+                grouping,
+                ..
+            } => {
+                // subtotal rows of rollup and cube have NULL keys
+                match grouping {
+                    GroupingKind::Plain => {}
+                    // ... and so do rows of grouping sets that do not contain the key
+                    GroupingKind::Sets(sets) => self.nullable.extend(
+                        (partition.iter().enumerate())
+                            .filter(|(i, _)| !sets.iter().all(|set| set.contains(i)))
+                            .map(|(_, cid)| (*cid, true)),
+                    ),
+                    _ => self
+                        .nullable
+                        .extend(partition.iter().map(|cid| (*cid, true))),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn declare_table_ref(&mut self, table_ref: &TableRef, tables: &HashMap<TId, &TableDecl>) {
+        let described = describe_table_ref(table_ref, tables);
+
+        for (col, cid) in &table_ref.columns {
+            if let RelationColumn::Single(Some(name)) = col {
+                self.names.insert(*cid, name.clone());
+            }
+
+            let description = match col {
+                RelationColumn::Single(Some(name)) => {
+                    described.iter().find(|d| d.name.as_ref() == Some(name))
+                }
+                _ => None,
+            };
+            let ty = description.map(|d| d.ty);
+            self.columns.insert(*cid, ty.unwrap_or(ColumnType::Unknown));
+            self.set_nullable(*cid, description.and_then(|d| d.nullable));
+        }
+    }
+
+    fn mismatches_of_condition(&self, condition: &Expr) -> Vec<Warning> {
+        match &condition.kind {
+            ExprKind::Binary {
+                left,
+                op: BinOp::And,
+                right,
+            } => [
+                self.mismatches_of_condition(left),
+                self.mismatches_of_condition(right),
+            ]
+            .concat(),
+            ExprKind::Binary {
+                left,
+                op: BinOp::Eq | BinOp::Ne,
+                right,
+            } => self
+                .mismatch_of_comparison(left, right, "join")
+                .into_iter()
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Finds `in` lists with an item of a different type than the value,
+    /// reporting only the first such item of each list.
+    fn mismatches_of_in_lists(&self, expr: &Expr) -> Vec<Warning> {
+        match &expr.kind {
+            ExprKind::BuiltInFunction { name, args } if name == "std.in" => {
+                let (value, items) = args.split_first().unwrap();
+                (items.iter())
+                    .find_map(|item| self.mismatch_of_comparison(value, item, "in"))
+                    .into_iter()
+                    .collect()
+            }
+            ExprKind::Binary { left, right, .. } => [
+                self.mismatches_of_in_lists(left),
+                self.mismatches_of_in_lists(right),
+            ]
+            .concat(),
+            ExprKind::Unary { expr, .. } => self.mismatches_of_in_lists(expr),
+            ExprKind::Switch(cases) => (cases.iter())
+                .flat_map(|case| [&case.condition, &case.value])
+                .flat_map(|expr| self.mismatches_of_in_lists(expr))
+                .collect(),
+            ExprKind::BuiltInFunction { args, .. } => (args.iter())
+                .flat_map(|arg| self.mismatches_of_in_lists(arg))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn mismatch_of_comparison(
+        &self,
+        left: &Expr,
+        right: &Expr,
+        construct: &str,
+    ) -> Option<Warning> {
+        let (left_ty, right_ty) = (self.of_expr(left), self.of_expr(right));
+
+        // string and number: suggest converting the column of strings into
+        // numbers, or the numbers into strings if strings are literals
+        let (cast, ty) = match (left_ty, right_ty) {
+            (ColumnType::String, ColumnType::Integer | ColumnType::Float) => {
+                if matches!(left.kind, ExprKind::Literal(_)) {
+                    (right, ColumnType::String)
+                } else {
+                    (left, right_ty)
+                }
+            }
+            (ColumnType::Integer | ColumnType::Float, ColumnType::String) => {
+                if matches!(right.kind, ExprKind::Literal(_)) {
+                    (left, ColumnType::String)
+                } else {
+                    (right, left_ty)
+                }
+            }
+            _ => return None,
+        };
+
+        let type_name = match ty {
+            ColumnType::Integer => "int",
+            ColumnType::Float => "float",
+            _ => "text",
+        };
+        let suggestion = match self.name_of(cast) {
+            Some(name) => format!("i.e. `(as {type_name} {name})`"),
+            None => format!("with `as {type_name}`"),
+        };
+
+        let describe = |expr: &Expr, ty: ColumnType| match self.name_of(expr) {
+            Some(name) => format!("`{name}` ({ty})"),
+            None => format!("an expression ({ty})"),
+        };
+        Some(Warning {
+            construct: construct.to_string(),
+            target: None,
+            message: format!(
+                "condition compares {} with {}, which fails or never matches on engines that don't cast implicitly; cast explicitly, {suggestion}",
+                describe(left, left_ty),
+                describe(right, right_ty),
+            ),
+            spans: left.span.into_iter().chain(right.span).collect(),
+        })
+    }
+
+    /// Name of a column, or the value of a literal.
+    fn name_of(&self, expr: &Expr) -> Option<String> {
+        match &expr.kind {
+            ExprKind::ColumnRef(cid) => self.names.get(cid).cloned(),
+            ExprKind::Literal(lit) => Some(lit.to_string()),
+            _ => None,
+        }
+    }
+
+    fn of_cid(&self, cid: &CId) -> ColumnType {
+        self.columns
+            .get(cid)
+            .cloned()
+            .unwrap_or(ColumnType::Unknown)
+    }
+
+    fn of_expr(&self, expr: &Expr) -> ColumnType {
+        match &expr.kind {
+            ExprKind::ColumnRef(cid) => self.of_cid(cid),
+            ExprKind::Literal(lit) => type_of_literal(lit),
+            ExprKind::Binary { left, op, right } => match op {
+                BinOp::Eq
+                | BinOp::Ne
+                | BinOp::Gt
+                | BinOp::Lt
+                | BinOp::Gte
+                | BinOp::Lte
+                | BinOp::RegexSearch
+                | BinOp::And
+                | BinOp::Or => ColumnType::Bool,
+
+                BinOp::Coalesce => unify_all(self.of_non_null(&[left.as_ref(), right.as_ref()])),
+
+                BinOp::Mul | BinOp::Div | BinOp::Mod | BinOp::Add | BinOp::Sub => {
+                    match (self.of_expr(left), self.of_expr(right)) {
+                        (ColumnType::Integer, ColumnType::Integer) => ColumnType::Integer,
+                        (ColumnType::Integer | ColumnType::Float, ColumnType::Float)
+                        | (ColumnType::Float, ColumnType::Integer) => ColumnType::Float,
+                        _ => ColumnType::Unknown,
+                    }
+                }
+            },
+            ExprKind::Unary { op, expr } => match op {
+                UnOp::Not => ColumnType::Bool,
+                UnOp::Neg => self.of_expr(expr),
+            },
+            // s-strings are opaque, we don't even try
+            ExprKind::SString(_) => ColumnType::Unknown,
+            ExprKind::FString(_) => ColumnType::String,
+            ExprKind::Switch(cases) => {
+                let values = cases.iter().map(|c| &c.value).collect::<Vec<_>>();
+                unify_all(self.of_non_null(&values))
+            }
+            ExprKind::BuiltInFunction { name, args } => self.of_built_in(name, args),
+            ExprKind::Quantified { .. } => ColumnType::Unknown,
+        }
+    }
+
+    fn of_built_in(&self, name: &str, args: &[Expr]) -> ColumnType {
+        let name = name.strip_prefix("std.").unwrap_or(name);
+        match name {
+            "count" | "count_distinct" | "count_if" | "rank" | "rank_dense" | "row_number"
+            | "grouping" => ColumnType::Integer,
+            "average" | "avg" | "stddev" => ColumnType::Float,
+            "in" => ColumnType::Bool,
+            "format_num" | "format_date" => ColumnType::String,
+
+            // type of the last argument (the column)
+            "min" | "max" | "sum" | "lag" | "lead" | "first" | "last" | "round" => args
+                .last()
+                .map(|arg| self.of_expr(arg))
+                .unwrap_or(ColumnType::Unknown),
+
+            // type of the first argument (the column)
+            "min_by" | "max_by" => args
+                .first()
+                .map(|arg| self.of_expr(arg))
+                .unwrap_or(ColumnType::Unknown),
+
+            "as" => match args.first().map(|a| &a.kind) {
+                Some(ExprKind::SString(items)) => match items.as_slice() {
+                    [InterpolateItem::String(ty)] => type_of_sql_type_name(ty),
+                    _ => ColumnType::Unknown,
+                },
+                _ => ColumnType::Unknown,
+            },
+
+            _ => ColumnType::Unknown,
+        }
+    }
+
+    fn nullable_of_cid(&self, cid: &CId) -> Option<bool> {
+        self.nullable.get(cid).cloned()
+    }
+
+    fn set_nullable(&mut self, cid: CId, nullable: Option<bool>) {
+        match nullable {
+            Some(nullable) => self.nullable.insert(cid, nullable),
+            None => self.nullable.remove(&cid),
+        };
+    }
+
+    /// Whether the expression may be NULL, `None` if it is not known.
+    fn is_nullable(&self, expr: &Expr) -> Option<bool> {
+        match &expr.kind {
+            ExprKind::ColumnRef(cid) => self.nullable_of_cid(cid),
+            ExprKind::Literal(lit) => Some(matches!(lit, Literal::Null)),
+            ExprKind::Binary {
+                left,
+                op: BinOp::Coalesce,
+                right,
+            } => all_nullable([self.is_nullable(left), self.is_nullable(right)]),
+            ExprKind::Binary { left, right, .. } => {
+                any_nullable([self.is_nullable(left), self.is_nullable(right)])
+            }
+            ExprKind::Unary { expr, .. } => self.is_nullable(expr),
+            ExprKind::SString(items) | ExprKind::FString(items) => {
+                any_nullable(items.iter().map(|item| match item {
+                    InterpolateItem::Expr(expr) => self.is_nullable(expr),
+                    InterpolateItem::String(_) => Some(false),
+                }))
+            }
+            ExprKind::Switch(cases) => {
+                any_nullable(cases.iter().map(|c| self.is_nullable(&c.value)))
+            }
+            ExprKind::BuiltInFunction { name, args } => {
+                let name = name.strip_prefix("std.").unwrap_or(name);
+                match name {
+                    "count" | "count_distinct" | "rank" | "rank_dense" | "row_number"
+                    | "grouping" => Some(false),
+                    _ => any_nullable(args.iter().map(|arg| self.is_nullable(arg))),
+                }
+            }
+            // the relation may be empty or contain nulls
+            ExprKind::Quantified { .. } => Some(true),
+        }
+    }
+
+    /// Types of expressions that are not `null` literals.
+    fn of_non_null(&self, exprs: &[&Expr]) -> Vec<ColumnType> {
+        exprs
+            .iter()
+            .filter(|e| !matches!(e.kind, ExprKind::Literal(Literal::Null)))
+            .map(|e| self.of_expr(e))
+            .collect()
+    }
+}
+
+/// Nullability of a value that is NULL if any of its parts is.
+fn any_nullable<I: IntoIterator<Item = Option<bool>>>(parts: I) -> Option<bool> {
+    let mut known = true;
+    for part in parts {
+        match part {
+            Some(true) => return Some(true),
+            Some(false) => {}
+            None => known = false,
+        }
+    }
+    known.then_some(false)
+}
+
+/// Nullability of a value that is NULL only if all of its parts are, as the
+/// result of COALESCE.
+fn all_nullable<I: IntoIterator<Item = Option<bool>>>(parts: I) -> Option<bool> {
+    any_nullable(parts.into_iter().map(|part| part.map(|p| !p))).map(|n| !n)
+}
+
+pub(crate) fn type_of_literal(lit: &Literal) -> ColumnType {
+    match lit {
+        Literal::Integer(_) => ColumnType::Integer,
+        Literal::Float(_) => ColumnType::Float,
+        Literal::Boolean(_) => ColumnType::Bool,
+        Literal::String(_) => ColumnType::String,
+        Literal::Date(_) => ColumnType::Date,
+        Literal::Time(_) => ColumnType::Time,
+        Literal::Timestamp(_) => ColumnType::Timestamp,
+        Literal::Null | Literal::ValueAndUnit(_) | Literal::Relation(_) => ColumnType::Unknown,
+    }
+}
+
+fn type_of_sql_type_name(name: &str) -> ColumnType {
+    match name.to_lowercase().as_str() {
+        "int" | "integer" | "int2" | "int4" | "int8" | "smallint" | "bigint" | "tinyint" => {
+            ColumnType::Integer
+        }
+        "float" | "float4" | "float8" | "real" | "double" | "decimal" | "numeric" => {
+            ColumnType::Float
+        }
+        "bool" | "boolean" => ColumnType::Bool,
+        "text" | "string" | "varchar" | "char" => ColumnType::String,
+        "date" => ColumnType::Date,
+        "time" => ColumnType::Time,
+        "timestamp" | "datetime" => ColumnType::Timestamp,
+        _ => ColumnType::Unknown,
+    }
+}
+
+/// Finds a common type of all given types.
+/// Integers and floats unify into floats, anything else must be equal.
+fn unify_all<I: IntoIterator<Item = ColumnType>>(types: I) -> ColumnType {
+    let mut types = types.into_iter();
+    let Some(first) = types.next() else {
+        return ColumnType::Unknown;
+    };
+
+    types.fold(first, |a, b| match (a, b) {
+        (a, b) if a == b => a,
+        (ColumnType::Integer, ColumnType::Float) | (ColumnType::Float, ColumnType::Integer) => {
+            ColumnType::Float
+        }
+        _ => ColumnType::Unknown,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use insta::assert_yaml_snapshot;
+
+    use super::*;
+    use crate::{parser::parse, semantic::resolve};
+
+    fn describe(prql: &str) -> Vec<ColumnDescription> {
+        let query = resolve(parse(prql).unwrap()).unwrap();
+        describe_query(&query)
+    }
+
+    fn types_of(prql: &str) -> Vec<String> {
+        describe(prql)
+            .into_iter()
+            .map(|c| format!("{}: {}", c.name.unwrap_or_default(), c.ty))
+            .collect()
+    }
+
+    #[test]
+    fn test_literals_and_casts() {
+        assert_eq!(
+            types_of(
+                r#"
+        from employees
+        select [
+            a = 1, b = 1.5, c = true, d = "x", e = @2020-01-01,
+            f = (as int salary), g = (as text salary), h = salary
+        ]
+        "#
+            ),
+            vec![
+                "a: integer",
+                "b: float",
+                "c: bool",
+                "d: string",
+                "e: date",
+                "f: integer",
+                "g: string",
+                "h: unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_derive_and_operators() {
+        assert_eq!(
+            types_of(
+                r#"
+        from employees
+        derive [a = 1, b = a + 2, c = b * 1.5, d = b > 3, e = !d, f = -c]
+        select [a, b, c, d, e, f, g = s"RANDOM()", h = f"{a}!"]
+        "#
+            ),
+            vec![
+                "a: integer",
+                "b: integer",
+                "c: float",
+                "d: bool",
+                "e: bool",
+                "f: float",
+                "g: unknown",
+                "h: string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_aggregates() {
+        assert_eq!(
+            types_of(
+                r#"
+        from employees
+        derive bonus = 10
+        group dept (
+            aggregate [n = count, avg_salary = average salary, total_bonus = sum bonus]
+        )
+        "#
+            ),
+            vec![
+                "dept: unknown",
+                "n: integer",
+                "avg_salary: float",
+                "total_bonus: integer",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coalesce_and_switch() {
+        assert_eq!(
+            types_of(
+                r#"
+        from employees
+        derive [a = 1 ?? 2.5, b = null ?? "x", c = salary ?? 0]
+        derive d = switch [a > 1 -> 1, a > 0 -> 2.5, true -> null]
+        derive e = switch [a > 1 -> "one", true -> 2]
+        select [a, b, c, d, e]
+        "#
+            ),
+            vec![
+                "a: float",
+                "b: string",
+                "c: unknown",
+                "d: float",
+                "e: unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_append() {
+        assert_eq!(
+            types_of(
+                r#"
+        from employees
+        select [a = 1, b = 1.5, c = true]
+        append (from managers | select [a = 2.5, b = 1, c = @2020-01-01])
+        "#
+            ),
+            vec!["a: float", "b: float", "c: unknown"]
+        );
+    }
+
+    #[test]
+    fn test_table_decls() {
+        assert_eq!(
+            types_of(
+                r#"
+        table t = (
+            from employees
+            derive x = 5
+            select [x, name]
+        )
+        from t
+        select [x, name]
+        "#
+            ),
+            vec!["x: integer", "name: unknown"]
+        );
+    }
+
+    #[test]
+    fn test_serialization() {
+        assert_yaml_snapshot!(describe(r#"
+        from employees
+        select [id, n = 4]
+        "#), @r###"
+        ---
+        - name: id
+          ty: unknown
+          nullable: ~
+        - name: n
+          ty: integer
+          nullable: false
+        "###);
+    }
+
+    fn nullability_of(prql: &str) -> Vec<String> {
+        describe(prql)
+            .into_iter()
+            .map(|c| {
+                let nullable = c.nullable.map_or("unknown".to_string(), |n| n.to_string());
+                format!("{}: {nullable}", c.name.unwrap_or_default())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_joins() {
+        let query = |side: &str| {
+            format!(
+                r#"
+        from e = employees
+        join side:{side} d = departments [==dept_id]
+        select [e.name, d.title, t = d.title ?? "none", n = 1]
+        "#
+            )
+        };
+
+        assert_eq!(
+            nullability_of(&query("inner")),
+            vec!["name: unknown", "title: unknown", "t: false", "n: false"]
+        );
+        assert_eq!(
+            nullability_of(&query("left")),
+            vec!["name: unknown", "title: true", "t: false", "n: false"]
+        );
+        assert_eq!(
+            nullability_of(&query("right")),
+            vec!["name: true", "title: unknown", "t: false", "n: false"]
+        );
+        assert_eq!(
+            nullability_of(&query("full")),
+            vec!["name: true", "title: true", "t: false", "n: false"]
+        );
+
+        // left columns come first, then the right ones
+        assert_eq!(
+            types_of(
+                r#"
+        from a = (from x | select [id, v = 1])
+        join side:left b = (from y | select [id, w = "w"]) [==id]
+        "#
+            ),
+            vec!["id: unknown", "v: integer", "id: unknown", "w: string"]
+        );
+    }
+}
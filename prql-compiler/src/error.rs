@@ -7,7 +7,7 @@ use std::fmt::{self, Debug, Display, Formatter};
 use std::ops::{Add, Range};
 
 use crate::parser::PestError;
-use crate::utils::IntoOnly;
+use crate::utils::{line_col, IntoOnly};
 
 #[derive(Clone, PartialEq, Eq, Copy, Serialize, Deserialize)]
 pub struct Span {
@@ -20,6 +20,63 @@ pub struct Error {
     pub span: Option<Span>,
     pub reason: Reason,
     pub help: Option<String>,
+    pub code: Option<ErrorCode>,
+}
+
+/// Stable codes of error categories, for tools that need to tell them apart.
+///
+/// Errors that don't specify a code get one based on their [Reason].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum ErrorCode {
+    /// The query cannot be parsed.
+    #[strum(to_string = "E0001")]
+    #[serde(rename = "E0001")]
+    Parse,
+
+    /// A name (of a column, table, function or target) cannot be resolved.
+    #[strum(to_string = "E0002")]
+    #[serde(rename = "E0002")]
+    UnknownName,
+
+    /// A value or an argument is not of the expected type or shape.
+    #[strum(to_string = "E0003")]
+    #[serde(rename = "E0003")]
+    Mismatch,
+
+    /// The query is well-formed, but cannot be compiled, e.g. `join` without `from`.
+    #[strum(to_string = "E0004")]
+    #[serde(rename = "E0004")]
+    Invalid,
+
+    /// The query cannot be expressed in the target or by this version of the compiler.
+    #[strum(to_string = "E0005")]
+    #[serde(rename = "E0005")]
+    Unsupported,
+
+    /// The generated SQL exceeds a limit set in the options.
+    #[strum(to_string = "E0006")]
+    #[serde(rename = "E0006")]
+    LimitExceeded,
+
+    /// The `sql_transform` hook provided in the options failed.
+    #[strum(to_string = "E0007")]
+    #[serde(rename = "E0007")]
+    Hook,
+
+    /// The compilation was cancelled or timed out. See [crate::Cancellation].
+    #[strum(to_string = "E0008")]
+    #[serde(rename = "E0008")]
+    Cancelled,
+
+    /// A table or function is denied by the policy. See [crate::Policy].
+    #[strum(to_string = "E0009")]
+    #[serde(rename = "E0009")]
+    Denied,
+
+    /// An invariant of the compiler was violated. This is a bug, please report it.
+    #[strum(to_string = "E9999")]
+    #[serde(rename = "E9999")]
+    Internal,
 }
 
 /// Location within the source file.
@@ -56,9 +113,23 @@ impl Error {
             span: None,
             reason,
             help: None,
+            code: None,
         }
     }
 
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code.unwrap_or(match self.reason {
+            Reason::NotFound { .. } => ErrorCode::UnknownName,
+            Reason::Expected { .. } | Reason::Unexpected { .. } => ErrorCode::Mismatch,
+            Reason::Simple(_) => ErrorCode::Invalid,
+        })
+    }
+
     pub fn with_help<S: Into<String>>(mut self, help: S) -> Self {
         self.help = Some(help.into());
         self
@@ -72,6 +143,8 @@ impl Error {
 
 #[derive(Debug, Clone, Serialize)]
 pub struct ErrorMessage {
+    /// Stable code of the error category
+    pub code: ErrorCode,
     /// Plain text of the error
     pub reason: String,
     /// A list of suggestions of how to fix the error
@@ -112,6 +185,10 @@ impl Display for Error {
     }
 }
 
+/// An error that did not stop processing of a query, i.e. a syntax error that
+/// [crate::parse_recovering] recovered from.
+pub type Diagnostic = ErrorMessage;
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ErrorMessages {
     pub inner: Vec<ErrorMessage>,
@@ -135,6 +212,7 @@ impl Display for ErrorMessages {
 pub fn downcast(error: anyhow::Error) -> ErrorMessages {
     let mut span = None;
     let mut hint = None;
+    let mut code = ErrorCode::Internal;
 
     let error = match error.downcast::<ErrorMessages>() {
         Ok(messages) => return messages,
@@ -144,6 +222,7 @@ pub fn downcast(error: anyhow::Error) -> ErrorMessages {
     let reason = match error.downcast::<Error>() {
         Ok(error) => {
             span = error.span;
+            code = error.code();
             hint = error.help;
 
             error.reason.message()
@@ -156,10 +235,12 @@ pub fn downcast(error: anyhow::Error) -> ErrorMessages {
                         start: range.start,
                         end: range.end,
                     });
+                    code = ErrorCode::Parse;
 
                     pest::as_message(&error)
                 }
                 Err(error) => {
+                    // errors that are not [Error] are internal, so `code` is not changed
                     // default to basic Display
                     format!("{:#?}", error)
                 }
@@ -168,6 +249,7 @@ pub fn downcast(error: anyhow::Error) -> ErrorMessages {
     };
 
     ErrorMessage {
+        code,
         reason,
         hint,
         span,
@@ -185,10 +267,9 @@ impl ErrorMessages {
     /// Computes message location and builds the pretty display.
     pub fn composed(mut self, source_id: &str, source: &str, color: bool) -> Self {
         for e in &mut self.inner {
-            let source = Source::from(source);
-            let cache = (source_id, source);
+            e.location = e.compose_location(source);
 
-            e.location = e.compose_location(&cache.1);
+            let cache = (source_id, Source::from(source));
             e.display = e.compose_display(source_id, cache, color);
         }
         self
@@ -204,6 +285,18 @@ impl IntoOnly for ErrorMessages {
 }
 
 impl ErrorMessage {
+    /// Machine-readable representation of the error, containing its code,
+    /// message, span and hint.
+    pub fn to_json(&self) -> String {
+        let value = serde_json::json!({
+            "code": self.code,
+            "message": self.reason,
+            "span": self.span,
+            "hint": self.hint,
+        });
+        value.to_string()
+    }
+
     fn compose_display<'a, C>(&self, source_id: &'a str, cache: C, color: bool) -> Option<String>
     where
         C: Cache<&'a str>,
@@ -226,14 +319,12 @@ impl ErrorMessage {
         String::from_utf8(out).ok()
     }
 
-    fn compose_location(&self, source: &Source) -> Option<SourceLocation> {
+    fn compose_location(&self, source: &str) -> Option<SourceLocation> {
         let span = self.span?;
 
-        let start = source.get_offset_line(span.start)?;
-        let end = source.get_offset_line(span.end)?;
         Some(SourceLocation {
-            start: (start.1, start.2),
-            end: (end.1, end.2),
+            start: line_col(source, span.start)?,
+            end: line_col(source, span.end)?,
         })
     }
 }
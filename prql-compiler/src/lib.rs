@@ -39,25 +39,44 @@
 // yak-shaving exercise in the future.
 #![allow(clippy::result_large_err)]
 
+mod analyze;
 pub mod ast;
+mod cancellation;
 #[cfg(all(feature = "cli", not(target_family = "wasm")))]
 mod cli;
+mod describe;
 mod error;
+mod lint;
+mod metadata;
+mod notes;
 mod parser;
+mod policy;
 pub mod semantic;
 pub mod sql;
 #[cfg(test)]
 mod test;
+mod timings;
 mod utils;
 
+pub use analyze::Analysis;
+pub use cancellation::Cancellation;
 #[cfg(all(feature = "cli", not(target_family = "wasm")))]
 pub use cli::Cli;
-pub use error::{downcast, ErrorMessage, ErrorMessages, SourceLocation};
+pub use describe::{ColumnDescription, ColumnType};
+pub use error::{downcast, Diagnostic, ErrorCode, ErrorMessage, ErrorMessages, SourceLocation};
+pub use metadata::{verify, Metadata};
+pub use notes::{Notes, OptimizationNote, TargetNote, TargetSource, Warning};
+pub use policy::Policy;
+pub use timings::CompileTimings;
 pub use utils::IntoOnly;
 
+use std::time::Duration;
+
 use once_cell::sync::Lazy;
 use semver::Version;
 
+use crate::timings::timed;
+
 static PRQL_VERSION: Lazy<Version> =
     Lazy::new(|| Version::parse(env!("CARGO_PKG_VERSION")).expect("Invalid PRQL version number"));
 
@@ -68,13 +87,200 @@ static PRQL_VERSION: Lazy<Version> =
 /// - [pl_to_rq] — Finds variable references, validates functions calls, determines frames and converts PL to RQ.
 /// - [rq_to_sql] — Convert RQ AST into an SQL string.
 pub fn compile(prql: &str, options: Option<sql::Options>) -> Result<String, ErrorMessages> {
+    compile_with_notes(prql, options).map(|(sql, _)| sql)
+}
+
+/// Compile a PRQL string into a separate SQL query for each of the CTEs, followed
+/// by the main query. See [sql::compile_split].
+pub fn compile_split(
+    prql: &str,
+    options: Option<sql::Options>,
+) -> Result<Vec<(String, String)>, ErrorMessages> {
     parser::parse(prql)
-        .and_then(semantic::resolve)
-        .and_then(|rq| sql::compile(rq, options))
+        .and_then(|pl| resolve(pl, &options))
+        .and_then(|rq| sql::compile_split(rq, options))
+        .map_err(error::downcast)
+        .map_err(|e| e.composed("", prql, false))
+}
+
+/// Compile a PRQL string into a SQL string and describe the columns of the
+/// resulting relation.
+///
+/// Column types are inferred on a best-effort basis: when a type cannot be
+/// determined, it is reported as [ColumnType::Unknown].
+pub fn compile_and_describe(
+    prql: &str,
+    options: Option<sql::Options>,
+) -> Result<(String, Vec<ColumnDescription>), ErrorMessages> {
+    parser::parse(prql)
+        .and_then(|pl| resolve(pl, &options))
+        .and_then(|rq| {
+            let columns = describe::describe_query(&rq);
+            Ok((sql::compile(rq, options)?, columns))
+        })
+        .map_err(error::downcast)
+        .map_err(|e| e.composed("", prql, false))
+}
+
+/// Compile a PRQL string into a SQL string and report normalizations that the
+/// compiler applied to the query, such as eliminated or merged transforms, and
+/// warnings about constructs that may not behave as expected on the target.
+pub fn compile_with_notes(
+    prql: &str,
+    options: Option<sql::Options>,
+) -> Result<(String, Notes), ErrorMessages> {
+    compile_timed(prql, options).map(|(sql, notes, _)| (sql, notes))
+}
+
+/// Compile a PRQL string into a SQL string and report how long each phase of
+/// the compilation took.
+///
+/// Durations are measured only with [sql::Options::timings], otherwise they
+/// are all zero.
+pub fn compile_with_timings(
+    prql: &str,
+    options: Option<sql::Options>,
+) -> Result<(String, CompileTimings), ErrorMessages> {
+    compile_timed(prql, options).map(|(sql, _, timings)| (sql, timings))
+}
+
+fn compile_timed(
+    prql: &str,
+    options: Option<sql::Options>,
+) -> Result<(String, Notes, CompileTimings), ErrorMessages> {
+    let mut timings = CompileTimings::default();
+    let enabled = options.as_ref().map_or(false, |o| o.timings);
+
+    let mut total = Duration::ZERO;
+    let res = timed(enabled.then_some(&mut total), || {
+        compile_phases(prql, options, enabled.then_some(&mut timings))
+    });
+    timings.total = total;
+    if enabled {
+        log::info!("compile timings: {timings}");
+    }
+
+    res.map(|(sql, notes)| (sql, notes, timings))
         .map_err(error::downcast)
         .map_err(|e| e.composed("", prql, false))
 }
 
+fn compile_phases(
+    prql: &str,
+    options: Option<sql::Options>,
+    mut timings: Option<&mut CompileTimings>,
+) -> anyhow::Result<(String, Notes)> {
+    let pl = timed(timings.as_mut().map(|t| &mut t.parse), || {
+        parser::parse(prql)
+    })?;
+    let (rq, mut notes) = timed(timings.as_mut().map(|t| &mut t.resolve), || {
+        resolve_and_note(pl, &options)
+    })?;
+
+    let (sql, sql_notes) = sql::compile_timed(rq, options.clone(), timings)?;
+    notes.extend(sql_notes);
+
+    let sql = append_metadata_trailer(sql, prql, &notes, options);
+    Ok((sql, notes))
+}
+
+fn append_metadata_trailer(
+    sql: String,
+    prql: &str,
+    notes: &Notes,
+    options: Option<sql::Options>,
+) -> String {
+    let options = options.unwrap_or_default();
+    if !options.metadata_trailer || options.wrap_as.is_some() {
+        return sql;
+    }
+
+    let target = (notes.target.as_ref())
+        .map(|note| note.target.clone())
+        .unwrap_or_default();
+    let trailer = Metadata::new(prql, &target, &options).to_trailer();
+
+    // on its own line, so it does not end up within the signature comment
+    let post = if options.format { "\n" } else { "" };
+    format!("{}\n{trailer}{post}", sql.trim_end())
+}
+
+/// Compile a PRQL string into RQ and a SQL string.
+///
+/// Unlike the SQL, RQ does not depend on formatting [sql::Options], so
+/// [ast::rq::Query::hash] can be used (along with the options) as a key for
+/// caching compiled queries.
+pub fn compile_to_intermediate(
+    prql: &str,
+    options: Option<sql::Options>,
+) -> Result<(ast::rq::Query, String), ErrorMessages> {
+    parser::parse(prql)
+        .and_then(|pl| resolve(pl, &options))
+        .and_then(|rq| {
+            let sql = sql::compile(rq.clone(), options)?;
+            Ok((rq, sql))
+        })
+        .map_err(error::downcast)
+        .map_err(|e| e.composed("", prql, false))
+}
+
+/// Collect statistics of a PRQL query, such as counts of transforms and the
+/// number of CTEs it would compile into, without generating SQL.
+///
+/// The query is parsed and resolved, so this is cheaper than [compile], but
+/// still reports errors of invalid queries.
+pub fn analyze(prql: &str) -> Result<Analysis, ErrorMessages> {
+    parser::parse(prql)
+        .and_then(analyze::analyze_statements)
+        .map_err(error::downcast)
+        .map_err(|e| e.composed("", prql, false))
+}
+
+/// Compile a PRQL string into a SQL string for each of the targets.
+///
+/// The query is parsed and resolved only once, after which RQ is translated
+/// for each target separately. Failing to translate for one of the targets
+/// (i.e. because it does not support a construct used in the query) does not
+/// affect other targets.
+///
+/// The target in `options` is ignored.
+pub fn compile_all_dialects(
+    prql: &str,
+    targets: &[sql::Target],
+    options: Option<sql::Options>,
+) -> Result<Vec<(sql::Target, Result<String, ErrorMessages>)>, ErrorMessages> {
+    let rq = parser::parse(prql)
+        .and_then(|pl| resolve(pl, &options))
+        .map_err(error::downcast)
+        .map_err(|e| e.composed("", prql, false))?;
+
+    let options = options.unwrap_or_default();
+    let results = targets.iter().map(|target| {
+        let options = options.clone().with_target(target.clone());
+        let sql = sql::compile(rq.clone(), Some(options))
+            .map_err(error::downcast)
+            .map_err(|e| e.composed("", prql, false));
+        (target.clone(), sql)
+    });
+    Ok(results.collect())
+}
+
+fn resolve(
+    pl: Vec<ast::pl::Stmt>,
+    options: &Option<sql::Options>,
+) -> anyhow::Result<ast::rq::Query> {
+    resolve_and_note(pl, options).map(|(rq, _)| rq)
+}
+
+fn resolve_and_note(
+    pl: Vec<ast::pl::Stmt>,
+    options: &Option<sql::Options>,
+) -> anyhow::Result<(ast::rq::Query, Notes)> {
+    let cancellation = options.as_ref().and_then(|o| o.cancellation.clone());
+    let policy = options.as_ref().and_then(|o| o.policy.clone());
+    semantic::resolve_with_policy(pl, cancellation, policy)
+}
+
 /// Parse PRQL into a PL AST
 pub fn prql_to_pl(prql: &str) -> Result<Vec<ast::pl::Stmt>, ErrorMessages> {
     parser::parse(prql)
@@ -82,6 +288,51 @@ pub fn prql_to_pl(prql: &str) -> Result<Vec<ast::pl::Stmt>, ErrorMessages> {
         .map_err(|e| e.composed("", prql, false))
 }
 
+/// Build PL AST from a PRQL string, reusing the PL AST of the previous version
+/// of the string for statements before the edited part, for tools that parse
+/// the query on every edit. The result is the same as of [prql_to_pl].
+pub fn prql_to_pl_incremental(
+    prev_prql: &str,
+    prev_pl: Vec<ast::pl::Stmt>,
+    prql: &str,
+) -> Result<Vec<ast::pl::Stmt>, ErrorMessages> {
+    parser::parse_incremental(prev_prql, prev_pl, prql)
+        .map_err(error::downcast)
+        .map_err(|e| e.composed("", prql, false))
+}
+
+/// Build PL AST from a PRQL string, recovering from syntax errors, for tools
+/// that need an AST of a query while it is being edited.
+///
+/// Each line that fails to parse is reported as a diagnostic and replaced with
+/// a placeholder transform that spans the line and leaves the relation
+/// unchanged, so the rest of the query can still be parsed and resolved.
+pub fn parse_recovering(prql: &str) -> (Vec<ast::pl::Stmt>, Vec<Diagnostic>) {
+    let (stmts, errors) = parser::parse_recovering(prql);
+
+    let diagnostics = (errors.into_iter())
+        .flat_map(|e| error::downcast(e).composed("", prql, false).inner)
+        .collect();
+    (stmts, diagnostics)
+}
+
+/// Check the syntax of a PRQL string, for tools such as pre-commit hooks that
+/// check many files and need to be fast.
+///
+/// The query is only parsed: names are not resolved, the standard library is
+/// not loaded and no SQL is generated, so semantic errors are not reported.
+/// Parsing recovers from syntax errors as in [parse_recovering], so all the
+/// errors are reported, each with its [SourceLocation].
+pub fn check(prql: &str) -> Result<(), Vec<Diagnostic>> {
+    let (_, diagnostics) = parse_recovering(prql);
+
+    if diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(diagnostics)
+    }
+}
+
 /// Perform semantic analysis and convert PL to RQ.
 pub fn pl_to_rq(pl: Vec<ast::pl::Stmt>) -> Result<ast::rq::Query, ErrorMessages> {
     semantic::resolve(pl).map_err(error::downcast)
@@ -0,0 +1,152 @@
+//! Lints that point out likely mistakes in queries that are nevertheless valid.
+//!
+//! Lints are opt-in (see [crate::sql::Options::lint]) and report
+//! [Warning]s, which do not affect the generated SQL.
+use std::collections::HashMap;
+
+use itertools::Itertools;
+
+use crate::ast::pl::{BinOp, Literal, RelationLiteral};
+use crate::ast::rq::{CId, Expr, ExprKind, Query, Relation, RelationKind, TId, Transform};
+use crate::error::Span;
+use crate::notes::Warning;
+
+/// Runs all lints over the query.
+pub fn lint_query(query: &Query) -> Vec<Warning> {
+    let literals: HashMap<TId, &Relation> = (query.tables.iter())
+        .filter(|t| matches!(t.relation.kind, RelationKind::Literal(_)))
+        .map(|t| (t.id, &t.relation))
+        .collect();
+
+    let relations = query.tables.iter().map(|t| &t.relation);
+    let pipelines = relations
+        .chain(Some(&query.relation))
+        .filter_map(|r| r.kind.as_pipeline());
+
+    let mut warnings = Vec::new();
+    for pipeline in pipelines {
+        warnings.extend(unsatisfiable_filters(pipeline, &literals));
+    }
+    warnings
+}
+
+/// A column of a relation literal.
+struct LiteralColumn<'a> {
+    name: &'a str,
+    values: Vec<&'a Literal>,
+    span: Option<Span>,
+}
+
+/// Finds filters that compare a column of a relation literal with a value
+/// that none of the rows satisfies, i.e. `filter status == "archived"` when
+/// the literal contains only statuses "active" and "deleted".
+///
+/// Only conjunctions of `==` and `!=` between a column and a literal are
+/// evaluated, other expressions are assumed to be satisfiable.
+fn unsatisfiable_filters(
+    pipeline: &[Transform],
+    literals: &HashMap<TId, &Relation>,
+) -> Vec<Warning> {
+    let mut columns = HashMap::new();
+    let mut warnings = Vec::new();
+
+    for transform in pipeline {
+        match transform {
+            Transform::From(table_ref)
+            | Transform::Join {
+                with: table_ref, ..
+            } => {
+                let Some(relation) = literals.get(&table_ref.source) else {
+                    continue;
+                };
+                let RelationKind::Literal(lit) = &relation.kind else {
+                    continue;
+                };
+
+                for (index, (_, cid)) in table_ref.columns.iter().enumerate() {
+                    columns.insert(*cid, literal_column(lit, index, relation.span));
+                }
+            }
+            Transform::Filter(filter) => {
+                for conjunct in conjuncts(filter) {
+                    if let Some(warning) = check_comparison(conjunct, &columns) {
+                        warnings.push(warning);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    warnings
+}
+
+fn literal_column(lit: &RelationLiteral, index: usize, span: Option<Span>) -> LiteralColumn {
+    LiteralColumn {
+        name: &lit.columns[index],
+        values: lit.rows.iter().map(|row| &row[index]).collect(),
+        span,
+    }
+}
+
+fn conjuncts(expr: &Expr) -> Vec<&Expr> {
+    match &expr.kind {
+        ExprKind::Binary {
+            left,
+            op: BinOp::And,
+            right,
+        } => [conjuncts(left), conjuncts(right)].concat(),
+        _ => vec![expr],
+    }
+}
+
+fn check_comparison(expr: &Expr, columns: &HashMap<CId, LiteralColumn>) -> Option<Warning> {
+    let ExprKind::Binary { left, op, right } = &expr.kind else {
+        return None;
+    };
+    if !matches!(op, BinOp::Eq | BinOp::Ne) {
+        return None;
+    }
+
+    let (cid, value) = match (&left.kind, &right.kind) {
+        (ExprKind::ColumnRef(cid), ExprKind::Literal(value))
+        | (ExprKind::Literal(value), ExprKind::ColumnRef(cid)) => (cid, value),
+        _ => return None,
+    };
+    let column = columns.get(cid)?;
+
+    // comparisons with nulls become IS NULL, which rows padded by joins may satisfy
+    if matches!(value, Literal::Null) {
+        return None;
+    }
+
+    let matching = column.values.iter().filter(|v| literals_eq(v, value));
+    let satisfiable = match op {
+        BinOp::Eq => matching.count() > 0,
+        _ => matching.count() < column.values.len(),
+    };
+    if satisfiable {
+        return None;
+    }
+
+    let values = column.values.iter().unique_by(|v| v.to_string()).join(", ");
+    let op = if let BinOp::Eq = op { "==" } else { "!=" };
+    Some(Warning {
+        construct: "filter".to_string(),
+        target: None,
+        message: format!(
+            "`{} {op} {value}` is false for all rows of the relation literal, where `{}` is one of: {values}",
+            column.name, column.name
+        ),
+        spans: expr.span.into_iter().chain(column.span).collect(),
+    })
+}
+
+/// Compares literals as SQL would, so `1 == 1.0`.
+fn literals_eq(a: &Literal, b: &Literal) -> bool {
+    match (a, b) {
+        (Literal::Integer(a), Literal::Float(b)) | (Literal::Float(b), Literal::Integer(a)) => {
+            *a as f64 == *b
+        }
+        _ => a == b,
+    }
+}
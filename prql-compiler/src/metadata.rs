@@ -0,0 +1,73 @@
+//! Metadata about how a query was compiled.
+//!
+//! With [sql::Options::metadata_trailer], it is appended to the SQL as a
+//! `-- prql: {...}` comment, so a stored query can be traced back to the
+//! compiler, target and PRQL source that produced it.
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::sql::{self, Target};
+use crate::PRQL_VERSION;
+
+const TRAILER_PREFIX: &str = "-- prql: ";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// Version of the compiler.
+    pub version: String,
+
+    /// Dialect the query was compiled for, i.e. `postgres`.
+    pub dialect: String,
+
+    /// Version of the database, as passed in [sql::Options::dialect_version].
+    pub dialect_version: Option<String>,
+
+    /// SHA-256 of the [sql::Options], serialized as JSON.
+    pub options_sha256: String,
+
+    /// SHA-256 of the PRQL source.
+    pub input_sha256: String,
+}
+
+impl Metadata {
+    pub(crate) fn new(prql: &str, target: &Target, options: &sql::Options) -> Self {
+        let serialized_options = serde_json::to_string(options).unwrap();
+        let target = target.to_string();
+
+        Metadata {
+            version: PRQL_VERSION.to_string(),
+            dialect: target.trim_start_matches("sql.").to_string(),
+            dialect_version: options.dialect_version.clone(),
+            options_sha256: sha256_hex(serialized_options.as_bytes()),
+            input_sha256: sha256_hex(prql.as_bytes()),
+        }
+    }
+
+    /// The `-- prql: {...}` comment line.
+    pub fn to_trailer(&self) -> String {
+        format!("{TRAILER_PREFIX}{}", serde_json::to_string(self).unwrap())
+    }
+
+    /// Reads the metadata from the last trailer of a SQL query, if it has one.
+    pub fn from_sql(sql: &str) -> Option<Metadata> {
+        let line = sql.lines().rev().find_map(|line| {
+            let line = line.trim_start();
+            line.strip_prefix(TRAILER_PREFIX)
+        })?;
+        serde_json::from_str(line).ok()
+    }
+}
+
+/// Checks that a SQL query was compiled from the PRQL source, by comparing
+/// the source with the hash in the metadata trailer of the query.
+///
+/// Returns false if the query has no trailer.
+pub fn verify(sql: &str, prql: &str) -> bool {
+    Metadata::from_sql(sql).map_or(false, |metadata| {
+        metadata.input_sha256 == sha256_hex(prql.as_bytes())
+    })
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
@@ -0,0 +1,159 @@
+//! Notes about normalizations that the compiler applied to a query.
+//!
+//! When a transform is eliminated or merged with another, the generated SQL
+//! may not resemble the PRQL source. These notes explain why.
+//!
+//! Warnings point out constructs that compile, but may not behave as expected.
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Span;
+use crate::sql::Target;
+
+/// Notes and warnings collected while compiling a query.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notes {
+    pub optimizations: Vec<OptimizationNote>,
+
+    pub warnings: Vec<Warning>,
+
+    /// The target the query was compiled for and where it was taken from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<TargetNote>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OptimizationNote {
+    /// Name of the compiler pass that made the decision.
+    pub pass: String,
+
+    /// Span of the affected PRQL source, if it is known.
+    pub span: Option<Span>,
+
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Warning {
+    /// The construct the warning is about, i.e. `average` or `f-string`.
+    pub construct: String,
+
+    /// Target whose translation of the construct causes the warning.
+    pub target: Option<Target>,
+
+    pub message: String,
+
+    /// Spans of the PRQL source that the warning is about.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spans: Vec<Span>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetNote {
+    pub target: Target,
+
+    pub source: TargetSource,
+}
+
+/// Where the target was taken from. Sources are listed in order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, strum::Display)]
+pub enum TargetSource {
+    /// [crate::sql::Options::target]
+    #[strum(to_string = "options")]
+    Options,
+
+    /// `target` (or deprecated `dialect`) in the query header
+    #[strum(to_string = "query header")]
+    Header,
+
+    #[strum(to_string = "default")]
+    Default,
+}
+
+impl Notes {
+    pub fn extend(&mut self, other: Notes) {
+        self.optimizations.extend(other.optimizations);
+        self.warnings.extend(other.warnings);
+        if other.target.is_some() {
+            self.target = other.target;
+        }
+    }
+
+    /// Adds a warning, unless there already is the same one about the same
+    /// construct.
+    pub(crate) fn warn(&mut self, warning: Warning) {
+        if !self
+            .warnings
+            .iter()
+            .any(|w| w.construct == warning.construct && w.message == warning.message)
+        {
+            log::warn!("{warning}");
+            self.warnings.push(warning);
+        }
+    }
+}
+
+impl OptimizationNote {
+    pub(crate) fn new<S: ToString>(pass: &str, span: Option<Span>, description: S) -> Self {
+        let note = OptimizationNote {
+            pass: pass.to_string(),
+            span,
+            description: description.to_string(),
+        };
+        log::info!("{note}");
+        note
+    }
+}
+
+impl Display for OptimizationNote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.pass, self.description)?;
+        if let Some(span) = self.span {
+            write!(f, " (at {}..{})", span.start, span.end)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "warning: {}", self.construct)?;
+        if let Some(target) = &self.target {
+            write!(f, " on {target}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl Display for TargetNote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "target: {} (from {})", self.target, self.source)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_warn_dedupes_same_message() {
+        let warning = |message: &str| Warning {
+            construct: "average".to_string(),
+            target: None,
+            message: message.to_string(),
+            spans: Vec::new(),
+        };
+
+        let mut notes = Notes::default();
+        notes.warn(warning("the argument is cast to FLOAT"));
+        notes.warn(warning("the argument is cast to FLOAT"));
+        notes.warn(warning("NULLs are ignored"));
+
+        let messages: Vec<_> = notes.warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec!["the argument is cast to FLOAT", "NULLs are ignored"]
+        );
+    }
+}
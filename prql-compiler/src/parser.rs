@@ -8,14 +8,16 @@ use std::str::FromStr;
 use anyhow::bail;
 use anyhow::{anyhow, Result};
 use itertools::Itertools;
+use pest::error::InputLocation;
 use pest::iterators::Pair;
 use pest::iterators::Pairs;
 use pest::Parser;
 use pest_derive::Parser;
 
+use super::ast::pl::fold::{fold_stmt_kind, AstFold};
 use super::ast::pl::*;
 use super::utils::*;
-use crate::error::Span;
+use crate::error::{Error, ErrorCode, Reason, Span};
 
 #[derive(Parser)]
 #[grammar = "prql.pest"]
@@ -31,6 +33,183 @@ pub fn parse(string: &str) -> Result<Vec<Stmt>> {
     stmts_of_parse_pairs(pairs)
 }
 
+/// Build PL AST from a PRQL query string, reusing statements of a previous
+/// version of the source that precede the edited part.
+///
+/// `prev_stmts` must be the result of parsing `prev_source`. A statement is
+/// reused when its text and the start of the statement after it are
+/// unchanged, since that is where the parse of the statement ends. The rest of
+/// the source is parsed on its own and its spans are shifted. If that fails,
+/// the whole source is parsed again, so errors are the same as of [parse].
+pub fn parse_incremental(
+    prev_source: &str,
+    prev_stmts: Vec<Stmt>,
+    source: &str,
+) -> Result<Vec<Stmt>> {
+    let common_len = (prev_source.bytes().zip(source.bytes()))
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let spans: Option<Vec<_>> = prev_stmts.iter().map(|s| s.span).collect();
+    let reused = spans.map_or(0, |spans| {
+        (spans.iter().skip(1))
+            .take_while(|next| next.start < common_len)
+            .count()
+    });
+    if reused == 0 {
+        return parse(source);
+    }
+
+    let mut stmts = prev_stmts;
+    stmts.truncate(reused);
+    let offset = stmts.last().and_then(|s| s.span).unwrap().end;
+
+    let suffix = parse(&source[offset..]).and_then(|suffix| {
+        // the header is only valid at the start of the source
+        if (suffix.iter()).any(|s| matches!(s.kind, StmtKind::QueryDef(_))) {
+            bail!(Error::new(Reason::Simple(
+                "query header after other statements".to_string()
+            ))
+            .with_code(ErrorCode::Parse));
+        }
+        SpanShifter { offset }.fold_stmts(suffix)
+    });
+    match suffix {
+        Ok(suffix) => {
+            stmts.extend(suffix);
+            Ok(stmts)
+        }
+        Err(_) => parse(source),
+    }
+}
+
+/// Moves spans by an offset, for statements parsed from a part of the source.
+struct SpanShifter {
+    offset: usize,
+}
+
+impl SpanShifter {
+    fn shift(&self, span: Option<Span>) -> Option<Span> {
+        span.map(|span| Span {
+            start: span.start + self.offset,
+            end: span.end + self.offset,
+        })
+    }
+}
+
+impl AstFold for SpanShifter {
+    fn fold_stmt(&mut self, mut stmt: Stmt) -> Result<Stmt> {
+        stmt.span = self.shift(stmt.span);
+        stmt.kind = fold_stmt_kind(self, stmt.kind)?;
+        Ok(stmt)
+    }
+
+    fn fold_expr(&mut self, mut expr: Expr) -> Result<Expr> {
+        expr.span = self.shift(expr.span);
+        expr.kind = self.fold_expr_kind(expr.kind)?;
+        Ok(expr)
+    }
+}
+
+/// Build PL AST from a PRQL query string, recovering from syntax errors.
+///
+/// A line that fails to parse is replaced with a placeholder transform
+/// (`derive []`, which leaves the relation unchanged) that spans the line, and
+/// parsing is retried, so the lines after it are still parsed. Errors of the
+/// replaced lines are returned alongside the statements. If the error cannot
+/// be confined to a line, no statements are returned.
+pub fn parse_recovering(string: &str) -> (Vec<Stmt>, Vec<anyhow::Error>) {
+    let mut source = string.to_string();
+    let mut placeholders = Vec::new();
+    let mut errors = Vec::new();
+
+    loop {
+        let (error, pos) = match PrqlParser::parse(Rule::statements, &source) {
+            Ok(pairs) => match stmts_of_parse_pairs(pairs) {
+                Ok(stmts) => {
+                    let mut replacer = PlaceholderReplacer { placeholders };
+                    let stmts = replacer.fold_stmts(stmts).unwrap();
+                    return (stmts, errors);
+                }
+                Err(error) => {
+                    let pos = (error.downcast_ref::<Error>())
+                        .and_then(|e| e.span)
+                        .map(|span| span.start);
+                    (error, pos)
+                }
+            },
+            Err(error) => {
+                let pos = match error.location {
+                    InputLocation::Pos(pos) => pos,
+                    InputLocation::Span((start, _)) => start,
+                };
+                (anyhow!(error), Some(pos))
+            }
+        };
+        errors.push(error);
+
+        let line = pos.and_then(|pos| line_content_at(&source, pos));
+        let line = match line {
+            Some(line) if !placeholders.contains(&line.start) => line,
+            _ => return (Vec::new(), errors),
+        };
+
+        let placeholder = format!("_{}", " ".repeat(line.len() - 1));
+        source.replace_range(line.clone(), &placeholder);
+        placeholders.push(line.start);
+    }
+}
+
+/// Range of the non-whitespace content of the line containing the position.
+/// For positions on blank lines (i.e. at the end of an unterminated
+/// expression), the content of the preceding non-blank line.
+fn line_content_at(source: &str, pos: usize) -> Option<std::ops::Range<usize>> {
+    let pos = pos.min(source.len());
+    let start = source[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let end = source[pos..].find('\n').map_or(source.len(), |i| pos + i);
+
+    let line = &source[start..end];
+    let content = line.trim();
+    if content.is_empty() {
+        return if start == 0 {
+            None
+        } else {
+            line_content_at(source, start - 1)
+        };
+    }
+
+    let content_start = start + (line.len() - line.trim_start().len());
+    Some(content_start..content_start + content.len())
+}
+
+/// Replaces placeholder idents that [parse_recovering] inserted with `derive []`.
+struct PlaceholderReplacer {
+    /// Start positions of placeholders
+    placeholders: Vec<usize>,
+}
+
+impl AstFold for PlaceholderReplacer {
+    fn fold_expr(&mut self, mut expr: Expr) -> Result<Expr> {
+        let is_underscore =
+            matches!(&expr.kind, ExprKind::Ident(i) if i.path.is_empty() && i.name == "_");
+        let is_placeholder =
+            is_underscore && (expr.span).map_or(false, |s| self.placeholders.contains(&s.start));
+        if !is_placeholder {
+            expr.kind = self.fold_expr_kind(expr.kind)?;
+            return Ok(expr);
+        }
+
+        let derive = Expr::from(ExprKind::Ident(Ident::from_name("derive")));
+        let mut placeholder = Expr::from(ExprKind::FuncCall(FuncCall {
+            name: Box::new(derive),
+            args: vec![Expr::from(ExprKind::List(vec![]))],
+            named_args: HashMap::new(),
+        }));
+        placeholder.span = expr.span;
+        Ok(placeholder)
+    }
+}
+
 /// Parse a string into a parse tree / concrete syntax tree, made up of pest Pairs.
 fn parse_tree_of_str(source: &str, rule: Rule) -> Result<Pairs<Rule>> {
     Ok(PrqlParser::parse(rule, source)?)
@@ -75,7 +254,10 @@ fn stmt_of_parse_pair(pair: Pair<Rule>) -> Result<Stmt> {
             StmtKind::QueryDef(QueryDef { version, other })
         }
         Rule::func_def => {
-            let mut pairs = pair.into_inner();
+            let mut pairs = pair.into_inner().peekable();
+            let is_override = pairs
+                .next_if(|p| p.as_rule() == Rule::func_override)
+                .is_some();
             let name = pairs.next().unwrap();
             let params = pairs.next().unwrap();
             let body = pairs.next().unwrap();
@@ -103,12 +285,32 @@ fn stmt_of_parse_pair(pair: Pair<Rule>) -> Result<Stmt> {
                 }
             }
 
+            let body = expr_of_parse_pair(body)?;
+
+            // a function that takes a relation must pass it through a pipeline;
+            // built-in transforms have no body
+            let takes_table =
+                (positional_params.iter()).any(|p| matches!(p.ty, Some(Ty::Table(_))));
+            let is_built_in = matches!(body.kind, ExprKind::Literal(Literal::Null));
+            if takes_table && !is_built_in && !matches!(body.kind, ExprKind::Pipeline(_)) {
+                bail!(Error::new(Reason::Simple(format!(
+                    "function `{name}` takes a relation, but its body is not a pipeline"
+                )))
+                .with_span(Some(Span {
+                    start: span.start(),
+                    end: span.end(),
+                }))
+                .with_help("start the body with the relation param, e.g. `(rel | filter ...)`")
+                .with_code(ErrorCode::Parse));
+            }
+
             StmtKind::FuncDef(FuncDef {
                 name,
                 positional_params,
                 named_params,
-                body: Box::from(expr_of_parse_pair(body)?),
+                body: Box::from(body),
                 return_ty: return_type,
+                is_override,
             })
         }
         Rule::table_def => {
@@ -116,12 +318,30 @@ fn stmt_of_parse_pair(pair: Pair<Rule>) -> Result<Stmt> {
 
             let name = parse_ident_part(pairs.next().unwrap());
             let pipeline = expr_of_parse_pair(pairs.next().unwrap())?;
+            let materialized = pairs.next().map(|hint| hint.as_str() == "@materialized");
 
             StmtKind::TableDef(TableDef {
                 name,
                 value: Box::new(pipeline),
+                materialized,
             })
         }
+        Rule::var_def => {
+            let mut pairs = pair.into_inner();
+
+            let name = parse_ident_part(pairs.next().unwrap());
+            let value = expr_of_parse_pair(pairs.next().unwrap())?;
+
+            StmtKind::VarDef(VarDef {
+                name,
+                value: Box::new(value),
+            })
+        }
+        Rule::expect_stmt => {
+            let columns = pair.into_inner().map(parse_ident_part).collect();
+
+            StmtKind::Expect(Expect { columns })
+        }
         _ => unreachable!("{pair}"),
     };
     let mut stmt = Stmt::from(kind);
@@ -147,7 +367,7 @@ fn expr_of_parse_pair(pair: Pair<Rule>) -> Result<Expr> {
 
     let kind = match rule {
         Rule::list => ExprKind::List(exprs_of_parse_pairs(pair.into_inner())?),
-        Rule::expr_mul | Rule::expr_add | Rule::expr_compare | Rule::expr => {
+        Rule::expr_mul | Rule::expr_add | Rule::expr_compare | Rule::expr_coalesce | Rule::expr => {
             let mut pairs = pair.into_inner();
 
             let mut expr = expr_of_parse_pair(pairs.next().unwrap())?;
@@ -178,31 +398,6 @@ fn expr_of_parse_pair(pair: Pair<Rule>) -> Result<Expr> {
             }
         }
 
-        // With coalesce, we need to grab the left and the right,
-        // because we're transforming it into a function call rather
-        // than passing along the operator. So this is unlike the rest
-        // of the parsing (and maybe isn't optimal).
-        Rule::expr_coalesce => {
-            let mut pairs = pair.into_inner();
-            let left = expr_of_parse_pair(pairs.next().unwrap())?;
-
-            if pairs.next().is_none() {
-                // If there's no coalescing, just return the single expression.
-                left.kind
-            } else {
-                let right = expr_of_parse_pair(pairs.next().unwrap())?;
-                ExprKind::Binary {
-                    left: Box::new(left),
-                    op: BinOp::Coalesce,
-                    right: Box::new(right),
-                }
-            }
-        }
-        // This makes the previous parsing a bit easier, but is hacky;
-        // ideally find a better way (but it doesn't seem that easy to
-        // parse parts of a Pairs).
-        Rule::operator_coalesce => ExprKind::Ident(Ident::from_name("-")),
-
         Rule::assign | Rule::alias => {
             let (a, expr) = parse_named(pair.into_inner())?;
             alias = Some(a);
@@ -248,6 +443,8 @@ fn expr_of_parse_pair(pair: Pair<Rule>) -> Result<Expr> {
             ExprKind::Ident(Ident { path: parts, name })
         }
 
+        Rule::wildcard => ExprKind::Ident(Ident::from_name("*")),
+
         Rule::number => {
             let str = pair.as_str();
 
@@ -256,7 +453,10 @@ fn expr_of_parse_pair(pair: Pair<Rule>) -> Result<Expr> {
             } else if let Ok(f) = str.parse::<f64>() {
                 Literal::Float(f)
             } else {
-                bail!("cannot parse {str} as number")
+                bail!(
+                    Error::new(Reason::Simple(format!("cannot parse {str} as number")))
+                        .with_code(ErrorCode::Parse)
+                )
             };
             ExprKind::Literal(lit)
         }
@@ -278,6 +478,16 @@ fn expr_of_parse_pair(pair: Pair<Rule>) -> Result<Expr> {
                 _ => ExprKind::Pipeline(Pipeline { exprs: nodes }),
             }
         }
+        Rule::quantified => {
+            let mut pairs = pair.into_inner();
+
+            let quantifier = Quantifier::from_str(pairs.next().unwrap().as_str())?;
+            let pipeline = expr_of_parse_pair(pairs.next().unwrap())?;
+            ExprKind::Quantified {
+                quantifier,
+                pipeline: Box::new(pipeline),
+            }
+        }
         Rule::nested_pipeline => {
             if let Some(pipeline) = pair.into_inner().next() {
                 expr_of_parse_pair(pipeline)?.kind
@@ -299,15 +509,29 @@ fn expr_of_parse_pair(pair: Pair<Rule>) -> Result<Expr> {
                 })
                 .collect::<Vec<_>>()
                 .try_into()
-                .map_err(|e| anyhow!("Expected start, separator, end; {e:?}"))?;
+                .map_err(|e| {
+                    Error::new(Reason::Simple(format!(
+                        "Expected start, separator, end; {e:?}"
+                    )))
+                    .with_span(Some(Span {
+                        start: span.start(),
+                        end: span.end(),
+                    }))
+                    .with_code(ErrorCode::Parse)
+                })?;
             ExprKind::Range(Range { start, end })
         }
 
         Rule::value_and_unit => {
             let pairs: Vec<_> = pair.into_inner().into_iter().collect();
-            let [n, unit]: [Pair<Rule>; 2] = pairs
-                .try_into()
-                .map_err(|e| anyhow!("Expected two items; {e:?}"))?;
+            let [n, unit]: [Pair<Rule>; 2] = pairs.try_into().map_err(|e| {
+                Error::new(Reason::Simple(format!("Expected two items; {e:?}")))
+                    .with_span(Some(Span {
+                        start: span.start(),
+                        end: span.end(),
+                    }))
+                    .with_code(ErrorCode::Parse)
+            })?;
 
             ExprKind::Literal(Literal::ValueAndUnit(ValueAndUnit {
                 n: n.as_str().parse()?,
@@ -318,6 +542,22 @@ fn expr_of_parse_pair(pair: Pair<Rule>) -> Result<Expr> {
         Rule::date | Rule::time | Rule::timestamp => {
             let inner = pair.into_inner().into_only()?.as_str().to_string();
 
+            if let Some(field) = invalid_date_time_field(rule, &inner) {
+                let kind = match rule {
+                    Rule::date => "date",
+                    Rule::time => "time",
+                    _ => "timestamp",
+                };
+                bail!(Error::new(Reason::Simple(format!(
+                    "`@{inner}` is not a valid {kind}: the {field} is out of range"
+                )))
+                .with_span(Some(Span {
+                    start: span.start(),
+                    end: span.end(),
+                }))
+                .with_code(ErrorCode::Parse));
+            }
+
             ExprKind::Literal(match rule {
                 Rule::date => Literal::Date(inner),
                 Rule::time => Literal::Time(inner),
@@ -417,20 +657,89 @@ fn parse_ident_part(pair: Pair<Rule>) -> String {
     pair.into_inner().next().unwrap().as_str().to_string()
 }
 
+/// Finds a field of a date, time or timestamp literal that is out of range,
+/// i.e. the day of `@2021-02-30` or the hour of `@25:00`. The grammar
+/// guarantees the digits are there, but not their values.
+fn invalid_date_time_field(rule: Rule, value: &str) -> Option<&'static str> {
+    let (date, time) = match rule {
+        Rule::date => (Some(value), None),
+        Rule::time => (None, Some(value)),
+        _ => {
+            let (date, time) = value.split_once('T')?;
+            (Some(date), Some(time))
+        }
+    };
+
+    if let Some(date) = date {
+        let fields: Vec<u32> = date.split('-').map(|f| f.parse().unwrap()).collect();
+        let [year, month, day] = fields[..] else {
+            return None;
+        };
+
+        let is_leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap => 29,
+            2 => 28,
+            _ => return Some("month"),
+        };
+        if day == 0 || day > days {
+            return Some("day");
+        }
+    }
+
+    if let Some(time) = time {
+        // the offset is not checked
+        let time = time.trim_end_matches('Z');
+        let time = time.split(['+', '-']).next().unwrap();
+
+        let fields = time.split(':').map(|f| {
+            let whole = f.split('.').next().unwrap();
+            whole.parse::<u32>().ok()
+        });
+        for (value, (field, max)) in fields.zip([("hour", 23), ("minute", 59), ("second", 59)]) {
+            if value.map_or(false, |v| v > max) {
+                return Some(field);
+            }
+        }
+    }
+    None
+}
+
 fn ast_of_interpolate_items(pair: Pair<Rule>) -> Result<Vec<InterpolateItem>> {
-    pair.into_inner()
-        .map(|x| {
-            Ok(match x.as_rule() {
-                Rule::interpolate_string_inner_literal
-                // The double bracket literals are already stripped of their
-                // outer brackets by pest, so we pass them through as strings.
-                | Rule::interpolate_double_bracket_literal => {
-                    InterpolateItem::String(x.as_str().to_string())
-                }
-                _ => InterpolateItem::Expr(Box::new(expr_of_parse_pair(x)?)),
-            })
-        })
-        .collect::<Result<_>>()
+    let mut items = Vec::new();
+    for x in pair.into_inner() {
+        let string = match x.as_rule() {
+            Rule::interpolate_string_inner_literal => x.as_str(),
+            // `{{` is `{` and `}}` is `}`
+            Rule::interpolate_escaped_brace => &x.as_str()[..1],
+            Rule::interpolate_unclosed => {
+                let span = x.as_span();
+                bail!(Error::new(Reason::Simple(
+                    "unclosed `{` in interpolated string".to_string()
+                ))
+                .with_span(Some(Span {
+                    start: span.start(),
+                    end: span.end(),
+                }))
+                .with_help("use `{{` for a literal brace")
+                .with_code(ErrorCode::Parse));
+            }
+            _ => {
+                items.push(InterpolateItem::Expr(Box::new(expr_of_parse_pair(x)?)));
+                continue;
+            }
+        };
+
+        // merge consecutive strings
+        if let Some(InterpolateItem::String(last)) = items.last_mut() {
+            last.push_str(string);
+        } else {
+            items.push(InterpolateItem::String(string.to_string()));
+        }
+    }
+    Ok(items)
 }
 
 #[cfg(test)]
@@ -2021,6 +2330,20 @@ join `my-proj`.`dataset`.`table`
 
         assert!(parse("derive x = @2020-01-0").is_err());
 
+        // fields are checked
+        assert!(parse("derive x = @2020-02-29").is_ok());
+        assert!(parse("derive x = @2020-02-30T10:00").is_err());
+        assert!(parse("derive x = @2021-02-29").is_err());
+        assert!(parse("derive x = @2021-13-01").is_err());
+        assert!(parse("derive x = @24:00").is_err());
+        assert!(parse("derive x = @2021-01-01T10:60:00+01:00").is_err());
+        assert!(parse("derive x = @2021-12-31T23:59:59.999-08:00").is_ok());
+
+        let err = parse("derive x = @2021-02-30").unwrap_err();
+        let err = err.downcast::<Error>().unwrap();
+        assert_eq!(err.span, Some(Span { start: 11, end: 22 }));
+        assert_eq!(err.code, Some(ErrorCode::Parse));
+
         Ok(())
     }
 
@@ -2079,6 +2402,22 @@ join `my-proj`.`dataset`.`table`
         "### )
     }
 
+    #[test]
+    fn test_parse_quantified() {
+        let expr = expr_of_string("salary > all (from benchmarks | select p99)", Rule::expr);
+        assert!(matches!(
+            expr.unwrap().kind,
+            ExprKind::Binary { right, .. } if matches!(right.kind, ExprKind::Quantified { .. })
+        ));
+
+        // without a sub-pipeline, `any` is not a quantifier
+        let expr = expr_of_string("flag == any (x > 0)", Rule::expr);
+        assert!(matches!(
+            expr.unwrap().kind,
+            ExprKind::Binary { right, .. } if !matches!(right.kind, ExprKind::Quantified { .. })
+        ));
+    }
+
     #[test]
     fn test_parse_literal() {
         assert_yaml_snapshot!(parse(r###"
@@ -2274,4 +2613,145 @@ join s=salaries [==id]
                     named_args: {}
         "###);
     }
+
+    #[test]
+    fn test_operators_are_structured() {
+        use crate::ast::pl::fold::AstFold;
+
+        /// Collects idents that are actually operators, which would mean that an
+        /// operator was not parsed into [BinOp] or [UnOp].
+        #[derive(Default)]
+        struct OperatorIdents {
+            found: Vec<String>,
+            binary: usize,
+        }
+        impl AstFold for OperatorIdents {
+            fn fold_expr(&mut self, mut expr: Expr) -> Result<Expr> {
+                match &expr.kind {
+                    ExprKind::Ident(ident) => {
+                        if BinOp::from_str(&ident.name).is_ok()
+                            || UnOp::from_str(&ident.name).is_ok()
+                            || ident.name == "+"
+                        {
+                            self.found.push(ident.to_string());
+                        }
+                    }
+                    ExprKind::Binary { .. } => self.binary += 1,
+                    _ => {}
+                }
+                expr.kind = self.fold_expr_kind(expr.kind)?;
+                Ok(expr)
+            }
+        }
+
+        // The example from the README
+        let stmts = parse(
+            r#"
+        from employees
+        filter start_date > @2021-01-01
+        derive [
+          gross_salary = salary + (tax ?? 0),
+          gross_cost = gross_salary + benefits_cost,
+        ]
+        filter gross_cost > 0
+        group [title, country] (
+          aggregate [
+            average gross_salary,
+            sum_gross_cost = sum gross_cost,
+          ]
+        )
+        filter sum_gross_cost > 100000
+        derive id = f"{title}_{country}"
+        derive country_code = s"LEFT(country, 2)"
+        sort [sum_gross_cost, -country]
+        take 1..20
+        "#,
+        )
+        .unwrap();
+
+        let mut visitor = OperatorIdents::default();
+        visitor.fold_stmts(stmts).unwrap();
+
+        assert_eq!(visitor.found, Vec::<String>::new());
+        assert_eq!(visitor.binary, 6);
+    }
+
+    #[test]
+    fn test_parse_incremental() {
+        let base = r#"prql target:sql.postgres
+
+func add_one x -> x + 1
+let limit_rows = 10
+
+table top = (
+  from employees
+  sort [-salary]
+  take limit_rows
+)
+
+# the main query
+from top
+derive [bonus = add_one salary]
+select [name, bonus]
+"#;
+        let fragments = [
+            "\n",
+            " ",
+            "# c",
+            "x",
+            "1",
+            "(",
+            ")",
+            "|",
+            ",",
+            "=",
+            "prql ",
+            "\nlet y = 2\n",
+            "table t = (from a)\n",
+            "derive z = 3\n",
+        ];
+
+        // a simple LCG, so edits are random but the same in every run
+        let mut state: u64 = 42;
+        let mut next = |n: usize| {
+            state = (state.wrapping_mul(6364136223846793005)).wrapping_add(1442695040888963407);
+            (state >> 33) as usize % n
+        };
+
+        let mut prev = base.to_string();
+        let mut prev_stmts = parse(&prev).unwrap();
+        for i in 0..1000 {
+            if i % 25 == 0 {
+                prev = base.to_string();
+                prev_stmts = parse(&prev).unwrap();
+            }
+
+            let mut source = prev.clone();
+            let pos = next(source.len() + 1);
+            match next(3) {
+                0 => source.insert_str(pos, fragments[next(fragments.len())]),
+                1 => {
+                    let end = (pos + 1 + next(5)).min(source.len());
+                    source.replace_range(pos.min(end)..end, "");
+                }
+                _ => {
+                    let end = (pos + 1).min(source.len());
+                    source.replace_range(pos.min(end)..end, fragments[next(fragments.len())]);
+                }
+            }
+
+            let full = parse(&source);
+            let incremental = parse_incremental(&prev, prev_stmts.clone(), &source);
+            match full {
+                Ok(full) => {
+                    let incremental = incremental.unwrap();
+                    assert_eq!(incremental, full, "edit {i} of:\n{prev}\ninto:\n{source}");
+
+                    prev = source;
+                    prev_stmts = incremental;
+                }
+                Err(_) => assert!(incremental.is_err(), "edit {i}:\n{source}"),
+            }
+        }
+    }
 }
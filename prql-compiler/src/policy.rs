@@ -0,0 +1,170 @@
+//! Restrictions of what queries may reference, for embedding the compiler
+//! where queries are written by users that should not reach everything.
+//!
+//! Tables and functions are checked during resolution, where they are
+//! referenced. A table declared with `table` is checked by the tables its
+//! declaration reads. S-strings are checked in the query before resolution,
+//! because functions of std are implemented with them.
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::ast::pl::fold::AstFold;
+use crate::ast::pl::{Expr, ExprKind, Ident, Stmt};
+use crate::error::{Error, ErrorCode, Reason, Span};
+
+/// Tables and functions that a query may reference. See
+/// [crate::sql::Options::policy].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    /// Patterns of names of tables that may be read, where `*` matches any
+    /// characters and `?` matches one, i.e. `sales.*`. When set, all other
+    /// tables are denied. Names are matched case-insensitively, as most
+    /// databases resolve them.
+    #[serde(default)]
+    pub allowed_tables: Option<Vec<String>>,
+
+    /// Patterns of names of tables that may not be read, even if they are
+    /// allowed by `allowed_tables`.
+    #[serde(default)]
+    pub denied_tables: Vec<String>,
+
+    /// Denies s-strings, which can contain arbitrary SQL. F-strings are still
+    /// allowed.
+    #[serde(default)]
+    pub deny_sstrings: bool,
+
+    /// Functions that may not be called, either by name (`sum`) or by fully
+    /// qualified name (`std.sum`).
+    #[serde(default)]
+    pub denied_functions: Vec<String>,
+}
+
+impl Policy {
+    pub fn with_allowed_tables(mut self, patterns: Vec<String>) -> Self {
+        self.allowed_tables = Some(patterns);
+        self
+    }
+
+    pub fn with_denied_tables(mut self, patterns: Vec<String>) -> Self {
+        self.denied_tables = patterns;
+        self
+    }
+
+    pub fn with_deny_sstrings(mut self) -> Self {
+        self.deny_sstrings = true;
+        self
+    }
+
+    pub fn with_denied_functions(mut self, names: Vec<String>) -> Self {
+        self.denied_functions = names;
+        self
+    }
+
+    /// Checks a reference to a table of the database.
+    pub(crate) fn check_table(&self, name: &str, span: Option<Span>) -> Result<()> {
+        if let Some(allowed) = &self.allowed_tables {
+            if !allowed.iter().any(|p| matches_pattern(p, name)) {
+                return Err(denied(format!("table `{name}`"), "allowed_tables", span).into());
+            }
+        }
+        if self.denied_tables.iter().any(|p| matches_pattern(p, name)) {
+            return Err(denied(format!("table `{name}`"), "denied_tables", span).into());
+        }
+        Ok(())
+    }
+
+    /// Checks a call of a function, by its fully qualified name.
+    pub(crate) fn check_function(&self, fq_ident: &Ident, span: Option<Span>) -> Result<()> {
+        let fq_name = fq_ident.to_string();
+        let is_denied =
+            (self.denied_functions.iter()).any(|name| *name == fq_ident.name || *name == fq_name);
+        if is_denied {
+            let what = format!("function `{}`", fq_ident.name);
+            return Err(denied(what, "denied_functions", span).into());
+        }
+        Ok(())
+    }
+
+    /// Checks the statements of a query, before they are resolved.
+    pub(crate) fn check_stmts(&self, stmts: &[Stmt]) -> Result<()> {
+        if self.deny_sstrings {
+            SStringDenier.fold_stmts(stmts.to_vec())?;
+        }
+        Ok(())
+    }
+}
+
+fn denied(what: String, rule: &str, span: Option<Span>) -> Error {
+    Error::new(Reason::Simple(format!(
+        "{what} is denied by the `{rule}` rule of the policy"
+    )))
+    .with_span(span)
+    .with_code(ErrorCode::Denied)
+}
+
+struct SStringDenier;
+
+impl AstFold for SStringDenier {
+    fn fold_expr(&mut self, mut expr: Expr) -> Result<Expr> {
+        if let ExprKind::SString(_) = expr.kind {
+            return Err(denied("s-string".to_string(), "deny_sstrings", expr.span).into());
+        }
+        expr.kind = self.fold_expr_kind(expr.kind)?;
+        Ok(expr)
+    }
+}
+
+/// Matches a name against a pattern, where `*` matches any characters and
+/// `?` matches one. ASCII letters match regardless of case, so a denied table
+/// cannot be reached by changing the case of its name.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let name: Vec<char> = name.chars().map(|c| c.to_ascii_lowercase()).collect();
+
+    // positions in the pattern and the name to continue from when the last
+    // `*` should match one more character
+    let mut backtrack = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("employees", "employees"));
+        assert!(!matches_pattern("employees", "employee"));
+        assert!(matches_pattern("sales.*", "sales.orders"));
+        assert!(!matches_pattern("sales.*", "hr.salaries"));
+        assert!(matches_pattern("*_pii", "users_pii"));
+        assert!(matches_pattern("*", "anything"));
+        assert!(matches_pattern("t?", "t1"));
+        assert!(!matches_pattern("t?", "t12"));
+        assert!(matches_pattern("a*b*c", "axxbyyc"));
+        assert!(!matches_pattern("a*b*c", "axxbyy"));
+        assert!(matches_pattern("salar*", "Salaries"));
+        assert!(matches_pattern("HR.*", "hr.Salaries"));
+    }
+}
@@ -8,7 +8,10 @@ use std::{collections::HashMap, fmt::Debug};
 use super::module::{Module, NS_DEFAULT_DB, NS_SELF, NS_STD};
 use crate::ast::pl::*;
 use crate::ast::rq::RelationColumn;
+use crate::cancellation::Cancellation;
 use crate::error::Span;
+use crate::notes::{Notes, Warning};
+use crate::policy::Policy;
 
 /// Context of the pipeline.
 #[derive(Default, Serialize, Deserialize, Clone)]
@@ -19,6 +22,20 @@ pub struct Context {
     pub(crate) span_map: HashMap<usize, Span>,
 
     pub(crate) inferred_columns: HashMap<usize, Vec<RelationColumn>>,
+
+    /// Normalizations applied to the query so far.
+    #[serde(skip)]
+    pub(crate) notes: Notes,
+
+    /// Set once std is loaded. Functions declared after that are user-defined
+    /// and are declared in the root module, where they shadow std functions.
+    pub(crate) std_loaded: bool,
+
+    #[serde(skip)]
+    pub(crate) cancellation: Option<Cancellation>,
+
+    #[serde(skip)]
+    pub(crate) policy: Option<Policy>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -60,6 +77,10 @@ pub struct TableDecl {
     /// None means that this is an extern table (actual table in database)
     /// Some means a CTE
     pub expr: Option<Box<Expr>>,
+
+    /// Materialization hint of the CTE, see [crate::ast::rq::TableDecl::materialized].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub materialized: Option<bool>,
 }
 
 #[derive(Clone, Eq, Debug, PartialEq, Serialize, Deserialize)]
@@ -72,8 +93,23 @@ impl Context {
     pub fn declare_func(&mut self, func_def: FuncDef, id: Option<usize>) {
         let name = func_def.name.clone();
 
-        let path = vec![NS_STD.to_string()];
-        let ident = Ident { name, path };
+        let ident = if self.std_loaded {
+            let std_ident = Ident::from_path(vec![NS_STD.to_string(), name.clone()]);
+            if self.root_mod.get(&std_ident).is_some() && !func_def.is_override {
+                let span = id.and_then(|id| self.span_map.get(&id)).cloned();
+                self.notes.warn(Warning {
+                    construct: format!("func {name}"),
+                    target: None,
+                    message: format!(
+                        "function `{name}` shadows `{std_ident}`, which remains available by its qualified name; declare it with `override func` if this is intended"
+                    ),
+                    spans: span.into_iter().collect(),
+                });
+            }
+            Ident::from_name(name)
+        } else {
+            Ident::from_path(vec![NS_STD.to_string(), name])
+        };
 
         let decl = Decl {
             kind: DeclKind::FuncDef(func_def),
@@ -96,14 +132,27 @@ impl Context {
             .collect();
 
         let expr = Some(table_def.value);
+        let materialized = table_def.materialized;
         let decl = Decl {
             declared_at: id,
-            kind: DeclKind::TableDecl(TableDecl { columns, expr }),
+            kind: DeclKind::TableDecl(TableDecl {
+                columns,
+                expr,
+                materialized,
+            }),
         };
 
         self.root_mod.insert(ident, decl).unwrap();
     }
 
+    pub fn declare_var(&mut self, name: String, value: Expr, id: Option<usize>) {
+        let decl = Decl {
+            declared_at: id,
+            kind: DeclKind::Expr(Box::new(value)),
+        };
+        self.root_mod.insert(Ident::from_name(name), decl).unwrap();
+    }
+
     pub fn resolve_ident(&mut self, ident: &Ident) -> Result<Ident, String> {
         // lookup the name
         let decls = self.root_mod.lookup(ident);
@@ -115,6 +164,25 @@ impl Context {
             // single match, great!
             1 => return Ok(decls.into_iter().next().unwrap()),
 
+            // user-defined functions shadow std
+            _ if decls
+                .iter()
+                .all(|d| d.path.is_empty() || d.path == [NS_STD]) =>
+            {
+                if let Some(local) = decls.iter().find(|d| d.path.is_empty()) {
+                    return Ok(local.clone());
+                }
+            }
+
+            // std functions shadow columns, which are preferred only for values
+            _ if decls.iter().filter(|d| d.path == [NS_STD]).count() == 1
+                && decls
+                    .iter()
+                    .all(|d| d.path == [NS_STD] || self.is_column(d)) =>
+            {
+                return Ok(decls.into_iter().find(|d| d.path == [NS_STD]).unwrap());
+            }
+
             // ambiguous
             _ => {
                 let decls = decls.into_iter().map(|d| d.to_string()).join(", ");
@@ -122,8 +190,57 @@ impl Context {
             }
         }
 
+        self.resolve_wildcard_column(ident)
+    }
+
+    /// Resolves an ident that is used as a value. Unlike in [Self::resolve_ident],
+    /// columns take precedence over functions of std, and a function of std that
+    /// requires arguments and is not a transform is resolved only when no input
+    /// of the frame could contain a column of that name.
+    pub fn resolve_value_ident(&mut self, ident: &Ident) -> Result<Ident, String> {
+        if !ident.path.is_empty() {
+            return self.resolve_ident(ident);
+        }
+
+        let decls = self.root_mod.lookup(ident);
+        let (std_decls, mut others): (Vec<_>, Vec<_>) =
+            decls.into_iter().partition(|d| d.path == [NS_STD]);
+        let Some(std_decl) = std_decls.into_iter().next() else {
+            return self.resolve_ident(ident);
+        };
+
+        match others.len() {
+            0 => {
+                let is_scalar_func = (self.root_mod.get(&std_decl))
+                    .and_then(|d| d.kind.as_func_def())
+                    .map_or(false, |f| {
+                        !f.positional_params.is_empty()
+                            && !matches!(f.return_ty, Some(Ty::Table(_)))
+                    });
+                if is_scalar_func {
+                    if let Ok(column) = self.resolve_wildcard_column(ident) {
+                        return Ok(column);
+                    }
+                }
+                Ok(std_decl)
+            }
+            1 => Ok(others.remove(0)),
+            _ => {
+                let decls = others.into_iter().map(|d| d.to_string()).join(", ");
+                Err(format!("Ambiguous name. Could be from any of {decls}"))
+            }
+        }
+    }
+
+    fn is_column(&self, ident: &Ident) -> bool {
+        let decl = self.root_mod.get(ident);
+        decl.map_or(false, |d| matches!(d.kind, DeclKind::Column(_)))
+    }
+
+    /// Resolves an ident to a column of the only input with unknown columns.
+    fn resolve_wildcard_column(&mut self, ident: &Ident) -> Result<Ident, String> {
         // this variable can be from a namespace that we don't know all columns of
-        let decls = if ident.name != "*" {
+        let mut decls = if ident.name != "*" {
             self.root_mod.lookup(&Ident {
                 path: ident.path.clone(),
                 name: "*".to_string(),
@@ -132,6 +249,14 @@ impl Context {
             HashSet::new()
         };
 
+        // names that don't name an input don't refer to columns merged by joins
+        if decls.len() > 1 {
+            decls.retain(|d| {
+                let column = d.clone().pop().unwrap() + Ident::from_name(&ident.name);
+                !self.root_mod.is_merged(&column)
+            });
+        }
+
         match decls.len() {
             0 => Err(format!("Unknown name {ident}")),
 
@@ -267,7 +392,7 @@ impl std::fmt::Display for DeclKind {
         match self {
             Self::Module(arg0) => f.debug_tuple("Module").field(arg0).finish(),
             Self::LayeredModules(arg0) => f.debug_tuple("LayeredModules").field(arg0).finish(),
-            Self::TableDecl(TableDecl { columns, expr }) => {
+            Self::TableDecl(TableDecl { columns, expr, .. }) => {
                 write!(f, "TableDef: {} {expr:?}", RelationColumns(columns))
             }
             Self::InstanceOf(arg0) => write!(f, "InstanceOf: {arg0}"),
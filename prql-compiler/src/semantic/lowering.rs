@@ -1,5 +1,5 @@
 use std::collections::hash_map::RandomState;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::iter::zip;
 
 use anyhow::Result;
@@ -7,21 +7,25 @@ use itertools::Itertools;
 
 use crate::ast::pl::fold::AstFold;
 use crate::ast::pl::{
-    self, Expr, ExprKind, FrameColumn, Ident, InterpolateItem, Range, SwitchCase, TableExternRef,
-    Ty, WindowFrame,
+    self, Expr, ExprKind, Frame, FrameColumn, Ident, InterpolateItem, Range, SwitchCase,
+    TableExternRef, Ty, WindowFrame,
 };
 use crate::ast::rq::{self, CId, Query, RelationColumn, TId, TableDecl, Transform};
-use crate::error::{Error, Reason, Span};
+use crate::error::{Error, ErrorCode, Reason, Span};
+use crate::notes::{Notes, OptimizationNote, Warning};
 use crate::semantic::module::Module;
+use crate::sql::Target;
 use crate::utils::{toposort, IdGenerator};
 
 use super::context::{self, Context, DeclKind};
+use super::type_resolver::validate_std_function_args;
+use super::RANKING_FUNCTIONS;
 
 /// Convert AST into IR and make sure that:
 /// - transforms are not nested,
 /// - transforms have correct partition, window and sort set,
 /// - make sure there are no unresolved
-pub fn lower_ast_to_ir(statements: Vec<pl::Stmt>, context: Context) -> Result<Query> {
+pub fn lower_ast_to_ir(statements: Vec<pl::Stmt>, context: Context) -> Result<(Query, Notes)> {
     let mut l = Lowerer::new(context);
 
     TableExtractor::extract(&mut l)?;
@@ -31,21 +35,56 @@ pub fn lower_ast_to_ir(statements: Vec<pl::Stmt>, context: Context) -> Result<Qu
 
     for statement in statements {
         match statement.kind {
-            pl::StmtKind::QueryDef(def) => query_def = Some(def),
+            pl::StmtKind::QueryDef(mut def) => {
+                if let Some(dialect) = def.other.remove("dialect") {
+                    let target =
+                        target_of_dialect(&dialect).map_err(|e| e.with_span(statement.span))?;
+                    l.context.notes.warn(Warning {
+                        construct: "dialect".to_string(),
+                        target: None,
+                        message: format!(
+                            "`dialect` in the query header is deprecated; use `target:{target}`"
+                        ),
+                        spans: statement.span.into_iter().collect(),
+                    });
+
+                    // `target` takes precedence over `dialect`
+                    def.other
+                        .entry("target".to_string())
+                        .or_insert_with(|| target.to_string());
+                }
+
+                // validate early, while we still know where the header is
+                if let Some(target) = def.other.get("target") {
+                    Target::from_name(target).map_err(|e| e.with_span(statement.span))?;
+                }
+                query_def = Some(def)
+            }
             pl::StmtKind::Main(expr) => {
                 let relation = l.lower_relation(*expr)?;
                 main_pipeline = Some(relation);
             }
-            pl::StmtKind::FuncDef(_) | pl::StmtKind::TableDef(_) => {}
+            pl::StmtKind::FuncDef(_)
+            | pl::StmtKind::TableDef(_)
+            | pl::StmtKind::VarDef(_)
+            | pl::StmtKind::Expect(_) => {}
         }
     }
 
-    Ok(Query {
+    let query = Query {
         def: query_def.unwrap_or_default(),
         tables: l.table_buffer,
         relation: main_pipeline
             .ok_or_else(|| Error::new(Reason::Simple("missing main pipeline".to_string())))?,
-    })
+    };
+    Ok((query, l.context.notes))
+}
+
+/// Parses the deprecated `dialect` header, which named targets without the
+/// `sql.` prefix, i.e. `prql dialect:mssql`.
+fn target_of_dialect(dialect: &str) -> Result<Target, Error> {
+    Target::from_name(dialect)
+        .or_else(|e| Target::from_name(&format!("sql.{dialect}")).map_err(|_| e))
 }
 
 struct Lowerer {
@@ -68,6 +107,15 @@ struct Lowerer {
 
     /// A buffer to be added into query tables
     table_buffer: Vec<TableDecl>,
+
+    /// Id of the parameter of the pipeline of the loop that is being lowered.
+    /// The pipeline starts from the frame preceding the loop, so this
+    /// parameter is not lowered into a From.
+    loop_param: Option<usize>,
+
+    /// Columns excluded with `select ![...]`, which are no longer included
+    /// when a wildcard of their table is expanded.
+    excluded: HashSet<CId>,
 }
 
 #[derive(Clone)]
@@ -76,8 +124,8 @@ enum LoweredTarget {
     Compute(CId),
 
     /// Lowered node was a pipeline input.
-    /// Contains mapping from column names to CIds, along with order in frame.
-    Input(HashMap<RelationColumn, (CId, usize)>),
+    /// Contains columns and their CIds, in order of the frame.
+    Input(Vec<(RelationColumn, CId)>),
 }
 
 impl Lowerer {
@@ -94,6 +142,8 @@ impl Lowerer {
             window: None,
             pipeline: Vec::new(),
             table_buffer: Vec::new(),
+            loop_param: None,
+            excluded: HashSet::new(),
         }
     }
 
@@ -114,6 +164,7 @@ impl Lowerer {
             ExprKind::TransformCall(_) => {
                 // pipeline that has to be pulled out into a table
                 let id = expr.id.unwrap();
+                let alias = expr.alias.clone();
 
                 // create a new table
                 let tid = self.tid.gen();
@@ -128,10 +179,11 @@ impl Lowerer {
                     id: tid,
                     name: None,
                     relation,
+                    materialized: None,
                 });
 
                 // return an instance of this new table
-                let table_ref = self.create_a_table_instance(id, None, tid);
+                let table_ref = self.create_a_table_instance(id, alias, tid);
 
                 let redirects = zip(cids, table_ref.columns.iter().map(|(_, c)| *c)).collect();
                 self.redirect_mappings(redirects);
@@ -150,6 +202,7 @@ impl Lowerer {
                 let relation = rq::Relation {
                     kind: rq::RelationKind::SString(items),
                     columns: cols.clone(),
+                    span: None,
                 };
 
                 log::debug!("lowering sstring table, columns = {:?}", cols);
@@ -157,11 +210,38 @@ impl Lowerer {
                     id: tid,
                     name: None,
                     relation,
+                    materialized: None,
                 });
 
                 // return an instance of this new table
                 self.create_a_table_instance(id, None, tid)
             }
+            ExprKind::Literal(pl::Literal::Relation(lit)) => {
+                let id = expr.id.unwrap();
+
+                // create a new table
+                let tid = self.tid.gen();
+
+                let cols = (lit.columns.iter())
+                    .map(|name| RelationColumn::Single(Some(name.clone())))
+                    .collect();
+                let relation = rq::Relation {
+                    kind: rq::RelationKind::Literal(lit),
+                    columns: cols,
+                    span: expr.span,
+                };
+
+                log::debug!("lowering literal table, columns = {:?}", relation.columns);
+                self.table_buffer.push(TableDecl {
+                    id: tid,
+                    name: None,
+                    relation,
+                    materialized: None,
+                });
+
+                // return an instance of this new table
+                self.create_a_table_instance(id, expr.alias, tid)
+            }
             _ => {
                 return Err(Error::new(Reason::Expected {
                     who: None,
@@ -170,6 +250,7 @@ impl Lowerer {
                 })
                 .with_help("are you missing `from` statement?")
                 .with_span(expr.span)
+                .with_code(ErrorCode::Invalid)
                 .into())
             }
         })
@@ -184,7 +265,7 @@ impl Lowerer {
                     }
                 }
                 LoweredTarget::Input(mapping) => {
-                    for (cid, _) in mapping.values_mut() {
+                    for (_, cid) in mapping.iter_mut() {
                         if let Some(new) = redirects.get(cid) {
                             *cid = *new;
                         }
@@ -214,14 +295,8 @@ impl Lowerer {
 
         log::debug!("... columns = {:?}", columns);
 
-        let input_cids: HashMap<_, _> = columns
-            .iter()
-            .cloned()
-            .enumerate()
-            .map(|(index, (col, cid))| (col, (cid, index)))
-            .collect();
         self.node_mapping
-            .insert(id, LoweredTarget::Input(input_cids));
+            .insert(id, LoweredTarget::Input(columns.clone()));
         rq::TableRef {
             source: tid,
             name,
@@ -243,6 +318,7 @@ impl Lowerer {
         let relation = rq::Relation {
             kind: rq::RelationKind::Pipeline(transforms),
             columns,
+            span: None,
         };
         Ok(relation)
     }
@@ -251,6 +327,9 @@ impl Lowerer {
     fn lower_pipeline(&mut self, ast: pl::Expr) -> Result<()> {
         let transform_call = match ast.kind {
             pl::ExprKind::TransformCall(transform) => transform,
+            _ if ast.target_id.is_some() && ast.target_id == self.loop_param => {
+                return Ok(());
+            }
             _ => {
                 let table_ref = self.lower_table_ref(ast)?;
                 self.pipeline.push(Transform::From(table_ref));
@@ -279,6 +358,30 @@ impl Lowerer {
             }
             pl::TransformKind::Select { assigns, .. } => {
                 let select = self.declare_as_columns(assigns, false)?;
+
+                // the SQL backend keeps only the last select of each query anyway
+                let prev_select = self.pipeline.last().and_then(|t| t.as_select());
+                if prev_select == Some(&select) {
+                    self.context.notes.optimizations.push(OptimizationNote::new(
+                        "lowering",
+                        ast.span,
+                        "select has the same columns as the preceding select, so it has no effect",
+                    ));
+                }
+                self.pipeline.push(Transform::Select(select));
+            }
+            pl::TransformKind::Exclude { columns } => {
+                let excluded = self.declare_as_columns(columns, false)?;
+                self.excluded.extend(excluded.iter().cloned());
+
+                // known columns are left out of the frame, the rest are in
+                // wildcards and have to be excluded from them
+                let frame = ast.ty.clone().unwrap().into_table().unwrap_or_default();
+                let select = (self.columns_of_frame(&frame)?.into_iter())
+                    .map(|(_, cid)| cid)
+                    .collect();
+
+                self.pipeline.push(Transform::Exclude(excluded));
                 self.pipeline.push(Transform::Select(select));
             }
             pl::TransformKind::Filter { filter, .. } => {
@@ -286,29 +389,63 @@ impl Lowerer {
 
                 self.pipeline.push(Transform::Filter(filter));
             }
-            pl::TransformKind::Aggregate { assigns, .. } => {
+            pl::TransformKind::Aggregate { assigns, grouping } => {
                 let window = self.window.take();
 
                 let compute = self.declare_as_columns(assigns, true)?;
 
                 let partition = window.unwrap().partition;
-                self.pipeline
-                    .push(Transform::Aggregate { partition, compute });
+                self.pipeline.push(Transform::Aggregate {
+                    partition,
+                    compute,
+                    grouping,
+                });
             }
             pl::TransformKind::Sort { by, .. } => {
                 let sorts = self.lower_sorts(by)?;
                 self.pipeline.push(Transform::Sort(sorts));
             }
-            pl::TransformKind::Take { range, .. } => {
+            pl::TransformKind::Take { range, percent } => {
                 let window = self.window.take().unwrap_or_default();
                 let range = self.lower_range(range)?;
 
                 validate_take_range(&range, ast.span)?;
 
+                // rows of groups are numbered, which cannot express a percentage
+                if percent && !window.partition.is_empty() {
+                    return Err(Error::new(Reason::Simple(
+                        "`take` with a percentage cannot be used in `group`".to_string(),
+                    ))
+                    .with_span(ast.span)
+                    .with_code(ErrorCode::Unsupported)
+                    .into());
+                }
+
                 self.pipeline.push(Transform::Take(rq::Take {
                     range,
                     partition: window.partition,
                     sort: window.sort,
+                    percent,
+                }));
+            }
+            pl::TransformKind::Sample { size, percent } => {
+                if self
+                    .window
+                    .as_ref()
+                    .map_or(false, |w| !w.partition.is_empty())
+                {
+                    return Err(Error::new(Reason::Simple(
+                        "`sample` cannot be used in `group`".to_string(),
+                    ))
+                    .with_span(ast.span)
+                    .with_code(ErrorCode::Unsupported)
+                    .into());
+                }
+
+                self.pipeline.push(Transform::Sample(rq::Sample {
+                    size,
+                    percent,
+                    span: ast.span,
                 }));
             }
             pl::TransformKind::Join {
@@ -329,6 +466,40 @@ impl Lowerer {
                 let transform = Transform::Concat(bottom);
                 self.pipeline.push(transform);
             }
+            pl::TransformKind::Intersect(bottom) => {
+                let bottom = self.lower_table_ref(*bottom)?;
+
+                self.pipeline.push(Transform::Intersect(bottom));
+            }
+            pl::TransformKind::Remove(bottom) => {
+                let bottom = self.lower_table_ref(*bottom)?;
+
+                self.pipeline.push(Transform::Remove(bottom));
+            }
+            pl::TransformKind::Unique => {
+                self.pipeline.push(Transform::Unique);
+            }
+            pl::TransformKind::Loop(pipeline) => {
+                // a recursive CTE cannot contain another one
+                if self.loop_param.is_some() {
+                    return Err(Error::new(Reason::Simple(
+                        "`loop` cannot be used in the pipeline of another `loop`".to_string(),
+                    ))
+                    .with_span(ast.span)
+                    .with_code(ErrorCode::Unsupported)
+                    .into());
+                }
+
+                let closure = pipeline.kind.into_closure().unwrap();
+                let param_id = closure.params[0].name.parse::<usize>().unwrap();
+
+                self.loop_param = Some(param_id);
+                let step = self.lower_relation(*closure.body);
+                self.loop_param = None;
+
+                let step = step?.kind.into_pipeline().unwrap();
+                self.pipeline.push(Transform::Loop(step));
+            }
             pl::TransformKind::Group { .. } | pl::TransformKind::Window { .. } => unreachable!(
                 "transform `{}` cannot be lowered.",
                 (*transform_call.kind).as_ref()
@@ -349,10 +520,20 @@ impl Lowerer {
 
     fn lower_sorts(&mut self, by: Vec<pl::ColumnSort>) -> Result<Vec<pl::ColumnSort<CId>>> {
         by.into_iter()
-            .map(|pl::ColumnSort { column, direction }| {
-                let column = self.declare_as_column(column, false)?;
-                Ok(pl::ColumnSort { direction, column })
-            })
+            .map(
+                |pl::ColumnSort {
+                     column,
+                     direction,
+                     nulls,
+                 }| {
+                    let column = self.declare_as_column(column, false)?;
+                    Ok(pl::ColumnSort {
+                        direction,
+                        column,
+                        nulls,
+                    })
+                },
+            )
             .try_collect()
     }
 
@@ -366,6 +547,17 @@ impl Lowerer {
 
         log::debug!("push_select of a frame: {:?}", frame);
 
+        let columns = self.columns_of_frame(&frame)?;
+
+        let (cols, cids) = columns.into_iter().unzip();
+
+        log::debug!("... cids={:?}", cids);
+        transforms.push(Transform::Select(cids));
+
+        Ok(cols)
+    }
+
+    fn columns_of_frame(&mut self, frame: &Frame) -> Result<Vec<(RelationColumn, CId)>> {
         let mut columns = Vec::new();
 
         // normal columns
@@ -383,24 +575,16 @@ impl Lowerer {
                     match &self.node_mapping[&input.id] {
                         LoweredTarget::Compute(_cid) => unreachable!(),
                         LoweredTarget::Input(input_cols) => {
-                            let mut input_cols = input_cols.iter().collect_vec();
-                            input_cols.sort_by_key(|e| e.1 .1);
-
-                            for (col, (cid, _)) in input_cols {
-                                columns.push((col.clone(), *cid));
-                            }
+                            let input_cols = (input_cols.iter())
+                                .filter(|(_, cid)| !self.excluded.contains(cid))
+                                .cloned();
+                            columns.extend(input_cols);
                         }
                     }
                 }
             }
         }
-
-        let (cols, cids) = columns.into_iter().unzip();
-
-        log::debug!("... cids={:?}", cids);
-        transforms.push(Transform::Select(cids));
-
-        Ok(cols)
+        Ok(columns)
     }
 
     fn declare_as_columns(
@@ -423,6 +607,7 @@ impl Lowerer {
         let alias = expr_ast.alias.clone();
         let has_alias = alias.is_some();
         let needs_window = expr_ast.needs_window;
+        let ignores_sort = expr_ast.ignores_sort;
         expr_ast.needs_window = false;
         let alias_for = if has_alias {
             expr_ast.kind.as_ident().map(|x| x.name.clone())
@@ -444,7 +629,8 @@ impl Lowerer {
 
         // determine window
         let window = if needs_window {
-            self.window.clone()
+            let window = self.window.clone();
+            window.map(|w| self.window_of_ranking(&expr, ignores_sort, w))
         } else {
             None
         };
@@ -463,6 +649,56 @@ impl Lowerer {
         Ok(cid)
     }
 
+    /// Ranking functions number rows in the ambient sort, unless called with
+    /// `over:()`. They ignore the frame, so the default one is not rendered.
+    fn window_of_ranking(
+        &mut self,
+        expr: &rq::Expr,
+        ignores_sort: bool,
+        mut window: rq::Window,
+    ) -> rq::Window {
+        let rq::ExprKind::BuiltInFunction { name, .. } = &expr.kind else {
+            return window;
+        };
+        let name = name.strip_prefix("std.").unwrap_or(name);
+        if !RANKING_FUNCTIONS.contains(&name) {
+            return window;
+        }
+
+        if ignores_sort {
+            window.sort.clear();
+        } else if window.sort.is_empty() && name == "row_number" {
+            self.context.notes.warn(Warning {
+                construct: "row_number".to_string(),
+                target: None,
+                message: "no `sort` applies, so rows are numbered in an arbitrary order \
+                    (use `over:()` if this is intended)"
+                    .to_string(),
+                spans: Vec::new(),
+            });
+        }
+
+        let is_unbounded = window.frame.kind == pl::WindowKind::Rows
+            && window.frame.range.start.is_none()
+            && window.frame.range.end.is_none();
+        if is_unbounded && !window.sort.is_empty() {
+            // default frame of a sorted window
+            let current_row = rq::Expr {
+                kind: rq::ExprKind::Literal(pl::Literal::Integer(0)),
+                span: None,
+            };
+            window.frame = WindowFrame {
+                kind: pl::WindowKind::Range,
+                range: Range {
+                    start: None,
+                    end: Some(current_row),
+                },
+            };
+        }
+
+        window
+    }
+
     fn lower_expr(&mut self, ast: pl::Expr) -> Result<rq::Expr> {
         if ast.needs_window {
             let span = ast.span;
@@ -472,6 +708,10 @@ impl Lowerer {
             return Ok(rq::Expr { kind, span });
         }
 
+        if let pl::ExprKind::Closure(closure) = &ast.kind {
+            validate_std_function_args(closure, ast.span)?;
+        }
+
         let kind = match ast.kind {
             pl::ExprKind::Ident(ident) => {
                 log::debug!("lowering ident {ident} (target {:?})", ast.target_id);
@@ -523,6 +763,13 @@ impl Lowerer {
 
                 rq::ExprKind::BuiltInFunction { name, args }
             }
+            pl::ExprKind::Quantified {
+                quantifier,
+                pipeline,
+            } => rq::ExprKind::Quantified {
+                quantifier,
+                relation: self.lower_table_ref(*pipeline)?,
+            },
 
             pl::ExprKind::FuncCall(_)
             | pl::ExprKind::Range(_)
@@ -583,13 +830,23 @@ impl Lowerer {
                 };
                 log::trace!("lookup cid of name={name:?} in input {input_columns:?}");
 
-                if let Some((cid, _)) = input_columns.get(&name) {
+                if let Some((_, cid)) = input_columns.iter().find(|(col, _)| col == &name) {
                     *cid
                 } else {
-                    panic!("cannot find cid by id={id} and name={name:?}");
+                    return Err(Error::new(Reason::Simple(format!(
+                        "cannot find cid by id={id} and name={name:?}"
+                    )))
+                    .with_code(ErrorCode::Internal)
+                    .into());
                 }
             }
-            None => panic!("cannot find cid by id={id}"),
+            None => {
+                return Err(
+                    Error::new(Reason::Simple(format!("cannot find cid by id={id}")))
+                        .with_code(ErrorCode::Internal)
+                        .into(),
+                )
+            }
         };
 
         Ok(cid)
@@ -685,7 +942,11 @@ fn lower_table(lowerer: &mut Lowerer, table: context::TableDecl, fq_ident: Ident
         .entry(fq_ident.clone())
         .or_insert_with(|| lowerer.tid.gen());
 
-    let context::TableDecl { columns, expr } = table;
+    let context::TableDecl {
+        columns,
+        expr,
+        materialized,
+    } = table;
 
     let relation = if let Some(expr) = expr {
         // this is a CTE
@@ -697,7 +958,12 @@ fn lower_table(lowerer: &mut Lowerer, table: context::TableDecl, fq_ident: Ident
 
     log::debug!("lowering table {name:?}, columns = {:?}", relation.columns);
 
-    let table = TableDecl { id, name, relation };
+    let table = TableDecl {
+        id,
+        name,
+        relation,
+        materialized,
+    };
     lowerer.table_buffer.push(table);
     Ok(())
 }
@@ -709,6 +975,7 @@ fn relation_from_extern_ref(mut columns: Vec<RelationColumn>, table_name: String
     rq::Relation {
         kind: rq::RelationKind::ExternRef(TableExternRef::LocalTable(table_name)),
         columns,
+        span: None,
     }
 }
 
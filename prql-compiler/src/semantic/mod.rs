@@ -15,24 +15,74 @@ pub use self::module::Module;
 use crate::ast::pl::frame::{Frame, FrameColumn};
 use crate::ast::pl::Stmt;
 use crate::ast::rq::Query;
+use crate::cancellation::Cancellation;
+use crate::describe;
+use crate::error::{Error, ErrorCode, Reason};
+use crate::notes::Notes;
+use crate::policy::Policy;
 use crate::PRQL_VERSION;
 
 use anyhow::{bail, Result};
 use semver::{Version, VersionReq};
 
+/// Std functions that number rows of their window. They follow the sort of the
+/// pipeline, unless called with `over:()`.
+pub(crate) const RANKING_FUNCTIONS: [&str; 4] = ["row_number", "rank", "rank_dense", "ntile"];
+
 /// Runs semantic analysis on the query and lowers PL to RQ.
 pub fn resolve(statements: Vec<Stmt>) -> Result<Query> {
-    let context = load_std_lib();
+    resolve_and_note(statements).map(|(query, _)| query)
+}
+
+/// Same as [resolve], but also returns notes about normalizations that were
+/// applied to the query.
+pub fn resolve_and_note(statements: Vec<Stmt>) -> Result<(Query, Notes)> {
+    resolve_cancellable(statements, None)
+}
+
+/// Same as [resolve_and_note], but aborts with an error when the cancellation
+/// is triggered.
+pub fn resolve_cancellable(
+    statements: Vec<Stmt>,
+    cancellation: Option<Cancellation>,
+) -> Result<(Query, Notes)> {
+    resolve_with_policy(statements, cancellation, None)
+}
+
+/// Same as [resolve_cancellable], but also fails on references to tables and
+/// functions that the policy denies.
+pub fn resolve_with_policy(
+    statements: Vec<Stmt>,
+    cancellation: Option<Cancellation>,
+    policy: Option<Policy>,
+) -> Result<(Query, Notes)> {
+    if let Some(policy) = &policy {
+        policy.check_stmts(&statements)?;
+    }
+
+    let mut context = load_std_lib();
+    context.cancellation = cancellation;
+    context.policy = policy;
 
     let (statements, context) = resolver::resolve(statements, context)?;
 
-    let query = lowering::lower_ast_to_ir(statements, context)?;
+    lower(statements, context)
+}
+
+/// Lowers statements resolved by [resolve_only] to RQ.
+pub fn lower(statements: Vec<Stmt>, context: Context) -> Result<(Query, Notes)> {
+    let (query, mut notes) = lowering::lower_ast_to_ir(statements, context)?;
+
+    // implicit casts exist, so mismatched join keys and `in` lists are not errors
+    notes
+        .warnings
+        .extend(describe::comparison_type_mismatches(&query));
 
     if let Some(ref version) = query.def.version {
         check_query_version(version, &PRQL_VERSION)?;
     }
 
-    Ok(query)
+    Ok((query, notes))
 }
 
 /// Runs semantic analysis on the query.
@@ -55,13 +105,17 @@ pub fn load_std_lib() -> Context {
         ..Context::default()
     };
 
-    let (_, context) = resolver::resolve(statements, context).unwrap();
+    let (_, mut context) = resolver::resolve(statements, context).unwrap();
+    context.std_loaded = true;
     context
 }
 
 fn check_query_version(query_version: &VersionReq, prql_version: &Version) -> Result<()> {
     if !query_version.matches(prql_version) {
-        bail!("This query uses a version of PRQL that is not supported by your prql-compiler. You may want to upgrade the compiler.");
+        bail!(Error::new(Reason::Simple(
+            "This query uses a version of PRQL that is not supported by your prql-compiler. You may want to upgrade the compiler.".to_string()
+        ))
+        .with_code(ErrorCode::Unsupported));
     }
 
     Ok(())
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::ast::pl::{Expr, Ident};
 use crate::ast::rq::RelationColumn;
+use crate::error::{Error, ErrorCode, Reason};
 
 use super::context::{Decl, DeclKind, TableDecl};
 use super::{Frame, FrameColumn};
@@ -28,6 +29,11 @@ pub struct Module {
 
     /// A declaration that has been shadowed (overwritten) by this module.
     pub shadowed: Option<Box<Decl>>,
+
+    /// Names (relative to the redirects) that lookups through the redirects
+    /// only find when nothing else is found. See [Frame::merged].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub merged: Vec<Ident>,
 }
 
 impl Module {
@@ -50,11 +56,13 @@ impl Module {
                                 TableDecl {
                                     columns: vec![RelationColumn::Wildcard],
                                     expr: None,
+                                    materialized: None,
                                 },
                             )))),
                         )]),
                         shadowed: None,
                         redirects: vec![],
+                        merged: vec![],
                     })),
                 ),
                 (NS_STD.to_string(), Decl::from(DeclKind::default())),
@@ -66,6 +74,7 @@ impl Module {
                 Ident::from_name(NS_PARAM),
                 Ident::from_name(NS_STD),
             ],
+            merged: vec![],
         }
     }
 
@@ -79,7 +88,10 @@ impl Module {
                 DeclKind::Module(inner) => {
                     ns = inner;
                 }
-                _ => bail!("path does not resolve to a module or a table"),
+                _ => bail!(Error::new(Reason::Simple(
+                    "path does not resolve to a module or a table".to_string()
+                ))
+                .with_code(ErrorCode::UnknownName)),
             }
         }
 
@@ -189,16 +201,36 @@ impl Module {
 
         res.extend(lookup_in(self, ident.clone()));
 
+        let mut merged = HashSet::new();
         for redirect in &self.redirects {
             log::trace!("... following redirect {redirect}");
-            res.extend(lookup_in(self, redirect.clone() + ident.clone()));
+            let redirected = redirect.clone() + ident.clone();
+            if self.merged.contains(&redirected) {
+                merged.extend(lookup_in(self, redirected));
+            } else {
+                res.extend(lookup_in(self, redirected));
+            }
+        }
+        if res.is_empty() {
+            res = merged;
         }
         res
     }
 
+    /// Whether the column of a frame namespace, i.e. `_frame.salaries.emp_no`,
+    /// is merged into a column of another input. See [Frame::merged].
+    pub(super) fn is_merged(&self, column: &Ident) -> bool {
+        let (namespace, Some(column)) = column.clone().pop_front() else {
+            return false;
+        };
+        let namespace = self.names.get(&namespace).and_then(|d| d.kind.as_module());
+        namespace.map_or(false, |ns| ns.merged.contains(&column))
+    }
+
     pub(super) fn insert_frame(&mut self, frame: &Frame, namespace: &str) {
         let namespace = self.names.entry(namespace.to_string()).or_default();
         let namespace = namespace.kind.as_module_mut().unwrap();
+        namespace.merged.extend(frame.merged.iter().cloned());
 
         for column in &frame.columns {
             // determine input name
@@ -342,6 +374,10 @@ impl std::fmt::Debug for Module {
         if let Some(f) = &self.shadowed {
             ds.field("shadowed", f);
         }
+        if !self.merged.is_empty() {
+            let merged = self.merged.iter().map(|x| x.to_string()).collect_vec();
+            ds.field("merged", &merged);
+        }
         ds.finish()
     }
 }
@@ -1,21 +1,26 @@
 use std::collections::HashMap;
-use std::iter::zip;
+use std::iter::{once, zip};
 
 use anyhow::{anyhow, bail, Result};
 use itertools::{Itertools, Position};
 
 use crate::ast::pl::{fold::*, *};
 use crate::ast::rq::RelationColumn;
-use crate::error::{Error, Reason, Span};
+use crate::cancellation;
+use crate::error::{Error, ErrorCode, Reason, Span};
+use crate::notes::{Notes, Warning};
 use crate::semantic::context::TableDecl;
 use crate::semantic::static_analysis;
 use crate::utils::IdGenerator;
 
 use super::context::{Context, Decl, DeclKind};
-use super::module::{Module, NS_FRAME, NS_FRAME_RIGHT, NS_PARAM};
+use super::module::{Module, NS_DEFAULT_DB, NS_FRAME, NS_FRAME_RIGHT, NS_PARAM, NS_STD};
 use super::reporting::debug_call_tree;
 use super::transforms::{self, Flattener};
-use super::type_resolver::{resolve_type, type_of_closure, validate_type};
+use super::type_resolver::{
+    resolve_type, type_of_closure, validate_std_function_args, validate_type,
+};
+use super::RANKING_FUNCTIONS;
 
 /// Runs semantic analysis on the query, using current state.
 ///
@@ -36,6 +41,10 @@ pub struct Resolver {
     /// Sometimes ident closures must be resolved and sometimes not. See [test::test_func_call_resolve].
     in_func_call_name: bool,
 
+    /// The transform that dropped columns of the relation in scope, i.e.
+    /// `select`, for the help of names that cannot be resolved.
+    narrowed_by: Option<&'static str>,
+
     pub(super) id: IdGenerator<usize>,
 }
 
@@ -45,6 +54,7 @@ impl Resolver {
             decls: context,
             default_namespace: None,
             in_func_call_name: false,
+            narrowed_by: None,
             id: IdGenerator::new(),
         }
     }
@@ -62,7 +72,13 @@ impl AstFold for Resolver {
 
             let kind = match stmt.kind {
                 StmtKind::QueryDef(d) => StmtKind::QueryDef(d),
-                StmtKind::FuncDef(func_def) => {
+                StmtKind::FuncDef(mut func_def) => {
+                    if let Some(position) = relation_param(&func_def) {
+                        let param = &func_def.positional_params[position];
+                        if matches!(param.ty, Some(Ty::Table(_))) {
+                            func_def = relation_func_def(func_def, position);
+                        }
+                    }
                     self.decls.declare_func(func_def, stmt.id);
                     continue;
                 }
@@ -83,10 +99,23 @@ impl AstFold for Resolver {
                     self.decls.declare_table(table_def, stmt.id);
                     continue;
                 }
+                StmtKind::VarDef(var_def) => {
+                    ensure_constant(&var_def, &self.decls)?;
+
+                    let value = self.fold_expr(*var_def.value)?;
+                    self.decls.declare_var(var_def.name, value, stmt.id);
+                    continue;
+                }
                 StmtKind::Main(expr) => {
                     let expr = Flattener::fold(self.fold_expr(*expr)?);
                     StmtKind::Main(Box::new(expr))
                 }
+                StmtKind::Expect(expect) => {
+                    let main = (res.iter().rev()).find_map(|s: &Stmt| s.kind.as_main());
+                    let main = main.map(|expr| expr.as_ref());
+                    check_expect(&expect, main, stmt.span, &mut self.decls)?;
+                    continue;
+                }
             };
 
             res.push(Stmt { kind, ..stmt })
@@ -112,7 +141,11 @@ impl AstFold for Resolver {
         let mut r = match node.kind {
             ExprKind::Ident(ident) => {
                 log::debug!("resolving ident {ident}...");
-                let fq_ident = self.resolve_ident(&ident, node.span)?;
+                let fq_ident = if self.in_func_call_name {
+                    self.resolve_ident(&ident, node.span)?
+                } else {
+                    self.resolve_value_ident(&ident, node.span)?
+                };
                 log::debug!("... resolved to {fq_ident}");
                 let entry = self.decls.root_mod.get(&fq_ident).unwrap();
                 log::debug!("... which is {entry}");
@@ -140,7 +173,11 @@ impl AstFold for Resolver {
                         ..node
                     },
 
-                    DeclKind::TableDecl(TableDecl { columns, .. }) => {
+                    DeclKind::TableDecl(TableDecl { columns, expr, .. }) => {
+                        if let (Some(policy), None) = (&self.decls.policy, expr) {
+                            policy.check_table(&extern_table_name(&fq_ident), span)?;
+                        }
+
                         let alias = node.alias.unwrap_or_else(|| ident.name.clone());
 
                         let instance_frame = Frame {
@@ -164,6 +201,7 @@ impl AstFold for Resolver {
                                     },
                                 })
                                 .collect(),
+                            merged: Vec::new(),
                         };
 
                         log::debug!("instanced table {fq_ident} as {instance_frame:?}");
@@ -234,6 +272,22 @@ impl AstFold for Resolver {
                 Expr { kind, ..node }
             }
 
+            ExprKind::Quantified {
+                quantifier,
+                pipeline,
+            } => {
+                let pipeline = self.fold_expr(*pipeline)?;
+                ensure_single_column(&pipeline, quantifier)?;
+
+                Expr {
+                    kind: ExprKind::Quantified {
+                        quantifier,
+                        pipeline: Box::new(pipeline),
+                    },
+                    ..node
+                }
+            }
+
             item => Expr {
                 kind: fold_expr_kind(self, item)?,
                 ..node
@@ -246,11 +300,232 @@ impl AstFold for Resolver {
         if r.ty.is_none() {
             r.ty = Some(resolve_type(&r)?);
         }
+        // an aliased pipeline (i.e. `join s = (from salaries | ...)`) is a
+        // relation named by the alias
+        if let (ExprKind::TransformCall(_), Some(alias), Some(Ty::Table(frame))) =
+            (&r.kind, &r.alias, &mut r.ty)
+        {
+            if !frame.inputs.is_empty() {
+                frame.rename(alias);
+            }
+        }
         let r = static_analysis::static_analysis(r);
         Ok(r)
     }
 }
 
+/// Value of `let` is substituted wherever it is referenced, so it cannot
+/// reference columns or tables, only other constants.
+fn ensure_constant(var_def: &VarDef, context: &Context) -> Result<()> {
+    let mut collector = IdentCollector::default();
+    collector.fold_expr(var_def.value.as_ref().clone())?;
+
+    for (ident, span) in collector.idents {
+        let decls = context.root_mod.lookup(&ident);
+        let decl = decls.iter().exactly_one().ok();
+        let decl = decl.and_then(|fq_ident| context.root_mod.get(fq_ident));
+
+        if !matches!(decl.map(|d| &d.kind), Some(DeclKind::Expr(_))) {
+            bail!(Error::new(Reason::Simple(format!(
+                "`let {}` can only reference constants, but it references `{ident}`",
+                var_def.name
+            )))
+            .with_span(span)
+            .with_help("use `derive` to compute a column")
+            .with_code(ErrorCode::Invalid));
+        }
+    }
+    Ok(())
+}
+
+/// Compares the columns of the main pipeline with `expect columns [...]`.
+///
+/// When the pipeline produces all columns of a table (`table.*`), the
+/// expectation cannot be verified, which is reported as a warning.
+fn check_expect(
+    expect: &Expect,
+    main: Option<&Expr>,
+    span: Option<Span>,
+    context: &mut Context,
+) -> Result<()> {
+    let frame = main
+        .and_then(|main| main.ty.as_ref())
+        .and_then(|t| t.as_table());
+    let Some(frame) = frame else {
+        bail!(Error::new(Reason::Simple(
+            "`expect columns` must follow the main pipeline".to_string()
+        ))
+        .with_span(span)
+        .with_code(ErrorCode::Invalid));
+    };
+
+    let mut found = Vec::new();
+    for column in &frame.columns {
+        match column {
+            FrameColumn::Single { name, .. } => {
+                let name = name.as_ref().map(|n| n.name.clone());
+                found.push(name.unwrap_or_else(|| "<unnamed>".to_string()));
+            }
+            FrameColumn::Wildcard { input_name } => {
+                context.notes.warn(Warning {
+                    construct: "expect".to_string(),
+                    target: None,
+                    message: format!(
+                        "`expect columns` cannot be verified, since columns of `{input_name}` are not known"
+                    ),
+                    spans: span.into_iter().collect(),
+                });
+                return Ok(());
+            }
+        }
+    }
+
+    let missing = (expect.columns.iter())
+        .filter(|c| !found.contains(c))
+        .map(|c| format!("`{c}`"))
+        .join(", ");
+    let extra = (found.iter())
+        .filter(|c| !expect.columns.contains(c))
+        .map(|c| format!("`{c}`"))
+        .join(", ");
+    if missing.is_empty() && extra.is_empty() {
+        return Ok(());
+    }
+
+    let mut delta = Vec::new();
+    if !missing.is_empty() {
+        delta.push(format!("missing {missing}"));
+    }
+    if !extra.is_empty() {
+        delta.push(format!("extra {extra}"));
+    }
+    bail!(Error::new(Reason::Simple(format!(
+        "columns of the main pipeline do not match `expect columns`: {}",
+        delta.join("; ")
+    )))
+    .with_span(span)
+    .with_help(format!("the pipeline produces [{}]", found.join(", ")))
+    .with_code(ErrorCode::Mismatch));
+}
+
+/// Values of `all (...)` and `any (...)` are compared one by one, so the
+/// pipeline must produce exactly one column.
+fn ensure_single_column(pipeline: &Expr, quantifier: Quantifier) -> Result<()> {
+    let columns = match &pipeline.ty {
+        Some(Ty::Table(frame)) => Some(&frame.columns),
+        _ => None,
+    };
+
+    match columns.map(|c| c.as_slice()) {
+        Some([FrameColumn::Single { .. }]) => Ok(()),
+        Some(columns) => {
+            let mut error = Error::new(Reason::Simple(format!(
+                "`{quantifier}` needs a pipeline that produces exactly one column"
+            )))
+            .with_span(pipeline.span)
+            .with_code(ErrorCode::Invalid);
+
+            let first_named = columns.iter().find_map(|col| match col {
+                FrameColumn::Single {
+                    name: Some(name), ..
+                } => Some(&name.name),
+                _ => None,
+            });
+            if let Some(name) = first_named {
+                error = error.with_help(format!(
+                    "select the compared column, i.e. append `select {name}` to the pipeline"
+                ));
+            }
+            Err(error.into())
+        }
+        None => bail!(Error::new(Reason::Expected {
+            who: Some(quantifier.to_string()),
+            expected: "a pipeline that resolves to a table".to_string(),
+            found: format!("`{pipeline}`"),
+        })
+        .with_span(pipeline.span)
+        .with_code(ErrorCode::Mismatch)),
+    }
+}
+
+/// Collects idents that are used as values (not as names of called functions).
+#[derive(Default)]
+struct IdentCollector {
+    idents: Vec<(Ident, Option<Span>)>,
+}
+
+impl AstFold for IdentCollector {
+    fn fold_expr(&mut self, expr: Expr) -> Result<Expr> {
+        if let ExprKind::Ident(ident) = &expr.kind {
+            self.idents.push((ident.clone(), expr.span));
+        }
+        Ok(Expr {
+            kind: fold_expr_kind(self, expr.kind)?,
+            ..expr
+        })
+    }
+
+    fn fold_func_call(&mut self, func_call: FuncCall) -> Result<FuncCall> {
+        Ok(FuncCall {
+            name: func_call.name,
+            args: self.fold_exprs(func_call.args)?,
+            named_args: (func_call.named_args.into_iter())
+                .map(|(name, arg)| Ok((name, self.fold_expr(arg)?)))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+/// Name of the transform that dropped columns of a relation, looking through
+/// transforms that keep all columns of their input.
+fn narrowing_transform(relation: &Expr) -> Option<&'static str> {
+    let ExprKind::TransformCall(call) = &relation.kind else {
+        return None;
+    };
+    match call.kind.as_ref() {
+        TransformKind::Select { .. } => Some("select"),
+        TransformKind::Aggregate { .. } => Some("aggregate"),
+        TransformKind::Derive { .. }
+        | TransformKind::Filter { .. }
+        | TransformKind::Sort { .. }
+        | TransformKind::Take { .. }
+        | TransformKind::Unique => narrowing_transform(&call.input),
+        _ => None,
+    }
+}
+
+/// Name of a table of the database, as it is referenced in the query.
+fn extern_table_name(fq_ident: &Ident) -> String {
+    (fq_ident.path.iter())
+        .skip_while(|part| *part == NS_DEFAULT_DB)
+        .chain(once(&fq_ident.name))
+        .join(".")
+}
+
+/// Removes `over:()` from named args of a ranking function and returns
+/// whether it was passed.
+fn take_over_of_ranking(closure: &Closure, named_args: &mut HashMap<String, Expr>) -> Result<bool> {
+    let is_ranking = (closure.name.as_ref()).map_or(false, |n| {
+        n.path == [NS_STD] && RANKING_FUNCTIONS.contains(&n.name.as_str())
+    });
+    if !is_ranking {
+        return Ok(false);
+    }
+    let Some(over) = named_args.remove("over") else {
+        return Ok(false);
+    };
+
+    if !matches!(over.kind, ExprKind::Literal(Literal::Null)) {
+        bail!(Error::new(Reason::Expected {
+            who: Some("parameter `over`".to_string()),
+            expected: "`()`".to_string(),
+            found: format!("{over}"),
+        })
+        .with_span(over.span))
+    }
+    Ok(true)
+}
+
 fn closure_of_func_def(func_def: &FuncDef, fq_ident: Ident) -> Closure {
     Closure {
         name: Some(fq_ident),
@@ -266,11 +541,52 @@ fn closure_of_func_def(func_def: &FuncDef, fq_ident: Ident) -> Closure {
     }
 }
 
+/// A function whose body is a pipeline starting with one of its params is
+/// a user-defined transform when a relation is piped into it:
+/// `func only_active rel -> (rel | filter active)`.
+/// Elsewhere, like `func median x -> (x | percentile 50)` in a derive, it is
+/// a plain function, unless the param is annotated as `<table>`.
+///
+/// Returns position of that param.
+fn relation_param(func_def: &FuncDef) -> Option<usize> {
+    let ExprKind::Pipeline(Pipeline { exprs }) = &func_def.body.kind else {
+        return None;
+    };
+    match exprs.first().map(|e| &e.kind) {
+        Some(ExprKind::Ident(ident)) if ident.path.is_empty() => (func_def.positional_params)
+            .iter()
+            .position(|p| p.name == ident.name),
+        _ => None,
+    }
+}
+
+/// Converts a function into a transform, by typing its relation param as a
+/// table and moving it to the last position, so it receives the value piped
+/// into the function, as it does with std transforms.
+fn relation_func_def(mut func_def: FuncDef, position: usize) -> FuncDef {
+    let mut param = func_def.positional_params.remove(position);
+    param.ty.get_or_insert(Ty::Table(Frame::default()));
+    func_def.positional_params.push(param);
+    func_def
+        .return_ty
+        .get_or_insert(Ty::Table(Frame::default()));
+
+    func_def
+}
+
 impl Resolver {
     fn resolve_pipeline(&mut self, Pipeline { mut exprs }: Pipeline) -> Result<Expr> {
         let mut value = exprs.remove(0);
         value = self.fold_expr(value)?;
 
+        // whether the pipeline transforms a relation, either a resolved one or
+        // the one that will be passed to the leading transform
+        let pipes_relation = match &value.kind {
+            ExprKind::Closure(closure) => (closure.params.get(closure.args.len()))
+                .map_or(false, |p| matches!(p.ty, Some(Ty::Table(_)))),
+            _ => matches!(value.ty, Some(Ty::Table(_))),
+        };
+
         // This is a workaround for pipelines that start with a transform.
         // It checks if first value has resolved to a closure, and if it has,
         // constructs an adhoc closure around the pipeline.
@@ -294,10 +610,21 @@ impl Resolver {
         // the beef of this function: wrapping into func calls
         for expr in exprs {
             let span = expr.span;
+            let expr = if pipes_relation {
+                self.transform_of_relation_func(expr)
+            } else {
+                expr
+            };
+
+            let mut args = vec![value];
+            if self.is_std_func(&expr, "distinct") {
+                // bare `distinct` is distinct over all columns
+                args.insert(0, Expr::from(ExprKind::List(vec![])));
+            }
 
             value = Expr::from(ExprKind::FuncCall(FuncCall {
                 name: Box::new(expr),
-                args: vec![value],
+                args,
                 named_args: HashMap::new(),
             }));
             value.span = span;
@@ -330,20 +657,89 @@ impl Resolver {
         self.fold_expr(value)
     }
 
+    /// Applied to a relation in a pipeline, a function that pipes one of its
+    /// params is a transform of that relation, see [relation_param].
+    fn transform_of_relation_func(&mut self, mut expr: Expr) -> Expr {
+        let name = if expr.kind.is_func_call() {
+            expr.kind.as_func_call_mut().unwrap().name.as_mut()
+        } else {
+            &mut expr
+        };
+        let ExprKind::Ident(ident) = &name.kind else {
+            return expr;
+        };
+        let Ok(fq_ident) = self.resolve_ident(ident, name.span) else {
+            return expr;
+        };
+        let Some(DeclKind::FuncDef(func_def)) = self.decls.root_mod.get(&fq_ident).map(|d| &d.kind)
+        else {
+            return expr;
+        };
+        let Some(position) = relation_param(func_def) else {
+            return expr;
+        };
+        if !matches!(
+            func_def.positional_params[position].ty,
+            None | Some(Ty::Table(_))
+        ) {
+            return expr;
+        }
+
+        let func_def = relation_func_def(func_def.clone(), position);
+        let closure = closure_of_func_def(&func_def, fq_ident);
+        name.kind = ExprKind::Closure(Box::new(closure));
+        expr
+    }
+
+    /// Checks if an expr is a plain reference to a function of std.
+    fn is_std_func(&mut self, expr: &Expr, name: &str) -> bool {
+        let ident = match &expr.kind {
+            ExprKind::Ident(ident) => ident,
+            _ => return false,
+        };
+        let fq_ident = self.resolve_ident(ident, expr.span).ok();
+        fq_ident.map_or(false, |i| i.path == ["std"] && i.name == name)
+    }
+
     pub fn resolve_ident(&mut self, ident: &Ident, span: Option<Span>) -> Result<Ident> {
+        self.resolve_ident_as(ident, span, false)
+    }
+
+    /// Resolves an ident used as a value, see [Context::resolve_value_ident].
+    fn resolve_value_ident(&mut self, ident: &Ident, span: Option<Span>) -> Result<Ident> {
+        self.resolve_ident_as(ident, span, true)
+    }
+
+    fn resolve_ident_as(
+        &mut self,
+        ident: &Ident,
+        span: Option<Span>,
+        value: bool,
+    ) -> Result<Ident> {
         let res = if ident.path.is_empty() && self.default_namespace.is_some() {
             let defaulted = Ident {
                 path: vec![self.default_namespace.clone().unwrap()],
                 name: ident.name.clone(),
             };
             self.decls.resolve_ident(&defaulted)
+        } else if value {
+            self.decls.resolve_value_ident(ident)
         } else {
             self.decls.resolve_ident(ident)
         };
 
         res.map_err(|e| {
             log::debug!("cannot resolve, context={:#?}", self.decls);
-            anyhow!(Error::new(Reason::Simple(e)).with_span(span))
+            let mut err = Error::new(Reason::Simple(e))
+                .with_span(span)
+                .with_code(ErrorCode::UnknownName);
+            if let Some(transform) = self.narrowed_by.filter(|_| !self.in_func_call_name) {
+                let name = &ident.name;
+                err = err.with_help(format!(
+                    "the relation after `{transform}` does not contain `{name}`; use it before the `{transform}`, or include it in the `{transform}`"
+                ));
+            }
+            anyhow!(err)
         })
     }
 
@@ -351,11 +747,19 @@ impl Resolver {
         &mut self,
         closure: Closure,
         args: Vec<Expr>,
-        named_args: HashMap<String, Expr>,
+        mut named_args: HashMap<String, Expr>,
         span: Option<Span>,
     ) -> Result<Expr, anyhow::Error> {
+        if let (Some(policy), Some(name)) = (&self.decls.policy, &closure.name) {
+            policy.check_function(name, span)?;
+        }
+
+        let ignores_sort = take_over_of_ranking(&closure, &mut named_args)?;
         let closure = self.apply_args_to_closure(closure, args, named_args)?;
         let args_len = closure.args.len();
+        if args_len > closure.params.len() {
+            validate_std_function_args(&closure, span)?;
+        }
 
         log::debug!(
             "func {} {}/{} params",
@@ -434,6 +838,7 @@ impl Resolver {
             self.decls.root_mod.stack_pop(NS_PARAM).unwrap();
 
             res.needs_window = needs_window;
+            res.ignores_sort = ignores_sort;
             res
         } else {
             // not enough arguments: don't fold
@@ -458,6 +863,8 @@ impl Resolver {
             return Ok(Err(closure));
         }
 
+        cancellation::check(self.decls.cancellation.as_ref())?;
+
         Ok(match transforms::cast_transform(self, closure)? {
             // it a transform
             Ok(e) => Ok(e),
@@ -495,10 +902,11 @@ impl Resolver {
         }
         if let Some((name, _)) = named_args.into_iter().next() {
             // TODO: report all remaining named_args as separate errors
-            anyhow::bail!(
+            bail!(Error::new(Reason::Simple(format!(
                 "unknown named argument `{name}` to closure {:?}",
                 closure.name
-            )
+            )))
+            .with_code(ErrorCode::Mismatch))
         }
 
         // positional
@@ -527,6 +935,8 @@ impl Resolver {
             });
 
         let has_tables = !tables.is_empty();
+        let mut this_frame = None;
+        let narrowed_by = self.narrowed_by;
 
         // resolve tables
         if has_tables {
@@ -538,6 +948,7 @@ impl Resolver {
                 let (index, (param, arg)) = pos.into_inner();
 
                 // just fold the argument alone
+                let arg = relation_literal_of_list(arg, param)?;
                 let arg = self.fold_and_type_check(arg, param, func_name)?;
                 log::debug!("resolved arg to {}", arg.kind.as_ref());
 
@@ -545,6 +956,8 @@ impl Resolver {
                 if let Some(Ty::Table(frame)) = &arg.ty {
                     if is_last {
                         self.decls.root_mod.insert_frame(frame, NS_FRAME);
+                        this_frame = Some(frame.clone());
+                        self.narrowed_by = narrowing_transform(&arg);
                     } else {
                         self.decls.root_mod.insert_frame(frame, NS_FRAME_RIGHT);
                     }
@@ -557,6 +970,15 @@ impl Resolver {
         // resolve other positional
         for (index, (param, mut arg)) in other {
             if let ExprKind::List(items) = arg.kind {
+                let is_select = (func_name.as_ref())
+                    .map_or(false, |n| n.path == [NS_STD] && n.name == "select");
+                let items = match &this_frame {
+                    Some(frame) if is_select => {
+                        expand_select_wildcard(items, frame, &mut self.decls.notes)?
+                    }
+                    _ => items,
+                };
+
                 // if this is a list, resolve elements separately,
                 // so they can be added to scope, before resolving subsequent elements.
 
@@ -586,6 +1008,7 @@ impl Resolver {
         if has_tables {
             self.decls.root_mod.unshadow(NS_FRAME);
             self.decls.root_mod.unshadow(NS_FRAME_RIGHT);
+            self.narrowed_by = narrowed_by;
         }
 
         Ok(closure)
@@ -599,6 +1022,38 @@ impl Resolver {
     ) -> Result<Expr> {
         let mut arg = self.fold_within_namespace(arg, &param.name)?;
 
+        // relations cannot be used where a value is expected...
+        if let ExprKind::Closure(closure) = &arg.kind {
+            let expects_value = !matches!(
+                param.ty,
+                None | Some(Ty::Table(_) | Ty::Function(_) | Ty::Infer)
+            );
+            if expects_value && matches!(closure.body_ty, Some(Ty::Table(_))) {
+                let name = closure.as_debug_name();
+                bail!(Error::new(Reason::Simple(format!(
+                    "function `{name}` returns a relation and cannot be used as an expression"
+                )))
+                .with_span(arg.span)
+                .with_help(format!("use it as a transform: `from x | {name}`")));
+            }
+            if expects_value {
+                validate_std_function_args(closure, arg.span)?;
+            }
+        }
+
+        // ... and transforms cannot be applied to values, i.e. in `(salary | take 3)`
+        if let (Some(Ty::Table(_)), Some(Ty::Literal(found))) = (&param.ty, &arg.ty) {
+            let name = func_name
+                .as_ref()
+                .map(|n| n.name.as_str())
+                .unwrap_or("<anonymous>");
+            bail!(Error::new(Reason::Simple(format!(
+                "transform `{name}` cannot be applied to a {found}"
+            )))
+            .with_span(arg.span)
+            .with_help("transforms can only be applied to relations, i.e. `from x | take 3`"));
+        }
+
         // don't validate types of unresolved exprs
         if arg.ty.is_some() {
             // validate type
@@ -631,12 +1086,17 @@ impl Resolver {
     }
 
     fn resolve_eq_self(&mut self, expr: Expr, span: Option<Span>) -> Result<ExprKind> {
-        let ident = expr
-            .kind
-            .into_ident()
-            .map_err(|_| anyhow!("you can only use column names with self-equality operator."))?;
+        let ident = expr.kind.into_ident().map_err(|_| {
+            Error::new(Reason::Simple(
+                "you can only use column names with self-equality operator.".to_string(),
+            ))
+            .with_span(span)
+        })?;
         if !ident.path.is_empty() {
-            bail!("you cannot use namespace prefix with self-equality operator.");
+            bail!(Error::new(Reason::Simple(
+                "you cannot use namespace prefix with self-equality operator.".to_string()
+            ))
+            .with_span(span));
         }
         let mut left = Expr::from(ExprKind::Ident(Ident {
             path: vec![NS_FRAME.to_string()],
@@ -658,6 +1118,157 @@ impl Resolver {
     }
 }
 
+/// Expands `*` in `select [id, *, created_at]` into all columns of the frame
+/// that are not selected explicitly, keeping their original order.
+///
+/// Columns of inputs whose columns are not known are expanded to `input.*`,
+/// which may produce duplicated columns in the resulting relation. This is
+/// reported as a warning.
+fn expand_select_wildcard(items: Vec<Expr>, frame: &Frame, notes: &mut Notes) -> Result<Vec<Expr>> {
+    let is_wildcard = |item: &Expr| {
+        item.alias.is_none()
+            && matches!(&item.kind, ExprKind::Ident(i) if i.path.is_empty() && i.name == "*")
+    };
+
+    let mut wildcards = items.iter().filter(|i| is_wildcard(*i));
+    let Some(wildcard) = wildcards.next() else {
+        return Ok(items);
+    };
+    if let Some(second) = wildcards.next() {
+        bail!(Error::new(Reason::Simple(
+            "`*` can be used only once in select".to_string()
+        ))
+        .with_span(second.span));
+    }
+    let span = wildcard.span;
+
+    let explicit: Vec<_> = (items.iter())
+        .filter(|item| !is_wildcard(*item))
+        .filter_map(|item| match &item.kind {
+            _ if item.alias.is_some() => item.alias.clone().map(Ident::from_name),
+            ExprKind::Ident(ident) => Some(ident.clone()),
+            _ => None,
+        })
+        .collect();
+    // `id` matches a column `id` of any input, `a.id` only the one of `a`
+    let is_explicit = |name: &Ident| {
+        (explicit.iter()).any(|e| e.name == name.name && (e.path.is_empty() || e.path == name.path))
+    };
+
+    let mut unknown_inputs = Vec::new();
+    let mut expanded = Vec::new();
+    for col in &frame.columns {
+        let ident = match col {
+            FrameColumn::Wildcard { input_name } => {
+                unknown_inputs.push(format!("`{input_name}`"));
+                Ident {
+                    path: vec![input_name.clone()],
+                    name: "*".to_string(),
+                }
+            }
+            FrameColumn::Single {
+                name: Some(name), ..
+            } if !is_explicit(name) => name.clone(),
+            FrameColumn::Single { .. } => continue,
+        };
+        expanded.push(Expr {
+            span,
+            ..Expr::from(ExprKind::Ident(ident))
+        });
+    }
+
+    if !unknown_inputs.is_empty() && !explicit.is_empty() {
+        notes.warn(Warning {
+            construct: "select".to_string(),
+            target: None,
+            message: format!(
+                "columns of {} are not known, so `*` may repeat columns {}",
+                unknown_inputs.join(", "),
+                explicit.iter().map(|e| format!("`{e}`")).join(", ")
+            ),
+            spans: span.into_iter().collect(),
+        });
+    }
+
+    Ok(items
+        .into_iter()
+        .flat_map(|item| {
+            if is_wildcard(&item) {
+                expanded.clone()
+            } else {
+                vec![item]
+            }
+        })
+        .collect())
+}
+
+/// Converts a list of lists passed to a `<table>` param into a relation
+/// literal. First inner list contains names of the columns and all following
+/// lists are rows: `[[id, name], [1, "Tom"], [2, "Jerry"]]`.
+///
+/// Other expressions and arguments of other params are returned unchanged.
+fn relation_literal_of_list(expr: Expr, param: &FuncParam) -> Result<Expr> {
+    let is_table_param = matches!(param.ty, Some(Ty::Table(_)));
+    let is_relation = matches!(&expr.kind, ExprKind::List(items)
+        if !items.is_empty() && items.iter().all(|i| matches!(i.kind, ExprKind::List(_))));
+    if !is_table_param || !is_relation {
+        return Ok(expr);
+    }
+
+    let Expr {
+        kind, span, alias, ..
+    } = expr;
+    let mut rows = kind.into_list().unwrap().into_iter();
+
+    let header = rows.next().unwrap().kind.into_list().unwrap();
+    let columns: Vec<_> = (header.into_iter())
+        .map(|col| match &col.kind {
+            ExprKind::Ident(ident) if ident.path.is_empty() => Ok(ident.name.clone()),
+            _ => Err(Error::new(Reason::Expected {
+                who: Some("relation literal".to_string()),
+                expected: "a column name".to_string(),
+                found: format!("`{col}`"),
+            })
+            .with_span(col.span)),
+        })
+        .try_collect()?;
+
+    let rows: Vec<Vec<_>> = rows
+        .map(|row| {
+            let row_span = row.span;
+            let values = row.kind.into_list().unwrap();
+            if values.len() != columns.len() {
+                return Err(Error::new(Reason::Expected {
+                    who: Some("relation literal".to_string()),
+                    expected: format!("{} values", columns.len()),
+                    found: values.len().to_string(),
+                })
+                .with_span(row_span));
+            }
+
+            (values.into_iter())
+                .map(|value| match value.kind {
+                    ExprKind::Literal(lit) if !matches!(lit, Literal::Relation(_)) => Ok(lit),
+                    kind => Err(Error::new(Reason::Expected {
+                        who: Some("relation literal".to_string()),
+                        expected: "a literal value".to_string(),
+                        found: format!("`{}`", Expr::from(kind)),
+                    })
+                    .with_span(value.span)),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .try_collect()?;
+
+    let mut res = Expr::from(ExprKind::Literal(Literal::Relation(RelationLiteral {
+        columns,
+        rows,
+    })));
+    res.span = span;
+    res.alias = alias;
+    Ok(res)
+}
+
 fn env_of_closure(closure: Closure) -> (Module, Expr) {
     let mut func_env = Module::default();
 
@@ -698,6 +1309,33 @@ mod test {
         Ok(derive.kind.into_derive()?)
     }
 
+    #[test]
+    fn test_relation_literal_of_list() {
+        use crate::ast::pl::*;
+
+        let list = |items| Expr::from(ExprKind::List(items));
+        let rows = list(vec![
+            list(vec![Expr::from(ExprKind::Ident(Ident::from_name("a")))]),
+            list(vec![Expr::from(ExprKind::Literal(Literal::Integer(1)))]),
+        ]);
+        let param = |ty| FuncParam {
+            name: "x".to_string(),
+            ty,
+            default_value: None,
+        };
+
+        // lists of lists passed to scalar params stay lists
+        let scalar = super::relation_literal_of_list(rows.clone(), &param(None)).unwrap();
+        assert!(matches!(scalar.kind, ExprKind::List(_)));
+
+        let table = Some(Ty::Table(Frame::default()));
+        let relation = super::relation_literal_of_list(rows, &param(table)).unwrap();
+        assert!(matches!(
+            relation.kind,
+            ExprKind::Literal(Literal::Relation(_))
+        ));
+    }
+
     #[test]
     fn test_func_call_resolve() {
         assert_display_snapshot!(crate::test::compile(r#"
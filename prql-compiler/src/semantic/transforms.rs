@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
-use anyhow::{anyhow, bail, Result};
-use std::iter::zip;
+use anyhow::{bail, Result};
+use std::iter::{once, zip};
 
 use crate::ast::pl::fold::{fold_column_sorts, fold_transform_kind, AstFold};
 use crate::ast::pl::*;
@@ -30,8 +30,32 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
         "std.select" => {
             let [assigns, tbl] = unpack::<2>(closure);
 
-            let assigns = coerce_into_vec(assigns)?;
-            (TransformKind::Select { assigns }, tbl)
+            // `select ![a, b]` keeps all columns except the listed ones
+            let kind = match assigns.kind {
+                ExprKind::Unary {
+                    op: UnOp::Not,
+                    expr,
+                } => {
+                    let columns = coerce_into_vec(*expr)?;
+                    let not_column = columns
+                        .iter()
+                        .find(|c| !c.kind.is_ident() || c.alias.is_some());
+                    if let Some(expr) = not_column {
+                        bail!(Error::new(Reason::Expected {
+                            who: Some("`select`".to_string()),
+                            expected: "a column to exclude".to_string(),
+                            found: format!("`{expr}`"),
+                        })
+                        .with_span(expr.span));
+                    }
+                    TransformKind::Exclude { columns }
+                }
+                kind => {
+                    let assigns = coerce_into_vec(Expr { kind, ..assigns })?;
+                    TransformKind::Select { assigns }
+                }
+            };
+            (kind, tbl)
         }
         "std.filter" => {
             let [filter, tbl] = unpack::<2>(closure);
@@ -49,10 +73,30 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
             let [assigns, tbl] = unpack::<2>(closure);
 
             let assigns = coerce_into_vec(assigns)?;
-            (TransformKind::Aggregate { assigns }, tbl)
+            let grouping = GroupingKind::Plain;
+            (TransformKind::Aggregate { assigns, grouping }, tbl)
         }
         "std.sort" => {
-            let [by, tbl] = unpack::<2>(closure);
+            let [nulls, by, tbl] = unpack::<3>(closure);
+
+            let nulls = match nulls.kind {
+                ExprKind::Literal(Literal::Null) => None,
+                _ => {
+                    let span = nulls.span;
+                    let ident = nulls.try_cast(ExprKind::into_ident, Some("nulls"), "ident")?;
+                    Some(match ident.to_string().as_str() {
+                        "first" => NullsOrder::First,
+                        "last" => NullsOrder::Last,
+
+                        found => bail!(Error::new(Reason::Expected {
+                            who: Some("`nulls`".to_string()),
+                            expected: "first or last".to_string(),
+                            found: found.to_string()
+                        })
+                        .with_span(span)),
+                    })
+                }
+            };
 
             let by = coerce_into_vec(by)?
                 .into_iter()
@@ -64,7 +108,11 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
                         _ => (node, SortDirection::default()),
                     };
 
-                    ColumnSort { direction, column }
+                    ColumnSort {
+                        direction,
+                        column,
+                        nulls,
+                    }
                 })
                 .collect();
 
@@ -73,8 +121,24 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
         "std.take" => {
             let [expr, tbl] = unpack::<2>(closure);
 
+            let is_percent = |e: &Expr| matches!(&e.kind, ExprKind::Literal(Literal::ValueAndUnit(v)) if v.unit == "%");
+
+            let mut percent = false;
             let range = match expr.kind {
                 ExprKind::Literal(Literal::Integer(n)) => Range::from_ints(None, Some(n)),
+                ExprKind::Literal(Literal::ValueAndUnit(v)) if v.unit == "%" => {
+                    percent = true;
+
+                    let mut range = Range::from_ints(None, Some(v.n));
+                    range.end.as_mut().unwrap().span = expr.span;
+                    range
+                }
+                ExprKind::Range(range) if range.end.as_deref().map_or(false, is_percent) => {
+                    bail!(Error::new(Reason::Simple(
+                        "`take` with a percentage cannot have an offset".to_string()
+                    ))
+                    .with_span(expr.span))
+                }
                 ExprKind::Range(range) => range,
                 _ => bail!(Error::new(Reason::Expected {
                     who: Some("`take`".to_string()),
@@ -86,7 +150,26 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
                 .with_span(expr.span)),
             };
 
-            (TransformKind::Take { range }, tbl)
+            (TransformKind::Take { range, percent }, tbl)
+        }
+        "std.sample" => {
+            let [size, tbl] = unpack::<2>(closure);
+
+            let (n, percent) = match &size.kind {
+                ExprKind::Literal(Literal::Integer(n)) => (*n, false),
+                ExprKind::Literal(Literal::ValueAndUnit(v)) if v.unit == "%" => (v.n, true),
+                _ => (-1, false),
+            };
+            if n <= 0 || (percent && n > 100) {
+                bail!(Error::new(Reason::Expected {
+                    who: Some("`sample`".to_string()),
+                    expected: "a positive number of rows or a percentage".to_string(),
+                    found: size.to_string(),
+                })
+                .with_span(size.span));
+            }
+
+            (TransformKind::Sample { size: n, percent }, tbl)
         }
         "std.join" => {
             let [side, with, filter, tbl] = unpack::<4>(closure);
@@ -99,10 +182,12 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
                     "left" => JoinSide::Left,
                     "right" => JoinSide::Right,
                     "full" => JoinSide::Full,
+                    "semi" => JoinSide::Semi,
+                    "anti" => JoinSide::Anti,
 
                     found => bail!(Error::new(Reason::Expected {
                         who: Some("`side`".to_string()),
-                        expected: "inner, left, right or full".to_string(),
+                        expected: "inner, left, right, full, semi or anti".to_string(),
                         found: found.to_string()
                     })
                     .with_span(span)),
@@ -115,14 +200,71 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
             (TransformKind::Join { side, with, filter }, tbl)
         }
         "std.group" => {
-            let [by, pipeline, tbl] = unpack::<3>(closure);
+            let [rollup, cube, sets, by, pipeline, tbl] = unpack::<6>(closure);
+
+            let as_bool = |param: Expr, name: &str| {
+                let as_bool = param.kind.as_literal().and_then(|l| l.as_boolean());
+
+                as_bool.cloned().ok_or_else(|| {
+                    Error::new(Reason::Expected {
+                        who: Some(format!("parameter `{name}`")),
+                        expected: "a boolean".to_string(),
+                        found: format!("{param}"),
+                    })
+                    .with_span(param.span)
+                })
+            };
+            let span = rollup.span.or(cube.span).or(sets.span);
+            let grouping = match (as_bool(rollup, "rollup")?, as_bool(cube, "cube")?) {
+                (false, false) => GroupingKind::Plain,
+                (true, false) => GroupingKind::Rollup,
+                (false, true) => GroupingKind::Cube,
+                (true, true) => bail!(Error::new(Reason::Simple(
+                    "`rollup` and `cube` cannot be used together".to_string()
+                ))
+                .with_span(span)),
+            };
 
             let by = coerce_into_vec(by)?;
 
+            let grouping = match &sets.kind {
+                ExprKind::Literal(Literal::Null) => grouping,
+                _ if !grouping.is_plain() => bail!(Error::new(Reason::Simple(
+                    "`sets` cannot be used together with `rollup` or `cube`".to_string()
+                ))
+                .with_span(sets.span)),
+                _ => GroupingKind::Sets(grouping_sets_of_keys(sets, &by)?),
+            };
+
             let pipeline = fold_by_simulating_eval(resolver, pipeline, tbl.ty.clone().unwrap())?;
 
+            if !grouping.is_plain() {
+                let Closure { body, .. } = pipeline.kind.as_closure().unwrap().as_ref();
+                let is_aggregate = matches!(
+                    &body.kind,
+                    ExprKind::TransformCall(call) if call.kind.as_aggregate().is_some()
+                );
+                if !is_aggregate {
+                    let which = match &grouping {
+                        GroupingKind::Sets(_) => "`sets` requires",
+                        _ => "`rollup` and `cube` require",
+                    };
+                    bail!(Error::new(Reason::Simple(format!(
+                        "{which} the pipeline of `group` to be an `aggregate`"
+                    )))
+                    .with_span(span));
+                }
+            }
+
             let pipeline = Box::new(pipeline);
-            (TransformKind::Group { by, pipeline }, tbl)
+            (
+                TransformKind::Group {
+                    by,
+                    pipeline,
+                    grouping,
+                },
+                tbl,
+            )
         }
         "std.window" => {
             let [rows, range, expanding, rolling, pipeline, tbl] = unpack::<6>(closure);
@@ -153,9 +295,9 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
                 })?
             };
 
-            let rows = rows.try_cast(|r| r.into_range(), Some("parameter `rows`"), "a range")?;
+            let rows = range_or_none(rows, "parameter `rows`")?;
 
-            let range = range.try_cast(|r| r.into_range(), Some("parameter `range`"), "a range")?;
+            let range = range_or_none(range, "parameter `range`")?;
 
             let (kind, range) = if expanding {
                 (WindowKind::Rows, Range::from_ints(None, Some(0)))
@@ -164,9 +306,9 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
                     WindowKind::Rows,
                     Range::from_ints(Some(-rolling + 1), Some(0)),
                 )
-            } else if !rows.is_empty() {
+            } else if let Some(rows) = rows {
                 (WindowKind::Rows, rows)
-            } else if !range.is_empty() {
+            } else if let Some(range) = range {
                 (WindowKind::Range, range)
             } else {
                 (WindowKind::Rows, Range::unbounded())
@@ -181,11 +323,56 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
             };
             (transform_kind, tbl)
         }
+        "std.distinct" => {
+            let [columns, tbl] = unpack::<2>(closure);
+
+            let columns = coerce_into_vec(columns)?;
+            if columns.is_empty() {
+                (TransformKind::Unique, tbl)
+            } else {
+                // distinct over a subset of columns is `group columns (take 1)`
+                let take = Expr::from(ExprKind::FuncCall(FuncCall {
+                    name: Box::new(Expr::from(ExprKind::Ident(Ident::from_path(vec![
+                        "std", "take",
+                    ])))),
+                    args: vec![Expr::from(ExprKind::Literal(Literal::Integer(1)))],
+                    named_args: HashMap::new(),
+                }));
+                let pipeline = fold_by_simulating_eval(resolver, take, tbl.ty.clone().unwrap())?;
+
+                let pipeline = Box::new(pipeline);
+                (
+                    TransformKind::Group {
+                        by: columns,
+                        pipeline,
+                        grouping: GroupingKind::Plain,
+                    },
+                    tbl,
+                )
+            }
+        }
+        "std.loop" => {
+            let [pipeline, tbl] = unpack::<2>(closure);
+
+            let pipeline = fold_by_simulating_eval(resolver, pipeline, tbl.ty.clone().unwrap())?;
+
+            (TransformKind::Loop(Box::new(pipeline)), tbl)
+        }
         "std.concat" => {
             let [bottom, top] = unpack::<2>(closure);
 
             (TransformKind::Concat(Box::new(bottom)), top)
         }
+        "std.intersect" => {
+            let [bottom, top] = unpack::<2>(closure);
+
+            (TransformKind::Intersect(Box::new(bottom)), top)
+        }
+        "std.remove" => {
+            let [bottom, top] = unpack::<2>(closure);
+
+            (TransformKind::Remove(Box::new(bottom)), top)
+        }
 
         "std.in" => {
             // yes, this is not a transform, but this is the most appropriate place for it
@@ -214,10 +401,16 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
                         .unwrap_or_else(|| Expr::from(ExprKind::Literal(Literal::Boolean(true))));
                     return Ok(Ok(res));
                 }
-                ExprKind::List(_) => {
-                    // TODO: should translate into `value IN (...)`
-                    //   but RQ currently does not support sub queries or
-                    //   even expressions that evaluate to a list.
+                ExprKind::List(items) => {
+                    // `value IN (...)`, which matches nothing when the list is empty
+                    if items.is_empty() {
+                        let res = Expr::from(ExprKind::Literal(Literal::Boolean(false)));
+                        return Ok(Ok(res));
+                    }
+
+                    let args = once(value).chain(items).collect();
+                    let name = "std.in".to_string();
+                    return Ok(Ok(Expr::from(ExprKind::BuiltInFunction { name, args })));
                 }
                 _ => {}
             }
@@ -245,6 +438,50 @@ pub fn cast_transform(resolver: &mut Resolver, closure: Closure) -> Result<Resul
 /// Wraps non-list Exprs into a singleton List.
 // This function should eventually be applied to all function arguments that
 // expect a list.
+/// Range of a window, which defaults to `null` when not given.
+fn range_or_none(expr: Expr, who: &str) -> Result<Option<Range>> {
+    if let ExprKind::Literal(Literal::Null) = expr.kind {
+        return Ok(None);
+    }
+    Ok(Some(expr.try_cast(
+        |r| r.into_range(),
+        Some(who),
+        "a range",
+    )?))
+}
+
+/// Converts the `sets` param of `group` into positions of the keys in each grouping set.
+fn grouping_sets_of_keys(sets: Expr, keys: &[Expr]) -> Result<Vec<Vec<usize>>> {
+    let span = sets.span;
+    let sets = coerce_into_vec(sets)?;
+    if sets.is_empty() {
+        bail!(Error::new(Reason::Expected {
+            who: Some("parameter `sets`".to_string()),
+            expected: "at least one grouping set".to_string(),
+            found: "[]".to_string(),
+        })
+        .with_span(span))
+    }
+
+    let mut res = Vec::with_capacity(sets.len());
+    for set in sets {
+        let mut positions = Vec::new();
+        for column in coerce_into_vec(set)? {
+            let Some(position) = keys.iter().position(|key| key.kind == column.kind) else {
+                let name = (column.kind.as_ident())
+                    .map_or_else(|| column.to_string(), |i| i.name.clone());
+                bail!(Error::new(Reason::Simple(format!(
+                    "grouping set column `{name}` is not a key of `group`"
+                )))
+                .with_span(column.span))
+            };
+            positions.push(position);
+        }
+        res.push(positions);
+    }
+    Ok(res)
+}
+
 pub fn coerce_into_vec(expr: Expr) -> Result<Vec<Expr>> {
     Ok(match expr.kind {
         ExprKind::List(items) => {
@@ -261,7 +498,7 @@ pub fn coerce_into_vec(expr: Expr) -> Result<Vec<Expr>> {
     })
 }
 
-/// Simulate evaluation of the inner pipeline of group or window
+/// Simulate evaluation of the inner pipeline of group, window or loop
 // Creates a dummy node that acts as value that pipeline can be resolved upon.
 fn fold_by_simulating_eval(
     resolver: &mut Resolver,
@@ -330,7 +567,15 @@ impl TransformCall {
                 .as_ref()
                 .and_then(|t| t.as_table())
                 .cloned()
-                .ok_or_else(|| anyhow!("expected {expr:?} to have table type"))
+                .ok_or_else(|| {
+                    Error::new(Reason::Expected {
+                        who: None,
+                        expected: "a table".to_string(),
+                        found: format!("`{expr}`"),
+                    })
+                    .with_span(expr.span)
+                    .into()
+                })
         }
 
         Ok(match self.kind.as_ref() {
@@ -341,6 +586,21 @@ impl TransformCall {
                 frame.apply_assigns(assigns);
                 frame
             }
+            Exclude { columns } => {
+                let mut frame = ty_frame_or_default(&self.input)?;
+
+                // columns of inputs with unknown columns remain in the wildcard
+                frame.columns.retain(|col| {
+                    let FrameColumn::Single { name: Some(name), expr_id } = col else {
+                        return true;
+                    };
+                    !columns.iter().any(|c| {
+                        c.target_id == Some(*expr_id)
+                            && c.kind.as_ident().map(|i| &i.name) == Some(&name.name)
+                    })
+                });
+                frame
+            }
             Derive { assigns } => {
                 let mut frame = ty_frame_or_default(&self.input)?;
 
@@ -378,34 +638,122 @@ impl TransformCall {
 
                 body.ty.clone().unwrap().into_table().unwrap()
             }
-            Aggregate { assigns } => {
+            Loop(pipeline) => {
+                // rows of each iteration are appended to the relation, so the
+                // pipeline must produce columns of the relation
+                let frame = ty_frame_or_default(&self.input)?;
+
+                let Closure { body, .. } = pipeline.kind.as_closure().unwrap().as_ref();
+                let step = body.ty.as_ref().and_then(|t| t.as_table()).unwrap();
+
+                if frame.columns.len() != step.columns.len() {
+                    bail!(Error::new(Reason::Simple(
+                        "the pipeline of `loop` must produce the same number of columns as its input"
+                            .to_string()
+                    ))
+                    .with_help(format!(
+                        "input has {} columns, but the pipeline produces {}",
+                        frame.columns.len(),
+                        step.columns.len()
+                    )));
+                }
+                frame
+            }
+            Aggregate { assigns, .. } => {
                 let mut frame = ty_frame_or_default(&self.input)?;
                 frame.columns.clear();
 
                 frame.apply_assigns(assigns);
                 frame
             }
-            Join { with, .. } => {
+            // semi and anti joins only filter rows of the left relation
+            Join {
+                side: JoinSide::Semi | JoinSide::Anti,
+                ..
+            } => ty_frame_or_default(&self.input)?,
+            Join { side, with, filter } => {
                 let left = ty_frame_or_default(&self.input)?;
                 let right = ty_frame_or_default(with)?;
-                join(left, right)
+                join(left, right, side, filter)
             }
-            Concat(bottom) => {
+            Concat(bottom) | Intersect(bottom) | Remove(bottom) => {
                 let top = ty_frame_or_default(&self.input)?;
                 let bottom = ty_frame_or_default(bottom)?;
                 concat(top, bottom)?
             }
-            Sort { .. } | Filter { .. } | Take { .. } => ty_frame_or_default(&self.input)?,
+            Sort { .. } | Filter { .. } | Take { .. } | Sample { .. } | Unique => {
+                ty_frame_or_default(&self.input)?
+            }
         })
     }
 }
 
-fn join(mut lhs: Frame, rhs: Frame) -> Frame {
+/// Columns of a join are the columns of the left relation, followed by the
+/// columns of the right relation.
+///
+/// Columns that the condition requires to be equal to a column of the same
+/// name of the other relation (as `[==id]` does) are merged, as with
+/// `JOIN ... USING`: the column of the optional side of the join can only be
+/// referenced by its qualified name. A full join has no such side, so its
+/// columns are not merged.
+fn join(mut lhs: Frame, rhs: Frame, side: &JoinSide, filter: &Expr) -> Frame {
+    let keys = equal_columns(filter, &lhs, &rhs);
+    let merged = keys.into_iter().filter_map(|(left, right)| match side {
+        JoinSide::Inner | JoinSide::Left => Some(right),
+        JoinSide::Right => Some(left),
+        _ => None,
+    });
+    lhs.merged.extend(merged);
+
     lhs.columns.extend(rhs.columns);
     lhs.inputs.extend(rhs.inputs);
+    lhs.merged.extend(rhs.merged);
     lhs
 }
 
+/// A resolved column as `input.column`, without the namespace of its frame.
+fn input_column(expr: &Expr) -> Option<Ident> {
+    let ident = expr.kind.as_ident()?;
+    Some(Ident {
+        path: vec![ident.path.last()?.clone()],
+        name: ident.name.clone(),
+    })
+}
+
+/// Pairs of columns of the left and of the right relation that have the same
+/// name and that the condition requires to be equal. Columns are qualified by
+/// the names of their inputs.
+fn equal_columns(condition: &Expr, lhs: &Frame, rhs: &Frame) -> Vec<(Ident, Ident)> {
+    let ExprKind::Binary { left, op, right } = &condition.kind else {
+        return Vec::new();
+    };
+    match op {
+        BinOp::And => [
+            equal_columns(left, lhs, rhs),
+            equal_columns(right, lhs, rhs),
+        ]
+        .concat(),
+        BinOp::Eq => {
+            let (Some(left), Some(right)) = (input_column(left), input_column(right)) else {
+                return Vec::new();
+            };
+            if left.name != right.name {
+                return Vec::new();
+            }
+            let is_of = |frame: &Frame, column: &Ident| frame.find_input(&column.path[0]).is_some();
+
+            if is_of(lhs, &left) && is_of(rhs, &right) {
+                vec![(left, right)]
+            } else if is_of(lhs, &right) && is_of(rhs, &left) {
+                vec![(right, left)]
+            } else {
+                Vec::new()
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
 fn concat(mut top: Frame, bottom: Frame) -> Result<Frame, Error> {
     if top.columns.len() != bottom.columns.len() {
         return Err(Error::new(Reason::Simple(
@@ -478,6 +826,9 @@ pub struct Flattener {
     /// and unset after the folding.
     partition: Vec<Expr>,
 
+    /// Grouping sets of the group, applied to the aggregate in its pipeline.
+    grouping: GroupingKind,
+
     /// Window affects transforms in it's inner pipeline.
     /// This means that this field has to be set before folding inner pipeline,
     /// and unset after the folding.
@@ -529,7 +880,11 @@ impl AstFold for Flattener {
                             (input, TransformKind::Sort { by })
                         }
                     }
-                    TransformKind::Group { by, pipeline } => {
+                    TransformKind::Group {
+                        by,
+                        pipeline,
+                        grouping,
+                    } => {
                         let sort_undone = self.sort_undone;
                         self.sort_undone = true;
 
@@ -542,12 +897,14 @@ impl AstFold for Flattener {
 
                         self.replace_map.insert(param_id, input);
                         self.partition = by;
+                        self.grouping = grouping;
                         self.sort.clear();
 
                         let pipeline = self.fold_expr(*pipeline.body)?;
 
                         self.replace_map.remove(&param_id);
                         self.partition.clear();
+                        self.grouping = GroupingKind::Plain;
                         self.sort.clear();
                         self.sort_undone = sort_undone;
 
@@ -580,6 +937,13 @@ impl AstFold for Flattener {
                             ..pipeline
                         });
                     }
+                    TransformKind::Aggregate { assigns, .. } => {
+                        let input = self.fold_expr(*t.input)?;
+                        let assigns = self.fold_exprs(assigns)?;
+                        let grouping = self.grouping.clone();
+
+                        (input, TransformKind::Aggregate { assigns, grouping })
+                    }
                     kind => (self.fold_expr(*t.input)?, fold_transform_kind(self, kind)?),
                 };
 
@@ -3,7 +3,7 @@ use std::cmp::Ordering;
 use anyhow::Result;
 
 use crate::ast::pl::*;
-use crate::error::{Error, Reason, WithErrorInfo};
+use crate::error::{Error, Reason, Span, WithErrorInfo};
 
 pub fn resolve_type(node: &Expr) -> Result<Ty> {
     if let Some(ty) = &node.ty {
@@ -21,6 +21,7 @@ pub fn resolve_type(node: &Expr) -> Result<Ty> {
             Literal::Time(_) => TyLit::Time.into(),
             Literal::Timestamp(_) => TyLit::Timestamp.into(),
             Literal::ValueAndUnit(_) => Ty::Infer, // TODO
+            Literal::Relation(rel) => Ty::Table(frame_of_relation_literal(node, rel)),
         },
 
         ExprKind::Ident(_) | ExprKind::Pipeline(_) | ExprKind::FuncCall(_) => Ty::Infer,
@@ -36,6 +37,33 @@ pub fn resolve_type(node: &Expr) -> Result<Ty> {
     })
 }
 
+fn frame_of_relation_literal(node: &Expr, rel: &RelationLiteral) -> Frame {
+    let id = node.id.unwrap();
+    let input_name = (node.alias)
+        .clone()
+        .unwrap_or_else(|| format!("_literal_{id}"));
+
+    let columns = (rel.columns.iter())
+        .map(|name| FrameColumn::Single {
+            name: Some(Ident {
+                path: vec![input_name.clone()],
+                name: name.clone(),
+            }),
+            expr_id: id,
+        })
+        .collect();
+
+    Frame {
+        inputs: vec![FrameInput {
+            id,
+            name: input_name,
+            table: None,
+        }],
+        columns,
+        merged: Vec::new(),
+    }
+}
+
 #[allow(dead_code)]
 fn too_many_arguments(call: &FuncCall, expected_len: usize, passed_len: usize) -> Error {
     let err = Error::new(Reason::Expected {
@@ -80,6 +108,7 @@ where
                     table: None,
                 }],
                 columns: vec![FrameColumn::Wildcard { input_name }],
+                merged: Vec::new(),
             })
         } else {
             expected.clone()
@@ -126,3 +155,29 @@ pub fn type_of_closure(closure: &Closure) -> TyFunc {
         return_ty: Box::new(closure.body_ty.clone().unwrap_or(Ty::Infer)),
     }
 }
+
+/// Checks the number of arguments of a call of a std function that is
+/// translated into SQL directly. Such functions cannot be curried, so a call
+/// with too few arguments would otherwise be reported only when the closure
+/// is lowered, and extra arguments would be dropped.
+pub fn validate_std_function_args(closure: &Closure, span: Option<Span>) -> Result<(), Error> {
+    let Some(name) = &closure.name else {
+        return Ok(());
+    };
+    let is_std_function = name.path == ["std"]
+        && matches!(closure.body.kind, ExprKind::Literal(Literal::Null))
+        && !matches!(closure.body_ty, Some(Ty::Table(_)));
+
+    let (expected, found) = (closure.params.len(), closure.args.len());
+    if !is_std_function || expected == found {
+        return Ok(());
+    }
+
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    Err(Error::new(Reason::Expected {
+        who: Some(format!("`{}`", name.name)),
+        expected: format!("{expected} argument{}", plural(expected)),
+        found: found.to_string(),
+    })
+    .with_span(span))
+}
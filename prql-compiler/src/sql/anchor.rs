@@ -17,6 +17,8 @@ pub fn split_off_back(
     ctx: &mut AnchorContext,
     output: Vec<CId>,
     mut pipeline: Vec<Transform>,
+    supports_qualify: bool,
+    sample_ends_select: bool,
 ) -> (Option<RemainingPipeline>, Vec<Transform>) {
     if pipeline.is_empty() {
         return (None, Vec::new());
@@ -33,7 +35,12 @@ pub fn split_off_back(
     let mut curr_pipeline_rev = Vec::new();
     'pipeline: while let Some(transform) = pipeline.pop() {
         // stop if split is needed
-        let split = is_split_required(&transform, &mut following_transforms);
+        let split = is_split_required(
+            &transform,
+            &mut following_transforms,
+            supports_qualify,
+            sample_ends_select,
+        );
         if split {
             log::debug!("split required after {}", transform.as_ref());
             log::debug!(".. following={:?}", following_transforms);
@@ -195,6 +202,7 @@ pub fn anchor_split(
         };
 
         new_columns.push((col, new_cid));
+        ctx.column_types.redirect(old_cid, new_cid);
         cid_redirects.insert(*old_cid, new_cid);
     }
 
@@ -209,7 +217,9 @@ pub fn anchor_split(
             relation: Relation {
                 kind: RelationKind::SString(vec![]),
                 columns: vec![],
+                span: None,
             },
+            materialized: None,
         },
     );
 
@@ -233,19 +243,34 @@ pub fn anchor_split(
 /// fit into one SELECT statement.
 ///
 /// `following` contain names of following transforms in the pipeline.
-fn is_split_required(transform: &Transform, following: &mut HashSet<String>) -> bool {
+///
+/// When `supports_qualify` is set, filters that follow a window function
+/// are expressed with QUALIFY instead of a split.
+///
+/// When `sample_ends_select` is set, the clause of a sample is the last of
+/// the SELECT (i.e. `USING SAMPLE` of DuckDB), so the sampled table is
+/// selected on its own, without clauses that would precede the sample.
+fn is_split_required(
+    transform: &Transform,
+    following: &mut HashSet<String>,
+    supports_qualify: bool,
+    sample_ends_select: bool,
+) -> bool {
     // Pipeline must be split when there is a transform that is out of order:
     // - from (max 1x),
+    // - sample (of the table in from)
     // - join (no limit),
     // - filters (for WHERE)
     // - aggregate (max 1x)
     // - filters (for HAVING)
     // - compute (no limit)
     // - sort (no limit)
+    // - take with partition (for DISTINCT ON, max 1x)
     // - take (no limit)
     // - unique (for DISTINCT)
-    // - concat (max 1)
+    // - concat, intersect or remove (max 1)
     // - unique (for UNION)
+    // - sort and take of a concat (for ORDER BY and LIMIT of the UNION)
     //
     // Select is not affected by the order.
     use Transform::*;
@@ -269,14 +294,36 @@ fn is_split_required(transform: &Transform, following: &mut HashSet<String>) ->
 
     let split = match transform {
         From(_) => contains_any(following, ["From"]),
-        Join { .. } => contains_any(following, ["From"]),
+        // sample stays with the preceding `from`, as TABLESAMPLE of its table
+        Sample(_) if sample_ends_select => contains_any(
+            following,
+            ["From", "Join", "Filter", "Aggregate", "Compute"],
+        ),
+        Join { .. } | Sample(_) => contains_any(following, ["From"]),
         Aggregate { .. } => contains_any(following, ["From", "Join", "Aggregate"]),
         Filter(_) => contains_any(following, ["From", "Join"]),
+        Compute(decl)
+            if decl.window.is_some() && supports_qualify && !following.contains("Aggregate") =>
+        {
+            contains_any(following, ["From", "Join"])
+        }
         Compute(_) => contains_any(following, ["From", "Join", /* "Aggregate" */ "Filter"]),
-        Sort(_) => contains_any(following, ["From", "Join", "Compute", "Aggregate"]),
+        Sort(_) => contains_any(
+            following,
+            ["From", "Join", "Compute", "Aggregate", "DistinctOn"],
+        ),
         Take(_) => contains_any(
             following,
-            ["From", "Join", "Compute", "Filter", "Aggregate", "Sort"],
+            [
+                "From",
+                "Join",
+                "Compute",
+                "Filter",
+                "Aggregate",
+                "Sort",
+                "DistinctOn",
+                "Unique",
+            ],
         ),
         Unique => contains_any(
             following,
@@ -290,7 +337,25 @@ fn is_split_required(transform: &Transform, following: &mut HashSet<String>) ->
                 "Take",
             ],
         ),
+        // a sort or take that follows a concat becomes ORDER BY and LIMIT of
+        // the UNION, unless it needs more than that
         Concat(_) => contains_any(
+            following,
+            [
+                "From",
+                "Join",
+                "Compute",
+                "Filter",
+                "Aggregate",
+                "SortNulls",
+                "TakePercent",
+                "DistinctOn",
+                "Concat",
+                "Intersect",
+                "Remove",
+            ],
+        ),
+        Intersect(_) | Remove(_) => contains_any(
             following,
             [
                 "From",
@@ -301,13 +366,31 @@ fn is_split_required(transform: &Transform, following: &mut HashSet<String>) ->
                 "Sort",
                 "Take",
                 "Concat",
+                "Intersect",
+                "Remove",
             ],
         ),
         _ => false,
     };
 
     if !split {
-        following.insert(transform.as_ref().to_string());
+        let name = match transform {
+            // partitioned takes that remain after preprocessing become DISTINCT ON
+            Take(take) if !take.partition.is_empty() => "DistinctOn",
+            _ => transform.as_ref(),
+        };
+        following.insert(name.to_string());
+
+        // sorts and takes that cannot be applied to a UNION
+        match transform {
+            Sort(sorts) if sorts.iter().any(|s| s.nulls.is_some()) => {
+                following.insert("SortNulls".to_string());
+            }
+            Take(take) if take.percent => {
+                following.insert("TakePercent".to_string());
+            }
+            _ => {}
+        }
     }
     split
 }
@@ -348,7 +431,10 @@ impl std::fmt::Debug for Requirement {
 pub fn get_requirements(transform: &Transform, following: &HashSet<String>) -> Vec<Requirement> {
     use Transform::*;
 
-    if let Aggregate { partition, compute } = transform {
+    if let Aggregate {
+        partition, compute, ..
+    } = transform
+    {
         let mut r = Vec::new();
         r.extend(into_requirements(
             partition.clone(),
@@ -363,22 +449,71 @@ pub fn get_requirements(transform: &Transform, following: &HashSet<String>) -> V
         return r;
     }
 
+    // Columns in OVER of a window function must also be carried into the
+    // SELECT, otherwise their expressions would be inlined there, referencing
+    // columns of a preceding CTE that may not have been selected.
+    // They must be plain, because window functions cannot be nested and
+    // aggregations cannot be referenced from OVER outside of their SELECT.
+    if let Compute(rq::Compute {
+        expr,
+        window: Some(window),
+        ..
+    }) = transform
+    {
+        let mut r = into_requirements(
+            CidCollector::collect(expr.clone()),
+            Complexity::Plain,
+            false,
+        );
+        let window_cids = (window.partition.iter())
+            .chain(window.sort.iter().map(|s| &s.column))
+            .cloned()
+            .collect();
+        r.extend(into_requirements(window_cids, Complexity::Plain, false));
+        return r;
+    }
+
+    // A take carries the sort that precedes it. Its columns are required in
+    // the same way as columns of the Sort, so they are selected in all CTEs
+    // in front of the take, even when the pipeline is split.
+    if let Take(rq::Take {
+        range,
+        partition,
+        sort,
+        ..
+    }) = transform
+    {
+        let mut cids = partition.clone();
+        if let Some(e) = &range.start {
+            cids.extend(CidCollector::collect(e.clone()));
+        }
+        if let Some(e) = &range.end {
+            cids.extend(CidCollector::collect(e.clone()));
+        }
+        let mut r = into_requirements(cids, Complexity::Plain, false);
+
+        let sort_cids = sort.iter().map(|s| s.column).collect();
+        r.extend(into_requirements(sort_cids, Complexity::Aggregation, true));
+        return r;
+    }
+
     let cids = match transform {
         Compute(compute) => CidCollector::collect(compute.expr.clone()),
         Filter(expr) | Join { filter: expr, .. } => CidCollector::collect(expr.clone()),
         Sort(sorts) => sorts.iter().map(|s| s.column).collect(),
-        Take(rq::Take { range, .. }) => {
-            let mut cids = Vec::new();
-            if let Some(e) = &range.start {
-                cids.extend(CidCollector::collect(e.clone()));
-            }
-            if let Some(e) = &range.end {
-                cids.extend(CidCollector::collect(e.clone()));
-            }
-            cids
-        }
-
-        Select(_) | From(_) | Concat(_) | Aggregate { .. } | Unique => return Vec::new(),
+        // excluded columns are not selected, but their names must be known
+        Exclude(cids) => cids.clone(),
+
+        Select(_)
+        | From(_)
+        | Concat(_)
+        | Intersect(_)
+        | Remove(_)
+        | Aggregate { .. }
+        | Take(_)
+        | Sample(_)
+        | Unique
+        | Loop(_) => return Vec::new(),
     };
 
     let (max_complexity, selected) = match transform {
@@ -400,8 +535,7 @@ pub fn get_requirements(transform: &Transform, following: &HashSet<String>) -> V
         ),
         // ORDER BY uses aliased columns, so the columns can have high complexity
         Sort(_) => (Complexity::Aggregation, true),
-        Take(_) => (Complexity::Plain, false),
-        Join { .. } => (Complexity::Plain, false),
+        Join { .. } | Exclude(_) => (Complexity::Plain, false),
 
         _ => unreachable!(),
     };
@@ -456,6 +590,11 @@ impl RqFold for CidCollector {
         self.cids.insert(cid);
         Ok(cid)
     }
+
+    // columns of relations in `any` and `all` are not inputs of the expression
+    fn fold_table_ref(&mut self, table_ref: TableRef) -> Result<TableRef> {
+        Ok(table_ref)
+    }
 }
 
 struct CidRedirector<'a> {
@@ -7,7 +7,7 @@ use regex::Regex;
 use sqlparser::ast::{
     self as sql_ast, BinaryOperator, DateTimeField, Function, FunctionArg, FunctionArgExpr, Ident,
     Join, JoinConstraint, JoinOperator, ObjectName, OrderByExpr, SelectItem, TableAlias,
-    TableFactor, Top, UnaryOperator, Value, WindowFrameBound, WindowSpec,
+    TableFactor, TableWithJoins, Top, UnaryOperator, Value, WindowFrameBound, WindowSpec,
 };
 use sqlparser::keywords::{
     Keyword, ALL_KEYWORDS, ALL_KEYWORDS_INDEX, RESERVED_FOR_COLUMN_ALIAS, RESERVED_FOR_TABLE_ALIAS,
@@ -15,73 +15,113 @@ use sqlparser::keywords::{
 use std::collections::HashSet;
 
 use crate::ast::pl::{
-    BinOp, ColumnSort, InterpolateItem, JoinSide, Literal, Range, SortDirection, WindowFrame,
-    WindowKind,
+    BinOp, ColumnSort, InterpolateItem, JoinSide, Literal, NullsOrder, Quantifier, Range,
+    SortDirection, WindowFrame, WindowKind,
 };
 use crate::ast::rq::*;
-use crate::error::{Error, Reason};
+use crate::describe::type_of_literal;
+use crate::error::{Error, ErrorCode, Reason, Span};
 use crate::sql::context::ColumnDecl;
 use crate::utils::OrMap;
 
-use super::translator::Context;
-use super::Target;
+use super::target::TargetHandler;
+use super::translator::{is_emulated_bool_aggregate, query_of_set_expr, select_wildcard, Context};
+use super::{RegexMatch, Target};
 
 pub(super) fn translate_expr_kind(item: ExprKind, ctx: &mut Context) -> Result<sql_ast::Expr> {
     Ok(match item {
         ExprKind::ColumnRef(cid) => translate_cid(cid, ctx)?,
-        ExprKind::Binary { op, left, right } => {
-            if let Some(is_null) = try_into_is_null(&op, &left, &right, ctx)? {
-                is_null
-            } else if let Some(between) = try_into_between(&op, &left, &right, ctx)? {
-                between
-            } else {
-                let op = match op {
-                    BinOp::Mul => BinaryOperator::Multiply,
-                    BinOp::Div => BinaryOperator::Divide,
-                    BinOp::Mod => BinaryOperator::Modulo,
-                    BinOp::Add => BinaryOperator::Plus,
-                    BinOp::Sub => BinaryOperator::Minus,
-                    BinOp::Eq => BinaryOperator::Eq,
-                    BinOp::Ne => BinaryOperator::NotEq,
-                    BinOp::Gt => BinaryOperator::Gt,
-                    BinOp::Lt => BinaryOperator::Lt,
-                    BinOp::Gte => BinaryOperator::GtEq,
-                    BinOp::Lte => BinaryOperator::LtEq,
-                    BinOp::And => BinaryOperator::And,
-                    BinOp::Or => BinaryOperator::Or,
-                    BinOp::Coalesce => {
-                        let left = translate_operand(left.kind, 0, false, ctx)?;
-                        let right = translate_operand(right.kind, 0, false, ctx)?;
-
-                        return Ok(sql_ast::Expr::Function(Function {
-                            name: ObjectName(vec![Ident {
-                                value: "COALESCE".to_string(),
-                                quote_style: None,
-                            }]),
-                            args: vec![
-                                FunctionArg::Unnamed(FunctionArgExpr::Expr(*left)),
-                                FunctionArg::Unnamed(FunctionArgExpr::Expr(*right)),
-                            ],
-                            over: None,
-                            distinct: false,
-                            special: false,
-                        }));
-                    }
-                };
-
-                let strength = op.binding_strength();
-                let left = translate_operand(left.kind, strength, !op.associates_left(), ctx)?;
-                let right = translate_operand(right.kind, strength, !op.associates_right(), ctx)?;
-                sql_ast::Expr::BinaryOp { left, right, op }
+        ExprKind::Binary { op, left, right } => match *right {
+            Expr {
+                kind:
+                    ExprKind::Quantified {
+                        quantifier,
+                        relation,
+                    },
+                ..
+            } => translate_quantified(op, *left, quantifier, relation, ctx)?,
+            right => {
+                if let Some(is_null) = try_into_is_null(&op, &left, &right, ctx)? {
+                    is_null
+                } else if let Some(between) = try_into_between(&op, &left, &right, ctx)? {
+                    between
+                } else {
+                    let op = match op {
+                        BinOp::Mul => BinaryOperator::Multiply,
+                        BinOp::Div => BinaryOperator::Divide,
+                        BinOp::Mod => BinaryOperator::Modulo,
+                        BinOp::Add => BinaryOperator::Plus,
+                        BinOp::Sub => BinaryOperator::Minus,
+                        BinOp::Eq => BinaryOperator::Eq,
+                        BinOp::Ne => BinaryOperator::NotEq,
+                        BinOp::Gt => BinaryOperator::Gt,
+                        BinOp::Lt => BinaryOperator::Lt,
+                        BinOp::Gte => BinaryOperator::GtEq,
+                        BinOp::Lte => BinaryOperator::LtEq,
+                        BinOp::And => BinaryOperator::And,
+                        BinOp::Or => BinaryOperator::Or,
+                        BinOp::RegexSearch => return translate_regex_search(*left, right, ctx),
+                        BinOp::Coalesce => {
+                            // `a ?? b ?? c` is a single COALESCE(a, b, c)
+                            let mut operands = Vec::new();
+                            coalesce_operands(*left, &mut operands);
+                            coalesce_operands(right, &mut operands);
+
+                            // NULLs get the type of a literal among the operands
+                            let null_type =
+                                (operands.iter()).find_map(|operand| match &operand.kind {
+                                    ExprKind::Literal(lit) if !matches!(lit, Literal::Null) => {
+                                        ctx.target.type_name(type_of_literal(lit))
+                                    }
+                                    _ => None,
+                                });
+
+                            let args = (operands.into_iter())
+                                .map(|operand| {
+                                    let operand = match (operand.kind, null_type) {
+                                        (ExprKind::Literal(Literal::Null), Some(ty)) => {
+                                            Box::new(typed_null(ty))
+                                        }
+                                        (kind, _) => translate_operand(kind, 0, false, ctx)?,
+                                    };
+                                    Ok(FunctionArg::Unnamed(FunctionArgExpr::Expr(*operand)))
+                                })
+                                .collect::<Result<_>>()?;
+
+                            return Ok(sql_ast::Expr::Function(Function {
+                                name: ObjectName(vec![Ident {
+                                    value: "COALESCE".to_string(),
+                                    quote_style: None,
+                                }]),
+                                args,
+                                over: None,
+                                distinct: false,
+                                special: false,
+                            }));
+                        }
+                    };
+
+                    let strength = op.binding_strength();
+                    let left = translate_operand(left.kind, strength, !op.associates_left(), ctx)?;
+                    let right =
+                        translate_operand(right.kind, strength, !op.associates_right(), ctx)?;
+                    sql_ast::Expr::BinaryOp { left, right, op }
+                }
             }
-        }
+        },
 
         ExprKind::Unary { op, expr } => {
             let op = match op {
                 UnOp::Neg => UnaryOperator::Minus,
                 UnOp::Not => UnaryOperator::Not,
             };
-            let expr = translate_operand(expr.kind, op.binding_strength(), false, ctx)?;
+            let mut expr = translate_operand(expr.kind, op.binding_strength(), false, ctx)?;
+
+            // `NOT (a = 1)` is the same as `NOT a = 1`, but easier to read
+            let is_comparison = expr.binding_strength() == BinaryOperator::Eq.binding_strength();
+            if op == UnaryOperator::Not && is_comparison {
+                expr = Box::new(sql_ast::Expr::Nested(expr));
+            }
             sql_ast::Expr::UnaryOp { op, expr }
         }
 
@@ -94,6 +134,13 @@ pub(super) fn translate_expr_kind(item: ExprKind, ctx: &mut Context) -> Result<s
             sql_ast::Expr::Identifier(sql_ast::Ident::new(string))
         }
         ExprKind::FString(f_string_items) => {
+            if ctx.target.concat_ignores_nulls() {
+                ctx.warn_emulation(
+                    "f-string",
+                    "CONCAT treats NULL values as empty strings instead of producing NULL",
+                );
+            }
+
             let args = f_string_items
                 .into_iter()
                 .map(|item| match item {
@@ -116,9 +163,34 @@ pub(super) fn translate_expr_kind(item: ExprKind, ctx: &mut Context) -> Result<s
         ExprKind::Literal(l) => match l {
             Literal::Null => sql_ast::Expr::Value(Value::Null),
             Literal::String(s) => sql_ast::Expr::Value(Value::SingleQuotedString(s)),
+            // without boolean values, a literal can only be a condition
+            Literal::Boolean(b) if !ctx.target.supports_bool_values() => sql_ast::Expr::BinaryOp {
+                left: Box::new(expr_of_i64(1)),
+                op: BinaryOperator::Eq,
+                right: Box::new(expr_of_i64(i64::from(b))),
+            },
+            Literal::Boolean(b) if !ctx.target.supports_bool_literals() => {
+                sql_ast::Expr::Value(Value::Number(if b { "1" } else { "0" }.to_string(), false))
+            }
             Literal::Boolean(b) => sql_ast::Expr::Value(Value::Boolean(b)),
             Literal::Float(f) => sql_ast::Expr::Value(Value::Number(format!("{f:?}"), false)),
             Literal::Integer(i) => sql_ast::Expr::Value(Value::Number(format!("{i}"), false)),
+            lit @ (Literal::Date(_) | Literal::Time(_) | Literal::Timestamp(_))
+                if !ctx.target.supports_date_time_literals() =>
+            {
+                let ty = ctx.target.type_name(type_of_literal(&lit));
+                let (Literal::Date(value) | Literal::Time(value) | Literal::Timestamp(value)) = lit
+                else {
+                    unreachable!()
+                };
+                match ty {
+                    Some(ty) => {
+                        let value = value.replace('\'', "''");
+                        sql_ast::Expr::Identifier(Ident::new(format!("CAST('{value}' AS {ty})")))
+                    }
+                    None => sql_ast::Expr::Value(Value::SingleQuotedString(value)),
+                }
+            }
             Literal::Date(value) => sql_ast::Expr::TypedString {
                 data_type: sql_ast::DataType::Date,
                 value,
@@ -127,9 +199,12 @@ pub(super) fn translate_expr_kind(item: ExprKind, ctx: &mut Context) -> Result<s
                 data_type: sql_ast::DataType::Time(None, sql_ast::TimezoneInfo::None),
                 value,
             },
-            Literal::Timestamp(value) => sql_ast::Expr::TypedString {
-                data_type: sql_ast::DataType::Timestamp(None, sql_ast::TimezoneInfo::None),
-                value,
+            Literal::Timestamp(value) => match ctx.target.timestamp_keyword(&value) {
+                "TIMESTAMP" => sql_ast::Expr::TypedString {
+                    data_type: sql_ast::DataType::Timestamp(None, sql_ast::TimezoneInfo::None),
+                    value,
+                },
+                keyword => sql_ast::Expr::Identifier(Ident::new(format!("{keyword} '{value}'"))),
             },
             Literal::ValueAndUnit(vau) => {
                 let sql_parser_datetime = match vau.unit.as_str() {
@@ -139,7 +214,13 @@ pub(super) fn translate_expr_kind(item: ExprKind, ctx: &mut Context) -> Result<s
                     "hours" => DateTimeField::Hour,
                     "minutes" => DateTimeField::Minute,
                     "seconds" => DateTimeField::Second,
-                    _ => bail!("Unsupported interval unit: {}", vau.unit),
+                    "%" => bail!(Error::new(Reason::Simple(
+                        "a percentage can only be used in `take`".to_string()
+                    ))),
+                    unit => bail!(Error::new(Reason::Simple(format!(
+                        "Unsupported interval unit: {unit}"
+                    )))
+                    .with_code(ErrorCode::Unsupported)),
                 };
                 sql_ast::Expr::Interval {
                     value: Box::new(translate_expr_kind(
@@ -152,6 +233,12 @@ pub(super) fn translate_expr_kind(item: ExprKind, ctx: &mut Context) -> Result<s
                     fractional_seconds_precision: None,
                 }
             }
+            Literal::Relation(_) => {
+                bail!(Error::new(Reason::Simple(
+                    "relation literal cannot be used as an expression".to_string()
+                ))
+                .with_code(ErrorCode::Unsupported))
+            }
         },
         ExprKind::Switch(mut cases) => {
             let default = cases
@@ -169,9 +256,8 @@ pub(super) fn translate_expr_kind(item: ExprKind, ctx: &mut Context) -> Result<s
                 cases.pop();
             }
 
-            let else_result = default
-                .or(Some(sql_ast::Expr::Value(Value::Null)))
-                .map(Box::new);
+            // without a default, CASE evaluates to NULL
+            let else_result = default.map(Box::new);
 
             let cases: Vec<_> = cases
                 .into_iter()
@@ -193,22 +279,239 @@ pub(super) fn translate_expr_kind(item: ExprKind, ctx: &mut Context) -> Result<s
         ExprKind::BuiltInFunction { name, args } => {
             super::std::translate_built_in(name, args, ctx)?
         }
+        ExprKind::Quantified { quantifier, .. } => {
+            bail!(Error::new(Reason::Simple(format!(
+                "`{quantifier}` can only be used on the right of a comparison"
+            )))
+            .with_code(ErrorCode::Invalid))
+        }
+    })
+}
+
+/// Operands of nested `??`, in order.
+fn coalesce_operands(expr: Expr, operands: &mut Vec<Expr>) {
+    match expr.kind {
+        ExprKind::Binary {
+            left,
+            op: BinOp::Coalesce,
+            right,
+        } => {
+            coalesce_operands(*left, operands);
+            coalesce_operands(*right, operands);
+        }
+        _ => operands.push(expr),
+    }
+}
+
+/// Translates `expr ~= pattern` into the regex matching of the target. Targets
+/// without one reject it, instead of producing SQL that fails when executed.
+fn translate_regex_search(expr: Expr, pattern: Expr, ctx: &mut Context) -> Result<sql_ast::Expr> {
+    let Some(regex_match) = ctx.target.regex_match() else {
+        let span = match (expr.span, pattern.span) {
+            (Some(left), Some(right)) => Some(Span {
+                start: left.start,
+                end: right.end,
+            }),
+            _ => None,
+        };
+        bail!(Error::new(Reason::Simple(format!(
+            "`~=` is not supported by {}",
+            ctx.target_name()
+        )))
+        .with_span(span)
+        .with_help("use an s-string with the regex function of the target")
+        .with_code(ErrorCode::Unsupported))
+    };
+
+    // `~` of Postgres binds stronger than a comparison
+    if regex_match == RegexMatch::Tilde {
+        let op = BinaryOperator::PGRegexMatch;
+        let strength = op.binding_strength();
+        let left = translate_operand(expr.kind, strength, true, ctx)?;
+        let right = translate_operand(pattern.kind, strength, true, ctx)?;
+        return Ok(sql_ast::Expr::BinaryOp { left, op, right });
+    }
+
+    // operands of the other operators bind like operands of a comparison
+    let strength = if regex_match.is_operator() {
+        BinaryOperator::Eq.binding_strength()
+    } else {
+        0
+    };
+    let expr = translate_operand(expr.kind, strength, true, ctx)?.to_string();
+    let pattern = match pattern.kind {
+        // a raw string, so backslashes of the pattern are not read as escapes
+        ExprKind::Literal(Literal::String(s))
+            if regex_match == RegexMatch::RegexpContains && !s.contains('\'') =>
+        {
+            format!("r'{s}'")
+        }
+        kind => translate_operand(kind, strength, true, ctx)?.to_string(),
+    };
+
+    let matching = sql_ast::Expr::Identifier(Ident::new(regex_match.render(&expr, &pattern)));
+
+    // the rendered operator cannot be parenthesized by its parent
+    Ok(if regex_match.is_operator() {
+        sql_ast::Expr::Nested(Box::new(matching))
+    } else {
+        matching
+    })
+}
+
+/// Translates `std.in`, whose first argument is the value and the rest are
+/// the values of the list, into `value IN (...)`.
+pub(super) fn translate_in_list(args: Vec<Expr>, ctx: &mut Context) -> Result<sql_ast::Expr> {
+    let mut args = args.into_iter();
+    let value = args.next().unwrap();
+    let strength = sql_ast::Expr::InList {
+        expr: Box::new(sql_ast::Expr::Value(Value::Null)),
+        list: vec![],
+        negated: false,
+    }
+    .binding_strength();
+
+    Ok(sql_ast::Expr::InList {
+        expr: translate_operand(value.kind, strength, false, ctx)?,
+        list: args
+            .map(|arg| translate_expr_kind(arg.kind, ctx))
+            .try_collect()?,
+        negated: false,
     })
 }
 
-fn translate_cid(cid: CId, ctx: &mut Context) -> Result<sql_ast::Expr> {
-    if ctx.pre_projection {
+/// Translates `left op all (...)` and `left op any (...)`.
+///
+/// On targets without `ANY` and `ALL`, ordering comparisons are expressed by
+/// comparing with `MIN` or `MAX` of the relation.
+fn translate_quantified(
+    op: BinOp,
+    left: Expr,
+    quantifier: Quantifier,
+    relation: TableRef,
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    let op = match op {
+        BinOp::Eq => BinaryOperator::Eq,
+        BinOp::Ne => BinaryOperator::NotEq,
+        BinOp::Gt => BinaryOperator::Gt,
+        BinOp::Lt => BinaryOperator::Lt,
+        BinOp::Gte => BinaryOperator::GtEq,
+        BinOp::Lte => BinaryOperator::LtEq,
+        _ => bail!(Error::new(Reason::Simple(format!(
+            "`{quantifier}` can only be used on the right of a comparison"
+        )))
+        .with_span(left.span)
+        .with_code(ErrorCode::Invalid)),
+    };
+
+    let column = match relation.columns.first() {
+        Some((RelationColumn::Single(Some(name)), _)) => translate_ident_part(name.clone(), ctx),
+        _ => {
+            // suggest the name of the compared column, if it has one
+            let name = match &left.kind {
+                ExprKind::ColumnRef(cid) => ctx.anchor.column_names.get(cid).cloned(),
+                _ => None,
+            };
+            let name = name.unwrap_or_else(|| "value".to_string());
+
+            bail!(Error::new(Reason::Simple(format!(
+                "`{quantifier}` needs a named column to compare with"
+            )))
+            .with_span(left.span)
+            .with_help(format!("name the column, i.e. `select [{name} = ...]`"))
+            .with_code(ErrorCode::Invalid))
+        }
+    };
+    let column = sql_ast::Expr::Identifier(column);
+
+    let extreme = if ctx.target.supports_quantified_comparison() {
+        None
+    } else {
+        let (lower, upper) = match quantifier {
+            Quantifier::All => ("MIN", "MAX"),
+            Quantifier::Any => ("MAX", "MIN"),
+        };
+        let extreme = match op {
+            BinaryOperator::Gt | BinaryOperator::GtEq => upper,
+            BinaryOperator::Lt | BinaryOperator::LtEq => lower,
+            _ => bail!(Error::new(Reason::Simple(format!(
+                "`{op} {quantifier}` is not supported on {}",
+                ctx.target_name()
+            )))
+            .with_span(left.span)
+            .with_code(ErrorCode::Unsupported)),
+        };
+
+        ctx.warn_emulation(
+            &quantifier.to_string(),
+            "it is emulated with MIN or MAX, which differ on empty relations and NULL values",
+        );
+        Some(extreme)
+    };
+
+    let mut select = select_wildcard(TableWithJoins {
+        // no alias, the column is referenced without a prefix
+        relation: table_factor_of_tid(
+            TableRef {
+                name: None,
+                ..relation
+            },
+            None,
+            ctx,
+        ),
+        joins: vec![],
+    });
+    select.projection = vec![SelectItem::UnnamedExpr(match extreme {
+        Some(extreme) => sql_ast::Expr::Function(Function {
+            name: ObjectName(vec![Ident::new(extreme)]),
+            args: vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(column))],
+            over: None,
+            distinct: false,
+            special: false,
+        }),
+        None => column,
+    })];
+    let subquery = query_of_set_expr(sql_ast::SetExpr::Select(Box::new(select)));
+
+    let right = if extreme.is_some() {
+        sql_ast::Expr::Subquery(Box::new(subquery))
+    } else {
+        // The subquery is rendered as-is, since sqlparser would wrap a
+        // `Subquery` in another pair of parentheses, turning it into an array.
+        let subquery = sql_ast::Expr::Identifier(Ident::new(subquery.to_string()));
+        match quantifier {
+            Quantifier::All => sql_ast::Expr::AllOp(Box::new(subquery)),
+            Quantifier::Any => sql_ast::Expr::AnyOp(Box::new(subquery)),
+        }
+    };
+
+    let left = translate_operand(left.kind, op.binding_strength(), false, ctx)?;
+    Ok(sql_ast::Expr::BinaryOp {
+        left,
+        op,
+        right: Box::new(right),
+    })
+}
+
+pub(super) fn translate_cid(cid: CId, ctx: &mut Context) -> Result<sql_ast::Expr> {
+    if ctx.pre_projection && !ctx.aliased.contains(&cid) {
         log::debug!("translating {cid:?} pre projection");
         let decl = ctx.anchor.column_decls.get(&cid).expect("bad RQ ids");
 
         Ok(match decl {
             ColumnDecl::Compute(compute) => {
                 let window = compute.window.clone();
+                let expr = compute.expr.clone();
 
-                let expr = translate_expr_kind(compute.expr.kind.clone(), ctx)?;
+                if let Some(window) = &window {
+                    ensure_window_order(&expr, window, ctx)?;
+                }
+                let frameless = is_frameless(&expr);
+                let expr = translate_expr_kind(expr.kind, ctx)?;
 
                 if let Some(window) = window {
-                    translate_windowed(expr, window, ctx)?
+                    translate_windowed(expr, window, frameless, ctx)?
                 } else {
                     expr
                 }
@@ -253,20 +556,40 @@ fn translate_cid(cid: CId, ctx: &mut Context) -> Result<sql_ast::Expr> {
     }
 }
 
-pub(super) fn table_factor_of_tid(table_ref: TableRef, ctx: &Context) -> TableFactor {
+/// Table of a [TableRef], with `TABLESAMPLE` if it is sampled.
+pub(super) fn table_factor_of_tid(
+    table_ref: TableRef,
+    sample: Option<Sample>,
+    ctx: &Context,
+) -> TableFactor {
     let decl = ctx.anchor.table_decls.get(&table_ref.source).unwrap();
 
     let relation_name = decl.name.clone().unwrap();
+    let mut name = translate_ident(Some(relation_name), None, ctx);
+    let mut alias = if decl.name == table_ref.name {
+        None
+    } else {
+        table_ref.name.map(|ident| TableAlias {
+            name: translate_ident_part(ident, ctx),
+            columns: vec![],
+        })
+    };
+
+    // sqlparser cannot express TABLESAMPLE, so the clause is appended to the
+    // alias (or the name), which it follows in all dialects. A clause that
+    // ends the SELECT follows it too, as the table is sampled on its own.
+    let clause = sample.and_then(|s| ctx.target.table_sample()?.render(s.size, s.percent));
+    if let Some(clause) = clause {
+        if let Some(alias) = &mut alias {
+            alias.name = Ident::new(format!("{} {clause}", alias.name));
+        } else if let Some(last) = name.last_mut() {
+            *last = Ident::new(format!("{last} {clause}"));
+        }
+    }
+
     TableFactor::Table {
-        name: sql_ast::ObjectName(translate_ident(Some(relation_name), None, ctx)),
-        alias: if decl.name == table_ref.name {
-            None
-        } else {
-            table_ref.name.map(|ident| TableAlias {
-                name: translate_ident_part(ident, ctx),
-                columns: vec![],
-            })
-        },
+        name: sql_ast::ObjectName(name),
+        alias,
         args: None,
         with_hints: vec![],
     }
@@ -323,10 +646,97 @@ pub(super) fn translate_query_sstring(
         bail!(Error::new(Reason::Simple(
             "s-strings representing a table must start with `SELECT `".to_string()
         ))
-        .with_help("this is a limitation by current compiler implementation"))
+        .with_help("this is a limitation by current compiler implementation")
+        .with_code(ErrorCode::Unsupported))
+    }
+}
+
+/// A type as it is named by the target, for example `NVARCHAR(MAX)`.
+fn data_type_of_name(name: &str) -> sql_ast::DataType {
+    sql_ast::DataType::Custom(ObjectName(vec![Ident::new(name)]), vec![])
+}
+
+/// `CAST(NULL AS type)`, for engines that cannot infer the type of a NULL.
+fn typed_null(type_name: &str) -> sql_ast::Expr {
+    sql_ast::Expr::Cast {
+        expr: Box::new(sql_ast::Expr::Value(Value::Null)),
+        data_type: data_type_of_name(type_name),
     }
 }
 
+/// Translates a relation literal into a union of SELECTs, one for each of the rows.
+/// We don't use `VALUES`, because not all targets support naming its columns.
+pub(super) fn translate_relation_literal(
+    data: RelationLiteral,
+    ctx: &mut Context,
+) -> Result<sql_ast::Query> {
+    // Within a UNION, some engines cannot infer type of a NULL, so we take it
+    // from a non-null value in the same column.
+    let null_types: Vec<_> = (0..data.columns.len())
+        .map(|index| {
+            let mut values = data.rows.iter().map(|row| &row[index]);
+            let typed = values.find(|v| !matches!(v, Literal::Null));
+            typed.and_then(|v| ctx.target.type_name(type_of_literal(v)))
+        })
+        .collect();
+
+    let mut selects = Vec::with_capacity(data.rows.len());
+    for row in data.rows {
+        let projection = itertools::izip!(&data.columns, row, &null_types)
+            .map(|(name, value, null_type)| -> Result<_> {
+                let expr = match (value, null_type) {
+                    (Literal::Null, Some(ty)) => typed_null(ty),
+                    (value, _) => translate_expr_kind(ExprKind::Literal(value), ctx)?,
+                };
+                Ok(SelectItem::ExprWithAlias {
+                    expr,
+                    alias: translate_ident_part(name.clone(), ctx),
+                })
+            })
+            .try_collect()?;
+
+        selects.push(sql_ast::SetExpr::Select(Box::new(sql_ast::Select {
+            projection,
+            distinct: false,
+            top: None,
+            into: None,
+            from: Vec::new(),
+            lateral_views: Vec::new(),
+            selection: None,
+            group_by: Vec::new(),
+            cluster_by: Vec::new(),
+            distribute_by: Vec::new(),
+            sort_by: Vec::new(),
+            having: None,
+            qualify: None,
+        })));
+    }
+
+    let body = selects
+        .into_iter()
+        .reduce(|left, right| sql_ast::SetExpr::SetOperation {
+            op: sql_ast::SetOperator::Union,
+            set_quantifier: sql_ast::SetQuantifier::All,
+            left: Box::new(left),
+            right: Box::new(right),
+        })
+        .ok_or_else(|| {
+            Error::new(Reason::Simple(
+                "relation literal must contain at least one row".to_string(),
+            ))
+        })?;
+
+    Ok(sql_ast::Query {
+        body: Box::new(body),
+        with: None,
+        order_by: Vec::new(),
+        limit: None,
+        offset: None,
+        fetch: None,
+        locks: vec![],
+    })
+}
+
 /// Aggregate several ordered ranges into one, computing the intersection.
 ///
 /// Returns a tuple of `(start, end)`, where `end` is optional.
@@ -391,6 +801,22 @@ pub(super) fn try_into_exprs(cids: Vec<CId>, ctx: &mut Context) -> Result<Vec<sq
 pub(super) fn translate_select_item(cid: CId, ctx: &mut Context) -> Result<SelectItem> {
     let expr = translate_cid(cid, ctx)?;
 
+    // without boolean values, the comparison of an emulated `every` or `any`
+    // is a condition, which cannot be selected
+    let expr = if ctx.pre_projection
+        && !ctx.target.supports_bool_values()
+        && is_emulated_bool_aggregate(&cid, ctx)
+    {
+        sql_ast::Expr::Case {
+            operand: None,
+            conditions: vec![expr],
+            results: vec![expr_of_i64(1)],
+            else_result: Some(Box::new(expr_of_i64(0))),
+        }
+    } else {
+        expr
+    };
+
     let inferred_name = match &expr {
         // sql_ast::Expr::Identifier is used for s-strings
         sql_ast::Expr::CompoundIdentifier(parts) => parts.last().map(|p| &p.value),
@@ -472,11 +898,103 @@ fn try_into_between(
     }))
 }
 
+/// Name of the offset or ranking function that the expression calls, if any.
+/// These functions depend on the order of rows.
+fn ordered_window_function(expr: &Expr) -> Option<&str> {
+    let ExprKind::BuiltInFunction { name, .. } = &expr.kind else {
+        return None;
+    };
+    let name = name.strip_prefix("std.").unwrap_or(name);
+
+    let is_ordered = matches!(
+        name,
+        "lag" | "lead" | "first" | "last" | "rank" | "rank_dense" | "row_number" | "ntile"
+    );
+    is_ordered.then_some(name)
+}
+
+/// A frame only applies to aggregations and to `first` and `last`, engines
+/// such as SQL Server and BigQuery reject it for offset and ranking functions.
+fn is_frameless(expr: &Expr) -> bool {
+    ordered_window_function(expr).map_or(false, |name| !matches!(name, "first" | "last"))
+}
+
+/// Offset and ranking functions depend on the order of rows, so some targets
+/// reject them without `ORDER BY`.
+fn ensure_window_order(expr: &Expr, window: &Window, ctx: &Context) -> Result<()> {
+    let Some(name) = ordered_window_function(expr) else {
+        return Ok(());
+    };
+    if !window.sort.is_empty() || !ctx.target.requires_window_order() {
+        return Ok(());
+    }
+
+    bail!(Error::new(Reason::Simple(format!(
+        "`{name}` needs sorted rows on {}",
+        ctx.target_name()
+    )))
+    .with_span(expr.span)
+    .with_help(format!("use `sort` before `{name}`"))
+    .with_code(ErrorCode::Unsupported))
+}
+
+/// Translates an expression over a window. A window that is defined in the
+/// `WINDOW` clause of the SELECT is referenced by its name.
 fn translate_windowed(
     expr: sql_ast::Expr,
     window: Window,
+    frameless: bool,
     ctx: &mut Context,
 ) -> Result<sql_ast::Expr> {
+    let window = translate_window_spec(window, frameless, ctx)?;
+
+    let over = match ctx.named_windows.iter().position(|w| w == &window) {
+        Some(index) => format!("w{}", index + 1),
+        None => format!("({window})"),
+    };
+    Ok(sql_ast::Expr::Identifier(sql_ast::Ident::new(format!(
+        "{expr} OVER {over}"
+    ))))
+}
+
+/// Window specifications that are shared by more than one of the projected
+/// columns, in order of their first use. These are defined in the `WINDOW`
+/// clause of the SELECT, see [Context::named_windows].
+pub(super) fn shared_window_specs(cids: &[CId], ctx: &mut Context) -> Result<Vec<WindowSpec>> {
+    let windows = (cids.iter())
+        .filter_map(|cid| match ctx.anchor.column_decls.get(cid)? {
+            ColumnDecl::Compute(compute) => {
+                let window = compute.window.clone()?;
+                Some((window, is_frameless(&compute.expr)))
+            }
+            _ => None,
+        })
+        .collect_vec();
+
+    // specifications are translated again with their columns, which would
+    // duplicate notes of this translation
+    let notes = std::mem::take(&mut ctx.notes);
+    let specs: Result<Vec<_>> = (windows.into_iter())
+        .map(|(window, frameless)| translate_window_spec(window, frameless, ctx))
+        .try_collect();
+    ctx.notes = notes;
+
+    let mut counts: Vec<(WindowSpec, usize)> = Vec::new();
+    for spec in specs? {
+        match counts.iter_mut().find(|(s, _)| s == &spec) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((spec, 1)),
+        }
+    }
+    Ok((counts.into_iter())
+        .filter(|(_, count)| *count > 1)
+        .map(|(spec, _)| spec)
+        .collect())
+}
+
+/// The frame is omitted if it is the default one or if the function is
+/// `frameless`.
+fn translate_window_spec(window: Window, frameless: bool, ctx: &mut Context) -> Result<WindowSpec> {
     let default_frame = {
         let (kind, range) = if window.sort.is_empty() {
             (WindowKind::Rows, Range::unbounded())
@@ -495,22 +1013,19 @@ fn translate_windowed(
         WindowFrame { kind, range }
     };
 
-    let window = WindowSpec {
+    Ok(WindowSpec {
         partition_by: try_into_exprs(window.partition, ctx)?,
         order_by: (window.sort)
             .into_iter()
             .map(|sort| translate_column_sort(&sort, ctx))
-            .try_collect()?,
-        window_frame: if window.frame == default_frame {
+            .try_collect::<_, Vec<_>, _>()?
+            .concat(),
+        window_frame: if frameless || window.frame == default_frame {
             None
         } else {
             Some(try_into_window_frame(window.frame)?)
         },
-    };
-
-    Ok(sql_ast::Expr::Identifier(sql_ast::Ident::new(format!(
-        "{expr} OVER ({window})"
-    ))))
+    })
 }
 
 fn try_into_window_frame(frame: WindowFrame<Expr>) -> Result<sql_ast::WindowFrame> {
@@ -545,38 +1060,172 @@ fn try_into_window_frame(frame: WindowFrame<Expr>) -> Result<sql_ast::WindowFram
     })
 }
 
+/// Translates a sort into ORDER BY items. On targets without `NULLS FIRST`
+/// and `NULLS LAST`, placement of NULLs is expressed with a preceding
+/// `col IS NULL` item, since `FALSE` sorts before `TRUE`.
 pub(super) fn translate_column_sort(
     sort: &ColumnSort<CId>,
     ctx: &mut Context,
-) -> Result<OrderByExpr> {
-    Ok(OrderByExpr {
-        expr: translate_cid(sort.column, ctx)?,
-        asc: if matches!(sort.direction, SortDirection::Asc) {
-            None // default order is ASC, so there is no need to emit it
-        } else {
-            Some(false)
-        },
+) -> Result<Vec<OrderByExpr>> {
+    let expr = translate_cid(sort.column, ctx)?;
+    let asc = if matches!(sort.direction, SortDirection::Asc) {
+        None // default order is ASC, so there is no need to emit it
+    } else {
+        Some(false)
+    };
+
+    let Some(nulls) = sort.nulls else {
+        return Ok(vec![OrderByExpr {
+            expr,
+            asc,
+            nulls_first: None,
+        }]);
+    };
+    let nulls_first = matches!(nulls, NullsOrder::First);
+
+    if ctx.target.supports_nulls_ordering() {
+        return Ok(vec![OrderByExpr {
+            expr,
+            asc,
+            nulls_first: Some(nulls_first),
+        }]);
+    }
+
+    let is_null = OrderByExpr {
+        expr: sql_ast::Expr::IsNull(Box::new(expr.clone())),
+        asc: if nulls_first { Some(false) } else { None },
         nulls_first: None,
-    })
+    };
+    let sort = OrderByExpr {
+        expr,
+        asc,
+        nulls_first: None,
+    };
+    Ok(vec![is_null, sort])
 }
 
+/// Only the first join of a SELECT uses `USING`. It follows a single table,
+/// so the columns it names can only be from that table. The left side of a
+/// later join is already a join of several tables, which may have columns of
+/// the same name: `USING (id)` would then be ambiguous (or, with some
+/// databases, compare with the merged column of the first join instead of
+/// the column of the relation that the condition names), so later joins
+/// always use `ON`.
 pub(super) fn translate_join(
     (side, with, filter): (JoinSide, TableRef, Expr),
+    is_first: bool,
     ctx: &mut Context,
 ) -> Result<Join> {
-    let constraint = JoinConstraint::On(translate_expr_kind(filter.kind, ctx)?);
+    // an inner join without a condition (`join b []` or `join b (true)`)
+    let unconditional = matches!(filter.kind, ExprKind::Literal(Literal::Boolean(true)));
+    if side == JoinSide::Inner && unconditional {
+        return Ok(Join {
+            relation: table_factor_of_tid(with, None, ctx),
+            join_operator: JoinOperator::CrossJoin,
+        });
+    }
+
+    let using = using_columns(&filter, &with, ctx);
+    let constraint = match using {
+        Some(columns) if is_first && ctx.target.supports_join_using() => JoinConstraint::Using(
+            (columns.into_iter())
+                .map(|name| translate_ident_part(name, ctx))
+                .collect(),
+        ),
+        _ => JoinConstraint::On(translate_expr_kind(filter.kind, ctx)?),
+    };
 
     Ok(Join {
-        relation: table_factor_of_tid(with, ctx),
+        relation: table_factor_of_tid(with, None, ctx),
         join_operator: match side {
             JoinSide::Inner => JoinOperator::Inner(constraint),
             JoinSide::Left => JoinOperator::LeftOuter(constraint),
             JoinSide::Right => JoinOperator::RightOuter(constraint),
             JoinSide::Full => JoinOperator::FullOuter(constraint),
+            JoinSide::Semi | JoinSide::Anti => {
+                unreachable!("semi and anti joins are translated to EXISTS")
+            }
         },
     })
 }
 
+/// Names of columns of a join condition that only compares columns of the left
+/// relation with columns of the same name of `with`, as `[==id]` does.
+fn using_columns(filter: &Expr, with: &TableRef, ctx: &Context) -> Option<Vec<String>> {
+    let ExprKind::Binary { left, op, right } = &filter.kind else {
+        return None;
+    };
+    match op {
+        BinOp::And => {
+            let mut columns = using_columns(left, with, ctx)?;
+            columns.extend(using_columns(right, with, ctx)?);
+            Some(columns)
+        }
+        BinOp::Eq => {
+            let (ExprKind::ColumnRef(left), ExprKind::ColumnRef(right)) = (&left.kind, &right.kind)
+            else {
+                return None;
+            };
+            let is_of_with = |cid: &CId| with.columns.iter().any(|(_, c)| c == cid);
+            if is_of_with(left) || !is_of_with(right) {
+                return None;
+            }
+
+            let name_of = |cid: &CId| match ctx.anchor.column_decls.get(cid) {
+                Some(ColumnDecl::RelationColumn(_, _, RelationColumn::Single(Some(name)))) => {
+                    Some(name)
+                }
+                _ => None,
+            };
+            let name = name_of(left)?;
+            (name_of(right)? == name).then(|| name.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Semi and anti joins keep rows that have (or don't have) a match in `with`,
+/// so they are expressed as `EXISTS` and `NOT EXISTS` in WHERE. Unlike a JOIN,
+/// this never duplicates rows of the relation.
+pub(super) fn translate_exists_join(
+    (side, with, filter): (JoinSide, TableRef, Expr),
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    let mut matching = select_wildcard(TableWithJoins {
+        relation: table_factor_of_tid(with, None, ctx),
+        joins: vec![],
+    });
+    matching.projection = vec![SelectItem::UnnamedExpr(sql_ast::Expr::Value(
+        Value::Number("1".to_string(), false),
+    ))];
+    matching.selection = Some(translate_expr_kind(filter.kind, ctx)?);
+
+    Ok(sql_ast::Expr::Exists {
+        subquery: Box::new(query_of_set_expr(sql_ast::SetExpr::Select(Box::new(
+            matching,
+        )))),
+        negated: side == JoinSide::Anti,
+    })
+}
+
+/// Conjunction of two translated conditions, nested where needed.
+pub(super) fn translate_and(left: sql_ast::Expr, right: sql_ast::Expr) -> sql_ast::Expr {
+    let strength = BinaryOperator::And.binding_strength();
+    let nest = |expr: sql_ast::Expr| {
+        if expr.binding_strength() < strength {
+            sql_ast::Expr::Nested(Box::new(expr))
+        } else {
+            expr
+        }
+    };
+
+    sql_ast::Expr::BinaryOp {
+        left: Box::new(nest(left)),
+        op: BinaryOperator::And,
+        right: Box::new(nest(right)),
+    }
+}
+
 /// Translate a PRQL Ident to a Vec of SQL Idents.
 // We return a vec of SQL Idents because sqlparser sometimes uses
 // [ObjectName](sql_ast::ObjectName) and sometimes uses
@@ -632,6 +1281,11 @@ fn is_keyword(ident: &str) -> bool {
 }
 
 pub(super) fn translate_ident_part(ident: String, ctx: &Context) -> sql_ast::Ident {
+    quote_ident_part(ident, ctx.target.as_ref())
+}
+
+/// Quotes the ident if the target requires it, see [translate_ident_part].
+pub(super) fn quote_ident_part(ident: String, target: &dyn TargetHandler) -> sql_ast::Ident {
     // We'll remove this when we get the new dbt plugin working (so no need to
     // integrate into the regex)
     let is_jinja = ident.starts_with("{{") && ident.ends_with("}}");
@@ -640,6 +1294,9 @@ pub(super) fn translate_ident_part(ident: String, ctx: &Context) -> sql_ast::Ide
         // - `*`
         // - An ident starting with `a-z_\$` and containing other characters `a-z0-9_\$`
         //
+        // Idents with non-ASCII characters are always quoted, since engines
+        // disagree on which of them are allowed bare.
+        //
         // We could replace this with pomsky (regex<>pomsky : sql<>prql)
         // ^ ('*' | [ascii_lower '_$'] [ascii_lower ascii_digit '_$']* ) $
         static ref VALID_BARE_IDENT: Regex = Regex::new(r"^((\*)|(^[a-z_\$][a-z0-9_\$]*))$").unwrap();
@@ -647,10 +1304,19 @@ pub(super) fn translate_ident_part(ident: String, ctx: &Context) -> sql_ast::Ide
 
     let is_bare = VALID_BARE_IDENT.is_match(&ident);
 
-    if is_jinja || is_bare && !is_keyword(&ident) {
+    let is_dangerous = target.is_dangerous_bare_ident(&ident);
+
+    if is_jinja || is_bare && !is_keyword(&ident) && !is_dangerous {
         sql_ast::Ident::new(ident)
     } else {
-        sql_ast::Ident::with_quote(ctx.target.ident_quote(), ident)
+        let quote = target.ident_quote();
+        // sqlparser escapes the quote only for quotes that also close the ident
+        let ident = if quote == '[' {
+            ident.replace(']', "]]")
+        } else {
+            ident
+        };
+        sql_ast::Ident::with_quote(quote, ident)
     }
 }
 
@@ -721,7 +1387,9 @@ impl SQLExpression for sql_ast::Expr {
 
             sql_ast::Expr::UnaryOp { op, .. } => op.binding_strength(),
 
-            sql_ast::Expr::Like { .. } | sql_ast::Expr::ILike { .. } => 7,
+            sql_ast::Expr::Like { .. }
+            | sql_ast::Expr::ILike { .. }
+            | sql_ast::Expr::InList { .. } => 7,
 
             sql_ast::Expr::IsNull(_) | sql_ast::Expr::IsNotNull(_) => 5,
 
@@ -11,6 +11,7 @@ use itertools::Itertools;
 use crate::ast::rq::{
     fold_table, CId, Compute, Query, RelationColumn, RqFold, TId, TableDecl, TableRef, Transform,
 };
+use crate::describe::{self, ColumnTypes};
 use crate::utils::{IdGenerator, NameGenerator};
 
 #[derive(Default)]
@@ -22,6 +23,9 @@ pub struct AnchorContext {
 
     pub(super) table_instances: HashMap<TIId, TableRef>,
 
+    /// Types of columns, as far as they are known.
+    pub(super) column_types: ColumnTypes,
+
     pub(super) col_name: NameGenerator,
     pub(super) table_name: NameGenerator,
 
@@ -48,12 +52,14 @@ pub enum ColumnDecl {
 
 impl AnchorContext {
     pub fn of(query: Query) -> (Self, Query) {
+        let column_types = describe::column_types(&query);
         let (cid, tid, query) = IdGenerator::load(query);
 
         let context = AnchorContext {
             cid,
             tid,
             tiid: IdGenerator::new(),
+            column_types,
             col_name: NameGenerator::new("_expr_"),
             table_name: NameGenerator::new("table_"),
             ..Default::default()
@@ -129,9 +135,9 @@ impl AnchorContext {
                 ]
                 .concat(),
                 Transform::Select(cols) => cols.clone(),
-                Transform::Aggregate { partition, compute } => {
-                    [partition.clone(), compute.clone()].concat()
-                }
+                Transform::Aggregate {
+                    partition, compute, ..
+                } => [partition.clone(), compute.clone()].concat(),
                 _ => Self::determine_select_columns(remaining),
             }
         } else {
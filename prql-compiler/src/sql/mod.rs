@@ -8,39 +8,94 @@ mod std;
 mod target;
 mod translator;
 
-pub use target::Target;
+pub use sqlparser::ast as sql_ast;
+pub use target::{
+    BoolToNumber, ExcludeColumns, RegexMatch, TableSample, Target, TargetCapabilities,
+};
 
-use anyhow::Result;
+use ::std::fmt::{Debug, Formatter};
+use ::std::sync::Arc;
+
+use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::cancellation::Cancellation;
+use crate::error::{Error, ErrorCode, Reason};
+use crate::notes::Notes;
+use crate::policy::Policy;
+use crate::timings::{timed, CompileTimings};
 use crate::{ast::rq::Query, PRQL_VERSION};
 
 /// Translate a PRQL AST into a SQL string.
 pub fn compile(query: Query, options: Option<Options>) -> Result<String> {
+    compile_and_note(query, options).map(|(sql, _)| sql)
+}
+
+/// Same as [compile], but also returns notes about normalizations that were
+/// applied to the query and warnings about it.
+pub fn compile_and_note(query: Query, options: Option<Options>) -> Result<(String, Notes)> {
+    compile_timed(query, options, None)
+}
+
+/// Same as [compile_and_note], but also adds durations of the phases to
+/// `timings`, if there are any.
+pub(crate) fn compile_timed(
+    query: Query,
+    options: Option<Options>,
+    mut timings: Option<&mut CompileTimings>,
+) -> Result<(String, Notes)> {
     let options = options.unwrap_or_default();
 
-    let sql_ast = translator::translate_query(query, options.target)?;
+    let lints = if options.lint {
+        crate::lint::lint_query(&query)
+    } else {
+        Vec::new()
+    };
 
-    let sql = sql_ast.to_string();
+    let (sql_ast, mut notes, materialization) =
+        translator::translate_query(query, &options, timings.as_deref_mut())?;
+    notes.warnings.extend(lints);
 
-    // formatting
-    let sql = if options.format {
-        let formatted = sqlformat::format(
-            &sql,
-            &sqlformat::QueryParams::default(),
-            sqlformat::FormatOptions::default(),
-        );
+    let sql_ast = if let Some(sql_transform) = &options.sql_transform {
+        (sql_transform.0)(sql_ast).map_err(|e| {
+            Error::new(Reason::Simple(format!(
+                "`sql_transform` hook failed: {e:#}"
+            )))
+            .with_code(ErrorCode::Hook)
+        })?
+    } else {
+        sql_ast
+    };
 
-        // The sql formatter turns `{{` into `{ {`, and while that's reasonable SQL,
-        // we want to allow jinja expressions through. So we (somewhat hackily) replace
-        // any `{ {` with `{{`.
-        formatted.replace("{ {", "{{").replace("} }", "}}")
+    // several engines do not allow WITH in a derived table
+    let sql_ast = if options.wrap_as.is_some() {
+        translator::inline_ctes(sql_ast)
     } else {
-        sql
+        sql_ast
     };
+    let ctes = (sql_ast.with.as_ref()).map_or(0, |with| with.cte_tables.len());
+    check_max_ctes(ctes, &options)?;
+
+    let sql = timed(timings.map(|t| &mut t.format), || {
+        let sql = translator::render_query(&sql_ast, &materialization);
+        // the query as a derived table
+        let sql = if let Some(alias) = &options.wrap_as {
+            let alias = translator::translate_wrap_alias(alias, &notes, &options);
+            format!("({sql}) AS {alias}")
+        } else {
+            sql
+        };
+
+        // formatting
+        if options.format {
+            format_sql(&sql, &options)
+        } else {
+            sql
+        }
+    });
 
     // signature
-    let sql = if options.signature_comment {
+    let sql = if options.signature_comment && options.wrap_as.is_none() {
         let pre = if options.format { "\n\n" } else { " " };
         let post = if options.format { "\n" } else { "" };
         let signature = format!(
@@ -52,7 +107,91 @@ pub fn compile(query: Query, options: Option<Options>) -> Result<String> {
         sql
     };
 
-    Ok(sql)
+    if let Some(max_sql_bytes) = options.max_sql_bytes {
+        if sql.len() > max_sql_bytes {
+            bail!(Error::new(Reason::Simple(format!(
+                "query compiles to {} bytes of SQL, which exceeds the limit `max_sql_bytes` of {max_sql_bytes}",
+                sql.len()
+            )))
+            .with_code(ErrorCode::LimitExceeded));
+        }
+    }
+
+    Ok((sql, notes))
+}
+
+/// Translate a PRQL AST into a separate SQL query for each of the CTEs that
+/// [compile] would produce, followed by the main query.
+///
+/// Each of the queries refers to the preceding ones by their names, which
+/// are the names of the CTEs in output of [compile]. This allows materializing
+/// each of them as a table.
+///
+/// Formatting options and options of the translation (`target`,
+/// `strict_standard`, `append_casts`, `max_identifier_length` and
+/// `max_ctes`) are respected, other options are ignored.
+///
+/// Queries that contain `loop` are not supported, because the CTE of a loop
+/// references itself.
+pub fn compile_split(query: Query, options: Option<Options>) -> Result<Vec<(String, String)>> {
+    let options = options.unwrap_or_default();
+
+    let (queries, notes, _) = translator::translate_query_split(query, &options, None)?;
+    check_max_ctes(queries.len() - 1, &options)?;
+
+    let recursive =
+        (queries.iter()).find(|(name, query)| translator::is_recursive(&name.value, query));
+    if let Some((name, _)) = recursive {
+        bail!(Error::new(Reason::Simple(format!(
+            "`{}` is the recursive CTE of a `loop`, which cannot be a separate query",
+            name.value
+        )))
+        .with_code(ErrorCode::Unsupported));
+    }
+
+    let queries = queries.into_iter().map(|(name, query)| {
+        let sql = query.to_string();
+        let sql = if options.format {
+            format_sql(&sql, &options)
+        } else {
+            sql
+        };
+        (name.value, sql)
+    });
+    Ok(queries.collect())
+}
+
+fn check_max_ctes(ctes: usize, options: &Options) -> Result<()> {
+    if let Some(max_ctes) = options.max_ctes {
+        if ctes > max_ctes {
+            bail!(Error::new(Reason::Simple(format!(
+                "query compiles to {ctes} CTEs, which exceeds the limit `max_ctes` of {max_ctes}"
+            )))
+            .with_code(ErrorCode::LimitExceeded));
+        }
+    }
+    Ok(())
+}
+
+/// Number of atomic queries (CTEs and the main query) that the query would be
+/// split into, without translating them into SQL.
+pub(crate) fn count_atomic_queries(query: Query) -> Result<usize> {
+    let (atomics, _) = translator::split_query(query, &Options::default())?;
+    Ok(atomics.len())
+}
+
+fn format_sql(sql: &str, options: &Options) -> String {
+    let format_options = sqlformat::FormatOptions {
+        indent: sqlformat::Indent::Spaces(options.indent),
+        uppercase: options.uppercase_keywords,
+        ..Default::default()
+    };
+    let formatted = sqlformat::format(sql, &sqlformat::QueryParams::default(), format_options);
+
+    // The sql formatter turns `{{` into `{ {`, and while that's reasonable SQL,
+    // we want to allow jinja expressions through. So we (somewhat hackily) replace
+    // any `{ {` with `{{`.
+    formatted.replace("{ {", "{{").replace("} }", "}}")
 }
 
 /// Compilation options for SQL backend of the compiler.
@@ -61,9 +200,28 @@ pub struct Options {
     /// Pass generated SQL string trough a formatter that splits it
     /// into multiple lines and prettifies indentation and spacing.
     ///
+    /// Each select item, CTE and condition joined by `AND` or `OR` is put on
+    /// its own line regardless of width, so adding one of them to a query
+    /// changes one line of the output. Only short parenthesized lists (i.e.
+    /// function arguments) are kept on one line.
+    ///
     /// Defaults to true.
     pub format: bool,
 
+    /// Number of spaces the formatter indents with. Only applies when
+    /// `format` is true.
+    ///
+    /// Defaults to 2.
+    #[serde(default = "default_indent")]
+    pub indent: u8,
+
+    /// Makes the formatter convert keywords to upper case, including the ones
+    /// in s-strings. Only applies when `format` is true.
+    ///
+    /// Defaults to false.
+    #[serde(default)]
+    pub uppercase_keywords: bool,
+
     /// Target to compile to (generally a SQL dialect).
     ///
     /// Because PRQL compiles to a subset of SQL, not all SQL features are
@@ -77,38 +235,254 @@ pub struct Options {
     /// not exist, [Target::Generic] is used.
     pub target: Option<Target>,
 
+    /// Restricts the generated SQL to the SQL standard, even where the target
+    /// would accept more, so it stays portable across databases. Constructs
+    /// such as `QUALIFY`, `DISTINCT ON` or `TOP` are replaced by their
+    /// standard equivalents, and compilation fails where there are none.
+    /// The generated SQL stays valid on the target, so identifiers are
+    /// quoted as the target quotes them.
+    ///
+    /// Defaults to false.
+    #[serde(default)]
+    pub strict_standard: bool,
+
+    /// Casts columns of `append` to a string, where the other relation has
+    /// a string in the same position but this one has a different type.
+    /// Without it, such columns fail to compile.
+    ///
+    /// Defaults to false.
+    #[serde(default)]
+    pub append_casts: bool,
+
+    /// Maximum length of identifiers, in bytes, overriding the one of the
+    /// target, i.e. 30 for Oracle before 12.2. Names of CTEs, aliases and
+    /// generated columns that are longer are truncated and suffixed with a
+    /// hash, so they stay distinct. Longer names of tables and columns that
+    /// are visible outside of the query are reported in warnings.
+    ///
+    /// Defaults to None (the limit of the target).
+    #[serde(default)]
+    pub max_identifier_length: Option<usize>,
+
     /// Emits the compiler signature as a comment after generated SQL
     ///
     /// Defaults to true.
     pub signature_comment: bool,
+
+    /// Appends a `-- prql: {...}` comment with [crate::Metadata] of the
+    /// compilation: the compiler version, the dialect and its version, and
+    /// hashes of the options and of the PRQL source, which can be checked with
+    /// [crate::verify]. Only [crate::compile] and [crate::compile_with_notes]
+    /// know the source, so it has no effect on other entry points. Like the
+    /// signature, it is omitted with [Options::wrap_as].
+    ///
+    /// Defaults to false.
+    #[serde(default)]
+    pub metadata_trailer: bool,
+
+    /// Version of the database the query is compiled for, i.e. `15` for
+    /// Postgres 15. It is recorded in the metadata trailer and does not
+    /// change the generated SQL.
+    ///
+    /// Defaults to None.
+    #[serde(default)]
+    pub dialect_version: Option<String>,
+
+    /// Maximum number of CTEs the generated query may contain, including the
+    /// ones of `loop`. Compilation fails if its WITH clause has more CTEs.
+    /// CTEs that are inlined as subqueries for `wrap_as` don't count. With
+    /// [compile_split], it limits the number of queries preceding the main one.
+    ///
+    /// Defaults to None (no limit).
+    pub max_ctes: Option<usize>,
+
+    /// Maximum length of the generated SQL, in bytes. Compilation fails if
+    /// this limit is exceeded.
+    ///
+    /// Defaults to None (no limit).
+    pub max_sql_bytes: Option<usize>,
+
+    /// A function that is applied to the generated SQL AST before it is
+    /// converted to a string. It can be used to enforce or apply rules that
+    /// are specific to a database or an organization.
+    ///
+    /// Defaults to None.
+    #[serde(skip)]
+    pub sql_transform: Option<SqlTransform>,
+
+    /// Run lints, which report likely mistakes in the query as warnings.
+    ///
+    /// Defaults to false.
+    #[serde(default)]
+    pub lint: bool,
+
+    /// Render the query as a derived table `(SELECT ...) AS alias`, so it can
+    /// be embedded into a larger SQL statement. Because most engines do not
+    /// allow WITH within a derived table, CTEs are inlined as subqueries.
+    /// The alias is used verbatim and the signature comment is omitted.
+    ///
+    /// Defaults to None.
+    #[serde(default)]
+    pub wrap_as: Option<String>,
+
+    /// Restricts the tables and functions the query may reference, i.e. for
+    /// queries written by users that should not reach everything. Only
+    /// applies when passed to entry points that resolve PRQL, such as
+    /// [crate::compile].
+    ///
+    /// Defaults to None.
+    #[serde(default)]
+    pub policy: Option<Policy>,
+
+    /// Aborts the compilation with an error when cancelled or past its
+    /// deadline. Also applies to resolution, when passed to [crate::compile].
+    ///
+    /// Defaults to None.
+    #[serde(skip)]
+    pub cancellation: Option<Cancellation>,
+
+    /// Measures the duration of each phase of the compilation, which is
+    /// returned by [crate::compile_with_timings] and logged at info level.
+    /// Without it, the clock is not read at all.
+    ///
+    /// Defaults to false.
+    #[serde(default)]
+    pub timings: bool,
+}
+
+/// Rewrites (or rejects) a SQL query. See [Options::sql_transform].
+#[derive(Clone)]
+pub struct SqlTransform(pub Arc<dyn Fn(sql_ast::Query) -> Result<sql_ast::Query> + Send + Sync>);
+
+impl Debug for SqlTransform {
+    fn fmt(&self, f: &mut Formatter<'_>) -> ::std::fmt::Result {
+        f.write_str("SqlTransform")
+    }
 }
 
 impl Default for Options {
     fn default() -> Self {
         Self {
             format: true,
+            indent: default_indent(),
+            uppercase_keywords: false,
             target: None,
+            strict_standard: false,
+            append_casts: false,
+            max_identifier_length: None,
             signature_comment: true,
+            metadata_trailer: false,
+            dialect_version: None,
+            max_ctes: None,
+            max_sql_bytes: None,
+            sql_transform: None,
+            lint: false,
+            wrap_as: None,
+            policy: None,
+            cancellation: None,
+            timings: false,
         }
     }
 }
 
+fn default_indent() -> u8 {
+    2
+}
+
 impl Options {
     pub fn no_format(mut self) -> Self {
         self.format = false;
         self
     }
 
+    pub fn with_indent(mut self, indent: u8) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    pub fn with_uppercase_keywords(mut self) -> Self {
+        self.uppercase_keywords = true;
+        self
+    }
+
     pub fn no_signature(mut self) -> Self {
         self.signature_comment = false;
         self
     }
 
+    pub fn with_metadata_trailer(mut self) -> Self {
+        self.metadata_trailer = true;
+        self
+    }
+
+    pub fn with_dialect_version(mut self, version: impl Into<String>) -> Self {
+        self.dialect_version = Some(version.into());
+        self
+    }
+
     pub fn with_target(mut self, target: Target) -> Self {
         self.target = Some(target);
         self
     }
 
+    pub fn with_strict_standard(mut self) -> Self {
+        self.strict_standard = true;
+        self
+    }
+
+    pub fn with_append_casts(mut self) -> Self {
+        self.append_casts = true;
+        self
+    }
+
+    pub fn with_max_identifier_length(mut self, max_identifier_length: usize) -> Self {
+        self.max_identifier_length = Some(max_identifier_length);
+        self
+    }
+
+    pub fn with_max_ctes(mut self, max_ctes: usize) -> Self {
+        self.max_ctes = Some(max_ctes);
+        self
+    }
+
+    pub fn with_max_sql_bytes(mut self, max_sql_bytes: usize) -> Self {
+        self.max_sql_bytes = Some(max_sql_bytes);
+        self
+    }
+
+    pub fn with_sql_transform<F>(mut self, sql_transform: F) -> Self
+    where
+        F: Fn(sql_ast::Query) -> Result<sql_ast::Query> + Send + Sync + 'static,
+    {
+        self.sql_transform = Some(SqlTransform(Arc::new(sql_transform)));
+        self
+    }
+
+    pub fn with_lints(mut self) -> Self {
+        self.lint = true;
+        self
+    }
+
+    pub fn with_wrap_as(mut self, alias: impl Into<String>) -> Self {
+        self.wrap_as = Some(alias.into());
+        self
+    }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    pub fn with_timings(mut self) -> Self {
+        self.timings = true;
+        self
+    }
+
     pub fn some(self) -> Option<Self> {
         Some(self)
     }
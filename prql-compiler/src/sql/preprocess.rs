@@ -1,12 +1,24 @@
-use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::iter::once;
 
 use anyhow::Result;
 
-use crate::ast::pl::{BinOp, ColumnSort, InterpolateItem, Literal, Range, WindowFrame, WindowKind};
-use crate::ast::rq::{new_binop, CId, Compute, Expr, ExprKind, RqFold, Take, Transform, Window};
+use crate::ast::pl::{
+    BinOp, ColumnSort, InterpolateItem, JoinSide, Literal, NullsOrder, Range, SortDirection,
+    SwitchCase, WindowFrame, WindowKind,
+};
+use crate::ast::rq::{
+    fold_table, new_binop, CId, Compute, Expr, ExprKind, Query, Relation, RelationColumn,
+    RelationKind, RqFold, TId, TableDecl, TableRef, Take, Transform, Window,
+};
+use crate::describe::{concat_mismatches, concat_nulls, ColumnType};
+use crate::error::{Error, ErrorCode, Reason};
+use crate::notes::{Notes, OptimizationNote};
+use crate::utils::truncate_ident;
 
 use super::anchor::{infer_complexity, Complexity};
-use super::context::AnchorContext;
+use super::context::{AnchorContext, ColumnDecl};
+use super::std::has_impl;
 use super::translator::Context;
 
 pub(super) fn preprocess_distinct(
@@ -14,20 +26,50 @@ pub(super) fn preprocess_distinct(
     context: &mut Context,
 ) -> Result<Vec<Transform>> {
     let mut d = TakeConverter {
+        supports_distinct_on: context.target.supports_distinct_on(),
+        supports_limit_by: context.target.supports_limit_by(),
         context: &mut context.anchor,
     };
     d.fold_transforms(pipeline)
 }
 /// Creates [Transform::Unique] from [Transform::Take]
+///
+/// Taking the first row of unsorted groups that span over all columns of the
+/// frame is `DISTINCT`. Otherwise, a take of the first row is kept (and
+/// translated to `DISTINCT ON`, ordered by its sort) if the target supports it,
+/// or is converted to a filter by row number. On targets with `LIMIT BY`, any
+/// take with an end is kept.
 struct TakeConverter<'a> {
     context: &'a mut AnchorContext,
+    supports_distinct_on: bool,
+    supports_limit_by: bool,
 }
 
 impl<'a> RqFold for TakeConverter<'a> {
     fn fold_transforms(&mut self, transforms: Vec<Transform>) -> Result<Vec<Transform>> {
         let mut res = Vec::new();
 
+        // columns of the current frame, if they are all known
+        let mut frame: Option<Vec<CId>> = None;
+
         for transform in transforms {
+            match &transform {
+                Transform::Select(cids) => frame = Some(cids.clone()),
+                Transform::From(table_ref) => frame = columns_of_table_ref(table_ref),
+                Transform::Compute(compute) => {
+                    if let Some(frame) = &mut frame {
+                        frame.push(compute.id);
+                    }
+                }
+                Transform::Aggregate {
+                    partition, compute, ..
+                } => {
+                    frame = Some([partition.as_slice(), compute.as_slice()].concat());
+                }
+                Transform::Join { .. } => frame = None,
+                _ => {}
+            }
+
             match transform {
                 Transform::Take(Take { ref partition, .. }) if partition.is_empty() => {
                     res.push(transform);
@@ -37,17 +79,35 @@ impl<'a> RqFold for TakeConverter<'a> {
                     range,
                     partition,
                     sort,
+                    ..
                 }) => {
-                    let range_int = range
-                        .clone()
-                        .try_map(as_int)
-                        .map_err(|_| anyhow::anyhow!("Invalid take arguments"))?;
+                    let range_int = range.clone().try_map(as_int).map_err(|_| {
+                        Error::new(Reason::Simple("Invalid take arguments".to_string()))
+                    })?;
 
                     let take_only_first =
                         range_int.start.unwrap_or(1) == 1 && matches!(range_int.end, Some(1));
                     if take_only_first && sort.is_empty() {
-                        // TODO: use distinct only if `by == all columns in frame`
-                        res.push(Transform::Unique);
+                        let by_all_columns = partition.iter().any(|c| self.is_wildcard(c))
+                            || (frame.as_ref())
+                                .map_or(false, |frame| frame.iter().all(|c| partition.contains(c)));
+
+                        if by_all_columns {
+                            res.push(Transform::Unique);
+                            continue;
+                        }
+                    }
+
+                    // the sort becomes the ORDER BY of DISTINCT ON (or LIMIT BY)
+                    let keep = (take_only_first && self.supports_distinct_on)
+                        || (self.supports_limit_by && range_int.end.is_some());
+                    if keep {
+                        res.push(Transform::Take(Take {
+                            range,
+                            partition,
+                            sort,
+                            percent: false,
+                        }));
                         continue;
                     }
 
@@ -65,6 +125,16 @@ impl<'a> RqFold for TakeConverter<'a> {
     }
 }
 
+/// Columns of a table instance, unless they include a wildcard.
+fn columns_of_table_ref(table_ref: &TableRef) -> Option<Vec<CId>> {
+    (table_ref.columns.iter())
+        .map(|(col, cid)| match col {
+            RelationColumn::Single(_) => Some(*cid),
+            RelationColumn::Wildcard => None,
+        })
+        .collect()
+}
+
 fn as_int(expr: Expr) -> Result<i64, ()> {
     let lit = expr.kind.as_literal().ok_or(())?;
     lit.as_integer().cloned().ok_or(())
@@ -78,6 +148,13 @@ fn int_expr(i: i64) -> Box<Expr> {
 }
 
 impl<'a> TakeConverter<'a> {
+    fn is_wildcard(&self, cid: &CId) -> bool {
+        matches!(
+            self.context.column_decls.get(cid),
+            Some(ColumnDecl::RelationColumn(_, _, RelationColumn::Wildcard))
+        )
+    }
+
     fn create_filter_by_row_number(
         &mut self,
         range: Range<Expr>,
@@ -166,42 +243,759 @@ impl<'a> TakeConverter<'a> {
     }
 }
 
-/// Pull Compose transforms in front of other transforms if possible.
-/// Position of Compose is important for two reasons:
+/// Keeps [Transform::Sample] that directly follows `from` of a table, if the
+/// target has a syntax for it, so it becomes `TABLESAMPLE` of the table (or
+/// `USING SAMPLE` of its SELECT).
+/// Otherwise, a sample of rows is converted into:
+///
+/// ```prql
+/// derive _expr_0 = s"RANDOM()"
+/// sort _expr_0
+/// take n
+/// ```
+///
+/// The random column is selected for the sort, so the sample ends up in a
+/// CTE of its own, which the following query selects from.
+pub(super) fn preprocess_sample(
+    pipeline: Vec<Transform>,
+    context: &mut Context,
+) -> Result<Vec<Transform>> {
+    let table_sample = context.target.table_sample();
+
+    let mut res = Vec::with_capacity(pipeline.len());
+    for transform in pipeline {
+        let Transform::Sample(sample) = transform else {
+            res.push(transform);
+            continue;
+        };
+
+        let follows_table = matches!(
+            res.last(),
+            Some(Transform::From(table_ref)) if is_extern_table(table_ref, &context.anchor)
+        );
+        let native = table_sample.and_then(|s| s.render(sample.size, sample.percent));
+        if follows_table && native.is_some() {
+            res.push(Transform::Sample(sample));
+            continue;
+        }
+
+        if sample.percent {
+            let message = if native.is_some() {
+                "`sample` with a percentage must directly follow `from` of a table".to_string()
+            } else {
+                format!(
+                    "`sample` with a percentage is not supported by {}",
+                    context.target_name()
+                )
+            };
+            return Err(Error::new(Reason::Simple(message))
+                .with_span(sample.span)
+                .with_code(ErrorCode::Unsupported)
+                .into());
+        }
+
+        res.extend(create_random_take(sample.size, context));
+    }
+    Ok(res)
+}
+
+/// Rewrites joins of an atomic query that the target does not support.
+///
+/// A right join that is the first join of its SELECT is a left join with the
+/// relations swapped. Further joins are applied to the result of both, so
+/// they are not affected by the swap.
+///
+/// A full join that directly follows `from` has already been emulated when
+/// the pipeline was split, so other full joins are reported.
+pub(super) fn preprocess_joins(
+    pipeline: Vec<Transform>,
+    context: &Context,
+) -> Result<Vec<Transform>> {
+    let supports_right_join = context.target.supports_right_join();
+    let supports_full_join = context.target.supports_full_join();
+
+    let mut res: Vec<Transform> = Vec::with_capacity(pipeline.len());
+    for transform in pipeline {
+        let Transform::Join { side, with, filter } = transform else {
+            res.push(transform);
+            continue;
+        };
+
+        let unsupported = |side: &str, help: &str| {
+            Error::new(Reason::Simple(format!(
+                "`join side:{side}` is not supported by {}",
+                context.target_name()
+            )))
+            .with_span(filter.span)
+            .with_help(help)
+            .with_code(ErrorCode::Unsupported)
+        };
+
+        match side {
+            JoinSide::Full if !supports_full_join => {
+                let help = "use `side:full` as the first join, directly after `from`";
+                return Err(unsupported("full", help).into());
+            }
+            JoinSide::Right if !supports_right_join => {
+                let is_first =
+                    (res.iter()).all(|t| matches!(t, Transform::From(_) | Transform::Select(_)));
+                let from = res.iter().position(|t| matches!(t, Transform::From(_)));
+                let Some(from) = from.filter(|_| is_first) else {
+                    let help = "use `side:left` with the relations swapped, as the first join";
+                    return Err(unsupported("right", help).into());
+                };
+
+                let Transform::From(left) = std::mem::replace(&mut res[from], Transform::From(with)) else {
+                    unreachable!()
+                };
+                res.push(Transform::Join {
+                    side: JoinSide::Left,
+                    with: left,
+                    filter,
+                });
+            }
+            _ => res.push(Transform::Join { side, with, filter }),
+        }
+    }
+    Ok(res)
+}
+
+/// Truncates names of CTEs and aliases of tables that are longer than
+/// `max_len`. These are not visible outside of the query, so they can be
+/// renamed, while the engine would truncate them silently and possibly make
+/// two of them equal.
+pub(super) fn truncate_names(query: Query, max_len: usize) -> Result<Query> {
+    let extern_names = (query.tables.iter())
+        .filter(|decl| matches!(decl.relation.kind, RelationKind::ExternRef(_)))
+        .map(|decl| (decl.id, decl.name.clone()))
+        .collect();
+
+    NameTruncator {
+        max_len,
+        extern_names,
+    }
+    .fold_query(query)
+}
+
+struct NameTruncator {
+    max_len: usize,
+    extern_names: HashMap<TId, Option<String>>,
+}
+
+impl RqFold for NameTruncator {
+    fn fold_table(&mut self, mut table: TableDecl) -> Result<TableDecl> {
+        if !self.extern_names.contains_key(&table.id) {
+            table.name = table.name.map(|n| truncate_ident(n, self.max_len));
+        }
+        fold_table(self, table)
+    }
+
+    fn fold_table_ref(&mut self, mut table_ref: TableRef) -> Result<TableRef> {
+        // an extern table referenced by its own name has no alias
+        let extern_name = self.extern_names.get(&table_ref.source);
+        if extern_name != Some(&table_ref.name) {
+            table_ref.name = table_ref.name.map(|n| truncate_ident(n, self.max_len));
+        }
+        Ok(table_ref)
+    }
+}
+
+/// Checks that columns of `append` have a common type in both relations.
+///
+/// With `append_casts`, a column that is a string in one of the relations is
+/// cast to a string in the other one. Other mismatches are errors, because
+/// engines either reject them or coerce them in different ways. A column that
+/// is a bare `null` in one of the relations is cast to the type of the other
+/// one, on targets that need typed NULLs.
+pub(super) fn preprocess_append_casts(
+    mut query: Query,
+    append_casts: bool,
+    context: &mut Context,
+) -> Result<Query> {
+    let tables: HashMap<_, _> = query.tables.iter().map(|t| (t.id, t)).collect();
+    let relations = (query.tables.iter())
+        .map(|t| (Some(t.id), &t.relation))
+        .chain(once((None, &query.relation)));
+
+    let mut casts = Vec::new();
+    for (tid, relation) in relations {
+        for mismatch in concat_mismatches(relation, &tables) {
+            let top_is_string = mismatch.top == ColumnType::String;
+            let bottom_is_string = mismatch.bottom == ColumnType::String;
+
+            if !append_casts || !(top_is_string || bottom_is_string) {
+                let name = (mismatch.name.as_ref())
+                    .map(|n| format!("`{n}`"))
+                    .unwrap_or_else(|| format!("at position {}", mismatch.index + 1));
+                let mut error = Error::new(Reason::Simple(format!(
+                    "column {name} of `append` is {} in one relation and {} in the other",
+                    mismatch.top, mismatch.bottom
+                )))
+                .with_span(relation.span)
+                .with_code(ErrorCode::Mismatch);
+                if top_is_string || bottom_is_string {
+                    error = error.with_help("set `append_casts` to cast the column to a string");
+                }
+                return Err(error.into());
+            }
+
+            let cast_in = if top_is_string {
+                Some(mismatch.appended)
+            } else {
+                tid
+            };
+            let type_name = context.target.type_name(ColumnType::String);
+            casts.push((cast_in, mismatch.index, type_name.unwrap_or("TEXT")));
+        }
+
+        for null in concat_nulls(relation, &tables) {
+            if let Some(type_name) = context.target.type_name(null.ty) {
+                casts.push((null.appended.or(tid), null.index, type_name));
+            }
+        }
+    }
+
+    for (tid, index, type_name) in casts {
+        let relation = match tid {
+            Some(tid) => {
+                let decl = query.tables.iter_mut().find(|t| t.id == tid).unwrap();
+                &mut decl.relation
+            }
+            None => &mut query.relation,
+        };
+        cast_column(relation, index, type_name, context)?;
+    }
+    Ok(query)
+}
+
+/// Replaces the column at the position in the output of the relation with
+/// a cast of the column to the type.
+fn cast_column(
+    relation: &mut Relation,
+    index: usize,
+    type_name: &str,
+    context: &mut Context,
+) -> Result<()> {
+    let span = relation.span;
+    let Some(pipeline) = relation.kind.as_pipeline_mut() else {
+        return Err(Error::new(Reason::Simple(
+            "cannot cast columns of this relation for `append`".to_string(),
+        ))
+        .with_span(span)
+        .with_code(ErrorCode::Unsupported)
+        .into());
+    };
+
+    // the select that produces the columns of the set operation
+    let set_op = (pipeline.iter())
+        .position(|t| matches!(t, Transform::Concat(_)))
+        .unwrap_or(pipeline.len());
+    let Some(position) = pipeline[..set_op].iter().rposition(|t| t.as_select().is_some()) else {
+        return Ok(());
+    };
+    let column = pipeline[position].as_select().unwrap()[index];
+
+    let column_ref = Expr {
+        kind: ExprKind::ColumnRef(column),
+        span: None,
+    };
+    let compute = Compute {
+        id: context.anchor.cid.gen(),
+        expr: Expr {
+            kind: ExprKind::SString(vec![
+                InterpolateItem::String("CAST(".to_string()),
+                InterpolateItem::Expr(Box::new(column_ref)),
+                InterpolateItem::String(format!(" AS {type_name})")),
+            ]),
+            span: None,
+        },
+        window: None,
+        is_aggregation: false,
+    };
+    context.anchor.register_compute(compute.clone());
+
+    for transform in &mut pipeline[position..] {
+        if let Transform::Select(cids) = transform {
+            for cid in cids.iter_mut().filter(|c| **c == column) {
+                *cid = compute.id;
+            }
+        }
+    }
+    pipeline.insert(position, Transform::Compute(compute));
+    Ok(())
+}
+
+fn is_extern_table(table_ref: &TableRef, context: &AnchorContext) -> bool {
+    let decl = context.table_decls.get(&table_ref.source);
+    matches!(decl, Some(decl) if matches!(decl.relation.kind, RelationKind::ExternRef(_)))
+}
+
+fn create_random_take(size: i64, context: &mut Context) -> Vec<Transform> {
+    let random = context.target.random_function().to_string();
+    let compute = Compute {
+        id: context.anchor.cid.gen(),
+        expr: Expr {
+            kind: ExprKind::SString(vec![InterpolateItem::String(random)]),
+            span: None,
+        },
+        window: None,
+        is_aggregation: false,
+    };
+    context.anchor.register_compute(compute.clone());
+
+    let sort = vec![ColumnSort {
+        direction: SortDirection::Asc,
+        column: compute.id,
+        nulls: None,
+    }];
+    vec![
+        Transform::Compute(compute),
+        Transform::Sort(sort.clone()),
+        Transform::Take(Take {
+            range: Range {
+                start: None,
+                end: Some(*int_expr(size)),
+            },
+            partition: vec![],
+            sort,
+            percent: false,
+        }),
+    ]
+}
+
+/// Rewrites `min_by` and `max_by` on targets that don't have a native
+/// implementation. `max_by column by` is computed as:
+///
+/// ```sql
+/// MAX(CASE WHEN _expr_0 = 1 AND by IS NOT NULL THEN column END)
+/// ```
+///
+/// ... where `_expr_0` is `ROW_NUMBER() OVER (PARTITION BY <group keys> ORDER
+/// BY by DESC NULLS LAST)`. The window needs a preceding CTE, which is created
+/// when the pipeline is split.
+///
+/// Exactly one row of each group is numbered 1, so ties of `by` produce one of
+/// the tied values, as the native implementations do. Rows with NULL `by` are
+/// ignored.
+pub(super) fn preprocess_arg_extremes(
+    pipeline: Vec<Transform>,
+    context: &mut Context,
+) -> Result<Vec<Transform>> {
+    let target = context.target.target();
+    if has_impl("max_by", &target) && has_impl("min_by", &target) {
+        return Ok(pipeline);
+    }
+
+    // partition of each of the aggregated columns
+    let mut partitions = HashMap::new();
+    for transform in &pipeline {
+        if let Transform::Aggregate {
+            partition, compute, ..
+        } = transform
+        {
+            for cid in compute {
+                partitions.insert(*cid, partition.clone());
+            }
+        }
+    }
+
+    let mut res = Vec::with_capacity(pipeline.len());
+    for transform in pipeline {
+        let Transform::Compute(compute) = transform else {
+            res.push(transform);
+            continue;
+        };
+
+        let mut converter = ArgExtremeConverter {
+            context: &mut context.anchor,
+            partition: partitions.get(&compute.id).cloned(),
+            windows: Vec::new(),
+        };
+        let compute = converter.fold_compute(compute)?;
+        let windows = converter.windows;
+
+        if !windows.is_empty() {
+            context.anchor.register_compute(compute.clone());
+        }
+        res.extend(windows.into_iter().map(Transform::Compute));
+        res.push(Transform::Compute(compute));
+    }
+    Ok(res)
+}
+
+struct ArgExtremeConverter<'a> {
+    context: &'a mut AnchorContext,
+
+    /// Partition of the aggregate, `None` if the compute is not aggregated
+    partition: Option<Vec<CId>>,
+
+    /// Computes of row numbers (and their sort keys), which must precede the
+    /// aggregation
+    windows: Vec<Compute>,
+}
+
+impl<'a> ArgExtremeConverter<'a> {
+    fn push_compute(&mut self, expr: Expr, window: Option<Window>) -> CId {
+        let compute = Compute {
+            id: self.context.cid.gen(),
+            expr,
+            window,
+            is_aggregation: false,
+        };
+        self.context.register_compute(compute.clone());
+
+        let id = compute.id;
+        self.windows.push(compute);
+        id
+    }
+}
+
+impl<'a> RqFold for ArgExtremeConverter<'a> {
+    fn fold_expr(&mut self, mut expr: Expr) -> Result<Expr> {
+        expr.kind = self.fold_expr_kind(expr.kind)?;
+
+        let ExprKind::BuiltInFunction { name, args } = &expr.kind else {
+            return Ok(expr);
+        };
+        let direction = match name.as_str() {
+            "std.min_by" => SortDirection::Asc,
+            "std.max_by" => SortDirection::Desc,
+            _ => return Ok(expr),
+        };
+        let Some(partition) = self.partition.clone() else {
+            let name = name.strip_prefix("std.").unwrap_or(name);
+            return Err(Error::new(Reason::Simple(format!(
+                "`{name}` can only be used in `aggregate` on this target"
+            )))
+            .with_span(expr.span)
+            .with_code(ErrorCode::Unsupported)
+            .into());
+        };
+        let [column, by]: [Expr; 2] = args.clone().try_into().unwrap();
+
+        // rows can only be sorted by a column
+        let by = match by.kind {
+            ExprKind::ColumnRef(cid) => cid,
+            _ => self.push_compute(by, None),
+        };
+
+        // ROW_NUMBER() OVER (PARTITION BY ... ORDER BY by DESC NULLS LAST)
+        let row_number = Expr {
+            kind: ExprKind::SString(vec![InterpolateItem::String("ROW_NUMBER()".to_string())]),
+            span: None,
+        };
+        let window = Window {
+            frame: WindowFrame {
+                kind: WindowKind::Range,
+                range: Range {
+                    start: None,
+                    end: Some(*int_expr(0)),
+                },
+            },
+            partition,
+            sort: vec![ColumnSort {
+                direction,
+                column: by,
+                nulls: Some(NullsOrder::Last),
+            }],
+        };
+        let row_number = self.push_compute(row_number, Some(window));
+
+        // MAX(CASE WHEN _expr_0 = 1 AND by IS NOT NULL THEN column END)
+        let col_ref = |cid| Expr {
+            kind: ExprKind::ColumnRef(cid),
+            span: None,
+        };
+        let null = Expr {
+            kind: ExprKind::Literal(Literal::Null),
+            span: None,
+        };
+        let is_extreme = new_binop(
+            new_binop(Some(col_ref(row_number)), BinOp::Eq, Some(*int_expr(1))),
+            BinOp::And,
+            new_binop(Some(col_ref(by)), BinOp::Ne, Some(null)),
+        );
+        let case = Expr {
+            kind: ExprKind::Switch(vec![SwitchCase {
+                condition: is_extreme.unwrap(),
+                value: column,
+            }]),
+            span: None,
+        };
+
+        Ok(Expr {
+            kind: ExprKind::BuiltInFunction {
+                name: "std.max".to_string(),
+                args: vec![case],
+            },
+            span: expr.span,
+        })
+    }
+}
+
+/// Collapses filters that compare a column with one of several literals into
+/// `std.in`, which is translated to `IN`:
+///
+/// `status == "a" || status == "b" || status == "a"` -> `status IN ('a', 'b')`
+///
+/// Only disjunctions of `==` between the same column and literals (other than
+/// `null`) are collapsed, others are left as they are. Duplicate literals are
+/// removed.
+pub(super) fn preprocess_in_lists(
+    pipeline: Vec<Transform>,
+    context: &mut Context,
+) -> Result<Vec<Transform>> {
+    let mut collapser = InListCollapser {
+        notes: &mut context.notes,
+    };
+
+    (pipeline.into_iter())
+        .map(|transform| match transform {
+            Transform::Filter(filter) => Ok(Transform::Filter(collapser.fold_expr(filter)?)),
+            _ => Ok(transform),
+        })
+        .collect()
+}
+
+struct InListCollapser<'a> {
+    notes: &'a mut Notes,
+}
+
+impl<'a> RqFold for InListCollapser<'a> {
+    fn fold_expr(&mut self, expr: Expr) -> Result<Expr> {
+        if !matches!(expr.kind, ExprKind::Binary { op: BinOp::Or, .. }) {
+            return Ok(Expr {
+                kind: self.fold_expr_kind(expr.kind)?,
+                span: expr.span,
+            });
+        }
+
+        if let Some(in_list) = in_list_of_disjunction(&expr) {
+            self.notes.optimizations.push(OptimizationNote::new(
+                "preprocess",
+                expr.span,
+                "collapsed comparisons of a column with literals into IN",
+            ));
+            return Ok(in_list);
+        }
+        self.fold_disjuncts(expr)
+    }
+}
+
+impl<'a> InListCollapser<'a> {
+    /// Folds operands of a disjunction that cannot be collapsed, without
+    /// collapsing a part of it.
+    fn fold_disjuncts(&mut self, expr: Expr) -> Result<Expr> {
+        let kind = match expr.kind {
+            ExprKind::Binary {
+                left,
+                op: BinOp::Or,
+                right,
+            } => ExprKind::Binary {
+                left: Box::new(self.fold_disjuncts(*left)?),
+                op: BinOp::Or,
+                right: Box::new(self.fold_disjuncts(*right)?),
+            },
+            kind => return self.fold_expr(Expr { kind, ..expr }),
+        };
+        Ok(Expr { kind, ..expr })
+    }
+}
+
+/// `std.in` of a disjunction of comparisons of the same column with literals.
+fn in_list_of_disjunction(expr: &Expr) -> Option<Expr> {
+    fn disjuncts<'a>(expr: &'a Expr, res: &mut Vec<&'a Expr>) {
+        match &expr.kind {
+            ExprKind::Binary {
+                left,
+                op: BinOp::Or,
+                right,
+            } => {
+                disjuncts(left, res);
+                disjuncts(right, res);
+            }
+            _ => res.push(expr),
+        }
+    }
+    let mut comparisons = Vec::new();
+    disjuncts(expr, &mut comparisons);
+
+    let mut column: Option<&Expr> = None;
+    let mut values: Vec<Expr> = Vec::new();
+    for comparison in comparisons {
+        let ExprKind::Binary { left, op: BinOp::Eq, right } = &comparison.kind else {
+            return None;
+        };
+        match (&left.kind, &right.kind) {
+            (ExprKind::ColumnRef(_), ExprKind::Literal(lit)) if !matches!(lit, Literal::Null) => {}
+            _ => return None,
+        }
+        if column.map_or(false, |column| column.kind != left.kind) {
+            return None;
+        }
+        column = Some(left);
+
+        if !values.iter().any(|value| value.kind == right.kind) {
+            values.push(right.as_ref().clone());
+        }
+    }
+
+    let args = once(column?.clone()).chain(values).collect();
+    Some(Expr {
+        kind: ExprKind::BuiltInFunction {
+            name: "std.in".to_string(),
+            args,
+        },
+        span: expr.span,
+    })
+}
+
+/// Pull Compute transforms in front of other transforms if possible.
+/// Position of Compute is important for two reasons:
 /// - when splitting pipelines, they provide information in which pipeline the
 ///   column is computed and subsquently, with which table name should be used
 ///   for name materialization.
 /// - the transform order in SQL requires Computes to be before Filter. This
 ///   can be circumvented by materializing the column earlier in the pipeline,
 ///   which is done in this function.
-pub(super) fn preprocess_reorder(mut pipeline: Vec<Transform>) -> Vec<Transform> {
-    // reorder Compose
-    pipeline.sort_by(|a, b| match (a, b) {
-        // don't reorder with From or Join or itself
-        (
-            Transform::From(_) | Transform::Join { .. } | Transform::Compute(_),
-            Transform::From(_) | Transform::Join { .. } | Transform::Compute(_),
-        ) => Ordering::Equal,
-
-        // reorder always
-        (Transform::Sort(_), Transform::Compute(_)) => Ordering::Greater,
-        (Transform::Compute(_), Transform::Sort(_)) => Ordering::Less,
-
-        // reorder if col decl is plain
-        (Transform::Take(_), Transform::Compute(decl))
-            if infer_complexity(decl) == Complexity::Plain =>
-        {
-            Ordering::Greater
+///
+/// Computes are only moved over Sorts and, if they are plain, over Takes.
+/// Everything else is a barrier, see [can_move_before].
+pub(super) fn preprocess_reorder(pipeline: Vec<Transform>) -> Vec<Transform> {
+    let mut res: Vec<Transform> = Vec::with_capacity(pipeline.len());
+
+    for transform in pipeline {
+        let mut position = res.len();
+
+        while position > 0 && can_move_before(&transform, &res[position - 1]) {
+            position -= 1;
         }
-        (Transform::Compute(decl), Transform::Take(_))
-            if infer_complexity(decl) == Complexity::Plain =>
-        {
-            Ordering::Less
+
+        res.insert(position, transform);
+    }
+
+    res
+}
+
+/// Commutation rules of transforms:
+///
+/// | transform | preceding | moved in front |
+/// |-----------|-----------|----------------|
+/// | Compute   | Sort      | always         |
+/// | Compute   | Take      | if plain       |
+/// | *         | *         | never          |
+///
+/// Take and Unique change which rows are in the relation. A plain Compute is
+/// computed for each row separately, so it can be moved over a Take, but a
+/// window or an aggregation would then include the rows that the Take removes.
+/// Filters are not moved either: over a window or an aggregation they would
+/// change its result and over a plain Compute they don't spare a split of the
+/// pipeline.
+fn can_move_before(transform: &Transform, preceding: &Transform) -> bool {
+    match (transform, preceding) {
+        (Transform::Compute(_), Transform::Sort(_)) => true,
+        (Transform::Compute(decl), Transform::Take(_)) => {
+            infer_complexity(decl) == Complexity::Plain
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::ast::pl::Range;
+
+    fn col(id: usize) -> Expr {
+        Expr {
+            kind: ExprKind::ColumnRef(CId::from(id)),
+            span: None,
+        }
+    }
+
+    fn compute(id: usize) -> Transform {
+        Transform::Compute(Compute {
+            id: CId::from(id),
+            expr: col(0),
+            window: None,
+            is_aggregation: false,
+        })
+    }
+
+    fn windowed(id: usize) -> Transform {
+        Transform::Compute(Compute {
+            window: Some(Window::default()),
+            ..compute(id).into_compute().unwrap()
+        })
+    }
+
+    fn aggregation(id: usize) -> Transform {
+        Transform::Compute(Compute {
+            is_aggregation: true,
+            ..compute(id).into_compute().unwrap()
+        })
+    }
+
+    fn filter(id: usize) -> Transform {
+        Transform::Filter(col(id))
+    }
+
+    fn take() -> Transform {
+        Transform::Take(Take {
+            range: Range {
+                start: None,
+                end: None,
+            },
+            partition: Vec::new(),
+            sort: Vec::new(),
+            percent: false,
+        })
+    }
+
+    fn label(transform: &Transform) -> String {
+        match transform {
+            Transform::Compute(decl) => format!("Compute {}", decl.id.get()),
+            Transform::Filter(expr) => {
+                format!("Filter {}", expr.kind.as_column_ref().unwrap().get())
+            }
+            t => t.as_ref().to_string(),
         }
+    }
 
-        // don't reorder by default
-        _ => Ordering::Equal,
-    });
+    #[test]
+    fn test_reorder() {
+        let sort = || Transform::Sort(Vec::new());
+        let cases = [
+            // computes move over sorts ...
+            (vec![sort(), compute(1)], "Compute 1, Sort"),
+            (vec![sort(), windowed(1)], "Compute 1, Sort"),
+            (vec![sort(), aggregation(1)], "Compute 1, Sort"),
+            (vec![sort(), sort(), compute(1)], "Compute 1, Sort, Sort"),
+            // ... and plain computes over takes ...
+            (vec![take(), compute(1)], "Compute 1, Take"),
+            (vec![take(), sort(), compute(1)], "Compute 1, Take, Sort"),
+            // ... but windows and aggregations don't, nor anything over uniques
+            (vec![take(), windowed(1)], "Take, Compute 1"),
+            (vec![take(), aggregation(1)], "Take, Compute 1"),
+            (vec![take(), sort(), windowed(1)], "Take, Compute 1, Sort"),
+            (vec![Transform::Unique, compute(1)], "Unique, Compute 1"),
+            (vec![filter(2), compute(1)], "Filter 2, Compute 1"),
+            // filters don't move at all
+            (vec![compute(1), filter(2)], "Compute 1, Filter 2"),
+            (vec![windowed(1), filter(2)], "Compute 1, Filter 2"),
+            (vec![aggregation(1), filter(2)], "Compute 1, Filter 2"),
+            (vec![take(), filter(1)], "Take, Filter 1"),
+            (vec![Transform::Unique, filter(1)], "Unique, Filter 1"),
+            (vec![sort(), filter(1)], "Sort, Filter 1"),
+            (vec![filter(1), filter(2)], "Filter 1, Filter 2"),
+        ];
 
-    pipeline
+        for (pipeline, expected) in cases {
+            let input = pipeline.iter().map(label).join(", ");
+            let output = preprocess_reorder(pipeline).iter().map(label).join(", ");
+            assert_eq!(output, expected, "reordering {input}");
+        }
+    }
 }
@@ -6,15 +6,58 @@ use once_cell::sync::Lazy;
 use sqlparser::ast::{self as sql_ast};
 
 use super::codegen;
+use super::context::ColumnDecl;
+use super::target::BoolToNumber;
 use super::translator::Context;
+use super::Target;
 use crate::ast::{pl, rq};
+use crate::describe::ColumnType;
+use crate::error::{Error, ErrorCode, Reason};
 use crate::semantic;
 
-static STD: Lazy<semantic::Module> = Lazy::new(load_std_impl);
+static STD: Lazy<semantic::Module> = Lazy::new(|| load_std_impl(include_str!("./std_impl.prql")));
 
-fn load_std_impl() -> semantic::Module {
+/// Target-specific implementations of std functions.
+/// Functions that are not defined here fall back to [STD].
+static STD_TARGETS: Lazy<HashMap<Target, semantic::Module>> = Lazy::new(|| {
+    HashMap::from([
+        (
+            Target::BigQuery,
+            load_std_impl(include_str!("./std_impl/bigquery.prql")),
+        ),
+        (
+            Target::MsSql,
+            load_std_impl(include_str!("./std_impl/mssql.prql")),
+        ),
+        (
+            Target::MySql,
+            load_std_impl(include_str!("./std_impl/mysql.prql")),
+        ),
+        (
+            Target::PostgreSql,
+            load_std_impl(include_str!("./std_impl/postgres.prql")),
+        ),
+        (
+            Target::SQLite,
+            load_std_impl(include_str!("./std_impl/sqlite.prql")),
+        ),
+        (
+            Target::ClickHouse,
+            load_std_impl(include_str!("./std_impl/clickhouse.prql")),
+        ),
+        (
+            Target::Snowflake,
+            load_std_impl(include_str!("./std_impl/snowflake.prql")),
+        ),
+        (
+            Target::DuckDb,
+            load_std_impl(include_str!("./std_impl/duckdb.prql")),
+        ),
+    ])
+});
+
+fn load_std_impl(std_lib: &str) -> semantic::Module {
     use crate::parser::parse;
-    let std_lib = include_str!("./std_impl.prql");
     let statements = parse(std_lib).unwrap();
 
     let context = semantic::Context {
@@ -28,6 +71,16 @@ fn load_std_impl() -> semantic::Module {
     std.kind.clone().into_module().unwrap()
 }
 
+/// Whether the std function has an implementation for the target. Functions
+/// without one have to be rewritten before translation.
+pub(super) fn has_impl(name: &str, target: &Target) -> bool {
+    let name = name.strip_prefix("std.").unwrap_or(name);
+    let ident = pl::Ident::from_name(name);
+
+    let target_impl = STD_TARGETS.get(target).and_then(|std| std.get(&ident));
+    target_impl.or_else(|| STD.get(&ident)).is_some()
+}
+
 pub(super) fn translate_built_in(
     name: String,
     args: Vec<rq::Expr>,
@@ -35,7 +88,26 @@ pub(super) fn translate_built_in(
 ) -> Result<sql_ast::Expr> {
     let name = name.strip_prefix("std.").unwrap();
 
-    let entry = STD.get(&pl::Ident::from_name(name)).unwrap();
+    if matches!(name, "every" | "any") && !ctx.target.supports_bool_aggregates() {
+        return translate_bool_aggregate(name, args, ctx);
+    }
+    if name == "in" {
+        return codegen::translate_in_list(args, ctx);
+    }
+
+    let ident = pl::Ident::from_name(name);
+    let entry = STD_TARGETS
+        .get(&ctx.target.target())
+        .and_then(|std| std.get(&ident))
+        .or_else(|| STD.get(&ident));
+    let Some(entry) = entry else {
+        return Err(Error::new(Reason::Simple(format!(
+            "`{name}` is not supported by {}",
+            ctx.target.target()
+        )))
+        .with_code(ErrorCode::Unsupported)
+        .into());
+    };
     let func_def = entry.kind.as_func_def().unwrap();
 
     let params = func_def
@@ -44,6 +116,29 @@ pub(super) fn translate_built_in(
         .chain(func_def.positional_params.iter())
         .map(|x| x.name.split('.').last().unwrap_or(x.name.as_str()));
 
+    let args = match name {
+        "sum" | "average" | "avg" if matches!(args.as_slice(), [arg] if is_boolean(arg, ctx)) => {
+            // AVG of ones and zeros is a ratio, so it should not be truncated
+            let one = if name == "sum" { "1" } else { "1.0" };
+            let style = ctx.target.bool_to_number();
+            args.into_iter()
+                .map(|arg| bool_to_number(arg, style, one))
+                .collect()
+        }
+        "average" | "avg"
+            if ctx.target.integer_avg_truncates()
+                && matches!(args.as_slice(), [arg] if may_be_integer(arg, ctx)) =>
+        {
+            ctx.warn_emulation(
+                "average",
+                "the argument is cast to FLOAT, so precision of large or DECIMAL values may be lost",
+            );
+            args.into_iter().map(cast_to_float).collect()
+        }
+        "format_num" | "format_date" => translate_format_arg(name, args, &ctx.target.target())?,
+        _ => args,
+    };
+
     let mut args: HashMap<&str, _> = zip(params, args.into_iter()).collect();
 
     // body can only be an s-string
@@ -70,3 +165,246 @@ pub(super) fn translate_built_in(
 
     Ok(sql_ast::Expr::Identifier(sql_ast::Ident::new(s_string)))
 }
+
+/// `every` as `MIN(x) = 1` and `any` as `MAX(x) = 1`. The comparison is
+/// a part of the expression, so it is kept wherever the aggregate is
+/// referenced, i.e. in HAVING.
+///
+/// On targets without boolean values, `x` is cast to an integer, since
+/// `MIN` and `MAX` don't accept `BIT`.
+fn translate_bool_aggregate(
+    name: &str,
+    mut args: Vec<rq::Expr>,
+    ctx: &mut Context,
+) -> Result<sql_ast::Expr> {
+    let function = if name == "every" { "MIN" } else { "MAX" };
+    let (prefix, suffix) = if ctx.target.supports_bool_values() {
+        (format!("{function}("), ")")
+    } else {
+        (format!("{function}(CAST("), " AS INT))")
+    };
+    let aggregate = codegen::translate_sstring(
+        vec![
+            pl::InterpolateItem::String(prefix),
+            pl::InterpolateItem::Expr(Box::new(args.pop().unwrap())),
+            pl::InterpolateItem::String(suffix.to_string()),
+        ],
+        ctx,
+    )?;
+
+    Ok(sql_ast::Expr::BinaryOp {
+        left: Box::new(sql_ast::Expr::Identifier(sql_ast::Ident::new(aggregate))),
+        op: sql_ast::BinaryOperator::Eq,
+        right: Box::new(sql_ast::Expr::Value(sql_ast::Value::Number(
+            "1".to_string(),
+            false,
+        ))),
+    })
+}
+
+/// Whether the expression evaluates to a boolean. Columns are followed to
+/// their computing expression, but the types of table columns are unknown.
+fn is_boolean(expr: &rq::Expr, ctx: &Context) -> bool {
+    match &expr.kind {
+        rq::ExprKind::Literal(pl::Literal::Boolean(_)) => true,
+        rq::ExprKind::Binary { op, .. } => matches!(
+            op,
+            pl::BinOp::Eq
+                | pl::BinOp::Ne
+                | pl::BinOp::Gt
+                | pl::BinOp::Lt
+                | pl::BinOp::Gte
+                | pl::BinOp::Lte
+                | pl::BinOp::RegexSearch
+                | pl::BinOp::And
+                | pl::BinOp::Or
+        ),
+        rq::ExprKind::Unary {
+            op: rq::UnOp::Not, ..
+        } => true,
+        rq::ExprKind::BuiltInFunction { name, .. } => name == "std.in",
+        rq::ExprKind::ColumnRef(cid) => match ctx.anchor.column_decls.get(cid) {
+            Some(ColumnDecl::Compute(compute)) => is_boolean(&compute.expr, ctx),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Whether values of the expression may be integers, i.e. its type is not
+/// known, as for columns of extern tables.
+fn may_be_integer(expr: &rq::Expr, ctx: &Context) -> bool {
+    matches!(
+        ctx.anchor.column_types.of_expr(expr),
+        ColumnType::Integer | ColumnType::Unknown
+    )
+}
+
+/// Converts a boolean into `one` or 0, in the syntax of the target.
+fn bool_to_number(expr: rq::Expr, style: BoolToNumber, one: &str) -> rq::Expr {
+    let span = expr.span;
+    let (prefix, suffix) = match style {
+        BoolToNumber::Case => ("CASE WHEN ".to_string(), format!(" THEN {one} ELSE 0 END")),
+        BoolToNumber::Cast => ("(".to_string(), ")::int".to_string()),
+        BoolToNumber::If => ("IF(".to_string(), ", 1, 0)".to_string()),
+    };
+    let kind = rq::ExprKind::SString(vec![
+        pl::InterpolateItem::String(prefix),
+        pl::InterpolateItem::Expr(Box::new(expr)),
+        pl::InterpolateItem::String(suffix),
+    ]);
+    rq::Expr { kind, span }
+}
+
+/// Wraps an expression into `CAST(expr AS FLOAT)`.
+fn cast_to_float(expr: rq::Expr) -> rq::Expr {
+    let span = expr.span;
+    let kind = rq::ExprKind::SString(vec![
+        pl::InterpolateItem::String("CAST(".to_string()),
+        pl::InterpolateItem::Expr(Box::new(expr)),
+        pl::InterpolateItem::String(" AS FLOAT)".to_string()),
+    ]);
+    rq::Expr { kind, span }
+}
+
+/// Converts the last argument of `format_num` (number of decimals) or
+/// `format_date` (strftime-style format) into the pattern syntax of the target.
+fn translate_format_arg(
+    name: &str,
+    mut args: Vec<rq::Expr>,
+    target: &Target,
+) -> Result<Vec<rq::Expr>> {
+    let arg = args.pop().unwrap();
+    let span = arg.span;
+
+    let pattern = match (name, arg.kind) {
+        ("format_num", rq::ExprKind::Literal(pl::Literal::Integer(decimals))) if decimals >= 0 => {
+            number_pattern(decimals, target)
+        }
+        ("format_date", rq::ExprKind::Literal(pl::Literal::String(format))) => {
+            let pattern = date_pattern(&format, target).map_err(|e| e.with_span(span))?;
+            pl::Literal::String(pattern)
+        }
+        (name, _) => {
+            let expected = if name == "format_num" {
+                "number of decimals of `format_num` must be a non-negative integer literal"
+            } else {
+                "format of `format_date` must be a string literal"
+            };
+            return Err(Error::new(Reason::Simple(expected.to_string()))
+                .with_span(span)
+                .with_code(ErrorCode::Mismatch)
+                .into());
+        }
+    };
+
+    args.push(rq::Expr {
+        kind: rq::ExprKind::Literal(pattern),
+        span,
+    });
+    Ok(args)
+}
+
+/// Pattern for a number with thousands separators and fixed number of decimals.
+fn number_pattern(decimals: i64, target: &Target) -> pl::Literal {
+    match target {
+        Target::PostgreSql | Target::Snowflake => {
+            let fraction = if decimals > 0 {
+                format!("D{}", "0".repeat(decimals as usize))
+            } else {
+                String::new()
+            };
+            pl::Literal::String(format!("FM999G999G999G999G990{fraction}"))
+        }
+        Target::MsSql => pl::Literal::String(format!("N{decimals}")),
+        Target::SQLite => pl::Literal::String(format!("%,.{decimals}f")),
+        _ => pl::Literal::Integer(decimals),
+    }
+}
+
+/// Translates a strftime-style format into the date pattern syntax of the
+/// target.
+fn date_pattern(format: &str, target: &Target) -> Result<String, Error> {
+    let specifiers: &[(char, &str)] = match target {
+        Target::PostgreSql | Target::Snowflake => &[
+            ('Y', "YYYY"),
+            ('m', "MM"),
+            ('d', "DD"),
+            ('H', "HH24"),
+            ('M', "MI"),
+            ('S', "SS"),
+        ],
+        Target::MsSql => &[
+            ('Y', "yyyy"),
+            ('m', "MM"),
+            ('d', "dd"),
+            ('H', "HH"),
+            ('M', "mm"),
+            ('S', "ss"),
+        ],
+        Target::MySql => &[
+            ('Y', "%Y"),
+            ('m', "%m"),
+            ('d', "%d"),
+            ('H', "%H"),
+            ('M', "%i"),
+            ('S', "%s"),
+        ],
+        // SQLite uses strftime
+        _ => &[
+            ('Y', "%Y"),
+            ('m', "%m"),
+            ('d', "%d"),
+            ('H', "%H"),
+            ('M', "%M"),
+            ('S', "%S"),
+        ],
+    };
+
+    let mut pattern = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            push_date_literal(&mut pattern, c, target);
+            continue;
+        }
+
+        let specifier = chars.next();
+        if specifier == Some('%') {
+            push_date_literal(&mut pattern, '%', target);
+            continue;
+        }
+
+        let translated = specifier.and_then(|s| specifiers.iter().find(|(c, _)| *c == s));
+        let Some((_, translated)) = translated else {
+            let specifier = specifier.map(String::from).unwrap_or_default();
+            return Err(Error::new(Reason::Simple(format!(
+                "format specifier `%{specifier}` cannot be translated to {target}"
+            )))
+            .with_help("supported specifiers are %Y, %m, %d, %H, %M, %S and %%")
+            .with_code(ErrorCode::Unsupported));
+        };
+        pattern.push_str(translated);
+    }
+    Ok(pattern)
+}
+
+/// Appends a character that is not a part of a specifier, escaping it if the
+/// target would otherwise interpret it.
+fn push_date_literal(pattern: &mut String, c: char, target: &Target) {
+    match target {
+        Target::PostgreSql | Target::Snowflake if c.is_alphanumeric() => {
+            pattern.push('"');
+            pattern.push(c);
+            pattern.push('"');
+        }
+        Target::PostgreSql | Target::Snowflake if c == '"' => pattern.push_str("\\\""),
+        Target::MsSql if c != ' ' => {
+            pattern.push('\\');
+            pattern.push(c);
+        }
+        Target::PostgreSql | Target::Snowflake | Target::MsSql => pattern.push(c),
+        _ if c == '%' => pattern.push_str("%%"),
+        _ => pattern.push(c),
+    }
+}
@@ -1,30 +1,58 @@
 use core::fmt::Debug;
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
-use strum;
+use strum::{self, IntoEnumIterator};
+
+use crate::describe::ColumnType;
+use crate::error::{Error, Reason};
 
+/// Parsing (both from the query header and from strings passed via API) is
+/// case-insensitive and accepts a few common aliases, such as `sql.postgresql`.
+/// Display always produces the canonical name.
 #[derive(
-    Debug, PartialEq, Eq, Clone, Serialize, Deserialize, strum::EnumString, strum::Display,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Serialize,
+    Deserialize,
+    strum::EnumString,
+    strum::Display,
+    strum::EnumIter,
 )]
+#[strum(ascii_case_insensitive)]
 pub enum Target {
-    #[strum(serialize = "sql.ansi")]
+    #[strum(to_string = "sql.ansi")]
     Ansi,
-    #[strum(serialize = "sql.bigquery")]
+    #[strum(to_string = "sql.bigquery", serialize = "sql.bq")]
     BigQuery,
-    #[strum(serialize = "sql.clickhouse")]
+    #[strum(to_string = "sql.clickhouse")]
     ClickHouse,
-    #[strum(serialize = "sql.generic")]
+    #[strum(to_string = "sql.duckdb")]
+    DuckDb,
+    #[strum(to_string = "sql.generic")]
     Generic,
-    #[strum(serialize = "sql.hive")]
+    #[strum(to_string = "sql.hive")]
     Hive,
-    #[strum(serialize = "sql.mssql")]
+    #[strum(
+        to_string = "sql.mssql",
+        serialize = "sql.sqlserver",
+        serialize = "sql.tsql"
+    )]
     MsSql,
-    #[strum(serialize = "sql.mysql")]
+    #[strum(to_string = "sql.mysql", serialize = "sql.mariadb")]
     MySql,
-    #[strum(serialize = "sql.postgres")]
+    #[strum(
+        to_string = "sql.postgres",
+        serialize = "sql.postgresql",
+        serialize = "sql.pg"
+    )]
     PostgreSql,
-    #[strum(serialize = "sql.sqlite")]
+    #[strum(to_string = "sql.sqlite")]
     SQLite,
-    #[strum(serialize = "sql.snowflake")]
+    #[strum(to_string = "sql.snowflake")]
     Snowflake,
 }
 
@@ -33,15 +61,309 @@ pub enum Target {
 // respective Enum?
 
 impl Target {
+    /// All supported targets, in declaration order.
+    pub fn all() -> Vec<Target> {
+        Target::iter().collect()
+    }
+
+    /// Names of all supported targets, as accepted by [Target::from_name].
+    pub fn names() -> Vec<String> {
+        Target::iter().map(|t| t.to_string()).collect()
+    }
+
+    /// Parses a target name, as in `prql target:sql.postgres`.
+    ///
+    /// Unlike [Target::from_str], the error lists all valid target names.
+    pub fn from_name(name: &str) -> Result<Target, Error> {
+        Target::from_str(name).map_err(|_| {
+            let names = Target::names().join(", ");
+
+            Error::new(Reason::NotFound {
+                name: name.to_string(),
+                namespace: "target".to_string(),
+            })
+            .with_help(format!("available targets: {names}"))
+        })
+    }
+
     pub fn handler(&self) -> Box<dyn TargetHandler> {
         match self {
             Target::MsSql => Box::new(MsSqlTarget),
             Target::MySql => Box::new(MySqlTarget),
             Target::BigQuery => Box::new(BigQueryTarget),
             Target::ClickHouse => Box::new(ClickHouseTarget),
+            Target::DuckDb => Box::new(DuckDbTarget),
+            Target::SQLite => Box::new(SQLiteTarget),
+            Target::Snowflake => Box::new(SnowflakeTarget),
+            Target::PostgreSql => Box::new(PostgresTarget),
             _ => Box::new(GenericTarget),
         }
     }
+
+    /// Features of SQL this target supports, as used by the compiler.
+    pub fn capabilities(&self) -> TargetCapabilities {
+        let handler = self.handler();
+        TargetCapabilities {
+            target: self.clone(),
+            use_top: handler.use_top(),
+            use_fetch_first: handler.use_fetch_first(),
+            supports_fetch_first: handler.supports_fetch_first(),
+            ident_quote: handler.ident_quote(),
+            supports_qualify: handler.supports_qualify(),
+            supports_cte: handler.supports_cte(),
+            supports_full_join: handler.supports_full_join(),
+            supports_right_join: handler.supports_right_join(),
+            supports_bool_literals: handler.supports_bool_literals(),
+            supports_date_time_literals: handler.supports_date_time_literals(),
+            supports_grouping_sets: handler.supports_grouping_sets(),
+            integer_avg_truncates: handler.integer_avg_truncates(),
+            concat_ignores_nulls: handler.concat_ignores_nulls(),
+            supports_alias_in_having: handler.supports_alias_in_having(),
+            supports_distinct_on: handler.supports_distinct_on(),
+            supports_limit_by: handler.supports_limit_by(),
+            supports_named_windows: handler.supports_named_windows(),
+            supports_cte_materialization: handler.supports_cte_materialization(),
+            supports_intersect_except: handler.supports_intersect_except(),
+            parenthesize_set_operands: handler.parenthesize_set_operands(),
+            supports_parenthesized_set_operands: handler.supports_parenthesized_set_operands(),
+            supports_join_using: handler.supports_join_using(),
+            requires_recursive_keyword: handler.requires_recursive_keyword(),
+            supports_bool_aggregates: handler.supports_bool_aggregates(),
+            supports_bool_values: handler.supports_bool_values(),
+            supports_quantified_comparison: handler.supports_quantified_comparison(),
+            requires_window_order: handler.requires_window_order(),
+            requires_offset_order: handler.requires_offset_order(),
+            supports_nulls_ordering: handler.supports_nulls_ordering(),
+            supports_take_percent: handler.supports_take_percent(),
+            table_sample: handler.table_sample(),
+            exclude_columns: handler.exclude_columns(),
+            regex_match: handler.regex_match(),
+            bool_to_number: handler.bool_to_number(),
+            max_identifier_length: handler.max_identifier_length(),
+        }
+    }
+}
+
+/// Answers of a [TargetHandler], in a form that can be serialized and shown to
+/// users (i.e. in a target picker).
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct TargetCapabilities {
+    pub target: Target,
+
+    /// Uses `SELECT TOP n` instead of `LIMIT n`.
+    pub use_top: bool,
+
+    /// Uses `FETCH FIRST n ROWS ONLY` instead of `LIMIT n`.
+    pub use_fetch_first: bool,
+
+    /// Supports `FETCH FIRST n ROWS ONLY`, even if it is not used.
+    pub supports_fetch_first: bool,
+
+    /// Character used for quoting identifiers.
+    pub ident_quote: char,
+
+    /// Supports `QUALIFY` clause for filtering on window functions.
+    pub supports_qualify: bool,
+
+    /// Supports common table expressions (`WITH`).
+    pub supports_cte: bool,
+
+    /// Supports `FULL JOIN`.
+    pub supports_full_join: bool,
+
+    /// Supports `RIGHT JOIN`.
+    pub supports_right_join: bool,
+
+    /// Supports `TRUE` and `FALSE`.
+    pub supports_bool_literals: bool,
+
+    /// Supports `DATE '2021-01-01'` and similar literals.
+    pub supports_date_time_literals: bool,
+
+    /// Supports `ROLLUP` and `CUBE` in `GROUP BY`.
+    pub supports_grouping_sets: bool,
+
+    /// `AVG` over an integer column produces an integer.
+    pub integer_avg_truncates: bool,
+
+    /// `CONCAT` treats NULL arguments as empty strings.
+    pub concat_ignores_nulls: bool,
+
+    /// `HAVING` can reference aliases of the projection.
+    pub supports_alias_in_having: bool,
+
+    /// Supports `SELECT DISTINCT ON (...)`.
+    pub supports_distinct_on: bool,
+
+    /// Supports `LIMIT n BY keys`, which takes rows of each group.
+    pub supports_limit_by: bool,
+
+    /// Supports `WINDOW w AS (...)`, referenced as `OVER w`.
+    pub supports_named_windows: bool,
+
+    /// Supports `AS MATERIALIZED` and `AS NOT MATERIALIZED` on CTEs.
+    pub supports_cte_materialization: bool,
+
+    /// Supports `INTERSECT` and `EXCEPT`.
+    pub supports_intersect_except: bool,
+
+    /// Operands of a sorted or limited `UNION` are in parentheses.
+    pub parenthesize_set_operands: bool,
+
+    /// Operands of `UNION` can be queries in parentheses.
+    pub supports_parenthesized_set_operands: bool,
+
+    /// Supports `JOIN ... USING (col)`.
+    pub supports_join_using: bool,
+
+    /// Recursive CTEs are declared with `WITH RECURSIVE`.
+    pub requires_recursive_keyword: bool,
+
+    /// Has aggregate functions for `every` and `any`.
+    pub supports_bool_aggregates: bool,
+
+    /// Booleans are values that can be selected, not only conditions.
+    pub supports_bool_values: bool,
+
+    /// Supports comparisons with `ANY` and `ALL` over a subquery.
+    pub supports_quantified_comparison: bool,
+
+    /// Offset and ranking window functions need `ORDER BY` in `OVER`.
+    pub requires_window_order: bool,
+
+    /// `OFFSET` needs `ORDER BY`.
+    pub requires_offset_order: bool,
+
+    /// Supports `NULLS FIRST` and `NULLS LAST` in `ORDER BY`.
+    pub supports_nulls_ordering: bool,
+
+    /// Supports `take` of a percentage of rows.
+    pub supports_take_percent: bool,
+
+    /// Syntax of sampling rows of a table, if there is one.
+    pub table_sample: Option<TableSample>,
+
+    /// Syntax of excluding columns from `*`, if there is one.
+    pub exclude_columns: Option<ExcludeColumns>,
+
+    /// Syntax of matching a regular expression, if there is one.
+    pub regex_match: Option<RegexMatch>,
+
+    /// Syntax of converting a boolean into 1 or 0, for `sum` and `average`.
+    pub bool_to_number: BoolToNumber,
+
+    /// Maximum length of identifiers, in bytes, if the target has one.
+    pub max_identifier_length: Option<usize>,
+}
+
+/// Syntax of sampling rows of a table in `FROM`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum TableSample {
+    /// `TABLESAMPLE SYSTEM (p)` of the SQL standard, a percentage only.
+    System,
+    /// `TABLESAMPLE SYSTEM (p PERCENT)`, a percentage only.
+    SystemPercent,
+    /// `TABLESAMPLE (n ROWS)` or `TABLESAMPLE (p PERCENT)`.
+    Rows,
+    /// `SAMPLE (n ROWS)` or `SAMPLE (p)`.
+    Sample,
+    /// `USING SAMPLE n ROWS` or `USING SAMPLE p PERCENT`, which ends the SELECT.
+    UsingSample,
+}
+
+impl TableSample {
+    /// The clause sampling `size` rows, or `size` percent of rows.
+    /// `None` if this syntax cannot express it.
+    pub fn render(&self, size: i64, percent: bool) -> Option<String> {
+        use TableSample::*;
+        Some(match (self, percent) {
+            (System, true) => format!("TABLESAMPLE SYSTEM ({size})"),
+            (SystemPercent, true) => format!("TABLESAMPLE SYSTEM ({size} PERCENT)"),
+            (Rows, true) => format!("TABLESAMPLE ({size} PERCENT)"),
+            (Rows, false) => format!("TABLESAMPLE ({size} ROWS)"),
+            (Sample, true) => format!("SAMPLE ({size})"),
+            (Sample, false) => format!("SAMPLE ({size} ROWS)"),
+            (UsingSample, true) => format!("USING SAMPLE {size} PERCENT"),
+            (UsingSample, false) => format!("USING SAMPLE {size} ROWS"),
+            (System | SystemPercent, false) => return None,
+        })
+    }
+
+    /// Whether the clause is the last of the SELECT rather than a clause of
+    /// the table, so the table is sampled in a SELECT of its own, where the
+    /// clause directly follows it.
+    pub fn ends_select(&self) -> bool {
+        *self == TableSample::UsingSample
+    }
+}
+
+/// Syntax of excluding columns from `*`, as in `SELECT * EXCLUDE (a, b)`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum ExcludeColumns {
+    /// `* EXCLUDE (a, b)`
+    Exclude,
+    /// `* EXCEPT (a, b)`
+    Except,
+}
+
+impl ExcludeColumns {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            ExcludeColumns::Exclude => "EXCLUDE",
+            ExcludeColumns::Except => "EXCEPT",
+        }
+    }
+}
+
+/// Syntax of matching a string against a regular expression, for `~=`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum RegexMatch {
+    /// `x ~ pattern`
+    Tilde,
+    /// `x REGEXP pattern`
+    Regexp,
+    /// `x RLIKE pattern`
+    Rlike,
+    /// `REGEXP_CONTAINS(x, pattern)`
+    RegexpContains,
+    /// `REGEXP_MATCHES(x, pattern)`
+    RegexpMatches,
+    /// `match(x, pattern)`
+    Match,
+}
+
+impl RegexMatch {
+    /// The expression matching `expr` against `pattern`.
+    pub fn render(&self, expr: &str, pattern: &str) -> String {
+        match self {
+            RegexMatch::Tilde => format!("{expr} ~ {pattern}"),
+            RegexMatch::Regexp => format!("{expr} REGEXP {pattern}"),
+            RegexMatch::Rlike => format!("{expr} RLIKE {pattern}"),
+            RegexMatch::RegexpContains => format!("REGEXP_CONTAINS({expr}, {pattern})"),
+            RegexMatch::RegexpMatches => format!("REGEXP_MATCHES({expr}, {pattern})"),
+            RegexMatch::Match => format!("match({expr}, {pattern})"),
+        }
+    }
+
+    /// Whether the match is an operator, which may need parentheses around it.
+    pub fn is_operator(&self) -> bool {
+        matches!(
+            self,
+            RegexMatch::Tilde | RegexMatch::Regexp | RegexMatch::Rlike
+        )
+    }
+}
+
+/// Syntax of converting a boolean into 1 (true) or 0 (false), so it can be
+/// summed or averaged.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum BoolToNumber {
+    /// `CASE WHEN x THEN 1 ELSE 0 END` of the SQL standard.
+    Case,
+    /// `(x)::int`
+    Cast,
+    /// `IF(x, 1, 0)`
+    If,
 }
 
 impl Default for Target {
@@ -55,16 +377,421 @@ pub struct MySqlTarget;
 pub struct MsSqlTarget;
 pub struct BigQueryTarget;
 pub struct ClickHouseTarget;
+pub struct DuckDbTarget;
+pub struct SQLiteTarget;
+pub struct SnowflakeTarget;
+pub struct PostgresTarget;
 
 pub trait TargetHandler {
     fn target(&self) -> Target;
+
+    /// Whether the target is restricted to the SQL standard.
+    /// See [StandardTarget].
+    fn strict_standard(&self) -> bool {
+        false
+    }
+
     fn use_top(&self) -> bool {
         false
     }
 
+    /// Whether `take` is written as `OFFSET n ROWS FETCH FIRST m ROWS ONLY`
+    /// of the SQL standard, instead of `LIMIT m OFFSET n`.
+    fn use_fetch_first(&self) -> bool {
+        false
+    }
+
+    /// Whether `OFFSET n ROWS FETCH FIRST m ROWS ONLY` is accepted, even if
+    /// `take` is written otherwise. Used with `strict_standard`, which does
+    /// not allow `LIMIT` and `TOP`.
+    fn supports_fetch_first(&self) -> bool {
+        true
+    }
+
+    /// Opening quote of identifiers that cannot be written bare. `[` is
+    /// closed by `]`.
     fn ident_quote(&self) -> char {
         '"'
     }
+
+    fn supports_qualify(&self) -> bool {
+        false
+    }
+
+    fn supports_cte(&self) -> bool {
+        true
+    }
+
+    /// Whether `FULL JOIN` is supported. Without it, a full join that directly
+    /// follows `from` is written as a left join and a `UNION ALL` of the rows
+    /// of the right relation without a match.
+    fn supports_full_join(&self) -> bool {
+        true
+    }
+
+    /// Whether `RIGHT JOIN` is supported. Without it, a right join that
+    /// directly follows `from` is written as a left join with the relations
+    /// swapped.
+    fn supports_right_join(&self) -> bool {
+        true
+    }
+
+    /// Whether `TRUE` and `FALSE` are supported. Without them, booleans are
+    /// written as `1` and `0`.
+    fn supports_bool_literals(&self) -> bool {
+        true
+    }
+
+    /// Whether date, time and timestamp literals (i.e. `DATE '2021-01-01'`)
+    /// are supported. Without them, the value is cast from a string to the
+    /// type of [TargetHandler::type_name].
+    fn supports_date_time_literals(&self) -> bool {
+        true
+    }
+
+    /// Keyword of a timestamp literal, i.e. `TIMESTAMP` in
+    /// `TIMESTAMP '2021-01-01T10:00'`.
+    fn timestamp_keyword(&self, _value: &str) -> &'static str {
+        "TIMESTAMP"
+    }
+
+    /// Whether `GROUP BY ROLLUP (...)` and `GROUP BY CUBE (...)` are supported.
+    fn supports_grouping_sets(&self) -> bool {
+        true
+    }
+
+    /// Whether `AVG` over integers truncates the result to an integer. On such
+    /// targets, the argument of `average` is cast to a float.
+    fn integer_avg_truncates(&self) -> bool {
+        false
+    }
+
+    /// Whether `CONCAT` (used for f-strings) ignores NULL arguments, instead
+    /// of producing NULL, as `||` does.
+    fn concat_ignores_nulls(&self) -> bool {
+        false
+    }
+
+    /// Whether `HAVING` can reference aliases of the projection. Otherwise,
+    /// aggregates are repeated in HAVING.
+    fn supports_alias_in_having(&self) -> bool {
+        false
+    }
+
+    /// Whether `SELECT DISTINCT ON (...)` can be used for taking the first row
+    /// of each group. Otherwise, rows are numbered with `ROW_NUMBER`.
+    fn supports_distinct_on(&self) -> bool {
+        false
+    }
+
+    /// Whether `LIMIT n BY keys` can be used for taking rows of each group.
+    /// Otherwise, rows are numbered with `ROW_NUMBER`.
+    fn supports_limit_by(&self) -> bool {
+        false
+    }
+
+    /// Whether window specifications that are shared by expressions of a
+    /// query can be defined once, in a `WINDOW` clause.
+    fn supports_named_windows(&self) -> bool {
+        true
+    }
+
+    /// Whether CTEs can be marked `MATERIALIZED` or `NOT MATERIALIZED`.
+    /// Otherwise, materialization hints of tables are dropped.
+    fn supports_cte_materialization(&self) -> bool {
+        false
+    }
+
+    /// Whether `INTERSECT` and `EXCEPT` can be used for `intersect` and
+    /// `remove`. Otherwise, they are expressed with `[NOT] EXISTS`.
+    fn supports_intersect_except(&self) -> bool {
+        true
+    }
+
+    /// Whether operands of a sorted or limited `UNION` need parentheses, so
+    /// that `ORDER BY` and `LIMIT` are not taken as those of the last operand.
+    fn parenthesize_set_operands(&self) -> bool {
+        false
+    }
+
+    /// Whether operands of `UNION` can be queries in parentheses. Otherwise,
+    /// the first operand is written without them, which is only possible
+    /// when it has no clauses that would apply to the whole union.
+    fn supports_parenthesized_set_operands(&self) -> bool {
+        true
+    }
+
+    /// Whether joins on columns of the same name (`join b [==id]`) can be
+    /// expressed as `USING (id)`. Otherwise, they are expressed with `ON`.
+    fn supports_join_using(&self) -> bool {
+        true
+    }
+
+    /// Whether CTEs that reference themselves (the CTEs of `loop`) must be
+    /// declared with `WITH RECURSIVE`. Otherwise, `RECURSIVE` is omitted.
+    fn requires_recursive_keyword(&self) -> bool {
+        true
+    }
+
+    /// Whether `any` and `all` can be used as `ANY (...)` and `ALL (...)`.
+    /// Otherwise, ordering comparisons are expressed with `MIN` or `MAX`.
+    fn supports_quantified_comparison(&self) -> bool {
+        true
+    }
+
+    /// Whether the aggregate functions `every` and `any` have an implementation.
+    /// Otherwise, booleans are assumed to be stored as 0 and 1, and they are
+    /// expressed as `MIN(x) = 1` and `MAX(x) = 1`.
+    fn supports_bool_aggregates(&self) -> bool {
+        true
+    }
+
+    /// Whether booleans are values that can be selected and aggregated.
+    /// Otherwise (i.e. `BIT` of SQL Server), a comparison can only be a
+    /// condition, so emulated `every` and `any` aggregate their argument as an
+    /// integer, and are converted into 1 or 0 when selected. Boolean literals
+    /// are then written as conditions `1 = 1` and `1 = 0`.
+    fn supports_bool_values(&self) -> bool {
+        true
+    }
+
+    /// Whether offset and ranking window functions (i.e. `LAG` or
+    /// `ROW_NUMBER`) need `ORDER BY` in `OVER`. On such targets, these functions
+    /// cannot be used on unsorted rows.
+    fn requires_window_order(&self) -> bool {
+        false
+    }
+
+    /// Whether `OFFSET` needs `ORDER BY`. On such targets, unsorted rows are
+    /// ordered by `(SELECT NULL)`, which keeps them in no particular order,
+    /// or by the first column in set operations, which cannot use it.
+    fn requires_offset_order(&self) -> bool {
+        false
+    }
+
+    /// Whether `take` can be a percentage of rows, as `TOP n PERCENT` or
+    /// `FETCH FIRST n PERCENT ROWS ONLY`.
+    fn supports_take_percent(&self) -> bool {
+        false
+    }
+
+    /// Whether `NULLS FIRST` and `NULLS LAST` can be used in `ORDER BY`.
+    /// Otherwise, placement of NULLs is expressed by sorting on `col IS NULL`.
+    fn supports_nulls_ordering(&self) -> bool {
+        true
+    }
+
+    /// Syntax of `sample` on a table. Without one (or when it cannot express
+    /// the size), rows are sorted by [TargetHandler::random_function] and taken.
+    fn table_sample(&self) -> Option<TableSample> {
+        None
+    }
+
+    /// Syntax of excluding columns from `*`. Without one, columns can be
+    /// excluded with `select ![...]` only from tables whose columns are known.
+    fn exclude_columns(&self) -> Option<ExcludeColumns> {
+        None
+    }
+
+    /// Syntax of matching a regular expression. Without one, `~=` cannot be
+    /// translated.
+    fn regex_match(&self) -> Option<RegexMatch> {
+        // there is no syntax that most engines share
+        None
+    }
+
+    /// Expression producing a random value for each row.
+    fn random_function(&self) -> &'static str {
+        "RANDOM()"
+    }
+
+    /// How booleans are converted into numbers. Aggregating a comparison
+    /// directly (i.e. `AVG(x = 1)`) is invalid in most engines.
+    fn bool_to_number(&self) -> BoolToNumber {
+        BoolToNumber::Case
+    }
+
+    /// Maximum length of identifiers, in bytes. Longer ones are silently
+    /// truncated by the engine, so names we generate are kept within it.
+    fn max_identifier_length(&self) -> Option<usize> {
+        None
+    }
+
+    /// Whether a column named `ident` would be read as a function or a
+    /// session variable when referenced bare, i.e. `SELECT user FROM t` returning
+    /// the database user. Such idents are always quoted, even though they are
+    /// not reserved keywords.
+    fn is_dangerous_bare_ident(&self, ident: &str) -> bool {
+        matches!(
+            ident,
+            "count"
+                | "current_date"
+                | "current_time"
+                | "current_timestamp"
+                | "current_user"
+                | "localtime"
+                | "localtimestamp"
+                | "session_user"
+                | "user"
+        )
+    }
+
+    /// Name of the SQL type, used for typing NULLs where the type cannot be
+    /// inferred by the engine. `None` if NULLs don't need a type.
+    fn type_name(&self, ty: ColumnType) -> Option<&'static str> {
+        Some(match ty {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Float => "FLOAT",
+            ColumnType::Bool => "BOOLEAN",
+            ColumnType::String => "TEXT",
+            ColumnType::Date => "DATE",
+            ColumnType::Time => "TIME",
+            ColumnType::Timestamp => "TIMESTAMP",
+            ColumnType::Unknown => return None,
+        })
+    }
+}
+
+/// Restricts a target to constructs of the SQL standard, so the generated SQL
+/// stays portable. See [super::Options::strict_standard].
+///
+/// Each capability is the intersection of the standard and the wrapped target,
+/// so the generated SQL is also valid on the target. Everything else is
+/// forwarded.
+pub(super) struct StandardTarget(pub Box<dyn TargetHandler>);
+
+impl TargetHandler for StandardTarget {
+    fn target(&self) -> Target {
+        self.0.target()
+    }
+    fn strict_standard(&self) -> bool {
+        true
+    }
+    fn use_top(&self) -> bool {
+        false
+    }
+    fn use_fetch_first(&self) -> bool {
+        // on other targets, `take` cannot be expressed
+        self.0.supports_fetch_first()
+    }
+    fn supports_fetch_first(&self) -> bool {
+        self.0.supports_fetch_first()
+    }
+    fn ident_quote(&self) -> char {
+        // double quotes are not an identifier quote on all targets
+        self.0.ident_quote()
+    }
+    fn supports_qualify(&self) -> bool {
+        false
+    }
+    fn supports_cte(&self) -> bool {
+        self.0.supports_cte()
+    }
+    fn supports_full_join(&self) -> bool {
+        self.0.supports_full_join()
+    }
+    fn supports_right_join(&self) -> bool {
+        self.0.supports_right_join()
+    }
+    fn supports_bool_literals(&self) -> bool {
+        self.0.supports_bool_literals()
+    }
+    fn supports_date_time_literals(&self) -> bool {
+        self.0.supports_date_time_literals()
+    }
+    fn timestamp_keyword(&self, value: &str) -> &'static str {
+        self.0.timestamp_keyword(value)
+    }
+    fn supports_grouping_sets(&self) -> bool {
+        // ROLLUP and CUBE are an optional feature, not part of the core
+        false
+    }
+    fn integer_avg_truncates(&self) -> bool {
+        self.0.integer_avg_truncates()
+    }
+    fn concat_ignores_nulls(&self) -> bool {
+        self.0.concat_ignores_nulls()
+    }
+    fn supports_alias_in_having(&self) -> bool {
+        false
+    }
+    fn supports_distinct_on(&self) -> bool {
+        false
+    }
+    fn supports_limit_by(&self) -> bool {
+        false
+    }
+    fn supports_named_windows(&self) -> bool {
+        self.0.supports_named_windows()
+    }
+    fn supports_cte_materialization(&self) -> bool {
+        false
+    }
+    fn supports_intersect_except(&self) -> bool {
+        self.0.supports_intersect_except()
+    }
+    fn parenthesize_set_operands(&self) -> bool {
+        self.0.parenthesize_set_operands()
+    }
+    fn supports_parenthesized_set_operands(&self) -> bool {
+        self.0.supports_parenthesized_set_operands()
+    }
+    fn supports_join_using(&self) -> bool {
+        self.0.supports_join_using()
+    }
+    fn requires_recursive_keyword(&self) -> bool {
+        true
+    }
+    fn supports_quantified_comparison(&self) -> bool {
+        self.0.supports_quantified_comparison()
+    }
+    fn supports_bool_aggregates(&self) -> bool {
+        self.0.supports_bool_aggregates()
+    }
+    fn supports_bool_values(&self) -> bool {
+        self.0.supports_bool_values()
+    }
+    fn requires_window_order(&self) -> bool {
+        self.0.requires_window_order()
+    }
+    fn requires_offset_order(&self) -> bool {
+        self.0.requires_offset_order()
+    }
+    fn supports_take_percent(&self) -> bool {
+        // `TOP n PERCENT` is not standard and `FETCH FIRST n PERCENT` is
+        // supported only where `TOP` is not used
+        self.0.supports_take_percent() && !self.0.use_top()
+    }
+    fn supports_nulls_ordering(&self) -> bool {
+        self.0.supports_nulls_ordering()
+    }
+    fn table_sample(&self) -> Option<TableSample> {
+        self.0
+            .table_sample()
+            .filter(|sample| *sample == TableSample::System)
+    }
+    fn exclude_columns(&self) -> Option<ExcludeColumns> {
+        None
+    }
+    fn regex_match(&self) -> Option<RegexMatch> {
+        // `LIKE_REGEX` of the standard is implemented by hardly any engine
+        None
+    }
+    fn random_function(&self) -> &'static str {
+        self.0.random_function()
+    }
+    fn bool_to_number(&self) -> BoolToNumber {
+        BoolToNumber::Case
+    }
+    fn max_identifier_length(&self) -> Option<usize> {
+        self.0.max_identifier_length()
+    }
+    fn is_dangerous_bare_ident(&self, ident: &str) -> bool {
+        self.0.is_dangerous_bare_ident(ident)
+    }
+    fn type_name(&self, ty: ColumnType) -> Option<&'static str> {
+        self.0.type_name(ty)
+    }
 }
 
 impl TargetHandler for GenericTarget {
@@ -77,34 +804,610 @@ impl TargetHandler for MsSqlTarget {
     fn target(&self) -> Target {
         Target::MsSql
     }
+    fn supports_join_using(&self) -> bool {
+        false
+    }
+    fn requires_recursive_keyword(&self) -> bool {
+        false
+    }
+    fn supports_bool_aggregates(&self) -> bool {
+        false
+    }
+    fn supports_bool_values(&self) -> bool {
+        false
+    }
+    fn supports_bool_literals(&self) -> bool {
+        false
+    }
+    fn type_name(&self, ty: ColumnType) -> Option<&'static str> {
+        Some(match ty {
+            ColumnType::Integer => "INT",
+            ColumnType::Bool => "BIT",
+            ColumnType::String => "NVARCHAR(MAX)",
+            ColumnType::Timestamp => "DATETIME2",
+            _ => return GenericTarget.type_name(ty),
+        })
+    }
+    fn supports_date_time_literals(&self) -> bool {
+        false
+    }
     fn use_top(&self) -> bool {
         true
     }
+    fn requires_window_order(&self) -> bool {
+        true
+    }
+    fn requires_offset_order(&self) -> bool {
+        true
+    }
+    fn supports_named_windows(&self) -> bool {
+        // only since SQL Server 2022
+        false
+    }
+    fn is_dangerous_bare_ident(&self, ident: &str) -> bool {
+        ident == "system_user" || GenericTarget.is_dangerous_bare_ident(ident)
+    }
+    fn supports_nulls_ordering(&self) -> bool {
+        false
+    }
+    fn supports_take_percent(&self) -> bool {
+        true
+    }
+    fn table_sample(&self) -> Option<TableSample> {
+        Some(TableSample::Rows)
+    }
+    fn random_function(&self) -> &'static str {
+        "NEWID()"
+    }
+    fn max_identifier_length(&self) -> Option<usize> {
+        Some(128)
+    }
+    fn integer_avg_truncates(&self) -> bool {
+        true
+    }
+    fn concat_ignores_nulls(&self) -> bool {
+        true
+    }
+    fn ident_quote(&self) -> char {
+        '['
+    }
+}
+
+impl TargetHandler for PostgresTarget {
+    fn target(&self) -> Target {
+        Target::PostgreSql
+    }
+    fn is_dangerous_bare_ident(&self, ident: &str) -> bool {
+        matches!(ident, "current_catalog" | "current_role" | "current_schema")
+            || GenericTarget.is_dangerous_bare_ident(ident)
+    }
+    fn concat_ignores_nulls(&self) -> bool {
+        true
+    }
+    fn supports_distinct_on(&self) -> bool {
+        true
+    }
+    fn supports_cte_materialization(&self) -> bool {
+        true
+    }
+    fn table_sample(&self) -> Option<TableSample> {
+        Some(TableSample::System)
+    }
+    fn max_identifier_length(&self) -> Option<usize> {
+        Some(63)
+    }
+    fn bool_to_number(&self) -> BoolToNumber {
+        BoolToNumber::Cast
+    }
+    fn regex_match(&self) -> Option<RegexMatch> {
+        Some(RegexMatch::Tilde)
+    }
 }
 
 impl TargetHandler for MySqlTarget {
     fn target(&self) -> Target {
         Target::MySql
     }
+    fn supports_fetch_first(&self) -> bool {
+        false
+    }
+    fn type_name(&self, ty: ColumnType) -> Option<&'static str> {
+        // CAST in MySQL accepts only a few types
+        Some(match ty {
+            ColumnType::Integer | ColumnType::Bool => "SIGNED",
+            ColumnType::Float => "DOUBLE",
+            ColumnType::String => "CHAR",
+            ColumnType::Timestamp => "DATETIME",
+            _ => return GenericTarget.type_name(ty),
+        })
+    }
     fn ident_quote(&self) -> char {
         '`'
     }
+    fn supports_nulls_ordering(&self) -> bool {
+        false
+    }
+    fn supports_grouping_sets(&self) -> bool {
+        // only has `GROUP BY ... WITH ROLLUP`
+        false
+    }
+    fn supports_alias_in_having(&self) -> bool {
+        true
+    }
+    fn supports_intersect_except(&self) -> bool {
+        // only since 8.0.31
+        false
+    }
+    fn parenthesize_set_operands(&self) -> bool {
+        true
+    }
+    fn random_function(&self) -> &'static str {
+        "RAND()"
+    }
+    fn max_identifier_length(&self) -> Option<usize> {
+        Some(64)
+    }
+    fn supports_bool_aggregates(&self) -> bool {
+        false
+    }
+    fn bool_to_number(&self) -> BoolToNumber {
+        BoolToNumber::If
+    }
+    fn regex_match(&self) -> Option<RegexMatch> {
+        Some(RegexMatch::Regexp)
+    }
+}
+
+impl TargetHandler for SQLiteTarget {
+    fn target(&self) -> Target {
+        Target::SQLite
+    }
+    fn supports_fetch_first(&self) -> bool {
+        false
+    }
+    fn supports_bool_aggregates(&self) -> bool {
+        false
+    }
+    fn type_name(&self, _: ColumnType) -> Option<&'static str> {
+        // values are dynamically typed
+        None
+    }
+    fn supports_full_join(&self) -> bool {
+        false
+    }
+    fn supports_right_join(&self) -> bool {
+        // before 3.39
+        false
+    }
+    fn supports_bool_literals(&self) -> bool {
+        // before 3.23
+        false
+    }
+    fn supports_grouping_sets(&self) -> bool {
+        false
+    }
+    fn concat_ignores_nulls(&self) -> bool {
+        true
+    }
+    fn supports_quantified_comparison(&self) -> bool {
+        false
+    }
+    fn supports_parenthesized_set_operands(&self) -> bool {
+        false
+    }
+    fn regex_match(&self) -> Option<RegexMatch> {
+        // with the `regexp` function of an extension
+        Some(RegexMatch::Regexp)
+    }
+}
+
+impl TargetHandler for SnowflakeTarget {
+    fn target(&self) -> Target {
+        Target::Snowflake
+    }
+    fn supports_named_windows(&self) -> bool {
+        false
+    }
+    fn supports_qualify(&self) -> bool {
+        true
+    }
+    fn table_sample(&self) -> Option<TableSample> {
+        Some(TableSample::Sample)
+    }
+    fn exclude_columns(&self) -> Option<ExcludeColumns> {
+        Some(ExcludeColumns::Exclude)
+    }
+    fn regex_match(&self) -> Option<RegexMatch> {
+        Some(RegexMatch::Rlike)
+    }
 }
 
 impl TargetHandler for ClickHouseTarget {
     fn target(&self) -> Target {
         Target::ClickHouse
     }
+    fn supports_bool_aggregates(&self) -> bool {
+        false
+    }
+    fn type_name(&self, ty: ColumnType) -> Option<&'static str> {
+        // only nullable types can hold a NULL
+        Some(match ty {
+            ColumnType::Integer => "Nullable(Int64)",
+            ColumnType::Float => "Nullable(Float64)",
+            ColumnType::Bool => "Nullable(Bool)",
+            ColumnType::String => "Nullable(String)",
+            ColumnType::Date => "Nullable(Date)",
+            ColumnType::Timestamp => "Nullable(DateTime)",
+            _ => return None,
+        })
+    }
     fn ident_quote(&self) -> char {
         '`'
     }
+    fn random_function(&self) -> &'static str {
+        "rand()"
+    }
+    fn supports_limit_by(&self) -> bool {
+        true
+    }
+    fn exclude_columns(&self) -> Option<ExcludeColumns> {
+        Some(ExcludeColumns::Except)
+    }
+    fn regex_match(&self) -> Option<RegexMatch> {
+        Some(RegexMatch::Match)
+    }
+}
+
+impl TargetHandler for DuckDbTarget {
+    fn target(&self) -> Target {
+        Target::DuckDb
+    }
+    fn supports_fetch_first(&self) -> bool {
+        false
+    }
+    fn supports_qualify(&self) -> bool {
+        true
+    }
+    fn exclude_columns(&self) -> Option<ExcludeColumns> {
+        Some(ExcludeColumns::Exclude)
+    }
+    fn regex_match(&self) -> Option<RegexMatch> {
+        Some(RegexMatch::RegexpMatches)
+    }
+    fn table_sample(&self) -> Option<TableSample> {
+        Some(TableSample::UsingSample)
+    }
 }
 
 impl TargetHandler for BigQueryTarget {
     fn target(&self) -> Target {
         Target::BigQuery
     }
+    fn supports_fetch_first(&self) -> bool {
+        false
+    }
+    fn type_name(&self, ty: ColumnType) -> Option<&'static str> {
+        Some(match ty {
+            ColumnType::Integer => "INT64",
+            ColumnType::Float => "FLOAT64",
+            ColumnType::Bool => "BOOL",
+            ColumnType::String => "STRING",
+            _ => return GenericTarget.type_name(ty),
+        })
+    }
+    fn timestamp_keyword(&self, value: &str) -> &'static str {
+        // TIMESTAMP is an instant, DATETIME is a civil time without a time zone
+        let time = value.split_once('T').map_or(value, |(_, time)| time);
+        if time.contains(['Z', '+', '-']) {
+            "TIMESTAMP"
+        } else {
+            "DATETIME"
+        }
+    }
     fn ident_quote(&self) -> char {
         '`'
     }
+    fn supports_qualify(&self) -> bool {
+        true
+    }
+    fn table_sample(&self) -> Option<TableSample> {
+        Some(TableSample::SystemPercent)
+    }
+    fn random_function(&self) -> &'static str {
+        "RAND()"
+    }
+    fn bool_to_number(&self) -> BoolToNumber {
+        BoolToNumber::If
+    }
+    fn exclude_columns(&self) -> Option<ExcludeColumns> {
+        Some(ExcludeColumns::Except)
+    }
+    fn regex_match(&self) -> Option<RegexMatch> {
+        Some(RegexMatch::RegexpContains)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_target_round_trip() {
+        for target in Target::all() {
+            let name = target.to_string();
+            assert_eq!(Target::from_str(&name).unwrap(), target);
+            assert_eq!(Target::from_str(&name.to_uppercase()).unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn test_target_aliases() {
+        assert_eq!(
+            Target::from_str("sql.PostgreSQL").unwrap(),
+            Target::PostgreSql
+        );
+        assert_eq!(Target::from_str("sql.sqlserver").unwrap(), Target::MsSql);
+        assert_eq!(Target::from_str("sql.MariaDB").unwrap(), Target::MySql);
+        assert!(Target::from_str("sql.oracle").is_err());
+    }
+
+    #[test]
+    fn test_target_from_name() {
+        assert_eq!(Target::from_name("sql.MsSql").unwrap(), Target::MsSql);
+
+        let err = Target::from_name("sql.oracle").unwrap_err();
+        assert!(matches!(&err.reason, Reason::NotFound { name, .. } if name == "sql.oracle"));
+        assert!(err
+            .help
+            .unwrap()
+            .starts_with("available targets: sql.ansi, sql.bigquery"));
+    }
+
+    #[test]
+    fn test_capabilities_match_handlers() {
+        let mssql = Target::MsSql.capabilities();
+        assert_eq!(mssql.use_top, MsSqlTarget.use_top());
+        let mysql = Target::MySql.capabilities();
+        assert_eq!(mysql.ident_quote, MySqlTarget.ident_quote());
+    }
+
+    #[test]
+    fn test_capability_take() {
+        let mssql = Target::MsSql.capabilities();
+        let mysql = Target::MySql.capabilities();
+        assert!(mssql.use_top);
+        assert!(!mysql.use_top);
+        assert!(!mssql.use_fetch_first);
+        assert!(mssql.supports_fetch_first);
+        assert!(Target::PostgreSql.capabilities().supports_fetch_first);
+        assert!(!mysql.supports_fetch_first);
+        assert!(mssql.requires_offset_order);
+        assert!(!mysql.requires_offset_order);
+        assert!(mssql.supports_take_percent);
+        assert!(!mysql.supports_take_percent);
+    }
+
+    #[test]
+    fn test_capability_ident_quote() {
+        assert_eq!(Target::MsSql.capabilities().ident_quote, '[');
+        assert_eq!(Target::MySql.capabilities().ident_quote, '`');
+    }
+
+    #[test]
+    fn test_capability_functions() {
+        let mssql = Target::MsSql.capabilities();
+        let mysql = Target::MySql.capabilities();
+        assert!(mssql.integer_avg_truncates);
+        assert!(mssql.concat_ignores_nulls);
+        assert!(!mysql.concat_ignores_nulls);
+        assert!(!mssql.supports_bool_aggregates);
+        assert!(Target::PostgreSql.capabilities().supports_bool_aggregates);
+        assert!(!mssql.supports_bool_values);
+        assert!(mysql.supports_bool_values);
+    }
+
+    #[test]
+    fn test_capability_alias_in_having() {
+        assert!(Target::MySql.capabilities().supports_alias_in_having);
+        assert!(!Target::MsSql.capabilities().supports_alias_in_having);
+    }
+
+    #[test]
+    fn test_capability_first_rows_of_groups() {
+        let mysql = Target::MySql.capabilities();
+        assert!(Target::PostgreSql.capabilities().supports_distinct_on);
+        assert!(!mysql.supports_distinct_on);
+        assert!(Target::ClickHouse.capabilities().supports_limit_by);
+        assert!(!mysql.supports_limit_by);
+    }
+
+    #[test]
+    fn test_capability_qualify() {
+        assert!(Target::DuckDb.capabilities().supports_qualify);
+        assert!(!Target::MySql.capabilities().supports_qualify);
+    }
+
+    #[test]
+    fn test_capability_windows() {
+        let mssql = Target::MsSql.capabilities();
+        assert!(Target::PostgreSql.capabilities().supports_named_windows);
+        assert!(!mssql.supports_named_windows);
+        assert!(mssql.requires_window_order);
+        assert!(!Target::MySql.capabilities().requires_window_order);
+    }
+
+    #[test]
+    fn test_capability_ctes() {
+        let mssql = Target::MsSql.capabilities();
+        let mysql = Target::MySql.capabilities();
+        assert!(
+            Target::PostgreSql
+                .capabilities()
+                .supports_cte_materialization
+        );
+        assert!(!mysql.supports_cte_materialization);
+        assert!(!mssql.requires_recursive_keyword);
+        assert!(mysql.requires_recursive_keyword);
+    }
+
+    #[test]
+    fn test_capability_set_operations() {
+        let mssql = Target::MsSql.capabilities();
+        let mysql = Target::MySql.capabilities();
+        let sqlite = Target::SQLite.capabilities();
+        assert!(!mysql.supports_intersect_except);
+        assert!(mssql.supports_intersect_except);
+        assert!(mysql.parenthesize_set_operands);
+        assert!(!mssql.parenthesize_set_operands);
+        assert!(mysql.supports_parenthesized_set_operands);
+        assert!(!sqlite.supports_parenthesized_set_operands);
+    }
+
+    #[test]
+    fn test_capability_joins() {
+        let mysql = Target::MySql.capabilities();
+        assert!(!Target::MsSql.capabilities().supports_join_using);
+        assert!(mysql.supports_join_using);
+        assert!(!Target::SQLite.capabilities().supports_right_join);
+        assert!(mysql.supports_right_join);
+        assert!(!Target::SQLite.capabilities().supports_full_join);
+        assert!(Target::PostgreSql.capabilities().supports_full_join);
+    }
+
+    #[test]
+    fn test_capability_literals() {
+        let mysql = Target::MySql.capabilities();
+        assert!(!Target::SQLite.capabilities().supports_bool_literals);
+        assert!(mysql.supports_bool_literals);
+        assert!(!Target::MsSql.capabilities().supports_bool_literals);
+        assert!(!Target::MsSql.capabilities().supports_date_time_literals);
+        assert!(mysql.supports_date_time_literals);
+    }
+
+    #[test]
+    fn test_capability_quantified_comparison() {
+        assert!(!Target::SQLite.capabilities().supports_quantified_comparison);
+        assert!(Target::MsSql.capabilities().supports_quantified_comparison);
+    }
+
+    #[test]
+    fn test_capability_nulls_ordering() {
+        assert!(!Target::MySql.capabilities().supports_nulls_ordering);
+        assert!(!Target::MsSql.capabilities().supports_nulls_ordering);
+        assert!(Target::PostgreSql.capabilities().supports_nulls_ordering);
+    }
+
+    #[test]
+    fn test_capability_table_sample() {
+        assert_eq!(
+            Target::MsSql.capabilities().table_sample,
+            Some(TableSample::Rows)
+        );
+        assert_eq!(Target::MySql.capabilities().table_sample, None);
+        assert_eq!(
+            Target::PostgreSql.capabilities().table_sample,
+            Some(TableSample::System)
+        );
+        assert_eq!(
+            Target::DuckDb.capabilities().table_sample,
+            Some(TableSample::UsingSample)
+        );
+        assert_eq!(TableSample::System.render(1000, false), None);
+        assert_eq!(
+            TableSample::SystemPercent.render(10, true).unwrap(),
+            "TABLESAMPLE SYSTEM (10 PERCENT)"
+        );
+    }
+
+    #[test]
+    fn test_capability_exclude_columns() {
+        assert_eq!(
+            Target::DuckDb.capabilities().exclude_columns,
+            Some(ExcludeColumns::Exclude)
+        );
+        assert_eq!(
+            Target::BigQuery.capabilities().exclude_columns,
+            Some(ExcludeColumns::Except)
+        );
+        assert_eq!(Target::MySql.capabilities().exclude_columns, None);
+    }
+
+    #[test]
+    fn test_capability_regex_match() {
+        assert_eq!(
+            Target::MySql.capabilities().regex_match,
+            Some(RegexMatch::Regexp)
+        );
+        assert_eq!(Target::MsSql.capabilities().regex_match, None);
+        assert_eq!(Target::Ansi.capabilities().regex_match, None);
+        assert_eq!(
+            Target::PostgreSql.capabilities().regex_match,
+            Some(RegexMatch::Tilde)
+        );
+        assert_eq!(
+            RegexMatch::RegexpContains.render("name", "'^a'"),
+            "REGEXP_CONTAINS(name, '^a')"
+        );
+    }
+
+    #[test]
+    fn test_capability_max_identifier_length() {
+        assert_eq!(
+            Target::PostgreSql.capabilities().max_identifier_length,
+            Some(63)
+        );
+        assert_eq!(Target::Generic.capabilities().max_identifier_length, None);
+    }
+
+    #[test]
+    fn test_capability_bool_to_number() {
+        assert_eq!(
+            Target::MySql.capabilities().bool_to_number,
+            BoolToNumber::If
+        );
+        assert_eq!(
+            Target::MsSql.capabilities().bool_to_number,
+            BoolToNumber::Case
+        );
+        assert_eq!(
+            Target::PostgreSql.capabilities().bool_to_number,
+            BoolToNumber::Cast
+        );
+    }
+
+    #[test]
+    fn test_standard_target() {
+        let mysql = StandardTarget(Target::MySql.handler());
+        assert_eq!(mysql.target(), Target::MySql);
+        assert_eq!(mysql.ident_quote(), '`');
+        assert!(!mysql.supports_intersect_except());
+
+        let postgres = StandardTarget(Target::PostgreSql.handler());
+        assert!(!postgres.supports_distinct_on());
+        assert!(!postgres.supports_cte_materialization());
+        assert_eq!(postgres.bool_to_number(), BoolToNumber::Case);
+        assert_eq!(postgres.regex_match(), None);
+
+        // capabilities are never wider than those of the target
+        let mssql = StandardTarget(Target::MsSql.handler());
+        assert!(!mssql.use_top());
+        assert!(mssql.use_fetch_first());
+        assert!(!mssql.supports_take_percent());
+        assert!(!mssql.supports_date_time_literals());
+        assert!(postgres.use_fetch_first());
+        assert!(!mysql.use_fetch_first());
+        assert!(!mysql.supports_take_percent());
+        assert!(mysql.parenthesize_set_operands());
+        assert_eq!(mysql.table_sample(), None);
+        assert_eq!(postgres.table_sample(), Some(TableSample::System));
+
+        let duckdb = StandardTarget(Target::DuckDb.handler());
+        assert_eq!(duckdb.exclude_columns(), None);
+
+        let bigquery = StandardTarget(Target::BigQuery.handler());
+        assert_eq!(
+            bigquery.timestamp_keyword("2021-01-01T10:00"),
+            BigQueryTarget.timestamp_keyword("2021-01-01T10:00")
+        );
+    }
 }
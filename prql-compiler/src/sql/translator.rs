@@ -2,26 +2,33 @@
 //! then to a String. We use sqlparser because it's trivial to create the string
 //! once it's in their AST (it's just `.to_string()`). It also lets us support a
 //! few dialects of SQL immediately.
-use std::collections::HashSet;
-use std::str::FromStr;
+use std::collections::{HashMap, HashSet};
+use std::iter::{once, zip};
+use std::time::Duration;
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use itertools::Itertools;
 use sqlparser::ast::{self as sql_ast, Select, SelectItem, SetExpr, TableWithJoins};
 
-use crate::ast::pl::{BinOp, Literal};
+use crate::ast::pl::{BinOp, ColumnSort, GroupingKind, JoinSide, Literal, SortDirection};
 use crate::ast::rq::{
-    CId, Expr, ExprKind, Query, Relation, RelationColumn, RelationKind, RqFold, TableDecl,
-    Transform,
+    CId, Expr, ExprKind, Query, Relation, RelationColumn, RelationKind, RqFold, TId, TableDecl,
+    TableRef, Take, Transform,
 };
-use crate::error::{Error, Reason};
+use crate::cancellation::{self, Cancellation};
+use crate::error::{Error, ErrorCode, Reason, Span};
+use crate::notes::{Notes, OptimizationNote, TargetNote, TargetSource, Warning};
 use crate::sql::context::ColumnDecl;
+use crate::timings::{timed, CompileTimings};
 use crate::utils::{BreakUp, IntoOnly, Pluck, TableCounter};
 
 use super::codegen::*;
-use super::preprocess::{preprocess_distinct, preprocess_reorder};
-use super::{anchor, Target};
-use super::{context::AnchorContext, target::TargetHandler};
+use super::preprocess::{
+    preprocess_append_casts, preprocess_arg_extremes, preprocess_distinct, preprocess_in_lists,
+    preprocess_joins, preprocess_reorder, preprocess_sample, truncate_names,
+};
+use super::{anchor, Options, Target};
+use super::{context::AnchorContext, target::StandardTarget, target::TargetHandler};
 
 pub(super) struct Context {
     pub target: Box<dyn TargetHandler>,
@@ -34,36 +41,626 @@ pub(super) struct Context {
     /// - WHERE needs `pre_projection=true`, but
     /// - ORDER BY needs `pre_projection=false`.
     pub pre_projection: bool,
+
+    /// Columns that are referenced by their alias in the projection, even
+    /// when `pre_projection=true`. Used for HAVING on targets that allow it.
+    pub aliased: HashSet<CId>,
+
+    /// Normalizations applied to the query and warnings about it.
+    pub notes: Notes,
+
+    /// Materialization hints of CTEs, by their names after truncation.
+    pub materialization: Materialization,
+
+    /// Window specifications that are defined in the `WINDOW` clause of the
+    /// SELECT whose projection is being translated. Expressions over these
+    /// windows reference them as `w1`, `w2`, ...
+    pub named_windows: Vec<sql_ast::WindowSpec>,
+}
+
+impl Context {
+    /// Warns that the construct is emulated on this target and that the
+    /// emulation can behave differently from the native form.
+    pub fn warn_emulation(&mut self, construct: &str, caveat: &str) {
+        self.notes.warn(Warning {
+            construct: construct.to_string(),
+            target: Some(self.target.target()),
+            message: caveat.to_string(),
+            spans: Vec::new(),
+        });
+    }
+
+    /// Name of the target, for messages about constructs it does not support.
+    pub fn target_name(&self) -> String {
+        if self.target.strict_standard() {
+            format!("{} with `strict_standard`", self.target.target())
+        } else {
+            self.target.target().to_string()
+        }
+    }
 }
 
-pub fn translate_query(query: Query, target: Option<Target>) -> Result<sql_ast::Query> {
-    let target = if let Some(target) = target {
-        target
+/// Materialization hints of CTEs, by the name of the CTE.
+/// See [render_query].
+pub(super) type Materialization = HashMap<String, bool>;
+
+pub fn translate_query(
+    query: Query,
+    options: &Options,
+    timings: Option<&mut CompileTimings>,
+) -> Result<(sql_ast::Query, Notes, Materialization)> {
+    let (mut queries, notes, hints) = translate_query_split(query, options, timings)?;
+    let strict_standard = options.strict_standard;
+
+    // take last query
+    let (_, mut main_query) = queries.remove(queries.len() - 1);
+
+    // hints are dropped on targets that don't support them
+    let supports_materialization = !strict_standard
+        && (notes.target.as_ref())
+            .map_or(false, |t| t.target.handler().supports_cte_materialization());
+    let materialization = if supports_materialization {
+        (queries.iter())
+            .filter_map(|(name, _)| Some((name.value.clone(), *hints.get(&name.value)?)))
+            .collect()
     } else {
-        let sql_target = query.def.other.get("target");
-        sql_target
-            .map(|target| {
-                super::Target::from_str(target).map_err(|_| {
-                    Error::new(Reason::NotFound {
-                        name: format!("{target:?}"),
-                        namespace: "target".to_string(),
-                    })
-                })
+        HashMap::new()
+    };
+
+    // the rest are CTEs
+    let ctes = queries
+        .into_iter()
+        .map(|(name, query)| sql_ast::Cte {
+            alias: sql_ast::TableAlias {
+                name,
+                columns: vec![],
+            },
+            query: Box::new(query),
+            from: None,
+        })
+        .collect_vec();
+
+    // CTEs of loops reference themselves
+    let recursive = ctes
+        .iter()
+        .any(|cte| is_recursive(&cte.alias.name.value, &cte.query));
+    let requires_keyword = strict_standard
+        || (notes.target.as_ref())
+            .map_or(true, |t| t.target.handler().requires_recursive_keyword());
+
+    // attach CTEs
+    if !ctes.is_empty() {
+        main_query.with = Some(sql_ast::With {
+            cte_tables: ctes,
+            recursive: recursive && requires_keyword,
+        });
+    }
+
+    Ok((main_query, notes, materialization))
+}
+
+/// Renders the query with `MATERIALIZED` or `NOT MATERIALIZED` in the
+/// definitions of its CTEs that have a hint. `sql_ast::Cte` has no field for
+/// the hint, so the WITH clause of such a query is rendered here, the same
+/// way as sqlparser renders it.
+pub(super) fn render_query(query: &sql_ast::Query, materialization: &Materialization) -> String {
+    let Some(with) = &query.with else {
+        return query.to_string();
+    };
+    let is_hinted = |cte: &sql_ast::Cte| materialization.contains_key(&cte.alias.name.value);
+    if !with.cte_tables.iter().any(is_hinted) {
+        return query.to_string();
+    }
+
+    let ctes = (with.cte_tables.iter())
+        .map(|cte| match materialization.get(&cte.alias.name.value) {
+            Some(true) => format!("{} AS MATERIALIZED ({})", cte.alias, cte.query),
+            Some(false) => format!("{} AS NOT MATERIALIZED ({})", cte.alias, cte.query),
+            None => cte.to_string(),
+        })
+        .join(", ");
+    let recursive = if with.recursive { "RECURSIVE " } else { "" };
+    let body = sql_ast::Query {
+        with: None,
+        ..query.clone()
+    };
+    format!("WITH {recursive}{ctes} {body}")
+}
+
+/// Removes WITH clause of the query by replacing references to CTEs with
+/// subqueries, in FROM and JOIN of all queries in the tree, including
+/// subqueries of expressions. A CTE that is referenced multiple times is
+/// repeated, unless it contains a LIMIT: repeated subqueries would not
+/// necessarily select the same rows, so such CTEs are kept in WITH.
+/// Recursive CTEs are kept too.
+pub(super) fn inline_ctes(mut query: sql_ast::Query) -> sql_ast::Query {
+    let Some(with) = query.with.take() else {
+        return query;
+    };
+
+    // count how many times each CTE would be repeated, starting from the main
+    // query, since each CTE can reference only the preceding ones
+    let mut repeats = HashMap::new();
+    count_table_refs(&query, 1, &mut repeats);
+    let mut kept = HashSet::new();
+    for cte in with.cte_tables.iter().rev() {
+        let name = &cte.alias.name.value;
+        let count = repeats.get(name).cloned().unwrap_or_default();
+
+        let keep = (count > 1 && is_limited(&cte.query)) || is_recursive(name, &cte.query);
+        if keep {
+            kept.insert(name.clone());
+        }
+        count_table_refs(&cte.query, if keep { 1 } else { count }, &mut repeats);
+    }
+
+    let recursive = with.recursive;
+    let mut ctes = HashMap::new();
+    let mut kept_ctes = Vec::new();
+    for mut cte in with.cte_tables {
+        visit_table_factors(&mut cte.query, &mut |factor| {
+            inline_ctes_in_table_factor(factor, &ctes)
+        });
+
+        if kept.contains(&cte.alias.name.value) {
+            kept_ctes.push(cte);
+        } else {
+            ctes.insert(cte.alias.name.value, *cte.query);
+        }
+    }
+
+    visit_table_factors(&mut query, &mut |factor| {
+        inline_ctes_in_table_factor(factor, &ctes)
+    });
+    if !kept_ctes.is_empty() {
+        query.with = Some(sql_ast::With {
+            cte_tables: kept_ctes,
+            recursive,
+        });
+    }
+    query
+}
+
+fn is_limited(query: &sql_ast::Query) -> bool {
+    let has_top = matches!(&*query.body, SetExpr::Select(select) if select.top.is_some());
+    query.limit.is_some() || query.fetch.is_some() || has_top
+}
+
+/// Adds `times` to the count of each of the tables referenced in the query.
+fn count_table_refs(query: &sql_ast::Query, times: usize, counts: &mut HashMap<String, usize>) {
+    visit_selects(query, &mut |select| {
+        let relations = (select.from.iter())
+            .flat_map(|from| once(&from.relation).chain(from.joins.iter().map(|j| &j.relation)));
+        for relation in relations {
+            if let sql_ast::TableFactor::Table { name, .. } = relation {
+                if let [ident] = &name.0[..] {
+                    let name = referenced_table(ident).to_string();
+                    *counts.entry(name).or_default() += times;
+                }
+            }
+        }
+    });
+}
+
+/// Calls `f` on each SELECT of the query and of all of its subqueries: the
+/// ones in CTEs, set operations, derived tables and expressions. A SELECT is
+/// visited before its subqueries.
+///
+/// Unlike [visit_table_factors], it only reads the query.
+fn visit_selects<F>(query: &sql_ast::Query, f: &mut F)
+where
+    F: FnMut(&Select),
+{
+    let ctes = query.with.iter().flat_map(|with| &with.cte_tables);
+    for cte in ctes {
+        visit_selects(&cte.query, f);
+    }
+    visit_selects_of_set_expr(&query.body, f);
+    for order in &query.order_by {
+        visit_selects_of_expr(&order.expr, f);
+    }
+}
+
+fn visit_selects_of_set_expr<F>(body: &SetExpr, f: &mut F)
+where
+    F: FnMut(&Select),
+{
+    use sql_ast::{JoinConstraint, JoinOperator, TableFactor};
+
+    match body {
+        SetExpr::Select(select) => {
+            f(select);
+
+            for item in &select.projection {
+                if let SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } = item
+                {
+                    visit_selects_of_expr(expr, f);
+                }
+            }
+            for from in &select.from {
+                let relations = once(&from.relation).chain(from.joins.iter().map(|j| &j.relation));
+                for relation in relations {
+                    if let TableFactor::Derived { subquery, .. } = relation {
+                        visit_selects(subquery, f);
+                    }
+                }
+                for join in &from.joins {
+                    if let JoinOperator::Inner(JoinConstraint::On(expr))
+                    | JoinOperator::LeftOuter(JoinConstraint::On(expr))
+                    | JoinOperator::RightOuter(JoinConstraint::On(expr))
+                    | JoinOperator::FullOuter(JoinConstraint::On(expr)) = &join.join_operator
+                    {
+                        visit_selects_of_expr(expr, f);
+                    }
+                }
+            }
+            let exprs = (select.selection.iter())
+                .chain(select.group_by.iter())
+                .chain(select.having.iter())
+                .chain(select.qualify.iter());
+            for expr in exprs {
+                visit_selects_of_expr(expr, f);
+            }
+        }
+        SetExpr::Query(query) => visit_selects(query, f),
+        SetExpr::SetOperation { left, right, .. } => {
+            visit_selects_of_set_expr(left, f);
+            visit_selects_of_set_expr(right, f);
+        }
+        _ => {}
+    }
+}
+
+fn visit_selects_of_expr<F>(expr: &sql_ast::Expr, f: &mut F)
+where
+    F: FnMut(&Select),
+{
+    use sql_ast::{Expr, FunctionArg, FunctionArgExpr};
+
+    match expr {
+        Expr::Exists { subquery, .. } | Expr::Subquery(subquery) => visit_selects(subquery, f),
+        Expr::InSubquery { expr, subquery, .. } => {
+            visit_selects_of_expr(expr, f);
+            visit_selects(subquery, f);
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            visit_selects_of_expr(left, f);
+            visit_selects_of_expr(right, f);
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr)
+        | Expr::Cast { expr, .. } => visit_selects_of_expr(expr, f),
+        Expr::InList { expr, list, .. } => {
+            for expr in once(&**expr).chain(list) {
+                visit_selects_of_expr(expr, f);
+            }
+        }
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            let exprs = (operand.iter().map(|e| &**e))
+                .chain(conditions)
+                .chain(results)
+                .chain(else_result.iter().map(|e| &**e));
+            for expr in exprs {
+                visit_selects_of_expr(expr, f);
+            }
+        }
+        Expr::Function(function) => {
+            for arg in &function.args {
+                if let FunctionArg::Named {
+                    arg: FunctionArgExpr::Expr(expr),
+                    ..
+                }
+                | FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) = arg
+                {
+                    visit_selects_of_expr(expr, f);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Calls `f` on each table factor in FROM and JOIN of the query and of all
+/// of its subqueries: the ones in set operations, derived tables and
+/// expressions, such as `EXISTS (...)` or `IN (...)`.
+fn visit_table_factors<F>(query: &mut sql_ast::Query, f: &mut F)
+where
+    F: FnMut(&mut sql_ast::TableFactor),
+{
+    if let Some(with) = &mut query.with {
+        for cte in &mut with.cte_tables {
+            visit_table_factors(&mut cte.query, f);
+        }
+    }
+    visit_set_expr(&mut query.body, f);
+    for order in &mut query.order_by {
+        visit_expr(&mut order.expr, f);
+    }
+}
+
+fn visit_set_expr<F>(body: &mut SetExpr, f: &mut F)
+where
+    F: FnMut(&mut sql_ast::TableFactor),
+{
+    match body {
+        SetExpr::Select(select) => {
+            for item in &mut select.projection {
+                match item {
+                    SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                        visit_expr(expr, f)
+                    }
+                    _ => {}
+                }
+            }
+            for from in &mut select.from {
+                visit_table_with_joins(from, f);
+            }
+            let exprs = (select.selection.iter_mut())
+                .chain(select.group_by.iter_mut())
+                .chain(select.having.iter_mut())
+                .chain(select.qualify.iter_mut());
+            for expr in exprs {
+                visit_expr(expr, f);
+            }
+        }
+        SetExpr::Query(query) => visit_table_factors(query, f),
+        SetExpr::SetOperation { left, right, .. } => {
+            visit_set_expr(left, f);
+            visit_set_expr(right, f);
+        }
+        _ => {}
+    }
+}
+
+fn visit_table_with_joins<F>(from: &mut TableWithJoins, f: &mut F)
+where
+    F: FnMut(&mut sql_ast::TableFactor),
+{
+    use sql_ast::{JoinConstraint, JoinOperator};
+
+    visit_table_factor(&mut from.relation, f);
+    for join in &mut from.joins {
+        visit_table_factor(&mut join.relation, f);
+        if let JoinOperator::Inner(JoinConstraint::On(expr))
+        | JoinOperator::LeftOuter(JoinConstraint::On(expr))
+        | JoinOperator::RightOuter(JoinConstraint::On(expr))
+        | JoinOperator::FullOuter(JoinConstraint::On(expr)) = &mut join.join_operator
+        {
+            visit_expr(expr, f);
+        }
+    }
+}
+
+fn visit_table_factor<F>(factor: &mut sql_ast::TableFactor, f: &mut F)
+where
+    F: FnMut(&mut sql_ast::TableFactor),
+{
+    match factor {
+        sql_ast::TableFactor::Derived { subquery, .. } => visit_table_factors(subquery, f),
+        _ => f(factor),
+    }
+}
+
+fn visit_expr<F>(expr: &mut sql_ast::Expr, f: &mut F)
+where
+    F: FnMut(&mut sql_ast::TableFactor),
+{
+    use sql_ast::{Expr, FunctionArg, FunctionArgExpr};
+
+    match expr {
+        Expr::Exists { subquery, .. } | Expr::Subquery(subquery) => {
+            visit_table_factors(subquery, f)
+        }
+        Expr::InSubquery { expr, subquery, .. } => {
+            visit_expr(expr, f);
+            visit_table_factors(subquery, f);
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            visit_expr(left, f);
+            visit_expr(right, f);
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::IsNull(expr)
+        | Expr::IsNotNull(expr)
+        | Expr::Cast { expr, .. } => visit_expr(expr, f),
+        Expr::InList { expr, list, .. } => {
+            visit_expr(expr, f);
+            for item in list {
+                visit_expr(item, f);
+            }
+        }
+        Expr::Case {
+            operand,
+            conditions,
+            results,
+            else_result,
+        } => {
+            let exprs = (operand.iter_mut().map(|e| &mut **e))
+                .chain(conditions.iter_mut())
+                .chain(results.iter_mut())
+                .chain(else_result.iter_mut().map(|e| &mut **e));
+            for expr in exprs {
+                visit_expr(expr, f);
+            }
+        }
+        Expr::Function(function) => {
+            for arg in &mut function.args {
+                match arg {
+                    FunctionArg::Named {
+                        arg: FunctionArgExpr::Expr(expr),
+                        ..
+                    }
+                    | FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)) => visit_expr(expr, f),
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn inline_ctes_in_table_factor(
+    factor: &mut sql_ast::TableFactor,
+    ctes: &HashMap<String, sql_ast::Query>,
+) {
+    let sql_ast::TableFactor::Table { name, alias, .. } = factor else {
+        return;
+    };
+    let [ident] = &name.0[..] else {
+        return;
+    };
+    let Some(cte) = ctes.get(referenced_table(ident)) else {
+        return;
+    };
+
+    // the subquery is named after the CTE, so column references stay valid,
+    // and keeps the clauses appended to the name
+    let alias = alias.take().unwrap_or_else(|| sql_ast::TableAlias {
+        name: ident.clone(),
+        columns: vec![],
+    });
+    *factor = sql_ast::TableFactor::Derived {
+        lateral: false,
+        subquery: Box::new(cte.clone()),
+        alias: Some(alias),
+    };
+}
+
+/// Name of the table that a table identifier references. Clauses that
+/// sqlparser cannot express, such as TABLESAMPLE and WINDOW, are appended to
+/// unquoted identifiers of tables.
+fn referenced_table(ident: &sql_ast::Ident) -> &str {
+    match ident.quote_style {
+        None => ident.value.split(' ').next().unwrap_or_default(),
+        Some(_) => &ident.value,
+    }
+}
+
+/// Translates each atomic query into a standalone SELECT that refers to
+/// preceding ones by name. The main query is last.
+pub fn translate_query_split(
+    query: Query,
+    options: &Options,
+    mut timings: Option<&mut CompileTimings>,
+) -> Result<(
+    Vec<(sql_ast::Ident, sql_ast::Query)>,
+    Notes,
+    Materialization,
+)> {
+    let cancellation = options.cancellation.as_ref();
+    let (atomics, mut context) = timed(timings.as_mut().map(|t| &mut t.split), || {
+        split_query(query, options)
+    })?;
+
+    // convert each of the atomics, in order
+    let queries: Vec<_> = atomics
+        .into_iter()
+        .map(|t| -> Result<_> {
+            cancellation::check(cancellation)?;
+
+            let duration = timings.as_mut().map(|timings| {
+                timings.atomic_queries.push(Duration::ZERO);
+                timings.atomic_queries.last_mut().unwrap()
+            });
+            timed(duration, || {
+                let name = translate_ident_part(t.name, &context);
+                let query = sql_query_of_relation(t.relation, &mut context)?;
+                let query = match t.step {
+                    Some(step) => {
+                        sql_query_of_loop(query, sql_query_of_pipeline(step, &mut context)?)
+                    }
+                    None => query,
+                };
+                Ok((name, query))
             })
-            .transpose()?
-            .unwrap_or_default()
+        })
+        .try_collect()?;
+
+    Ok((queries, context.notes, context.materialization))
+}
+
+fn target_handler(target: &Target, options: &Options) -> Box<dyn TargetHandler> {
+    if options.strict_standard {
+        Box::new(StandardTarget(target.handler()))
+    } else {
+        target.handler()
+    }
+}
+
+/// The alias of the query as a derived table, for `wrap_as`. It is quoted as
+/// the other identifiers of the query, per the target in `notes`.
+pub(super) fn translate_wrap_alias(
+    alias: &str,
+    notes: &Notes,
+    options: &Options,
+) -> sql_ast::Ident {
+    let target = (notes.target.as_ref()).map_or_else(Target::default, |t| t.target.clone());
+    quote_ident_part(alias.to_string(), target_handler(&target, options).as_ref())
+}
+
+/// Preprocesses the query and splits it into atomic queries, which would be
+/// translated into CTEs and the main query, without translating them.
+pub(super) fn split_query(query: Query, options: &Options) -> Result<(Vec<AtomicQuery>, Context)> {
+    let cancellation = options.cancellation.as_ref();
+
+    // options take precedence over the query header
+    let header = query.def.other.get("target");
+    let (target, source) = match (options.target, header) {
+        (Some(target), _) => (target, TargetSource::Options),
+        (None, Some(header)) => (Target::from_name(header)?, TargetSource::Header),
+        (None, None) => (Target::default(), TargetSource::Default),
     };
-    let target = target.handler();
+    log::debug!("compiling for {target}, set by {source}");
+    let target_note = TargetNote { target, source };
+    let target = target_handler(&target_note.target, options);
 
-    let (anchor, query) = AnchorContext::of(query);
+    let mut notes = Notes {
+        target: Some(target_note),
+        ..Notes::default()
+    };
+
+    let max_identifier_length =
+        (options.max_identifier_length).or_else(|| target.max_identifier_length());
+    let query = if let Some(max_len) = max_identifier_length {
+        warn_about_long_names(&query, max_len, &mut notes);
+        truncate_names(query, max_len)?
+    } else {
+        query
+    };
+
+    // hints are looked up by the names of CTEs, which are truncated by now
+    let materialization = (query.tables.iter())
+        .filter_map(|decl| Some((decl.name.clone()?, decl.materialized?)))
+        .collect();
+
+    let (mut anchor, query) = AnchorContext::of(query);
+    anchor.table_name.set_max_len(max_identifier_length);
+    anchor.col_name.set_max_len(max_identifier_length);
 
     let mut context = Context {
         target,
         anchor,
         omit_ident_prefix: false,
         pre_projection: false,
+        aliased: HashSet::new(),
+        notes,
+        materialization,
+        named_windows: Vec::new(),
     };
 
+    let query = preprocess_append_casts(query, options.append_casts, &mut context)?;
+
+    warn_about_lost_sorts(&query, &mut context.notes);
+    warn_about_shared_takes(&query, &mut context.notes);
+
     // extract tables and the pipeline
     let tables = into_tables(query.relation, query.tables, &mut context)?;
 
@@ -75,25 +672,27 @@ pub fn translate_query(query: Query, target: Option<Target>) -> Result<sql_ast::
             .unwrap_or_else(|| context.anchor.table_name.gen());
 
         match table.relation.kind {
-            RelationKind::Pipeline(pipeline) => {
-                // preprocess
-                let pipeline = preprocess_distinct(pipeline, &mut context)?;
-                let pipeline = preprocess_reorder(pipeline);
-
+            RelationKind::Pipeline(mut pipeline) => {
                 // load names of output columns
                 context.anchor.load_names(&pipeline, table.relation.columns);
 
-                // split to atomics
-                let ats = split_into_atomics(name, pipeline, &mut context.anchor);
+                // each loop is a recursive CTE, which the rest of the pipeline reads
+                while let Some(position) = pipeline.iter().position(|t| t.as_loop().is_some()) {
+                    let mut following = pipeline.split_off(position);
+                    let step = following.remove(0).into_loop().unwrap();
 
-                // ensure names for all columns that need it
-                ensure_names(&ats, &mut context.anchor);
+                    let (ats, rest) =
+                        split_loop(pipeline, step, following, &mut context, cancellation)?;
+                    atomics.extend(ats);
+                    pipeline = rest;
+                }
 
-                atomics.extend(ats);
+                atomics.extend(split_pipeline(name, pipeline, &mut context, cancellation)?);
             }
             RelationKind::Literal(_) | RelationKind::SString(_) => atomics.push(AtomicQuery {
                 name,
                 relation: table.relation.kind,
+                step: None,
             }),
             RelationKind::ExternRef(_) => {
                 // ref does not need it's own CTE
@@ -101,35 +700,413 @@ pub fn translate_query(query: Query, target: Option<Target>) -> Result<sql_ast::
         }
     }
 
-    // take last table
-    let main_query = atomics.remove(atomics.len() - 1);
-    let ctes = atomics;
+    Ok((atomics, context))
+}
 
-    // convert each of the CTEs
-    let ctes: Vec<_> = ctes
-        .into_iter()
-        .map(|t| table_to_sql_cte(t, &mut context))
-        .try_collect()?;
+/// Preprocesses a pipeline and splits it into atomic queries, the last of
+/// which is named `name`.
+fn split_pipeline(
+    name: String,
+    pipeline: Vec<Transform>,
+    context: &mut Context,
+    cancellation: Option<&Cancellation>,
+) -> Result<Vec<AtomicQuery>> {
+    // preprocess
+    let pipeline = preprocess_sample(pipeline, context)?;
+    let pipeline = preprocess_distinct(pipeline, context)?;
+    let pipeline = preprocess_arg_extremes(pipeline, context)?;
+    let pipeline = preprocess_in_lists(pipeline, context)?;
+    let pipeline = preprocess_reorder(pipeline);
+
+    let (mut atomics, pipeline) = split_full_joins(pipeline, context)?;
+
+    // split to atomics
+    let supports_qualify = context.target.supports_qualify();
+    let sample_ends_select = (context.target.table_sample()).map_or(false, |s| s.ends_select());
+    atomics.extend(split_into_atomics(
+        name,
+        pipeline,
+        &mut context.anchor,
+        supports_qualify,
+        sample_ends_select,
+        cancellation,
+    )?);
+
+    // joins can only be rewritten once it is known which of them share a SELECT
+    for atomic in &mut atomics {
+        if let RelationKind::Pipeline(pipeline) = &mut atomic.relation {
+            *pipeline = preprocess_joins(std::mem::take(pipeline), context)?;
+        }
+    }
 
-    // convert main query
-    let mut main_query = sql_query_of_relation(main_query.relation, &mut context)?;
+    // ensure names for all columns that need it
+    ensure_names(&atomics, &mut context.anchor);
 
-    // attach CTEs
-    if !ctes.is_empty() {
-        main_query.with = Some(sql_ast::With {
-            cte_tables: ctes,
-            recursive: false,
+    Ok(atomics)
+}
+
+/// Emulates full joins that directly follow `from` on targets without
+/// `FULL JOIN`, by splitting them off the pipeline into atomic queries:
+///
+/// ```sql
+/// WITH table_1 AS (
+///   SELECT a.*, b.* FROM b LEFT JOIN a ON a.id = b.id WHERE a.id IS NULL
+/// ),
+/// table_2 AS (
+///   SELECT a.*, b.* FROM a LEFT JOIN b ON a.id = b.id
+///   UNION ALL
+///   SELECT * FROM table_1
+/// )
+/// SELECT ... FROM table_2
+/// ```
+///
+/// Rows of the right relation without a match are recognized by a column of
+/// the left relation that is referenced in the condition, which is NULL only
+/// when the left relation was not matched.
+///
+/// Returns the atomic queries and the pipeline that reads from the last of
+/// them. Other full joins are reported by [preprocess_joins].
+fn split_full_joins(
+    mut pipeline: Vec<Transform>,
+    context: &mut Context,
+) -> Result<(Vec<AtomicQuery>, Vec<Transform>)> {
+    let mut atomics = Vec::new();
+    if context.target.supports_full_join() {
+        return Ok((atomics, pipeline));
+    }
+
+    loop {
+        let mut transforms =
+            (pipeline.iter().enumerate()).filter(|(_, t)| !matches!(t, Transform::Select(_)));
+        let is_from = matches!(transforms.next(), Some((_, Transform::From(_))));
+        let join = transforms.next().filter(|(_, t)| {
+            is_from
+                && matches!(
+                    t,
+                    Transform::Join {
+                        side: JoinSide::Full,
+                        ..
+                    }
+                )
+        });
+        let join = join.map(|(position, _)| position);
+        let Some(join) = join else {
+            return Ok((atomics, pipeline));
+        };
+
+        let following = pipeline.split_off(join + 1);
+        let columns = AnchorContext::determine_select_columns(&pipeline);
+        let Some(Transform::Join { with, filter, .. }) = pipeline.pop() else {
+            unreachable!()
+        };
+        let left = (pipeline.into_iter()).find_map(|t| t.into_from().ok());
+        let left = left.unwrap();
+
+        let left_columns: HashSet<_> = left.columns.iter().map(|(_, cid)| *cid).collect();
+        let referenced = anchor::CidCollector::collect(filter.clone());
+        let key = (referenced.into_iter()).find(|cid| left_columns.contains(cid));
+        let Some(key) = key else {
+            return Err(Error::new(Reason::Simple(format!(
+                "`join side:full` is not supported by {}",
+                context.target_name()
+            )))
+            .with_span(filter.span)
+            .with_help("reference a column of the left relation in the condition of the join")
+            .with_code(ErrorCode::Unsupported)
+            .into());
+        };
+
+        context.warn_emulation(
+            "full join",
+            "rows of the right relation without a match are those where a column of the left relation in the condition is NULL",
+        );
+
+        // rows of the right relation without a match
+        let unmatched_name = context.anchor.table_name.gen();
+        let unmatched = TableRef {
+            source: context.anchor.tid.gen(),
+            name: Some(unmatched_name.clone()),
+            columns: Vec::new(),
+        };
+        context.anchor.table_decls.insert(
+            unmatched.source,
+            TableDecl {
+                id: unmatched.source,
+                name: Some(unmatched_name.clone()),
+                relation: Relation {
+                    kind: RelationKind::SString(vec![]),
+                    columns: vec![],
+                    span: None,
+                },
+                materialized: None,
+            },
+        );
+        let key_is_null = Expr {
+            kind: ExprKind::Binary {
+                left: Box::new(Expr {
+                    kind: ExprKind::ColumnRef(key),
+                    span: None,
+                }),
+                op: BinOp::Eq,
+                right: Box::new(Expr {
+                    kind: ExprKind::Literal(Literal::Null),
+                    span: None,
+                }),
+            },
+            span: None,
+        };
+        atomics.push(AtomicQuery {
+            name: unmatched_name,
+            relation: RelationKind::Pipeline(vec![
+                Transform::From(with.clone()),
+                Transform::Join {
+                    side: JoinSide::Left,
+                    with: left.clone(),
+                    filter: filter.clone(),
+                },
+                Transform::Filter(key_is_null),
+                Transform::Select(columns.clone()),
+            ]),
+            step: None,
+        });
+
+        // all rows of the left relation, followed by the unmatched ones
+        let name = context.anchor.table_name.gen();
+        atomics.push(AtomicQuery {
+            name: name.clone(),
+            relation: RelationKind::Pipeline(vec![
+                Transform::From(left),
+                Transform::Join {
+                    side: JoinSide::Left,
+                    with,
+                    filter,
+                },
+                Transform::Select(columns.clone()),
+                Transform::Concat(unmatched),
+            ]),
+            step: None,
+        });
+
+        pipeline = anchor::anchor_split(&mut context.anchor, &name, &columns, following);
+        merge_wildcards(&mut pipeline);
+    }
+}
+
+/// Wildcards of both relations of a join are read from the table that the
+/// join was split into as one wildcard, which is selected only once.
+fn merge_wildcards(pipeline: &mut [Transform]) {
+    let Some(Transform::From(table_ref)) = pipeline.first() else {
+        return;
+    };
+    let wildcards = (table_ref.columns.iter())
+        .filter(|(col, _)| matches!(col, RelationColumn::Wildcard))
+        .map(|(_, cid)| *cid)
+        .collect_vec();
+    let Some((first, others)) = wildcards.split_first() else {
+        return;
+    };
+    for transform in pipeline.iter_mut() {
+        if let Transform::Select(cids) | Transform::Exclude(cids) = transform {
+            for cid in cids.iter_mut().filter(|c| others.contains(c)) {
+                *cid = *first;
+            }
+            let mut seen = false;
+            cids.retain(|cid| {
+                let is_repeated = *cid == *first && seen;
+                seen |= *cid == *first;
+                !is_repeated
+            });
+        }
+    }
+}
+
+/// Splits a pipeline at a loop into atomic queries, the last of which is a
+/// recursive CTE, and the pipeline that follows the loop, which reads from
+/// the CTE.
+///
+/// The CTE is the pipeline preceding the loop, followed by `UNION ALL` and
+/// the step of the loop, which reads from the CTE itself. The step cannot
+/// reference the CTE from another CTE, so it must fit into a single SELECT.
+fn split_loop(
+    initial: Vec<Transform>,
+    step: Vec<Transform>,
+    following: Vec<Transform>,
+    context: &mut Context,
+    cancellation: Option<&Cancellation>,
+) -> Result<(Vec<AtomicQuery>, Vec<Transform>)> {
+    let name = context.anchor.table_name.gen();
+
+    // columns of the CTE
+    let columns = AnchorContext::determine_select_columns(&initial);
+    let names: Vec<_> = (columns.iter())
+        .map(|cid| context.anchor.ensure_column_name(*cid).cloned())
+        .collect::<Option<_>>()
+        .ok_or_else(|| {
+            Error::new(Reason::Simple(
+                "`loop` needs to know all columns of its input".to_string(),
+            ))
+            .with_help("select the columns explicitly before `loop`")
+            .with_code(ErrorCode::Unsupported)
+        })?;
+
+    let mut atomics = split_pipeline(name.clone(), initial, context, cancellation)?;
+
+    // the step reads rows of the previous iteration from the CTE, and its
+    // columns are named as the columns of the CTE
+    let step = anchor::anchor_split(&mut context.anchor, &name, &columns, step);
+    let step_columns = AnchorContext::determine_select_columns(&step);
+    for (cid, col_name) in zip(step_columns, names) {
+        context.anchor.column_names.entry(cid).or_insert(col_name);
+    }
+
+    let mut steps = split_pipeline(name.clone(), step, context, cancellation)?;
+    if steps.len() > 1 {
+        return Err(Error::new(Reason::Simple(
+            "the pipeline of `loop` cannot be expressed with a single SELECT".to_string(),
+        ))
+        .with_help("move transforms that are not needed in each iteration after `loop`")
+        .with_code(ErrorCode::Unsupported)
+        .into());
+    }
+    let step = steps.remove(0).relation.into_pipeline().unwrap();
+    atomics.last_mut().unwrap().step = Some(step);
+
+    let following = anchor::anchor_split(&mut context.anchor, &name, &columns, following);
+    Ok((atomics, following))
+}
+
+/// Order of a relation is not preserved when it is referenced, so a sort in a
+/// table declaration does not apply to `take` in pipelines that consume it.
+fn warn_about_lost_sorts(query: &Query, notes: &mut Notes) {
+    let sorted_decls: HashMap<_, _> = (query.tables.iter())
+        .filter(|decl| {
+            let pipeline = decl.relation.kind.as_pipeline();
+            pipeline.map_or(false, |p| p.iter().any(|t| t.as_sort().is_some()))
+        })
+        .filter_map(|decl| Some((decl.id, decl.name.as_ref()?)))
+        .collect();
+
+    let relations = query.tables.iter().map(|decl| &decl.relation);
+    let pipelines = relations
+        .chain(once(&query.relation))
+        .filter_map(|r| r.kind.as_pipeline());
+    for pipeline in pipelines {
+        let Some(Transform::From(table_ref)) = pipeline.first() else {
+            continue;
+        };
+        let Some(name) = sorted_decls.get(&table_ref.source) else {
+            continue;
+        };
+
+        for transform in pipeline {
+            match transform {
+                Transform::Sort(_) | Transform::Aggregate { .. } => break,
+                Transform::Take(take) if take.sort.is_empty() => {
+                    notes.warn(Warning {
+                        construct: "sort".to_string(),
+                        target: None,
+                        message: format!(
+                            "`take` after `from {name}` ignores the sort in the declaration of `{name}`; sort again before `take`"
+                        ),
+                        spans: Vec::new(),
+                    });
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Names of extern tables and of columns are visible outside of the query, so
+/// unlike names of CTEs, they cannot be truncated to fit the target.
+fn warn_about_long_names(query: &Query, max_len: usize, notes: &mut Notes) {
+    let extern_tables = (query.tables.iter())
+        .filter(|decl| matches!(decl.relation.kind, RelationKind::ExternRef(_)));
+    let table_names =
+        (extern_tables.filter_map(|decl| decl.name.as_deref())).flat_map(|name| name.split('.'));
+
+    let relations = query.tables.iter().map(|decl| &decl.relation);
+    let column_names = (relations.chain(once(&query.relation)))
+        .flat_map(|relation| &relation.columns)
+        .filter_map(|col| match col {
+            RelationColumn::Single(Some(name)) => Some(name.as_str()),
+            _ => None,
         });
+
+    let long_names = (table_names.chain(column_names))
+        .filter(|name| name.len() > max_len)
+        .unique()
+        .map(|name| format!("`{name}`"))
+        .collect_vec();
+    if long_names.is_empty() {
+        return;
     }
 
-    Ok(main_query)
+    notes.warn(Warning {
+        construct: "identifier".to_string(),
+        target: None,
+        message: format!(
+            "identifiers longer than {max_len} bytes may be truncated by the database: {}",
+            long_names.join(", ")
+        ),
+        spans: Vec::new(),
+    });
 }
 
-/// A query that can be expressed with one SELECT statement
+/// A table declaration with an unsorted `take` selects arbitrary rows, so
+/// pipelines that reference it are not guaranteed to see the same rows.
+fn warn_about_shared_takes(query: &Query, notes: &mut Notes) {
+    let relations = query.tables.iter().map(|decl| &decl.relation);
+    let mut references: HashMap<TId, usize> = HashMap::new();
+    for pipeline in (relations.chain(once(&query.relation))).filter_map(|r| r.kind.as_pipeline()) {
+        for transform in pipeline {
+            match transform {
+                Transform::From(table_ref)
+                | Transform::Join {
+                    with: table_ref, ..
+                }
+                | Transform::Concat(table_ref)
+                | Transform::Intersect(table_ref)
+                | Transform::Remove(table_ref) => {
+                    *references.entry(table_ref.source).or_default() += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    for decl in &query.tables {
+        let (Some(name), Some(pipeline)) = (&decl.name, decl.relation.kind.as_pipeline()) else {
+            continue;
+        };
+        let count = references.get(&decl.id).cloned().unwrap_or_default();
+        let unsorted_take = pipeline
+            .iter()
+            .any(|t| matches!(t, Transform::Take(take) if take.sort.is_empty()));
+
+        if count > 1 && unsorted_take {
+            notes.warn(Warning {
+                construct: "take".to_string(),
+                target: None,
+                message: format!(
+                    "`{name}` is referenced {count} times, but `take` in its declaration has no sort, so the rows it selects are not deterministic; sort before `take`"
+                ),
+                spans: Vec::new(),
+            });
+        }
+    }
+}
+
+/// A query that can be expressed with one SELECT statement, or with two in
+/// a recursive CTE of a loop.
 #[derive(Debug)]
 pub struct AtomicQuery {
     name: String,
     relation: RelationKind,
+
+    /// Step of a loop, which is appended to the relation with `UNION ALL`.
+    step: Option<Vec<Transform>>,
 }
 
 fn into_tables(
@@ -141,31 +1118,53 @@ fn into_tables(
         id: context.anchor.tid.gen(),
         name: None,
         relation: main_pipeline,
+        materialized: None,
     };
     Ok([tables, vec![main]].concat())
 }
 
-fn table_to_sql_cte(table: AtomicQuery, context: &mut Context) -> Result<sql_ast::Cte> {
-    let alias = sql_ast::TableAlias {
-        name: translate_ident_part(table.name, context),
-        columns: vec![],
-    };
-    Ok(sql_ast::Cte {
-        alias,
-        query: Box::new(sql_query_of_relation(table.relation, context)?),
-        from: None,
-    })
-}
-
 fn sql_query_of_relation(relation: RelationKind, context: &mut Context) -> Result<sql_ast::Query> {
     match relation {
         RelationKind::ExternRef(_) => unreachable!(),
         RelationKind::Pipeline(pipeline) => sql_query_of_pipeline(pipeline, context),
-        RelationKind::Literal(_) => todo!(),
+        RelationKind::Literal(data) => translate_relation_literal(data, context),
         RelationKind::SString(items) => translate_query_sstring(items, context),
     }
 }
 
+/// Appends the step of a loop to its initial query, as the body of a
+/// recursive CTE.
+fn sql_query_of_loop(initial: sql_ast::Query, step: sql_ast::Query) -> sql_ast::Query {
+    // parentheses are needed only for clauses that would apply to the union
+    fn as_set_expr(query: sql_ast::Query) -> SetExpr {
+        let is_plain = query.with.is_none()
+            && query.order_by.is_empty()
+            && query.limit.is_none()
+            && query.offset.is_none()
+            && query.fetch.is_none();
+        if is_plain {
+            *query.body
+        } else {
+            SetExpr::Query(Box::new(query))
+        }
+    }
+
+    query_of_set_expr(SetExpr::SetOperation {
+        op: sql_ast::SetOperator::Union,
+        set_quantifier: sql_ast::SetQuantifier::All,
+        left: Box::new(as_set_expr(initial)),
+        right: Box::new(as_set_expr(step)),
+    })
+}
+
+/// Whether the query references a table of the name, as the recursive CTE
+/// of a loop references itself.
+pub(super) fn is_recursive(name: &str, query: &sql_ast::Query) -> bool {
+    let mut references = HashMap::new();
+    count_table_refs(query, 1, &mut references);
+    references.contains_key(name)
+}
+
 fn sql_query_of_pipeline(
     pipeline: Vec<Transform>,
     context: &mut Context,
@@ -175,11 +1174,11 @@ fn sql_query_of_pipeline(
     context.omit_ident_prefix = counter.count() == 1;
     log::debug!("atomic query contains {} tables", counter.count());
 
-    let (before_concat, after_concat) = pipeline.break_up(|t| matches!(t, Transform::Concat(_)));
+    let (before_concat, after_concat) = pipeline.break_up(is_set_operation);
 
     let select = sql_select_query_of_pipeline(before_concat, context)?;
 
-    sql_union_of_pipeline(select, after_concat, context)
+    sql_set_operation_of_pipeline(select, after_concat, context)
 }
 
 fn sql_select_query_of_pipeline(
@@ -188,176 +1187,816 @@ fn sql_select_query_of_pipeline(
 ) -> Result<sql_ast::Query> {
     context.pre_projection = true;
 
-    let projection = pipeline
+    let projected_cids = pipeline
         .pluck(|t| t.into_select())
         .into_only() // expect only one select
         .map(|cols| translate_wildcards(&context.anchor, cols))
-        .unwrap_or_default()
-        .into_iter()
-        .map(|id| translate_select_item(id, context))
-        .try_collect()?;
+        .unwrap_or_default();
 
+    // windows that are shared by projected columns are defined once, in the
+    // WINDOW clause, and the projection references them by name
+    let named_windows = if context.target.supports_named_windows() {
+        shared_window_specs(&projected_cids, context)?
+    } else {
+        Vec::new()
+    };
+    let outer_windows = std::mem::replace(&mut context.named_windows, named_windows);
+    let projection: Result<Vec<_>> = (projected_cids.iter())
+        .map(|id| translate_select_item(*id, context))
+        .try_collect();
+    let named_windows = std::mem::replace(&mut context.named_windows, outer_windows);
+    let mut projection = projection?;
+    let excluded = pipeline.pluck(|t| t.into_exclude()).concat();
+    exclude_from_wildcards(&mut projection, &projected_cids, &excluded, context)?;
+
+    // a sample that remains after preprocessing directly follows the from
+    let sample = pipeline.pluck(|t| t.into_sample()).into_iter().next();
     let mut from = pipeline
         .pluck(|t| t.into_from())
         .into_iter()
         .map(|source| TableWithJoins {
-            relation: table_factor_of_tid(source, context),
+            relation: table_factor_of_tid(source, sample.clone(), context),
             joins: vec![],
         })
         .collect::<Vec<_>>();
 
-    let joins = pipeline
-        .pluck(|t| t.into_join())
-        .into_iter()
-        .map(|j| translate_join(j, context))
+    // semi and anti joins don't add columns, they are conditions of WHERE
+    let (exists, joins): (Vec<_>, Vec<_>) = (pipeline.pluck(|t| t.into_join()).into_iter())
+        .partition(|(side, ..)| matches!(side, JoinSide::Semi | JoinSide::Anti));
+    let exists: Vec<_> = (exists.into_iter())
+        .map(|j| translate_exists_join(j, context))
+        .try_collect()?;
+    let joins = (joins.into_iter().enumerate())
+        .map(|(index, j)| translate_join(j, index == 0, context))
         .collect::<Result<Vec<_>>>()?;
     if !joins.is_empty() {
         if let Some(from) = from.last_mut() {
             from.joins = joins;
         } else {
-            return Err(anyhow!("Cannot use `join` without `from`"));
+            return Err(Error::new(Reason::Simple(
+                "Cannot use `join` without `from`".to_string(),
+            ))
+            .with_code(ErrorCode::Invalid)
+            .into());
         }
     }
 
     let sorts = pipeline.pluck(|t| t.into_sort());
+    // takes that are still partitioned after preprocessing are DISTINCT ON,
+    // or LIMIT BY on targets that support it
+    let partitioned = pipeline.pluck(|t| match t {
+        Transform::Take(take) if !take.partition.is_empty() => Ok(take),
+        t => Err(t),
+    });
+    let (distinct_on, limit_by) = match partitioned.into_iter().next() {
+        Some(take) if context.target.supports_limit_by() => (None, Some(take)),
+        Some(take) => (Some((take.partition, take.sort)), None),
+        None => (None, None),
+    };
     let takes = pipeline.pluck(|t| t.into_take());
     let unique = pipeline.iter().any(|t| matches!(t, Transform::Unique));
 
+    // Filters that follow a window function are applied after it, in QUALIFY.
+    // On targets without QUALIFY, the pipeline has been split before such filters.
+    let (mut pipeline, mut after_window) =
+        pipeline.break_up(|t| matches!(t, Transform::Compute(compute) if compute.window.is_some()));
+    let qualify = if context.target.supports_qualify() {
+        after_window.pluck(|t| t.into_filter())
+    } else {
+        Vec::new()
+    };
+    pipeline.extend(after_window);
+
     // Split the pipeline into before & after the aggregate
     let (mut before_agg, mut after_agg) =
-        pipeline.break_up(|t| matches!(t, Transform::Aggregate { .. } | Transform::Concat(_)));
+        pipeline.break_up(|t| matches!(t, Transform::Aggregate { .. }) || is_set_operation(t));
 
     // WHERE and HAVING
     let where_ = filter_of_conditions(before_agg.pluck(|t| t.into_filter()), context)?;
-    let having = filter_of_conditions(after_agg.pluck(|t| t.into_filter()), context)?;
+    let where_ = exists.into_iter().chain(where_).reduce(translate_and);
+    let having = after_agg.pluck(|t| t.into_filter());
+    let having = if context.target.supports_alias_in_having() {
+        // reference aggregates by their alias, instead of repeating them
+        context.aliased = (projected_cids.iter())
+            .filter(|cid| {
+                is_aggregation(cid, context)
+                    && !is_emulated_bool_aggregate(cid, context)
+                    && context.anchor.column_names.contains_key(cid)
+            })
+            .cloned()
+            .collect();
+        let having = filter_of_conditions(having, context)?;
+        context.aliased.clear();
+        having
+    } else {
+        filter_of_conditions(having, context)?
+    };
+    let qualify = filter_of_conditions(qualify, context)?;
 
     // GROUP BY
     let aggregate = after_agg.pluck(|t| t.into_aggregate()).into_iter().next();
-    let group_by: Vec<CId> = aggregate.map(|(part, _)| part).unwrap_or_default();
-    let group_by = try_into_exprs(group_by, context)?;
+    let (group_by, grouping) = aggregate
+        .map(|(part, _, grouping)| (part, grouping))
+        .unwrap_or_default();
+    // grouping by a constant is a no-op (and some engines reject it), so we
+    // only keep such columns in the projection
+    let mut non_constant = Vec::with_capacity(group_by.len());
+    for cid in group_by {
+        // ... but in subtotals of rollup and cube, the constant is NULL
+        if !grouping.is_plain() {
+            non_constant.push(cid);
+        } else if let Some(span) = constant_column_span(&cid, context) {
+            let note =
+                OptimizationNote::new("translator", span, "removed a constant from GROUP BY");
+            context.notes.optimizations.push(note);
+        } else {
+            non_constant.push(cid);
+        }
+    }
+    let group_by = try_into_exprs(non_constant, context)?;
+    let group_by = translate_grouping(group_by, grouping, context)?;
 
     context.pre_projection = false;
 
-    let ranges = takes.into_iter().map(|x| x.range).collect();
-    let take = range_of_ranges(ranges)?;
-    let offset = take.start.map(|s| s - 1).unwrap_or(0);
-    let limit = take.end.map(|e| e - offset);
+    let (limit_by, limit_by_sort) = match limit_by {
+        Some(take) => {
+            let (clause, sort) = translate_limit_by(take, context)?;
+            (Some(clause), sort)
+        }
+        None => (None, Vec::new()),
+    };
+    if let Some((partition, _)) = &distinct_on {
+        let keys: Vec<_> = (partition.iter())
+            .map(|cid| translate_cid(*cid, context).map(|expr| expr.to_string()))
+            .try_collect()?;
+        prepend_to_projection(
+            &mut projection,
+            format!("DISTINCT ON ({})", keys.join(", ")),
+        );
+    }
 
-    let offset = if offset == 0 {
-        None
+    // DISTINCT ON keeps the first row of each group in ORDER BY, which must
+    // start with the keys of DISTINCT ON
+    let sorts = match distinct_on {
+        Some((partition, sort)) if !sort.is_empty() => {
+            let keys = (partition.into_iter()).map(|column| ColumnSort {
+                direction: SortDirection::Asc,
+                column,
+                nulls: None,
+            });
+            vec![keys.chain(sort).collect()]
+        }
+        _ => sorts,
+    };
+    // LIMIT BY keeps the first rows of each group in ORDER BY
+    let sorts = if limit_by_sort.is_empty() {
+        sorts
     } else {
-        Some(sqlparser::ast::Offset {
-            value: translate_expr_kind(ExprKind::Literal(Literal::Integer(offset)), context)?,
-            rows: sqlparser::ast::OffsetRows::None,
-        })
+        vec![limit_by_sort]
     };
 
     // Use sorting from the frame
-    let order_by = sorts
+    let mut order_by = sorts
         .last()
         .map(|sorts| {
             sorts
                 .iter()
                 .map(|s| translate_column_sort(s, context))
-                .try_collect()
+                .try_collect::<_, Vec<_>, _>()
         })
         .transpose()?
-        .unwrap_or_default();
+        .unwrap_or_default()
+        .concat();
+    let limit = translate_limit(takes, false, &mut order_by, context)?;
+
+    // sqlparser cannot express LIMIT BY, so it is prepended to LIMIT
+    let limit_clause = match limit_by {
+        Some(clause) => {
+            let limit = (limit.limit.as_ref())
+                .map(|l| format!(" LIMIT {l}"))
+                .unwrap_or_default();
+            Some(sql_ast::Expr::Identifier(sql_ast::Ident::new(
+                clause + &limit,
+            )))
+        }
+        None => limit.limit,
+    };
+
+    let mut select = Select {
+        distinct: unique,
+        top: limit.top,
+        projection,
+        into: None,
+        from,
+        lateral_views: vec![],
+        selection: where_,
+        group_by,
+        cluster_by: vec![],
+        distribute_by: vec![],
+        sort_by: vec![],
+        having,
+        qualify,
+    };
+    if !named_windows.is_empty() {
+        append_window_clause(&mut select, &named_windows);
+    }
 
     Ok(sql_ast::Query {
-        body: Box::new(SetExpr::Select(Box::new(Select {
-            distinct: unique,
-            top: if context.target.use_top() {
-                limit.map(|l| top_of_i64(l, context))
-            } else {
-                None
-            },
-            projection,
-            into: None,
-            from,
-            lateral_views: vec![],
-            selection: where_,
-            group_by,
-            cluster_by: vec![],
-            distribute_by: vec![],
-            sort_by: vec![],
-            having,
-            qualify: None,
-        }))),
+        body: Box::new(SetExpr::Select(Box::new(select))),
         order_by,
         with: None,
-        limit: if context.target.use_top() {
-            None
-        } else {
-            limit.map(expr_of_i64)
-        },
-        offset,
-        fetch: None,
+        limit: limit_clause,
+        offset: limit.offset,
+        fetch: limit.fetch,
         locks: vec![],
     })
 }
 
-fn sql_union_of_pipeline(
+/// Excludes columns of `select ![...]` from stars of their tables. sqlparser
+/// cannot express the exclusion, so it is appended to the star.
+fn exclude_from_wildcards(
+    projection: &mut [SelectItem],
+    projected_cids: &[CId],
+    excluded: &[CId],
+    context: &Context,
+) -> Result<()> {
+    for (item, cid) in zip(projection.iter_mut(), projected_cids) {
+        let decl = &context.anchor.column_decls[cid];
+        let ColumnDecl::RelationColumn(tiid, _, RelationColumn::Wildcard) = decl else {
+            continue;
+        };
+        let names: Vec<_> = (excluded.iter())
+            .filter_map(|cid| match &context.anchor.column_decls[cid] {
+                ColumnDecl::RelationColumn(t, _, RelationColumn::Single(Some(name)))
+                    if t == tiid =>
+                {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        if names.is_empty() {
+            continue;
+        }
+
+        let Some(exclude) = context.target.exclude_columns() else {
+            let names = names.iter().map(|n| format!("`{n}`")).join(", ");
+            return Err(Error::new(Reason::Simple(format!(
+                "cannot exclude {names} from a table with unknown columns on {}",
+                context.target_name()
+            )))
+            .with_help("list the columns to keep with `select [...]` instead")
+            .with_code(ErrorCode::Unsupported)
+            .into());
+        };
+        let names = (names.into_iter())
+            .map(|name| translate_ident_part(name, context).to_string())
+            .join(", ");
+
+        if let SelectItem::UnnamedExpr(expr) = item {
+            *expr = sql_ast::Expr::Identifier(sql_ast::Ident::new(format!(
+                "{expr} {} ({names})",
+                exclude.keyword()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Defines the named windows of the SELECT in a `WINDOW` clause, which
+/// follows HAVING and precedes QUALIFY. sqlparser cannot express the clause,
+/// so it is appended to the clause that precedes it.
+fn append_window_clause(select: &mut Select, named_windows: &[sql_ast::WindowSpec]) {
+    let definitions = (named_windows.iter().enumerate())
+        .map(|(index, window)| format!("w{} AS ({window})", index + 1))
+        .join(", ");
+    let clause = match select.qualify.take() {
+        Some(qualify) => format!("WINDOW {definitions} QUALIFY {qualify}"),
+        None => format!("WINDOW {definitions}"),
+    };
+
+    let last_clause = (select.having.as_mut())
+        .or_else(|| select.group_by.last_mut())
+        .or(select.selection.as_mut());
+    if let Some(expr) = last_clause {
+        append_to_expr(expr, &clause);
+    } else if let Some(from) = select.from.last_mut() {
+        append_to_table_with_joins(from, &clause);
+    } else if let Some(last) = select.projection.last_mut() {
+        let item = format!("{last} {clause}");
+        *last = SelectItem::UnnamedExpr(sql_ast::Expr::Identifier(sql_ast::Ident::new(item)));
+    }
+}
+
+fn append_to_expr(expr: &mut sql_ast::Expr, text: &str) {
+    *expr = sql_ast::Expr::Identifier(sql_ast::Ident::new(format!("{expr} {text}")));
+}
+
+/// Appends raw text to the last join of the table, or to the table itself.
+/// Names of tables are kept as the first word of their identifiers, so
+/// references to CTEs can still be found, see [referenced_table].
+fn append_to_table_with_joins(from: &mut TableWithJoins, text: &str) {
+    use sql_ast::{JoinConstraint, JoinOperator, TableFactor};
+
+    let relation = match from.joins.last_mut() {
+        Some(join) => match &mut join.join_operator {
+            JoinOperator::Inner(constraint)
+            | JoinOperator::LeftOuter(constraint)
+            | JoinOperator::RightOuter(constraint)
+            | JoinOperator::FullOuter(constraint) => match constraint {
+                JoinConstraint::On(on) => return append_to_expr(on, text),
+                JoinConstraint::Using(_) => None,
+                _ => Some(&mut join.relation),
+            },
+            _ => Some(&mut join.relation),
+        },
+        None => Some(&mut from.relation),
+    };
+
+    match relation {
+        Some(
+            TableFactor::Table {
+                alias: Some(alias), ..
+            }
+            | TableFactor::Derived {
+                alias: Some(alias), ..
+            },
+        ) => {
+            alias.name = sql_ast::Ident::new(format!("{} {text}", alias.name));
+        }
+        Some(TableFactor::Table { name, .. }) if !name.0.is_empty() => {
+            let last = name.0.last_mut().unwrap();
+            *last = sql_ast::Ident::new(format!("{last} {text}"));
+        }
+        _ => {
+            // USING is parenthesized, so the text follows the whole FROM
+            let name = sql_ast::Ident::new(format!("{from} {text}"));
+            *from = TableWithJoins {
+                relation: TableFactor::Table {
+                    name: sql_ast::ObjectName(vec![name]),
+                    alias: None,
+                    args: None,
+                    with_hints: vec![],
+                },
+                joins: vec![],
+            };
+        }
+    }
+}
+
+/// The `n [OFFSET m] BY keys` part of `LIMIT n [OFFSET m] BY keys`, which
+/// keeps rows of each group of keys, in the order of the take's sort.
+fn translate_limit_by(take: Take, context: &mut Context) -> Result<(String, Vec<ColumnSort<CId>>)> {
+    let range = range_of_ranges(vec![take.range])?;
+    let offset = range.start.map(|s| s - 1).unwrap_or(0);
+    // unbounded takes are converted to a filter by row number in preprocessing
+    let limit = range.end.unwrap() - offset;
+
+    let keys: Vec<_> = (take.partition.into_iter())
+        .map(|cid| translate_cid(cid, context).map(|expr| expr.to_string()))
+        .try_collect()?;
+
+    let offset = if offset > 0 {
+        format!(" OFFSET {offset}")
+    } else {
+        String::new()
+    };
+    let clause = format!("{limit}{offset} BY {}", keys.join(", "));
+    Ok((clause, take.sort))
+}
+
+/// Wraps GROUP BY expressions into `ROLLUP (...)`, `CUBE (...)` or `GROUPING SETS (...)`.
+fn translate_grouping(
+    group_by: Vec<sql_ast::Expr>,
+    grouping: GroupingKind,
+    context: &Context,
+) -> Result<Vec<sql_ast::Expr>> {
+    if grouping.is_plain() || group_by.is_empty() {
+        return Ok(group_by);
+    }
+
+    if !context.target.supports_grouping_sets() {
+        let params = match grouping {
+            GroupingKind::Sets(_) => "`sets`",
+            _ => "`rollup` or `cube`",
+        };
+        return Err(Error::new(Reason::Simple(format!(
+            "`group` with {params} is not supported by {}",
+            context.target_name()
+        )))
+        .with_code(ErrorCode::Unsupported)
+        .into());
+    }
+
+    Ok(vec![match grouping {
+        GroupingKind::Rollup => {
+            sql_ast::Expr::Rollup(group_by.into_iter().map(|e| vec![e]).collect())
+        }
+        GroupingKind::Cube => sql_ast::Expr::Cube(group_by.into_iter().map(|e| vec![e]).collect()),
+        GroupingKind::Sets(sets) => sql_ast::Expr::GroupingSets(
+            (sets.into_iter())
+                .map(|set| set.into_iter().map(|i| group_by[i].clone()).collect())
+                .collect(),
+        ),
+        GroupingKind::Plain => unreachable!(),
+    }])
+}
+
+/// Returns `Some(span)` of the column's expression if the column is a constant.
+fn constant_column_span(cid: &CId, context: &Context) -> Option<Option<Span>> {
+    match context.anchor.column_decls.get(cid) {
+        Some(ColumnDecl::Compute(compute))
+            if matches!(compute.expr.kind, ExprKind::Literal(_)) && compute.window.is_none() =>
+        {
+            Some(compute.expr.span)
+        }
+        _ => None,
+    }
+}
+
+/// Prepends the first item of a projection with a keyword.
+// Hacky, but sqlparser does not support DISTINCT ON yet.
+fn prepend_to_projection(projection: &mut [SelectItem], prefix: String) {
+    if let Some(first) = projection.first_mut() {
+        let item = format!("{prefix} {first}");
+        *first = SelectItem::UnnamedExpr(sql_ast::Expr::Identifier(sql_ast::Ident::new(item)));
+    }
+}
+
+fn is_set_operation(transform: &Transform) -> bool {
+    matches!(
+        transform,
+        Transform::Concat(_) | Transform::Intersect(_) | Transform::Remove(_)
+    )
+}
+
+fn sql_set_operation_of_pipeline(
     top: sql_ast::Query,
     mut pipeline: Vec<Transform>,
     context: &mut Context,
 ) -> Result<sql_ast::Query, anyhow::Error> {
-    // union
-    let concat = pipeline.pluck(|t| t.into_concat()).into_iter().next();
+    use sql_ast::SetOperator;
+
+    let set_operation = (pipeline.pluck(|t| match t {
+        Transform::Concat(bottom) => Ok((SetOperator::Union, bottom)),
+        Transform::Intersect(bottom) => Ok((SetOperator::Intersect, bottom)),
+        Transform::Remove(bottom) => Ok((SetOperator::Except, bottom)),
+        t => Err(t),
+    }))
+    .into_iter()
+    .next();
     let unique = pipeline.iter().any(|t| matches!(t, Transform::Unique));
 
-    let bottom = if let Some(bottom) = concat {
-        bottom
+    let (op, bottom) = if let Some(set_operation) = set_operation {
+        set_operation
     } else {
         return Ok(top);
     };
 
+    if op != SetOperator::Union && !context.target.supports_intersect_except() {
+        return sql_exists_of_set_operation(top, op, bottom, context);
+    }
+
     let from = TableWithJoins {
-        relation: table_factor_of_tid(bottom, context),
+        relation: table_factor_of_tid(bottom, None, context),
         joins: vec![],
     };
 
-    Ok(sql_ast::Query {
-        with: None,
-        body: Box::new(SetExpr::SetOperation {
-            left: Box::new(SetExpr::Query(Box::new(top))),
-            right: Box::new(SetExpr::Select(Box::new(Select {
-                distinct: false,
-                top: None,
-                projection: vec![SelectItem::Wildcard(
-                    sql_ast::WildcardAdditionalOptions::default(),
-                )],
-                into: None,
-                from: vec![from],
-                lateral_views: vec![],
-                selection: None,
-                group_by: vec![],
-                cluster_by: vec![],
-                distribute_by: vec![],
-                sort_by: vec![],
-                having: None,
-                qualify: None,
-            }))),
-            set_quantifier: if unique {
-                sql_ast::SetQuantifier::Distinct
+    // sorts and takes that follow a concat apply to the whole UNION
+    let sorts = pipeline.pluck(|t| t.into_sort());
+    let takes = pipeline.pluck(|t| t.into_take());
+    let is_sorted_or_limited = !sorts.is_empty() || !takes.is_empty();
+
+    let bottom = SetExpr::Select(Box::new(select_wildcard(from)));
+    let bottom = if is_sorted_or_limited && context.target.parenthesize_set_operands() {
+        SetExpr::Query(Box::new(query_of_set_expr(bottom)))
+    } else {
+        bottom
+    };
+
+    // without parentheses, clauses of the first operand would apply to the union
+    let is_plain = top.with.is_none()
+        && top.order_by.is_empty()
+        && top.limit.is_none()
+        && top.offset.is_none()
+        && top.fetch.is_none();
+    let top = if is_plain && !context.target.supports_parenthesized_set_operands() {
+        *top.body
+    } else {
+        SetExpr::Query(Box::new(top))
+    };
+
+    let mut query = query_of_set_expr(SetExpr::SetOperation {
+        left: Box::new(top),
+        right: Box::new(bottom),
+        set_quantifier: match op {
+            // INTERSECT and EXCEPT discard duplicates by default
+            SetOperator::Union if unique => sql_ast::SetQuantifier::Distinct,
+            SetOperator::Union => sql_ast::SetQuantifier::All,
+            _ => sql_ast::SetQuantifier::None,
+        },
+        op,
+    });
+    if is_sorted_or_limited {
+        sort_and_limit_set_operation(&mut query, sorts, takes, context)?;
+    }
+    Ok(query)
+}
+
+/// Sets ORDER BY and LIMIT of a set operation. They can only reference the
+/// columns of the result by name, and TOP cannot be used.
+fn sort_and_limit_set_operation(
+    query: &mut sql_ast::Query,
+    sorts: Vec<Vec<ColumnSort<CId>>>,
+    takes: Vec<Take>,
+    context: &mut Context,
+) -> Result<()> {
+    let omit_ident_prefix = std::mem::replace(&mut context.omit_ident_prefix, true);
+    let order_by = (sorts.last().into_iter().flatten())
+        .map(|s| translate_column_sort(s, context))
+        .try_collect::<_, Vec<_>, _>();
+    context.omit_ident_prefix = omit_ident_prefix;
+    query.order_by = order_by?.concat();
+
+    let limit = translate_limit(takes, true, &mut query.order_by, context)?;
+    query.limit = limit.limit;
+    query.offset = limit.offset;
+    query.fetch = limit.fetch;
+    Ok(())
+}
+
+/// OFFSET, LIMIT, FETCH FIRST and TOP of a query.
+struct Limit {
+    offset: Option<sql_ast::Offset>,
+    limit: Option<sql_ast::Expr>,
+    fetch: Option<sql_ast::Fetch>,
+    top: Option<sql_ast::Top>,
+}
+
+/// Translates takes of a query into its OFFSET and LIMIT, or the equivalent
+/// of the target. TOP cannot skip rows, so targets that use it fall back to
+/// OFFSET and FETCH, as they always do in set operations. If such targets
+/// also require ORDER BY, an empty `order_by` gets a placeholder.
+fn translate_limit(
+    takes: Vec<Take>,
+    is_set_operation: bool,
+    order_by: &mut Vec<sql_ast::OrderByExpr>,
+    context: &mut Context,
+) -> Result<Limit> {
+    let use_top = context.target.use_top();
+
+    // a percentage of rows cannot be combined with a number of rows
+    let percent_span = (takes.iter())
+        .find(|t| t.percent)
+        .map(|t| t.range.end.as_ref().and_then(|e| e.span));
+    if let Some(span) = percent_span {
+        if takes.len() > 1 {
+            return Err(Error::new(Reason::Simple(
+                "`take` with a percentage cannot be combined with an offset or another `take`"
+                    .to_string(),
+            ))
+            .with_span(span)
+            .into());
+        }
+        // `TOP n PERCENT` cannot be used in set operations
+        if !context.target.supports_take_percent() || (is_set_operation && use_top) {
+            return Err(Error::new(Reason::Simple(format!(
+                "`take` with a percentage is not supported by {}",
+                context.target_name()
+            )))
+            .with_span(span)
+            .with_code(ErrorCode::Unsupported)
+            .into());
+        }
+    }
+    let percent = percent_span.is_some();
+
+    let ranges = takes.into_iter().map(|x| x.range).collect();
+    let take = range_of_ranges(ranges)?;
+    let offset = take.start.map(|s| s - 1).unwrap_or(0);
+    let limit = take.end.map(|e| e - offset);
+
+    let top = use_top && offset == 0 && !is_set_operation;
+    let fetch_first = context.target.use_fetch_first() || (use_top && !top);
+    if !top && !fetch_first && (limit.is_some() || offset != 0) {
+        ensure_standard_limit(context)?;
+    }
+
+    // targets with TOP need OFFSET in front of FETCH
+    let offset = if offset != 0 || (use_top && fetch_first && limit.is_some()) {
+        Some(sql_ast::Offset {
+            value: translate_expr_kind(ExprKind::Literal(Literal::Integer(offset)), context)?,
+            rows: if fetch_first {
+                sql_ast::OffsetRows::Rows
             } else {
-                sql_ast::SetQuantifier::All
+                sql_ast::OffsetRows::None
             },
-            op: sql_ast::SetOperator::Union,
-        }),
+        })
+    } else {
+        None
+    };
+    if offset.is_some() && order_by.is_empty() && context.target.requires_offset_order() {
+        // any order will do, the rows are not sorted anyway
+        order_by.push(sql_ast::OrderByExpr {
+            // set operations can only be ordered by their columns
+            expr: if is_set_operation {
+                expr_of_i64(1)
+            } else {
+                sql_ast::Expr::Identifier(sql_ast::Ident::new("(SELECT NULL)"))
+            },
+            asc: None,
+            nulls_first: None,
+        });
+    }
+
+    Ok(Limit {
+        offset,
+        limit: if top || fetch_first {
+            None
+        } else {
+            limit.map(expr_of_i64)
+        },
+        fetch: if fetch_first {
+            limit.map(|l| sql_ast::Fetch {
+                with_ties: false,
+                percent,
+                quantity: Some(expr_of_i64(l)),
+            })
+        } else {
+            None
+        },
+        top: if top {
+            limit.map(|l| sql_ast::Top {
+                percent,
+                ..top_of_i64(l, context)
+            })
+        } else {
+            None
+        },
+    })
+}
+
+/// `LIMIT` is not standard, so with `strict_standard`, targets without
+/// FETCH FIRST cannot express `take`.
+fn ensure_standard_limit(context: &Context) -> Result<()> {
+    if !context.target.strict_standard() {
+        return Ok(());
+    }
+    Err(Error::new(Reason::Simple(format!(
+        "`take` is not supported by {}",
+        context.target_name()
+    )))
+    .with_help("the target has no FETCH FIRST, and LIMIT is not a part of the SQL standard")
+    .with_code(ErrorCode::Unsupported)
+    .into())
+}
+
+/// Expresses INTERSECT as `WHERE EXISTS` and EXCEPT as `WHERE NOT EXISTS`,
+/// matching columns of the two relations by position.
+fn sql_exists_of_set_operation(
+    top: sql_ast::Query,
+    op: sql_ast::SetOperator,
+    bottom: TableRef,
+    context: &mut Context,
+) -> Result<sql_ast::Query> {
+    let construct = if op == sql_ast::SetOperator::Intersect {
+        "intersect"
+    } else {
+        "remove"
+    };
+    let unknown_columns = || {
+        Error::new(Reason::Simple(format!(
+            "`{construct}` needs to know all columns of both relations on {}",
+            context.target_name()
+        )))
+        .with_help("select the columns explicitly")
+        .with_code(ErrorCode::Unsupported)
+    };
+
+    let top_names = top_column_names(&top).ok_or_else(unknown_columns)?;
+    let bottom_names: Vec<_> = (bottom.columns.iter())
+        .map(|(col, _)| match col {
+            RelationColumn::Single(Some(name)) => Some(name.clone()),
+            _ => None,
+        })
+        .collect::<Option<_>>()
+        .ok_or_else(unknown_columns)?;
+
+    context.warn_emulation(
+        construct,
+        "it is emulated with EXISTS, where rows with NULL values never match",
+    );
+
+    let top_alias = translate_ident_part(context.anchor.table_name.gen(), context);
+    let bottom_relation = table_factor_of_tid(bottom, None, context);
+    let bottom_alias = match &bottom_relation {
+        sql_ast::TableFactor::Table {
+            alias: Some(alias), ..
+        } => vec![alias.name.clone()],
+        sql_ast::TableFactor::Table { name, .. } => name.0.clone(),
+        _ => unreachable!(),
+    };
+
+    let conditions = zip(top_names, bottom_names).map(|(t, b)| sql_ast::Expr::BinaryOp {
+        left: Box::new(sql_ast::Expr::CompoundIdentifier(vec![
+            top_alias.clone(),
+            translate_ident_part(t, context),
+        ])),
+        op: sql_ast::BinaryOperator::Eq,
+        right: Box::new(sql_ast::Expr::CompoundIdentifier(
+            [bottom_alias.clone(), vec![translate_ident_part(b, context)]].concat(),
+        )),
+    });
+    let condition = conditions.reduce(|left, right| sql_ast::Expr::BinaryOp {
+        left: Box::new(left),
+        op: sql_ast::BinaryOperator::And,
+        right: Box::new(right),
+    });
+
+    let mut matching = select_wildcard(TableWithJoins {
+        relation: bottom_relation,
+        joins: vec![],
+    });
+    matching.selection = condition;
+
+    let mut select = select_wildcard(TableWithJoins {
+        relation: sql_ast::TableFactor::Derived {
+            lateral: false,
+            subquery: Box::new(top),
+            alias: Some(sql_ast::TableAlias {
+                name: top_alias,
+                columns: vec![],
+            }),
+        },
+        joins: vec![],
+    });
+    select.distinct = true;
+    select.selection = Some(sql_ast::Expr::Exists {
+        subquery: Box::new(query_of_set_expr(SetExpr::Select(Box::new(matching)))),
+        negated: op == sql_ast::SetOperator::Except,
+    });
+
+    Ok(query_of_set_expr(SetExpr::Select(Box::new(select))))
+}
+
+/// Names of the columns of a SELECT, if they are all known.
+fn top_column_names(query: &sql_ast::Query) -> Option<Vec<String>> {
+    let SetExpr::Select(select) = query.body.as_ref() else {
+        return None;
+    };
+
+    (select.projection.iter())
+        .map(|item| match item {
+            SelectItem::ExprWithAlias { alias, .. } => Some(alias.value.clone()),
+            SelectItem::UnnamedExpr(sql_ast::Expr::Identifier(ident)) => Some(ident.value.clone()),
+            SelectItem::UnnamedExpr(sql_ast::Expr::CompoundIdentifier(parts)) => {
+                parts.last().map(|ident| ident.value.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+pub(super) fn select_wildcard(from: TableWithJoins) -> Select {
+    Select {
+        distinct: false,
+        top: None,
+        projection: vec![SelectItem::Wildcard(
+            sql_ast::WildcardAdditionalOptions::default(),
+        )],
+        into: None,
+        from: vec![from],
+        lateral_views: vec![],
+        selection: None,
+        group_by: vec![],
+        cluster_by: vec![],
+        distribute_by: vec![],
+        sort_by: vec![],
+        having: None,
+        qualify: None,
+    }
+}
+
+pub(super) fn query_of_set_expr(body: SetExpr) -> sql_ast::Query {
+    sql_ast::Query {
+        with: None,
+        body: Box::new(body),
         order_by: vec![],
         limit: None,
         offset: None,
         fetch: None,
         locks: vec![],
-    })
+    }
 }
 
 fn split_into_atomics(
     name: String,
     mut pipeline: Vec<Transform>,
     ctx: &mut AnchorContext,
-) -> Vec<AtomicQuery> {
+    supports_qualify: bool,
+    sample_ends_select: bool,
+    cancellation: Option<&Cancellation>,
+) -> Result<Vec<AtomicQuery>> {
     let outputs_cid = AnchorContext::determine_select_columns(&pipeline);
 
     let mut required_cols = outputs_cid.clone();
@@ -365,7 +2004,15 @@ fn split_into_atomics(
     // split pipeline, back to front
     let mut parts_rev = Vec::new();
     loop {
-        let (preceding, split) = anchor::split_off_back(ctx, required_cols, pipeline);
+        cancellation::check(cancellation)?;
+
+        let (preceding, split) = anchor::split_off_back(
+            ctx,
+            required_cols,
+            pipeline,
+            supports_qualify,
+            sample_ends_select,
+        );
 
         if let Some((preceding, cols_at_split)) = preceding {
             log::debug!(
@@ -408,6 +2055,7 @@ fn split_into_atomics(
         atomics.push(AtomicQuery {
             name: first_name.clone(),
             relation: RelationKind::Pipeline(first.0),
+            step: None,
         });
 
         let mut prev_name = first_name;
@@ -418,6 +2066,7 @@ fn split_into_atomics(
             atomics.push(AtomicQuery {
                 name: name.clone(),
                 relation: RelationKind::Pipeline(pipeline),
+                step: None,
             });
 
             prev_name = name;
@@ -428,9 +2077,10 @@ fn split_into_atomics(
     atomics.push(AtomicQuery {
         name,
         relation: RelationKind::Pipeline(last_pipeline),
+        step: None,
     });
 
-    atomics
+    Ok(atomics)
 }
 
 fn ensure_names(atomics: &[AtomicQuery], ctx: &mut AnchorContext) {
@@ -518,7 +2168,37 @@ pub fn translate_wildcards(ctx: &AnchorContext, cols: Vec<CId>) -> Vec<CId> {
     output
 }
 
+fn is_aggregation(cid: &CId, context: &Context) -> bool {
+    let decl = context.anchor.column_decls.get(cid);
+    matches!(decl, Some(ColumnDecl::Compute(compute)) if compute.is_aggregation)
+}
+
+/// `every` and `any` that are emulated with a comparison are repeated in
+/// HAVING, because an alias of a comparison may not be accepted as a condition.
+pub(super) fn is_emulated_bool_aggregate(cid: &CId, context: &Context) -> bool {
+    let Some(ColumnDecl::Compute(compute)) = context.anchor.column_decls.get(cid) else {
+        return false;
+    };
+    let is_bool_aggregate = matches!(
+        &compute.expr.kind,
+        ExprKind::BuiltInFunction { name, .. } if name == "std.every" || name == "std.any"
+    );
+    is_bool_aggregate && !context.target.supports_bool_aggregates()
+}
+
 fn filter_of_conditions(exprs: Vec<Expr>, context: &mut Context) -> Result<Option<sql_ast::Expr>> {
+    // conditions that were generated by the compiler have no span, so only
+    // merges of `filter` transforms of the query are reported
+    let spans = exprs.iter().filter_map(|e| e.span).collect_vec();
+    if spans.len() > 1 {
+        let span = spans.iter().cloned().reduce(|a, b| a + b);
+        context.notes.optimizations.push(OptimizationNote::new(
+            "translator",
+            span,
+            format!("merged {} filters into one condition", spans.len()),
+        ));
+    }
+
     Ok(if let Some(cond) = all(exprs) {
         Some(translate_expr_kind(cond.kind, context)?)
     } else {
@@ -556,6 +2236,10 @@ mod test {
             anchor,
             omit_ident_prefix: false,
             pre_projection: false,
+            aliased: HashSet::new(),
+            notes: Notes::default(),
+            materialization: HashMap::new(),
+            named_windows: Vec::new(),
         };
 
         let pipeline = query.relation.kind.into_pipeline().unwrap();
@@ -575,7 +2259,15 @@ mod test {
         "###;
 
         let (pipeline, mut context) = parse_and_resolve(prql).unwrap();
-        let queries = split_into_atomics("".to_string(), pipeline, &mut context.anchor);
+        let queries = split_into_atomics(
+            "".to_string(),
+            pipeline,
+            &mut context.anchor,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
         assert_eq!(queries.len(), 1);
 
         // One aggregate, but take at the top
@@ -588,7 +2280,15 @@ mod test {
         "###;
 
         let (pipeline, mut context) = parse_and_resolve(prql).unwrap();
-        let queries = split_into_atomics("".to_string(), pipeline, &mut context.anchor);
+        let queries = split_into_atomics(
+            "".to_string(),
+            pipeline,
+            &mut context.anchor,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
         assert_eq!(queries.len(), 2);
 
         // A take, then two aggregates
@@ -602,7 +2302,15 @@ mod test {
         "###;
 
         let (pipeline, mut context) = parse_and_resolve(prql).unwrap();
-        let queries = split_into_atomics("".to_string(), pipeline, &mut context.anchor);
+        let queries = split_into_atomics(
+            "".to_string(),
+            pipeline,
+            &mut context.anchor,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
         assert_eq!(queries.len(), 3);
 
         // A take, then a select
@@ -613,7 +2321,15 @@ mod test {
         "###;
 
         let (pipeline, mut context) = parse_and_resolve(prql).unwrap();
-        let queries = split_into_atomics("".to_string(), pipeline, &mut context.anchor);
+        let queries = split_into_atomics(
+            "".to_string(),
+            pipeline,
+            &mut context.anchor,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
         assert_eq!(queries.len(), 1);
     }
 
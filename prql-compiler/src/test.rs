@@ -7,6 +7,18 @@ pub fn compile(prql: &str) -> Result<String, crate::ErrorMessages> {
     crate::compile(prql, sql::Options::default().no_signature().some())
 }
 
+/// Compiles to SQL on a single line, which keeps snapshots short.
+pub fn compile_one_line(prql: &str) -> Result<String, crate::ErrorMessages> {
+    let options = sql::Options::default().no_signature().no_format();
+    crate::compile(prql, options.some())
+}
+
+/// Compiles to SQL on a single line, for the target.
+pub fn compile_for(target: sql::Target, prql: &str) -> Result<String, crate::ErrorMessages> {
+    let options = (sql::Options::default().no_signature().no_format()).with_target(target);
+    crate::compile(prql, options.some())
+}
+
 #[test]
 fn test_stdlib() {
     assert_snapshot!(compile(r###"
@@ -160,10 +172,19 @@ fn test_pipelines() {
     from employees
     group dept (take 1)
     "###).unwrap()), @r###"
+    WITH table_1 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER (PARTITION BY dept) AS _expr_0
+      FROM
+        employees
+    )
     SELECT
-      DISTINCT *
+      *
     FROM
-      employees
+      table_1
+    WHERE
+      _expr_0 <= 1
     "###);
 
     assert_display_snapshot!((compile(r###"
@@ -346,7 +367,7 @@ derive `from` = 5
       5 AS "from"
     FROM
       "UPPER"
-      JOIN some_schema.tablename ON "UPPER".id = some_schema.tablename.id
+      JOIN some_schema.tablename USING(id)
     "###);
 
     // GH-#852
@@ -362,7 +383,7 @@ join c = `db.schema.t-able` [`db.schema.table`.id == c.id]
       c.*
     FROM
       `db.schema.table`
-      JOIN `db.schema.table2` ON `db.schema.table`.id = `db.schema.table2`.id
+      JOIN `db.schema.table2` USING(id)
       JOIN `db.schema.t-able` AS c ON `db.schema.table`.id = c.id
     "###);
 
@@ -574,7 +595,7 @@ fn test_window_functions_02() {
         SUM(ol.price) AS total_price
       FROM
         cust_order AS co
-        JOIN order_line AS ol ON co.order_id = ol.order_id
+        JOIN order_line AS ol USING(order_id)
       GROUP BY
         TO_CHAR(co.order_date, '%Y-%m'),
         TO_CHAR(co.order_date, '%Y-%m-%d')
@@ -593,8 +614,7 @@ fn test_window_functions_02() {
       ) AS running_total_num_books,
       LAG(num_books, 7) OVER (
         ORDER BY
-          order_day ROWS BETWEEN UNBOUNDED PRECEDING
-          AND UNBOUNDED FOLLOWING
+          order_day
       ) AS num_books_last_week
     FROM
       table_1
@@ -658,8 +678,7 @@ fn test_window_functions_05() {
       RANK() OVER (
         PARTITION BY month
         ORDER BY
-          num_orders ROWS BETWEEN UNBOUNDED PRECEDING
-          AND CURRENT ROW
+          num_orders
       ),
       LAG(num_orders, 7) OVER () AS num_orders_last_week
     FROM
@@ -764,8 +783,47 @@ fn test_window_functions_10() {
     ORDER BY
       day
     "###);
+}
+
+#[test]
+fn test_window_functions_11() {
+    assert_display_snapshot!((compile(r###"
+    from employees
+    group department (
+        sort hired_at
+        window rows:-6..0 (
+            derive [rolling_salary = average salary]
+        )
+    )
+    "###).unwrap()), @r###"
+    SELECT
+      *,
+      AVG(salary) OVER (
+        PARTITION BY department
+        ORDER BY
+          hired_at ROWS BETWEEN 6 PRECEDING
+          AND CURRENT ROW
+      ) AS rolling_salary
+    FROM
+      employees
+    "###);
 
-    // TODO: add test for preceding
+    // an empty range is the current row, not a missing frame
+    assert_display_snapshot!((compile(r###"
+    from foo
+    window rows:0..0 (
+        derive [only_current = sum b]
+    )
+    "###).unwrap()), @r###"
+    SELECT
+      *,
+      SUM(b) OVER (
+        ROWS BETWEEN CURRENT ROW
+        AND CURRENT ROW
+      ) AS only_current
+    FROM
+      foo
+    "###);
 }
 
 #[test]
@@ -1098,16 +1156,24 @@ fn test_distinct() {
       employees
     "###);
 
-    // TODO: this should not use DISTINCT but ROW_NUMBER and WHERE, because we want
-    // row  distinct only over first_name and last_name.
+    // distinct only over first_name and last_name
     assert_display_snapshot!((compile(r###"
     from employees
     group [first_name, last_name] (take 1)
     "###).unwrap()), @r###"
+    WITH table_1 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER (PARTITION BY first_name, last_name) AS _expr_0
+      FROM
+        employees
+    )
     SELECT
-      DISTINCT *
+      *
     FROM
-      employees
+      table_1
+    WHERE
+      _expr_0 <= 1
     "###);
 
     // head
@@ -1179,7 +1245,7 @@ fn test_join() {
       y.*
     FROM
       x
-      JOIN y ON x.id = y.id
+      JOIN y USING(id)
     "###);
 
     compile("from x | join y [==x.id]").unwrap_err();
@@ -1407,7 +1473,7 @@ fn test_prql_to_sql_table() {
       average_salaries.average_country_salary
     FROM
       newest_employees
-      JOIN average_salaries ON newest_employees.country = average_salaries.country
+      JOIN average_salaries USING(country)
     "###
     );
 }
@@ -1535,7 +1601,7 @@ fn test_nonatomic_table() {
       b.average_country_salary
     FROM
       a
-      JOIN b ON a.country = b.country
+      JOIN b USING(country)
     "###);
 }
 
@@ -1557,7 +1623,7 @@ fn test_table_names_between_splits() {
         d.name
       FROM
         employees
-        JOIN department AS d ON employees.dept_no = d.dept_no
+        JOIN department AS d USING(dept_no)
       LIMIT
         10
     )
@@ -1567,7 +1633,7 @@ fn test_table_names_between_splits() {
       s.salary
     FROM
       table_1
-      JOIN salaries AS s ON table_1.emp_no = s.emp_no
+      JOIN salaries AS s USING(emp_no)
     "###);
 
     let prql = r###"
@@ -1591,7 +1657,7 @@ fn test_table_names_between_splits() {
       salaries.salary
     FROM
       table_1
-      JOIN salaries ON table_1.emp_no = salaries.emp_no
+      JOIN salaries USING(emp_no)
     "###);
 }
 
@@ -1651,10 +1717,10 @@ fn test_targets() {
 
     assert_display_snapshot!((compile(query).unwrap()), @r###"
     SELECT
-      TOP (3) "FirstName",
-      "last name"
+      TOP (3) [FirstName],
+      [last name]
     FROM
-      "Employees"
+      [Employees]
     "###);
 
     // MySQL
@@ -1896,7 +1962,7 @@ fn test_inline_tables() {
     join s = (from salaries | select [emp_id, salary]) [==emp_id]
     "###).unwrap(),
         @r###"
-    WITH table_1 AS (
+    WITH table_0 AS (
       SELECT
         emp_id,
         salary
@@ -1909,11 +1975,11 @@ fn test_inline_tables() {
       employees.surname,
       employees.type,
       employees.amount,
-      table_0.emp_id,
-      table_0.salary
+      s.emp_id,
+      s.salary
     FROM
       employees
-      JOIN table_1 AS table_0 ON employees.emp_id = table_0.emp_id
+      JOIN table_0 AS s USING(emp_id)
     "###
     );
 }
@@ -2041,7 +2107,7 @@ fn test_table_s_string() {
       table_1.*
     FROM
       table_2 AS table_0
-      JOIN table_3 AS table_1 ON table_0.id = table_1.id
+      JOIN table_3 AS table_1 USING(id)
     "###
     );
 
@@ -2202,7 +2268,7 @@ fn test_group_all() {
       SUM(salaries.salary) AS sal
     FROM
       table_1
-      JOIN salaries ON table_1.emp_no = salaries.emp_no
+      JOIN salaries USING(emp_no)
     GROUP BY
       table_1.*
     "###
@@ -2272,7 +2338,6 @@ fn test_switch() {
       CASE
         WHEN nickname IS NOT NULL THEN nickname
         WHEN first_name IS NOT NULL THEN CONCAT(first_name, ' ', last_name)
-        ELSE NULL
       END AS display_name
     FROM
       employees
@@ -2402,6 +2467,8 @@ fn test_errors() {
      4 │     select b
        ·            ┬
        ·            ╰── Unknown name b
+       ·
+       · Help: the relation after `select` does not contain `b`; use it before the `select`, or include it in the `select`
     ───╯
     "###);
 
@@ -2437,3 +2504,4266 @@ fn test_hint_missing_args() {
     ───╯
     "###)
 }
+
+#[test]
+fn test_group_by_constant() {
+    // constant keys are not included in GROUP BY
+    assert_display_snapshot!((compile(r###"
+    from employees
+    derive segment = "retail"
+    group [segment, dept] (
+        aggregate [n = count]
+    )
+    "###).unwrap()), @r###"
+    SELECT
+      'retail' AS segment,
+      dept,
+      COUNT(*) AS n
+    FROM
+      employees
+    GROUP BY
+      dept
+    "###);
+
+    // when all keys are constant, this is just a plain aggregate
+    assert_display_snapshot!((compile(r###"
+    from employees
+    derive segment = "retail"
+    group segment (
+        aggregate [n = count]
+    )
+    "###).unwrap()), @r###"
+    SELECT
+      'retail' AS segment,
+      COUNT(*) AS n
+    FROM
+      employees
+    "###);
+}
+
+#[test]
+fn test_join_relation_literal() {
+    assert_display_snapshot!((compile(r###"
+    from employees
+    join side:left ([[code, label], ["a", "Active"], ["b", "Blocked"]]) [status == code]
+    select [id, status_label = label ?? "Unknown"]
+    "###).unwrap()), @r###"
+    WITH table_1 AS (
+      SELECT
+        'a' AS code,
+        'Active' AS label
+      UNION
+      ALL
+      SELECT
+        'b' AS code,
+        'Blocked' AS label
+    )
+    SELECT
+      employees.id,
+      COALESCE(table_0.label, 'Unknown') AS status_label
+    FROM
+      employees
+      LEFT JOIN table_1 AS table_0 ON employees.status = table_0.code
+    "###);
+
+    assert_display_snapshot!(compile(r###"
+    from employees
+    join side:left ([[code, label], ["a"]]) [status == code]
+    "###).unwrap_err(), @r###"
+    Error:
+       ╭─[:3:37]
+       │
+     3 │     join side:left ([[code, label], ["a"]]) [status == code]
+       ·                                     ──┬──
+       ·                                       ╰──── relation literal expected 2 values, but found 1
+    ───╯
+    "###);
+}
+
+#[test]
+fn test_sql_options_limits() {
+    // each `take` followed by a `filter` requires a split
+    let query = r###"
+    from employees
+    take 10
+    filter salary > 0
+    take 5
+    filter age > 0
+    "###;
+
+    let options = sql::Options::default()
+        .no_signature()
+        .with_max_ctes(1)
+        .some();
+    let err = crate::compile(query, options).unwrap_err().to_string();
+    assert!(err.contains("query compiles to 2 CTEs"));
+    assert!(err.contains("`max_ctes` of 1"));
+
+    let options = sql::Options::default()
+        .no_signature()
+        .with_max_ctes(2)
+        .some();
+    assert!(crate::compile(query, options).is_ok());
+
+    // CTEs that are inlined into the wrapped query don't count
+    let options = sql::Options::default()
+        .no_signature()
+        .with_wrap_as("t")
+        .with_max_ctes(0)
+        .some();
+    assert!(crate::compile(query, options).is_ok());
+
+    // CTEs of a loop count too
+    let options = sql::Options::default()
+        .no_signature()
+        .with_max_ctes(1)
+        .some();
+    let err = crate::compile(
+        r###"
+    from ([[n], [1]])
+    loop (filter n < 4 | select n = n + 1)
+    "###,
+        options,
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("`max_ctes` of 1"));
+
+    let options = (sql::Options::default().no_signature().no_format())
+        .with_max_sql_bytes(10)
+        .some();
+    let err = crate::compile("from x", options).unwrap_err().to_string();
+    assert!(err.contains("query compiles to 15 bytes of SQL"));
+    assert!(err.contains("`max_sql_bytes` of 10"));
+}
+
+#[test]
+fn test_filter_after_group_take() {
+    // filter must be applied after the rows of each group are limited
+    assert_display_snapshot!((compile(r###"
+    from employees
+    group department (sort salary | take 3)
+    filter score > 10
+    "###).unwrap()), @r###"
+    WITH table_1 AS (
+      SELECT
+        *,
+        ROW_NUMBER() OVER (
+          PARTITION BY department
+          ORDER BY
+            salary
+        ) AS _expr_0
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_1
+    WHERE
+      _expr_0 <= 3
+      AND score > 10
+    "###);
+}
+
+#[test]
+fn test_date_parts() {
+    let query = r###"
+    from orders
+    derive [q = (quarter order_date), w = (iso_week order_date)]
+    select [q, w]
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT EXTRACT(QUARTER FROM order_date) AS q, EXTRACT(WEEK FROM order_date) AS w FROM orders"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Snowflake, query).unwrap(),
+        @"SELECT EXTRACT(QUARTER FROM order_date) AS q, WEEKISO(order_date) AS w FROM orders"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::BigQuery, query).unwrap(),
+        @"SELECT EXTRACT(QUARTER FROM order_date) AS q, EXTRACT(ISOWEEK FROM order_date) AS w FROM orders"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, query).unwrap(),
+        @"SELECT DATEPART(quarter, order_date) AS q, DATEPART(iso_week, order_date) AS w FROM orders"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, query).unwrap(),
+        @"SELECT QUARTER(order_date) AS q, WEEK(order_date, 3) AS w FROM orders"
+    );
+
+    // EXTRACT(WEEK) is not an ISO week on every engine
+    let err = compile_for(sql::Target::Generic, query).unwrap_err();
+    assert_eq!(
+        err.inner[0].reason,
+        "`iso_week` is not supported by sql.generic"
+    );
+}
+
+#[test]
+fn test_select_wildcard_position() {
+    // columns of t are known, so `*` is expanded to the remaining columns
+    assert_display_snapshot!((compile(r###"
+    table t = (
+        from employees
+        select [created_at, name, id, salary]
+    )
+    from t
+    select [id, *, created_at]
+    "###).unwrap()), @r###"
+    WITH t AS (
+      SELECT
+        created_at,
+        name,
+        id,
+        salary
+      FROM
+        employees
+    )
+    SELECT
+      id,
+      name,
+      salary,
+      created_at
+    FROM
+      t
+    "###);
+
+    // columns of employees are not known, so `*` is passed through
+    assert_display_snapshot!((compile(r###"
+    from employees
+    select [id, *, created_at]
+    "###).unwrap()), @r###"
+    SELECT
+      id,
+      *,
+      created_at
+    FROM
+      employees
+    "###);
+
+    let err = compile(
+        r###"
+    table t = (
+        from employees
+        select [id, name]
+    )
+    from t
+    select [salary, *]
+    "###,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Unknown name salary"));
+
+    // `a.id` is selected explicitly, but `b.id` is not
+    assert_display_snapshot!((compile(r###"
+    table a = (from x | select [id, v])
+    table b = (from y | select [id, w])
+    from a
+    join b [a.id == b.id]
+    select [a.id, *]
+    "###).unwrap()), @r###"
+    WITH a AS (
+      SELECT
+        id,
+        v
+      FROM
+        x
+    ),
+    b AS (
+      SELECT
+        id,
+        w
+      FROM
+        y
+    )
+    SELECT
+      a.id,
+      a.v,
+      b.id,
+      b.w
+    FROM
+      a
+      JOIN b ON a.id = b.id
+    "###);
+
+    // `*` of a table with unknown columns may repeat the explicit ones
+    let (_, notes) =
+        crate::compile_with_notes("from employees | select [id, *, created_at]", None).unwrap();
+    assert_eq!(notes.warnings.len(), 1);
+    let warning = &notes.warnings[0];
+    assert_eq!(warning.construct, "select");
+    assert_eq!(
+        warning.message,
+        "columns of `employees` are not known, so `*` may repeat columns `id`, `created_at`"
+    );
+
+    let err = compile(
+        r###"
+    from employees
+    select [*, id, *]
+    "###,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("`*` can be used only once"));
+}
+
+#[test]
+fn test_sql_transform() {
+    use sql::sql_ast::{Expr, Ident, Query, SelectItem, SetExpr, TableFactor};
+
+    fn is_wildcard(item: &SelectItem) -> bool {
+        match item {
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(..) => true,
+            SelectItem::UnnamedExpr(Expr::Identifier(ident)) => ident.value == "*",
+            SelectItem::UnnamedExpr(Expr::CompoundIdentifier(parts)) => {
+                parts.last().map_or(false, |p| p.value == "*")
+            }
+            _ => false,
+        }
+    }
+
+    // a hook that rejects wildcard projections
+    let options = sql::Options::default()
+        .no_signature()
+        .with_sql_transform(|query: Query| {
+            if let SetExpr::Select(select) = query.body.as_ref() {
+                if select.projection.iter().any(is_wildcard) {
+                    anyhow::bail!("SELECT * is not allowed");
+                }
+            }
+            Ok(query)
+        });
+
+    let err = crate::compile("from employees", options.clone().some()).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("`sql_transform` hook failed: SELECT * is not allowed"));
+    assert!(crate::compile("from employees | select [id]", options.some()).is_ok());
+
+    // a hook that prefixes all tables with a schema
+    let options = sql::Options::default()
+        .no_signature()
+        .with_sql_transform(|mut query: Query| {
+            if let SetExpr::Select(select) = query.body.as_mut() {
+                for table in &mut select.from {
+                    let joins = table.joins.iter_mut().map(|j| &mut j.relation);
+
+                    for factor in std::iter::once(&mut table.relation).chain(joins) {
+                        if let TableFactor::Table { name, .. } = factor {
+                            name.0.insert(0, Ident::new("analytics"));
+                        }
+                    }
+                }
+            }
+            Ok(query)
+        });
+
+    assert_display_snapshot!(crate::compile(r###"
+    from employees
+    join departments [==dept_id]
+    select [employees.name, departments.title]
+    "###, options.some()).unwrap(), @r###"
+    SELECT
+      employees.name,
+      departments.title
+    FROM
+      analytics.employees
+      JOIN analytics.departments USING(dept_id)
+    "###);
+}
+
+#[test]
+fn test_optimization_notes() {
+    let prql = r###"
+    from employees
+    select [id, name]
+    select [id, name]
+    filter id > 1
+    filter name == "x"
+    "###;
+    let options = sql::Options::default().no_signature().some();
+    let (sql, notes) = crate::compile_with_notes(prql, options).unwrap();
+
+    assert_display_snapshot!(sql, @r###"
+    SELECT
+      id,
+      name
+    FROM
+      employees
+    WHERE
+      id > 1
+      AND name = 'x'
+    "###);
+
+    // the second select is the redundant one
+    let redundant_select = prql.rfind("select [id, name]").unwrap();
+    let notes = notes.optimizations;
+    let span = notes[0].span.unwrap();
+    assert_eq!(notes[0].pass, "lowering");
+    assert_eq!(span.start, redundant_select);
+    assert_eq!(&prql[span.start..span.end], "select [id, name]");
+
+    assert_eq!(
+        notes[0].description,
+        "select has the same columns as the preceding select, so it has no effect"
+    );
+
+    assert_eq!(notes[1].pass, "translator");
+    assert_eq!(notes[1].description, "merged 2 filters into one condition");
+    assert_eq!(notes.len(), 2);
+
+    // neither a filter with several conditions nor the conditions that are
+    // generated by the compiler are merged filters
+    let (_, notes) = crate::compile_with_notes(
+        r###"
+    from employees
+    group department (sort age | take 1)
+    filter (age > 30 and name != "x")
+    "###,
+        None,
+    )
+    .unwrap();
+    assert!(notes.optimizations.is_empty());
+}
+
+#[test]
+fn test_target_header() {
+    // target names are case-insensitive and have aliases
+    assert_display_snapshot!((compile(r###"
+    prql target:sql.SQLServer
+    from Employees
+    select [FirstName]
+    take 3
+    "###).unwrap()), @r###"
+    SELECT
+      TOP (3) [FirstName]
+    FROM
+      [Employees]
+    "###);
+
+    // no header means generic target
+    let query = r###"
+    from Employees
+    select [FirstName]
+    take 3
+    "###;
+    let generic = format!("prql target:sql.generic\n{query}");
+    assert_eq!(compile(query).unwrap(), compile(&generic).unwrap());
+
+    // unknown target is an error that points to the header
+    let query = r###"
+    prql target:sql.oracle
+    from Employees
+    "###;
+    let err = compile(query).unwrap_err();
+    let err = &err.inner[0];
+    assert_eq!(err.reason, "target `sql.oracle` not found");
+    assert!(err.hint.as_ref().unwrap().contains("sql.postgres"));
+
+    let span = err.span.unwrap();
+    assert!(query[span.start..span.end].contains("prql target:sql.oracle"));
+}
+
+#[test]
+fn test_average_of_integers() {
+    let query = r###"
+    from orders
+    aggregate [avg_qty = average quantity]
+    "###;
+
+    // AVG over integers would truncate on MSSQL
+    assert_display_snapshot!((compile(&format!("prql target:sql.mssql\n{query}")).unwrap()), @r###"
+    SELECT
+      AVG(CAST(quantity AS FLOAT)) AS avg_qty
+    FROM
+      orders
+    "###);
+
+    assert_display_snapshot!((compile(&format!("prql target:sql.postgres\n{query}")).unwrap()), @r###"
+    SELECT
+      AVG(quantity) AS avg_qty
+    FROM
+      orders
+    "###);
+
+    // windowed
+    assert_display_snapshot!((compile(r###"
+    prql target:sql.mssql
+    from orders
+    group customer_id (derive avg_qty = average quantity)
+    "###).unwrap()), @r###"
+    SELECT
+      *,
+      AVG(CAST(quantity AS FLOAT)) OVER (PARTITION BY customer_id) AS avg_qty
+    FROM
+      orders
+    "###);
+
+    // DECIMAL and FLOAT values are not truncated, so they are kept as they are
+    let query = r###"
+    prql target:sql.mssql
+    from orders
+    derive price = (amount | as decimal)
+    group customer_id (derive avg_price = average price)
+    "###;
+    assert_display_snapshot!((compile(query).unwrap()), @r###"
+    SELECT
+      *,
+      CAST(amount AS decimal) AS price,
+      AVG(CAST(amount AS decimal)) OVER (PARTITION BY customer_id) AS avg_price
+    FROM
+      orders
+    "###);
+    let (_, notes) = crate::compile_with_notes(query, None).unwrap();
+    assert!(notes.warnings.iter().all(|w| w.construct != "average"));
+}
+
+#[test]
+fn test_error_codes() {
+    use crate::ErrorCode;
+
+    let code = |prql: &str| compile(prql).unwrap_err().inner[0].code;
+
+    assert_eq!(code("from x | select ["), ErrorCode::Parse);
+    assert_eq!(code("from x | derive y = foo 1"), ErrorCode::UnknownName);
+    assert_eq!(
+        code("prql target:sql.oracle\nfrom x"),
+        ErrorCode::UnknownName
+    );
+    assert_eq!(code("from x | select [a, *, *]"), ErrorCode::Invalid);
+    assert_eq!(code("join y [==id]"), ErrorCode::Invalid);
+
+    // codes are stable strings in JSON output
+    let err = compile("from x | derive y = foo 1")
+        .unwrap_err()
+        .inner
+        .remove(0);
+    let json: serde_json::Value = serde_json::from_str(&err.to_json()).unwrap();
+    assert_eq!(json["code"], "E0002");
+    assert!(json["message"].as_str().unwrap().contains("foo"));
+    assert!(json["span"].is_object());
+}
+
+#[test]
+fn test_relation_functions() {
+    assert_display_snapshot!((compile(r###"
+    func only_active rel -> (rel | filter deleted == false)
+
+    from users
+    only_active
+    select [id, name]
+    "###).unwrap()), @r###"
+    SELECT
+      id,
+      name
+    FROM
+      users
+    WHERE
+      deleted = false
+    "###);
+
+    // the relation param receives the piped value, regardless of its position
+    assert_display_snapshot!((compile(r###"
+    func top_n rel n -> (rel | sort [-score] | take n)
+
+    from players
+    top_n 3
+    "###).unwrap()), @r###"
+    SELECT
+      *
+    FROM
+      players
+    ORDER BY
+      score DESC
+    LIMIT
+      3
+    "###);
+
+    // ... while a function that is not applied to a relation stays scalar
+    assert_display_snapshot!((compile(r###"
+    func rounded x -> (x | round 2)
+
+    from employees
+    derive [r = (rounded salary), s = (salary | rounded)]
+    select [r, s]
+    "###).unwrap()), @r###"
+    SELECT
+      ROUND(salary, 2) AS r,
+      ROUND(salary, 2) AS s
+    FROM
+      employees
+    "###);
+
+    // functions that take a relation cannot be used in expression position
+    let err = compile(
+        r###"
+    func only_active rel<table> -> (rel | filter deleted == false)
+
+    from users
+    derive x = only_active
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.inner[0].reason,
+        "function `only_active` returns a relation and cannot be used as an expression"
+    );
+
+    // body of a function that takes a relation must be a pipeline
+    let err = compile(
+        r###"
+    func bad rel<table> -> 1
+
+    from users
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.inner[0].reason,
+        "function `bad` takes a relation, but its body is not a pipeline"
+    );
+    assert_eq!(err.inner[0].code, ErrorCode::Parse);
+}
+
+#[test]
+fn test_emulation_warnings() {
+    use itertools::Itertools;
+
+    let query = r###"
+    from employees
+    derive full_name = f"{first_name} {last_name}"
+    group full_name (
+        aggregate [avg_salary = average salary, avg_age = average age]
+    )
+    "###;
+    let notes_for = |target: sql::Target| {
+        let options = sql::Options::default().with_target(target).some();
+        crate::compile_with_notes(query, options).unwrap().1
+    };
+
+    // one warning per construct, even if it is used twice
+    let warnings = notes_for(sql::Target::MsSql).warnings;
+    let constructs: Vec<_> = warnings
+        .iter()
+        .map(|w| w.construct.as_str())
+        .sorted()
+        .collect();
+    assert_eq!(constructs, vec!["average", "f-string"]);
+    assert!(warnings
+        .iter()
+        .all(|w| w.target == Some(sql::Target::MsSql)));
+
+    let average = warnings.iter().find(|w| w.construct == "average").unwrap();
+    assert_eq!(
+        average.to_string(),
+        "warning: average on sql.mssql: the argument is cast to FLOAT, so precision of large or DECIMAL values may be lost"
+    );
+
+    // MySQL uses the native forms of both
+    assert!(notes_for(sql::Target::MySql).warnings.is_empty());
+}
+
+#[test]
+fn test_expression_pipelines() {
+    assert_display_snapshot!((compile(r###"
+    from employees
+    derive [
+        x = (salary | round 2),
+        y = (salary + bonus | as float | round 2),
+    ]
+    select [x, y]
+    "###).unwrap()), @r###"
+    SELECT
+      ROUND(salary, 2) AS x,
+      ROUND(CAST(salary + bonus AS float), 2) AS y
+    FROM
+      employees
+    "###);
+
+    assert_display_snapshot!((compile(r###"
+    from employees
+    group department (
+        aggregate [total = (salary | round 0 | sum)]
+    )
+    "###).unwrap()), @r###"
+    SELECT
+      department,
+      SUM(ROUND(salary, 0)) AS total
+    FROM
+      employees
+    GROUP BY
+      department
+    "###);
+
+    let err = compile(
+        r###"
+    from employees
+    derive x = (salary | take 3)
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.inner[0].reason,
+        "transform `take` cannot be applied to a column"
+    );
+}
+
+#[test]
+fn test_compile_split() {
+    let prql = r###"
+    table newest = (
+        from employees
+        sort [-hired_at]
+        take 100
+    )
+
+    from newest
+    group [title, country] (
+        aggregate [avg_salary = average salary]
+    )
+    group [country] (
+        aggregate [max_avg_salary = max avg_salary]
+    )
+    "###;
+    let options = sql::Options::default().no_format().no_signature();
+
+    let parts = crate::compile_split(prql, options.clone().some()).unwrap();
+    assert_eq!(parts.len(), 3);
+    assert_eq!(parts[0].0, "newest");
+
+    // wrapped back into CTEs, the parts make up the whole query
+    let (main, ctes) = parts.split_last().unwrap();
+    let ctes = ctes.iter().map(|(name, sql)| format!("{name} AS ({sql})"));
+    let combined = format!("WITH {} {}", ctes.collect::<Vec<_>>().join(", "), main.1);
+
+    assert_eq!(combined, crate::compile(prql, options.some()).unwrap());
+}
+
+#[test]
+fn test_sort_in_table_decl() {
+    let warnings = |main: &str| {
+        let prql = format!(
+            r#"
+        table top_earners = (
+            from employees
+            sort [-salary]
+        )
+
+        from top_earners
+        {main}
+        "#
+        );
+        let options = sql::Options::default().some();
+        let (_, notes) = crate::compile_with_notes(&prql, options).unwrap();
+        notes.warnings
+    };
+
+    // the sort would be silently lost
+    let taken = warnings("take 10");
+    assert_eq!(taken.len(), 1);
+    assert_eq!(taken[0].construct, "sort");
+    assert!(taken[0].message.contains("`from top_earners`"));
+
+    // re-sorted
+    assert!(warnings("sort [-salary]\ntake 10").is_empty());
+
+    // order does not matter for aggregation
+    assert!(warnings("aggregate [total = sum salary]").is_empty());
+}
+
+#[test]
+fn test_interpolation_escapes() {
+    let options = sql::Options::default().no_format().no_signature().some();
+    let sql = crate::compile(
+        r###"
+    from employees
+    derive [
+        j = s"data->'{{}}'",
+        k = f"{{{first_name}}}",
+        u = s"UPPER({f'{first_name}-{last_name}'})",
+    ]
+    select [j, k, u]
+    "###,
+        options,
+    )
+    .unwrap();
+    assert_eq!(
+        sql,
+        "SELECT data->'{}' AS j, CONCAT('{', first_name, '}') AS k, UPPER(CONCAT(first_name, '-', last_name)) AS u FROM employees"
+    );
+
+    // jinja passes through
+    assert_display_snapshot!((compile(r###"
+    from employees
+    select [x = s"{{{{ var('x') }}}}"]
+    "###).unwrap()), @r###"
+    SELECT
+      {{ var('x') }} AS x
+    FROM
+      employees
+    "###);
+
+    let err = compile(
+        r###"
+    from employees
+    derive y = s"foo({a)"
+    "###,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(err.reason, "unclosed `{` in interpolated string");
+    assert_eq!(err.hint.as_deref(), Some("use `{{` for a literal brace"));
+}
+
+#[test]
+fn test_interpolation_scope() {
+    // column was consumed by the select
+    let prql = r#"from employees | select [a] | derive x = s"UPPER({b})""#;
+    let err = compile(prql).unwrap_err().inner.remove(0);
+    assert_eq!(err.reason, "Unknown name b");
+    assert_eq!(err.code, crate::ErrorCode::UnknownName);
+
+    // ... and the error points inside of the braces
+    let span = err.span.unwrap();
+    let start = prql.find("{b}").unwrap() + 1;
+    assert_eq!((span.start, span.end), (start, start + 1));
+
+    let err = compile(r#"from employees | select [a] | derive x = f"{a}-{b}""#).unwrap_err();
+    assert_eq!(err.inner[0].reason, "Unknown name b");
+
+    assert_display_snapshot!(compile(r#"from employees | select [a] | derive x = s"UPPER({a})""#).unwrap(), @r###"
+    SELECT
+      a,
+      UPPER(a) AS x
+    FROM
+      employees
+    "###);
+
+    // columns of a wildcard frame are not known, so any name is accepted
+    assert_display_snapshot!(compile(r#"from employees | derive x = s"UPPER({b})""#).unwrap(), @r###"
+    SELECT
+      *,
+      UPPER(b) AS x
+    FROM
+      employees
+    "###);
+}
+
+#[test]
+fn test_null_literals() {
+    assert_display_snapshot!((compile(r###"
+    from employees
+    derive placeholder = null
+    select [id, placeholder, b = null ?? bonus]
+    "###).unwrap()), @r###"
+    SELECT
+      id,
+      NULL AS placeholder,
+      COALESCE(NULL, bonus) AS b
+    FROM
+      employees
+    "###);
+
+    // type of NULL in a relation literal is taken from other rows
+    assert_display_snapshot!((compile(r###"
+    prql target:sql.postgres
+    from employees
+    join side:left ([[code, bonus], ["a", null], ["b", 10]]) [level == code]
+    select [id, bonus]
+    "###).unwrap()), @r###"
+    WITH table_1 AS (
+      SELECT
+        'a' AS code,
+        CAST(NULL AS INTEGER) AS bonus
+      UNION
+      ALL
+      SELECT
+        'b' AS code,
+        10 AS bonus
+    )
+    SELECT
+      employees.id,
+      table_0.bonus
+    FROM
+      employees
+      LEFT JOIN table_1 AS table_0 ON employees.level = table_0.code
+    "###);
+
+    // SQLite does not need typed NULLs
+    let sql = compile(
+        r###"
+    prql target:sql.sqlite
+    from ([[a], [null], [1]])
+    "###,
+    )
+    .unwrap();
+    assert!(sql.contains("NULL AS a") && !sql.contains("CAST"));
+
+    let (_, columns) = crate::compile_and_describe("from ([[a], [null], [1]])", None).unwrap();
+    assert_eq!(columns[0].ty, crate::ColumnType::Integer);
+    assert_eq!(columns[0].nullable, Some(true));
+
+    // ... and in COALESCE, from a literal among its operands
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, "from employees | derive b = null ?? 0").unwrap(),
+        @"SELECT *, COALESCE(CAST(NULL AS INTEGER), 0) AS b FROM employees"
+    );
+
+    // ... and in `append`, from the other relation
+    let query = r###"
+    from employees
+    select [a = null, b = "x"]
+    append (from managers | select [a = 1, b = null])
+    "###;
+    let sql = compile_for(sql::Target::BigQuery, query).unwrap();
+    assert!(sql.contains("CAST(NULL AS INT64)"), "{sql}");
+    assert!(sql.contains("CAST(NULL AS STRING)"), "{sql}");
+    let sql = compile_for(sql::Target::SQLite, query).unwrap();
+    assert!(!sql.contains("CAST"), "{sql}");
+}
+
+#[test]
+fn test_group_rollup_cube() {
+    assert_display_snapshot!((compile(r###"
+    prql target:sql.postgres
+    from sales
+    group [region, product] rollup:true (
+      aggregate [revenue = sum amount]
+    )
+    "###).unwrap()), @r###"
+    SELECT
+      region,
+      product,
+      SUM(amount) AS revenue
+    FROM
+      sales
+    GROUP BY
+      ROLLUP (region, product)
+    "###);
+
+    assert_display_snapshot!((compile(r###"
+    prql target:sql.mssql
+    from sales
+    group [region, product] cube:true (
+      aggregate [revenue = sum amount, is_subtotal = grouping region]
+    )
+    "###).unwrap()), @r###"
+    SELECT
+      region,
+      product,
+      SUM(amount) AS revenue,
+      GROUPING(region) AS is_subtotal
+    FROM
+      sales
+    GROUP BY
+      CUBE (region, product)
+    "###);
+
+    let (_, columns) = crate::compile_and_describe(
+        r###"
+    from sales
+    group [region] rollup:true (aggregate [n = count, g = grouping region])
+    "###,
+        None,
+    )
+    .unwrap();
+    assert_eq!(columns[0].nullable, Some(true));
+    assert_eq!(columns[2].ty, crate::ColumnType::Integer);
+    assert_eq!(columns[2].nullable, Some(false));
+
+    let err = compile(
+        r###"
+    prql target:sql.sqlite
+    from sales
+    group region rollup:true (aggregate [n = count])
+    "###,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "`group` with `rollup` or `cube` is not supported by sql.sqlite"
+    );
+
+    let err = compile(
+        r###"
+    from sales
+    group region rollup:true (take 1)
+    "###,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "`rollup` and `cube` require the pipeline of `group` to be an `aggregate`"
+    );
+
+    assert_display_snapshot!((compile(r###"
+    prql target:sql.postgres
+    from sales
+    group [region, product] sets:[[region, product], [region], []] (
+      aggregate [revenue = sum amount]
+    )
+    "###).unwrap()), @r###"
+    SELECT
+      region,
+      product,
+      SUM(amount) AS revenue
+    FROM
+      sales
+    GROUP BY
+      GROUPING SETS ((region, product), (region), ())
+    "###);
+
+    let (_, columns) = crate::compile_and_describe(
+        r###"
+    from sales
+    group [region, product] sets:[[region, product], [region]] (aggregate [n = count])
+    "###,
+        None,
+    )
+    .unwrap();
+    // region is in all of the sets, so it is NULL only if the table has NULLs
+    assert_eq!(columns[0].nullable, None);
+    assert_eq!(columns[1].nullable, Some(true));
+
+    let err = compile(
+        r###"
+    from sales
+    group [region] sets:[[region], [product]] (aggregate [n = count])
+    "###,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "grouping set column `product` is not a key of `group`"
+    );
+
+    let err = compile(
+        r###"
+    from sales
+    group [region] rollup:true sets:[[region]] (aggregate [n = count])
+    "###,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "`sets` cannot be used together with `rollup` or `cube`"
+    );
+
+    let err = compile(
+        r###"
+    prql target:sql.sqlite
+    from sales
+    group region sets:[[region], []] (aggregate [n = count])
+    "###,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "`group` with `sets` is not supported by sql.sqlite"
+    );
+}
+
+#[test]
+fn test_rq_hash() {
+    let hash = |prql: &str| crate::compile_to_intermediate(prql, None).unwrap().0.hash();
+
+    let prql = r###"
+    from employees
+    filter salary > 1000
+    derive gross = salary + bonus
+    select [id, gross]
+    "###;
+    assert_eq!(hash(prql), hash(prql));
+
+    let reformatted = r###"
+from employees   # all of them
+filter salary>1000
+derive gross=salary+bonus
+select [id,gross]
+    "###;
+    assert_eq!(hash(prql), hash(reformatted));
+
+    let changed = r###"
+    from employees
+    filter salary > 2000
+    derive gross = salary + bonus
+    select [id, gross]
+    "###;
+    assert_ne!(hash(prql), hash(changed));
+
+    // SQL is returned along with RQ
+    let (_, sql) = crate::compile_to_intermediate(prql, None).unwrap();
+    assert_eq!(sql, crate::compile(prql, None).unwrap());
+}
+
+#[test]
+fn test_ranking_ambient_sort() {
+    // row numbers follow the sort of the pipeline
+    assert_display_snapshot!((compile(r###"
+    from employees
+    sort [-salary]
+    derive rn = row_number
+    "###).unwrap()), @r###"
+    SELECT
+      *,
+      ROW_NUMBER() OVER (
+        ORDER BY
+          salary DESC
+      ) AS rn
+    FROM
+      employees
+    ORDER BY
+      salary DESC
+    "###);
+
+    // without a sort, the numbering is arbitrary
+    let (sql, notes) = crate::compile_with_notes(
+        r###"
+    from employees
+    derive rn = row_number
+    "###,
+        None,
+    )
+    .unwrap();
+    assert!(sql.contains("ROW_NUMBER() OVER () AS rn"));
+    assert_eq!(notes.warnings.len(), 1);
+    assert_eq!(notes.warnings[0].construct, "row_number");
+    assert_eq!(notes.warnings[0].target, None);
+
+    // `over:()` opts out of the sort, without a warning
+    let (sql, notes) = crate::compile_with_notes(
+        r###"
+    from employees
+    sort salary
+    derive rn = (row_number over:())
+    "###,
+        None,
+    )
+    .unwrap();
+    assert!(sql.contains("ROW_NUMBER() OVER () AS rn"));
+    assert!(notes.warnings.is_empty());
+
+    let err = compile(
+        r###"
+    from employees
+    derive rn = (row_number over:false)
+    "###,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "parameter `over` expected `()`, but found false"
+    );
+}
+
+#[test]
+fn test_window_columns_across_ctes() {
+    // `gross` is only referenced in the OVER clause of a window function,
+    // which ends up in a different CTE than the derive.
+    let sql = compile(
+        r###"
+    from employees
+    derive gross = salary + bonus
+    filter bonus > 0
+    group department (sort gross | derive r = row_number)
+    select [id, r]
+    "###,
+    )
+    .unwrap();
+
+    assert_display_snapshot!(sql, @r###"
+    WITH table_1 AS (
+      SELECT
+        id,
+        department,
+        salary + bonus AS gross,
+        bonus
+      FROM
+        employees
+    )
+    SELECT
+      id,
+      ROW_NUMBER() OVER (
+        PARTITION BY department
+        ORDER BY
+          gross
+      ) AS r
+    FROM
+      table_1
+    WHERE
+      bonus > 0
+    "###);
+
+    let dialect = sqlparser::dialect::GenericDialect {};
+    assert!(sqlparser::parser::Parser::parse_sql(&dialect, &sql).is_ok());
+}
+
+#[test]
+fn test_derive_aggregate_filter() {
+    // the filter after the aggregation is HAVING, so `gross` is computed in a
+    // CTE and the filter references it by name, instead of its expression
+    let sql = compile(
+        r###"
+    from employees
+    derive gross = salary + bonus
+    group gross (aggregate [n = count])
+    filter gross > 0
+    "###,
+    )
+    .unwrap();
+
+    assert_display_snapshot!(sql, @r###"
+    WITH table_1 AS (
+      SELECT
+        salary + bonus AS gross
+      FROM
+        employees
+    )
+    SELECT
+      gross,
+      COUNT(*) AS n
+    FROM
+      table_1
+    GROUP BY
+      gross
+    HAVING
+      gross > 0
+    "###);
+
+    let dialect = sqlparser::dialect::GenericDialect {};
+    assert!(sqlparser::parser::Parser::parse_sql(&dialect, &sql).is_ok());
+}
+
+#[test]
+fn test_arg_extremes() {
+    let query = r###"
+    from orders
+    group customer_id (
+        aggregate [top_order = max_by order_id amount, first_order = min_by order_id created_at]
+    )
+    "###;
+
+    // generic targets number the rows of each group in a preceding CTE and
+    // take the value of the first one
+    let options = sql::Options::default().no_format().no_signature().some();
+    let sql = crate::compile(query, options).unwrap();
+    assert!(sql
+        .contains("ROW_NUMBER() OVER (PARTITION BY customer_id ORDER BY amount DESC NULLS LAST)"));
+    assert!(
+        sql.contains("ROW_NUMBER() OVER (PARTITION BY customer_id ORDER BY created_at NULLS LAST)")
+    );
+    assert!(sql.contains("= 1 AND amount IS NOT NULL THEN order_id END) AS top_order"));
+    assert!(sql.contains("= 1 AND created_at IS NOT NULL THEN order_id END) AS first_order"));
+    assert!(sql.contains("FROM table_1 GROUP BY customer_id"));
+
+    assert_display_snapshot!((compile(&format!("prql target:sql.clickhouse\n{query}")).unwrap()), @r###"
+    SELECT
+      customer_id,
+      argMax(order_id, amount) AS top_order,
+      argMin(order_id, created_at) AS first_order
+    FROM
+      orders
+    GROUP BY
+      customer_id
+    "###);
+
+    let sql = compile(&format!("prql target:sql.bigquery\n{query}")).unwrap();
+    assert!(sql.contains("ANY_VALUE(order_id HAVING MAX amount) AS top_order"));
+
+    assert_display_snapshot!((compile(&format!("prql target:sql.duckdb\n{query}")).unwrap()), @r###"
+    SELECT
+      customer_id,
+      MAX_BY(order_id, amount) AS top_order,
+      MIN_BY(order_id, created_at) AS first_order
+    FROM
+      orders
+    GROUP BY
+      customer_id
+    "###);
+
+    let err = compile(
+        r###"
+    from orders
+    derive top_order = max_by order_id amount
+    "###,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "`max_by` can only be used in `aggregate` on this target"
+    );
+}
+
+#[test]
+fn test_lint_unsatisfiable_filters() {
+    let warnings = |prql: &str| {
+        let options = sql::Options::default().with_lints().some();
+        crate::compile_with_notes(prql, options).unwrap().1.warnings
+    };
+
+    assert!(warnings(
+        r###"
+    from ([[id, status], [1, "a"], [2, "d"]])
+    filter status == "a"
+    "###
+    )
+    .is_empty());
+
+    let prql = r###"
+    from ([[id, status], [1, "a"], [2, "d"]])
+    filter status == "archived"
+    "###;
+    let found = warnings(prql);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].construct, "filter");
+    assert_eq!(
+        found[0].message,
+        r#"`status == "archived"` is false for all rows of the relation literal, where `status` is one of: "a", "d""#
+    );
+    let sources: Vec<_> = (found[0].spans.iter())
+        .map(|s| &prql[s.start..s.end])
+        .collect();
+    assert_eq!(
+        sources,
+        vec![
+            r#"status == "archived""#,
+            r#"[[id, status], [1, "a"], [2, "d"]]"#
+        ]
+    );
+
+    // comparisons with other columns are not evaluated
+    assert!(warnings(
+        r###"
+    from employees
+    join side:left ([[code, label], ["a", "Active"]]) [level == code]
+    filter level == "x"
+    "###
+    )
+    .is_empty());
+
+    // lints are opt-in
+    let (_, notes) = crate::compile_with_notes(prql, None).unwrap();
+    assert!(notes.warnings.is_empty());
+}
+
+#[test]
+fn test_compile_all_dialects() {
+    use sql::Target;
+
+    let options = sql::Options::default().no_signature().no_format().some();
+    let results = crate::compile_all_dialects(
+        r###"
+    from employees
+    group department (
+        sort [-salary]
+        take 2
+    )
+    "###,
+        &[Target::Snowflake, Target::Generic],
+        options.clone(),
+    )
+    .unwrap();
+    let [(snowflake, snowflake_sql), (generic, generic_sql)] = &results[..] else {
+        panic!("expected a result for each target");
+    };
+    assert_eq!(snowflake, &Target::Snowflake);
+    assert_eq!(generic, &Target::Generic);
+
+    // a filter on a window function can be expressed with QUALIFY ...
+    let snowflake_sql = snowflake_sql.as_ref().unwrap();
+    assert!(!snowflake_sql.contains("WITH"));
+    assert!(snowflake_sql
+        .contains("QUALIFY ROW_NUMBER() OVER (PARTITION BY department ORDER BY salary DESC) <= 2"));
+
+    // ... or with a CTE
+    let generic_sql = generic_sql.as_ref().unwrap();
+    assert!(generic_sql.starts_with("WITH table_1 AS"));
+    assert!(generic_sql.contains("WHERE _expr_0 <= 2"));
+    assert!(!generic_sql.contains("QUALIFY"));
+
+    // failure for one target does not affect the others
+    let results = crate::compile_all_dialects(
+        r###"
+    from employees
+    group rollup:true [department, title] (
+        aggregate [n = count]
+    )
+    "###,
+        &[Target::SQLite, Target::PostgreSql],
+        options,
+    )
+    .unwrap();
+    assert_eq!(
+        results[0].1.as_ref().unwrap_err().inner[0].reason,
+        "`group` with `rollup` or `cube` is not supported by sql.sqlite"
+    );
+    assert!(results[1].1.as_ref().unwrap().contains("ROLLUP"));
+
+    // errors in resolving are reported once
+    assert!(crate::compile_all_dialects("from x | select (", &[Target::Generic], None).is_err());
+}
+
+#[test]
+fn test_wrap_as() {
+    let compile_wrapped = |prql: &str| {
+        let options = sql::Options::default().no_format().with_wrap_as("e");
+        crate::compile(prql, options.some()).unwrap()
+    };
+
+    assert_display_snapshot!(compile_wrapped(r###"
+    from employees
+    select [first_name, salary]
+    "###),
+        @"(SELECT first_name, salary FROM employees) AS e"
+    );
+
+    // CTEs are inlined as subqueries, named the same
+    assert_display_snapshot!(compile_wrapped(r###"
+    from employees
+    derive rn = row_number
+    filter rn > 2
+    "###),
+        @"(SELECT * FROM (SELECT *, ROW_NUMBER() OVER () AS rn FROM employees) AS table_1 WHERE rn > 2) AS e"
+    );
+
+    // ... also in joins, where the alias is kept
+    assert_display_snapshot!(compile_wrapped(r###"
+    table managers = (
+        from employees
+        filter is_manager
+    )
+    from salaries
+    join m = managers [==emp_no]
+    "###),
+        @"(SELECT salaries.*, m.* FROM salaries JOIN (SELECT * FROM employees WHERE is_manager) AS m USING(emp_no)) AS e"
+    );
+
+    // ... and in subqueries of expressions
+    assert_display_snapshot!(compile_wrapped(r###"
+    table managers = (
+        from employees
+        filter is_manager
+    )
+    from employees
+    join side:semi managers [==id]
+    select [id, name]
+    "###),
+        @"(SELECT employees.id, employees.name FROM employees WHERE EXISTS (SELECT 1 FROM (SELECT * FROM employees WHERE is_manager) AS managers WHERE employees.id = managers.id)) AS e"
+    );
+
+    // the alias is quoted as other identifiers of the target
+    let compile_wrapped_as = |target, alias: &str| {
+        let options = (sql::Options::default().no_format())
+            .with_target(target)
+            .with_wrap_as(alias);
+        crate::compile("from employees", options.some()).unwrap()
+    };
+    assert_display_snapshot!(compile_wrapped_as(sql::Target::Generic, "order"),
+        @r###"(SELECT * FROM employees) AS "order""###
+    );
+    assert_display_snapshot!(compile_wrapped_as(sql::Target::MySql, "Employees"),
+        @"(SELECT * FROM employees) AS `Employees`"
+    );
+}
+
+#[test]
+fn test_count_if() {
+    let query = r###"
+    from logs
+    group service (
+        aggregate [errors = count_if status == "error"]
+    )
+    filter errors > 10
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::Generic, query).unwrap(),
+        @"SELECT service, SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) AS errors FROM logs GROUP BY service HAVING SUM(CASE WHEN status = 'error' THEN 1 ELSE 0 END) > 10"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT service, COUNT(*) FILTER (WHERE status = 'error') AS errors FROM logs GROUP BY service HAVING COUNT(*) FILTER (WHERE status = 'error') > 10"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::DuckDb, query).unwrap(),
+        @"SELECT service, COUNT(*) FILTER (WHERE status = 'error') AS errors FROM logs GROUP BY service HAVING COUNT(*) FILTER (WHERE status = 'error') > 10"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::BigQuery, query).unwrap(),
+        @"SELECT service, COUNTIF(status = 'error') AS errors FROM logs GROUP BY service HAVING COUNTIF(status = 'error') > 10"
+    );
+
+    // as a window function
+    assert_display_snapshot!((compile(r###"
+    from logs
+    group service (
+        derive service_errors = count_if status == "error"
+    )
+    "###).unwrap()), @r###"
+    SELECT
+      *,
+      SUM(
+        CASE
+          WHEN status = 'error' THEN 1
+          ELSE 0
+        END
+      ) OVER (PARTITION BY service) AS service_errors
+    FROM
+      logs
+    "###);
+}
+
+#[test]
+fn test_reorder_barriers() {
+    // a plain derive is moved in front of a take ...
+    assert_display_snapshot!((compile(r###"
+    from employees
+    take 10
+    derive gross = salary + bonus
+    "###).unwrap()), @r###"
+    SELECT
+      *,
+      salary + bonus AS gross
+    FROM
+      employees
+    LIMIT
+      10
+    "###);
+
+    // ... but a window function is not, as it would then number rows that
+    // the take removes
+    assert_display_snapshot!((compile(r###"
+    from employees
+    take 10
+    derive rn = row_number
+    "###).unwrap()), @r###"
+    WITH table_1 AS (
+      SELECT
+        *
+      FROM
+        employees
+      LIMIT
+        10
+    )
+    SELECT
+      *,
+      ROW_NUMBER() OVER () AS rn
+    FROM
+      table_1
+    "###);
+
+    // a filter is not applied before a window function, which would then
+    // number only the filtered rows
+    assert_display_snapshot!((compile(r###"
+    from employees
+    derive [gross = salary + bonus, rn = row_number]
+    filter country == "USA"
+    "###).unwrap()), @r###"
+    WITH table_1 AS (
+      SELECT
+        *,
+        salary + bonus AS gross,
+        ROW_NUMBER() OVER () AS rn
+      FROM
+        employees
+    )
+    SELECT
+      *
+    FROM
+      table_1
+    WHERE
+      country = 'USA'
+    "###);
+}
+
+#[test]
+fn test_format_functions() {
+    let query = r###"
+    from orders
+    derive [
+        pretty = format_num revenue 2,
+        ym = format_date order_date "%Y-%m",
+    ]
+    select [pretty, ym]
+    "###;
+
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT TO_CHAR(revenue, 'FM999G999G999G999G990D00') AS pretty, TO_CHAR(order_date, 'YYYY-MM') AS ym FROM orders"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, query).unwrap(),
+        @r###"SELECT FORMAT(revenue, 'N2') AS pretty, FORMAT(order_date, 'yyyy\-MM') AS ym FROM orders"###
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, query).unwrap(),
+        @"SELECT FORMAT(revenue, 2) AS pretty, DATE_FORMAT(order_date, '%Y-%m') AS ym FROM orders"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::SQLite, query).unwrap(),
+        @"SELECT printf('%,.2f', revenue) AS pretty, strftime('%Y-%m', order_date) AS ym FROM orders"
+    );
+
+    // all specifiers, with literal text between them
+    let query = r###"
+    from events
+    select [ts = format_date created "%d.%m.%Y %H:%M:%S at %%"]
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @r###"SELECT TO_CHAR(created, 'DD.MM.YYYY HH24:MI:SS "a""t" %') AS ts FROM events"###
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, query).unwrap(),
+        @r###"SELECT FORMAT(created, 'dd\.MM\.yyyy HH\:mm\:ss \a\t \%') AS ts FROM events"###
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, query).unwrap(),
+        @"SELECT DATE_FORMAT(created, '%d.%m.%Y %H:%i:%s at %%') AS ts FROM events"
+    );
+
+    let err = compile_for(
+        sql::Target::PostgreSql,
+        r###"
+    from events
+    select [d = format_date created "%j"]
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.inner[0].reason,
+        "format specifier `%j` cannot be translated to sql.postgres"
+    );
+
+    let err = compile_for(sql::Target::Generic, query).unwrap_err();
+    assert_eq!(
+        err.inner[0].reason,
+        "`format_date` is not supported by sql.generic"
+    );
+}
+
+#[test]
+fn test_group_by_joined_column() {
+    // Both relations may contain `region`, so GROUP BY must be prefixed the
+    // same way as the projection.
+    assert_display_snapshot!((compile(r###"
+    from orders
+    join customers [orders.cust_id == customers.id]
+    group [customers.region] (aggregate [n = count])
+    "###).unwrap()), @r###"
+    SELECT
+      customers.region,
+      COUNT(*) AS n
+    FROM
+      orders
+      JOIN customers ON orders.cust_id = customers.id
+    GROUP BY
+      customers.region
+    "###);
+
+    assert_display_snapshot!((compile(r###"
+    from o = orders
+    join c = customers [o.cust_id == c.id]
+    group [c.region] (aggregate [total = sum o.amount])
+    "###).unwrap()), @r###"
+    SELECT
+      c.region,
+      SUM(o.amount) AS total
+    FROM
+      orders AS o
+      JOIN customers AS c ON o.cust_id = c.id
+    GROUP BY
+      c.region
+    "###);
+}
+
+#[test]
+fn test_let_constants() {
+    let sql = compile(
+        r###"
+    let threshold = 50 * 2
+    let page = 10
+    from orders
+    filter amount > threshold + 1
+    take page
+    "###,
+    )
+    .unwrap();
+    // constant expressions are folded, also after substitution
+    assert_display_snapshot!(sql, @r###"
+    SELECT
+      *
+    FROM
+      orders
+    WHERE
+      amount > 101
+    LIMIT
+      10
+    "###);
+
+    // constants can reference other constants and be used in s-strings
+    assert_display_snapshot!((compile(r###"
+    let start = @2023-01-01
+    let since = start
+    from orders
+    select [id, recent = s"order_date >= {since}"]
+    "###).unwrap()), @r###"
+    SELECT
+      id,
+      order_date >= DATE '2023-01-01' AS recent
+    FROM
+      orders
+    "###);
+
+    let err = compile(
+        r###"
+    let threshold = amount * 2
+    from orders
+    filter amount > threshold
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.inner[0].reason,
+        "`let threshold` can only reference constants, but it references `amount`"
+    );
+    assert_eq!(
+        err.inner[0].hint.as_deref(),
+        Some("use `derive` to compute a column")
+    );
+
+    // `let` is not a keyword, so it can still name a column
+    assert_display_snapshot!((compile(r###"
+    from orders
+    select [let, amount]
+    filter let > 0
+    "###).unwrap()), @r###"
+    SELECT
+      let,
+      amount
+    FROM
+      orders
+    WHERE
+      let > 0
+    "###);
+}
+
+#[test]
+fn test_take_in_shared_table() {
+    let query = r###"
+    table sample = (
+        from events
+        take 1000
+    )
+    table errors = (
+        from sample
+        filter level == "error"
+    )
+    from sample
+    join errors [==id]
+    "###;
+
+    // one CTE with its LIMIT, referenced twice
+    assert_display_snapshot!(compile(query).unwrap(), @r###"
+    WITH sample AS (
+      SELECT
+        *
+      FROM
+        events
+      LIMIT
+        1000
+    ), errors AS (
+      SELECT
+        *
+      FROM
+        sample
+      WHERE
+        level = 'error'
+    )
+    SELECT
+      sample.*,
+      errors.*
+    FROM
+      sample
+      JOIN errors USING(id)
+    "###);
+
+    // when inlining CTEs, the limited one is kept
+    let options = sql::Options::default().no_format().with_wrap_as("w");
+    assert_display_snapshot!(crate::compile(query, options.clone().some()).unwrap(),
+        @"(WITH sample AS (SELECT * FROM events LIMIT 1000) SELECT sample.*, errors.* FROM sample JOIN (SELECT * FROM sample WHERE level = 'error') AS errors USING(id)) AS w"
+    );
+
+    // ... but a limited CTE that is referenced once is inlined
+    assert_display_snapshot!(crate::compile(r###"
+    table sample = (
+        from events
+        take 1000
+    )
+    from sample
+    filter level == "error"
+    "###, options.some()).unwrap(),
+        @"(SELECT * FROM (SELECT * FROM events LIMIT 1000) AS sample WHERE level = 'error') AS w"
+    );
+
+    let (_, notes) = crate::compile_with_notes(query, None).unwrap();
+    assert_eq!(notes.warnings.len(), 1);
+    assert_display_snapshot!(notes.warnings[0],
+        @"warning: take: `sample` is referenced 2 times, but `take` in its declaration has no sort, so the rows it selects are not deterministic; sort before `take`"
+    );
+
+    // a sorted take is deterministic
+    let (_, notes) = crate::compile_with_notes(
+        &query.replace("take 1000", "sort id\n        take 1000"),
+        None,
+    )
+    .unwrap();
+    assert!(notes.warnings.is_empty());
+}
+
+#[test]
+fn test_target_precedence() {
+    use crate::{TargetNote, TargetSource};
+    use sql::Target;
+
+    let compile_noted = |header: &str, target: Option<Target>| {
+        let mut options = sql::Options::default().no_signature().no_format();
+        options.target = target;
+        let prql = format!("{header}\nfrom employees\ntake 3");
+        crate::compile_with_notes(&prql, options.some()).unwrap()
+    };
+    let note = |target, source| Some(TargetNote { target, source });
+
+    // no header and no option means generic target
+    let (sql, notes) = compile_noted("", None);
+    assert_eq!(sql, "SELECT * FROM employees LIMIT 3");
+    assert_eq!(notes.target, note(Target::Generic, TargetSource::Default));
+    assert!(notes.warnings.is_empty());
+
+    // header
+    let (sql, notes) = compile_noted("prql target:sql.mssql", None);
+    assert_eq!(sql, "SELECT TOP (3) * FROM employees");
+    assert_eq!(notes.target, note(Target::MsSql, TargetSource::Header));
+    assert!(notes.warnings.is_empty());
+
+    // option takes precedence over the header
+    let (sql, notes) = compile_noted("prql target:sql.mssql", Some(Target::SQLite));
+    assert_eq!(sql, "SELECT * FROM employees LIMIT 3");
+    assert_eq!(notes.target, note(Target::SQLite, TargetSource::Options));
+
+    let (_, notes) = compile_noted("", Some(Target::MsSql));
+    assert_eq!(notes.target, note(Target::MsSql, TargetSource::Options));
+    assert_display_snapshot!(notes.target.unwrap(), @"target: sql.mssql (from options)");
+
+    // deprecated `dialect` header, with or without the `sql.` prefix
+    for header in ["prql dialect:mssql", "prql dialect:sql.mssql"] {
+        let (sql, notes) = compile_noted(header, None);
+        assert_eq!(sql, "SELECT TOP (3) * FROM employees");
+        assert_eq!(notes.target, note(Target::MsSql, TargetSource::Header));
+        assert_eq!(notes.warnings.len(), 1);
+        assert_display_snapshot!(notes.warnings[0],
+            @"warning: dialect: `dialect` in the query header is deprecated; use `target:sql.mssql`"
+        );
+    }
+
+    // ... which is overridden by the option
+    let (sql, notes) = compile_noted("prql dialect:mssql", Some(Target::Generic));
+    assert_eq!(sql, "SELECT * FROM employees LIMIT 3");
+    assert_eq!(notes.target, note(Target::Generic, TargetSource::Options));
+    assert_eq!(notes.warnings.len(), 1);
+
+    // ... and by `target` in the same header
+    let (sql, notes) = compile_noted("prql target:sql.sqlite dialect:mssql", None);
+    assert_eq!(sql, "SELECT * FROM employees LIMIT 3");
+    assert_eq!(notes.target, note(Target::SQLite, TargetSource::Header));
+
+    // unknown dialect is an error
+    let err = crate::compile("prql dialect:oracle\nfrom employees", None).unwrap_err();
+    assert_eq!(err.inner[0].reason, "target `oracle` not found");
+}
+
+#[test]
+fn test_window_ordered_by_excluded_column() {
+    // `hired_at` is only referenced in OVER and excluded from the final select
+    assert_display_snapshot!(compile(r###"
+    from employees
+    group department (sort hired_at | derive r = row_number)
+    select [department, r]
+    "###).unwrap(), @r###"
+    SELECT
+      department,
+      ROW_NUMBER() OVER (
+        PARTITION BY department
+        ORDER BY
+          hired_at
+      ) AS r
+    FROM
+      employees
+    "###);
+
+    // a window function cannot be nested in OVER of another one,
+    // so it is computed in a preceding CTE
+    let sql = compile(
+        r###"
+    from employees
+    derive overall = row_number
+    group department (sort overall | derive r = row_number)
+    select [id, r]
+    "###,
+    )
+    .unwrap();
+
+    assert_display_snapshot!(sql, @r###"
+    WITH table_1 AS (
+      SELECT
+        id,
+        department,
+        ROW_NUMBER() OVER () AS overall
+      FROM
+        employees
+    )
+    SELECT
+      id,
+      ROW_NUMBER() OVER (
+        PARTITION BY department
+        ORDER BY
+          overall
+      ) AS r
+    FROM
+      table_1
+    "###);
+
+    let dialect = sqlparser::dialect::GenericDialect {};
+    assert!(sqlparser::parser::Parser::parse_sql(&dialect, &sql).is_ok());
+}
+
+#[test]
+fn test_shadowing_std() {
+    let query = r###"
+    func average x -> s"AVG({x}) FILTER (WHERE {x} IS NOT NULL)"
+
+    from employees
+    aggregate [
+        a = average salary,
+        b = std.average salary,
+    ]
+    "###;
+
+    // the user-defined function shadows std, which is still available qualified
+    let (sql, notes) = crate::compile_with_notes(
+        query,
+        sql::Options::default().no_signature().no_format().some(),
+    )
+    .unwrap();
+    assert_display_snapshot!(sql,
+        @"SELECT AVG(salary) FILTER (WHERE salary IS NOT NULL) AS a, AVG(salary) AS b FROM employees"
+    );
+
+    assert_eq!(notes.warnings.len(), 1);
+    assert_display_snapshot!(notes.warnings[0],
+        @"warning: func average: function `average` shadows `std.average`, which remains available by its qualified name; declare it with `override func` if this is intended"
+    );
+
+    // explicit override does not warn
+    let (sql_override, notes) = crate::compile_with_notes(
+        &query.replace("func average", "override func average"),
+        sql::Options::default().no_signature().no_format().some(),
+    )
+    .unwrap();
+    assert_eq!(sql, sql_override);
+    assert!(notes.warnings.is_empty());
+
+    // neither does a name that is not in std
+    let (_, notes) = crate::compile_with_notes(
+        r###"
+    func mean x -> s"AVG({x})"
+
+    from employees
+    aggregate [a = mean salary]
+    "###,
+        None,
+    )
+    .unwrap();
+    assert!(notes.warnings.is_empty());
+
+    // `override` is kept when formatting
+    let pl = crate::prql_to_pl("override func average x -> x\nfrom t").unwrap();
+    assert!(crate::pl_to_prql(pl)
+        .unwrap()
+        .starts_with("override func average x -> x"));
+}
+
+#[test]
+fn test_having_aliases() {
+    let query = r###"
+    from orders
+    group customer_id (
+        aggregate [total = sum amount]
+    )
+    filter total > 100
+    "###;
+
+    // HAVING cannot reference aliases, so the aggregate is repeated
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT customer_id, SUM(amount) AS total FROM orders GROUP BY customer_id HAVING SUM(amount) > 100"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, query).unwrap(),
+        @"SELECT customer_id, SUM(amount) AS total FROM orders GROUP BY customer_id HAVING SUM(amount) > 100"
+    );
+
+    // ... unless the target allows it
+    assert_display_snapshot!(compile_for(sql::Target::MySql, query).unwrap(),
+        @"SELECT customer_id, SUM(amount) AS total FROM orders GROUP BY customer_id HAVING total > 100"
+    );
+
+    let query = r###"
+    from orders
+    group customer_id (
+        aggregate [total = sum amount, n = count]
+    )
+    filter total > 100 and n < 10
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT customer_id, SUM(amount) AS total, COUNT(*) AS n FROM orders GROUP BY customer_id HAVING SUM(amount) > 100 AND COUNT(*) < 10"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, query).unwrap(),
+        @"SELECT customer_id, SUM(amount) AS total, COUNT(*) AS n FROM orders GROUP BY customer_id HAVING total > 100 AND n < 10"
+    );
+}
+
+#[test]
+fn test_cancellation() {
+    use crate::{Cancellation, ErrorCode};
+
+    let query = r###"
+    from employees
+    derive gross = salary + bonus
+    take 10
+    group department (aggregate [total = sum gross])
+    "###;
+
+    // explicit cancellation aborts the compilation
+    let cancellation = Cancellation::new();
+    cancellation.cancel();
+    let options = sql::Options::default().with_cancellation(cancellation);
+    let err = crate::compile(query, options.some()).unwrap_err();
+    assert_eq!(err.inner[0].code, ErrorCode::Cancelled);
+    assert_eq!(err.inner[0].reason, "compilation cancelled");
+
+    // otherwise the result is unchanged
+    let expected = compile(query).unwrap();
+    let options = (sql::Options::default().no_signature()).with_cancellation(Cancellation::new());
+    assert_eq!(crate::compile(query, options.some()).unwrap(), expected);
+}
+
+#[cfg(not(target_family = "wasm"))]
+#[test]
+fn test_cancellation_deadline() {
+    use crate::{Cancellation, ErrorCode};
+    use std::time::{Duration, Instant};
+
+    let query = r###"
+    from employees
+    derive gross = salary + bonus
+    take 10
+    group department (aggregate [total = sum gross])
+    "###;
+    let compile_with = |cancellation: Cancellation| {
+        let options = sql::Options::default().with_cancellation(cancellation);
+        crate::compile(query, options.some())
+    };
+
+    // a passed deadline aborts the compilation
+    let err = compile_with(Cancellation::with_deadline(Instant::now())).unwrap_err();
+    assert_eq!(err.inner[0].code, ErrorCode::Cancelled);
+    assert_eq!(err.inner[0].reason, "compilation timed out");
+
+    // ... also when compiling from RQ, which is checked during splitting and codegen
+    let rq = crate::prql_to_pl(query).and_then(crate::pl_to_rq).unwrap();
+    let options =
+        sql::Options::default().with_cancellation(Cancellation::with_deadline(Instant::now()));
+    let err = crate::rq_to_sql(rq, options.some()).unwrap_err();
+    assert_eq!(err.inner[0].code, ErrorCode::Cancelled);
+
+    // a deadline that has not passed does not change the result
+    let expected = compile(query).unwrap();
+    let options = sql::Options::default()
+        .no_signature()
+        .with_cancellation(Cancellation::with_timeout(Duration::from_secs(60)));
+    assert_eq!(crate::compile(query, options.some()).unwrap(), expected);
+}
+
+#[test]
+fn test_derived_columns_order() {
+    // derived columns follow the wildcard, in order of the pipeline
+    assert_display_snapshot!(compile_one_line(r###"
+    from t
+    derive e = x + 5
+    derive d = x + 4
+    derive [c = x + 3, b = x + 2]
+    derive a = x + 1
+    "###).unwrap(),
+        @"SELECT *, x + 5 AS e, x + 4 AS d, x + 3 AS c, x + 2 AS b, x + 1 AS a FROM t"
+    );
+
+    // ... also when some of them are computed in a preceding CTE
+    assert_display_snapshot!(compile_one_line(r###"
+    from t
+    derive e = x + 5
+    derive d = x + 4
+    take 10
+    derive [c = d + 3, b = e + 2]
+    derive a = x + 1
+    "###).unwrap(),
+        @"WITH table_1 AS (SELECT *, x + 5 AS e, x + 4 AS d FROM t LIMIT 10) SELECT *, d + 3 AS c, e + 2 AS b, x + 1 AS a FROM table_1"
+    );
+}
+
+#[test]
+fn test_distinct_transform() {
+    // bare distinct is over all columns
+    assert_display_snapshot!(compile_for(sql::Target::Generic, r###"
+    from employees
+    select [first_name, last_name]
+    distinct
+    "###).unwrap(),
+        @"SELECT DISTINCT first_name, last_name FROM employees"
+    );
+
+    // distinct over a subset of columns
+    let query = r###"
+    from employees
+    distinct [first_name, last_name]
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT DISTINCT ON (first_name, last_name) * FROM employees"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Generic, query).unwrap(),
+        @"WITH table_1 AS (SELECT *, ROW_NUMBER() OVER (PARTITION BY first_name, last_name) AS _expr_0 FROM employees) SELECT * FROM table_1 WHERE _expr_0 <= 1"
+    );
+
+    // ... is the same as group & take
+    assert_eq!(
+        compile_for(sql::Target::PostgreSql, query).unwrap(),
+        compile_for(
+            sql::Target::PostgreSql,
+            "from employees | group [first_name, last_name] (take 1)"
+        )
+        .unwrap()
+    );
+
+    // take after distinct is applied to distinct rows
+    let query = r###"
+    from employees
+    distinct [department]
+    take 10
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT DISTINCT ON (department) * FROM employees LIMIT 10"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Generic, query).unwrap(),
+        @"WITH table_1 AS (SELECT *, ROW_NUMBER() OVER (PARTITION BY department) AS _expr_0 FROM employees) SELECT * FROM table_1 WHERE _expr_0 <= 1 LIMIT 10"
+    );
+
+    // ... while distinct after take is applied to the taken rows
+    assert_display_snapshot!(compile_for(sql::Target::Generic, r###"
+    from employees
+    select department
+    take 10
+    distinct
+    "###).unwrap(),
+        @"WITH table_1 AS (SELECT department FROM employees LIMIT 10) SELECT DISTINCT department FROM table_1"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, r###"
+    from employees
+    take 10
+    distinct [department]
+    "###).unwrap(),
+        @"WITH table_1 AS (SELECT * FROM employees LIMIT 10) SELECT DISTINCT ON (department) * FROM table_1"
+    );
+
+    // the sort of a take within a group is the ORDER BY of DISTINCT ON
+    let query = r###"
+    from employees
+    group department (
+        sort [-salary]
+        take 1
+    )
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT DISTINCT ON (department) * FROM employees ORDER BY department, salary DESC"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Generic, query).unwrap(),
+        @"WITH table_1 AS (SELECT *, ROW_NUMBER() OVER (PARTITION BY department ORDER BY salary DESC) AS _expr_0 FROM employees) SELECT * FROM table_1 WHERE _expr_0 <= 1"
+    );
+}
+
+#[test]
+fn test_join_key_types() {
+    let warnings = |prql: &str| {
+        let options = sql::Options::default().no_signature().some();
+        let (_, notes) = crate::compile_with_notes(prql, options).unwrap();
+        notes.warnings
+    };
+
+    // keys of the same type
+    assert!(warnings(
+        r###"
+    from ([[id, status_id], [1, 1], [2, 2]])
+    join ([[code, label], [1, "Open"], [2, "Closed"]]) [status_id == code]
+    "###
+    )
+    .is_empty());
+
+    // a string key of the lookup literal
+    let query = r###"
+    from ([[id, status_id], [1, 1], [2, 2]])
+    join ([[code, label], ["1", "Open"], ["2", "Closed"]]) [status_id == code]
+    "###;
+    let found = warnings(query);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].spans.len(), 2);
+    assert_display_snapshot!(found[0],
+        @"warning: join: condition compares `status_id` (integer) with `code` (string), which fails or never matches on engines that don't cast implicitly; cast explicitly, i.e. `(as int code)`"
+    );
+
+    // applying the suggested cast silences it
+    assert!(warnings(&query.replace("== code", "== (as int code)")).is_empty());
+
+    // items of `in` lists are checked the same way
+    let query = r###"
+    from ([[id, status_id], [1, 1], [2, 2]])
+    filter (status_id | in [1, 2])
+    "###;
+    assert!(warnings(query).is_empty());
+    let query = query.replace("[1, 2]", r#"["1", "2"]"#);
+    let found = warnings(&query);
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].spans.len(), 2);
+    assert_display_snapshot!(found[0],
+        @r###"warning: in: condition compares `status_id` (integer) with `"1"` (string), which fails or never matches on engines that don't cast implicitly; cast explicitly, i.e. `(as text status_id)`"###
+    );
+    assert!(warnings(&query.replace("status_id |", "(as text status_id) |")).is_empty());
+}
+
+#[test]
+fn test_parse_recovering() {
+    use crate::ErrorCode;
+
+    let prql = r###"
+from employees
+filter salary >
+derive bonus = salary * 0.1
+select [name, bonus]
+"###;
+    crate::prql_to_pl(prql).unwrap_err();
+
+    let (stmts, diagnostics) = crate::parse_recovering(prql);
+
+    // the diagnostic points at the broken line ...
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].code, ErrorCode::Parse);
+    assert_eq!(diagnostics[0].location.as_ref().unwrap().start.0, 2);
+
+    // ... which is replaced by a placeholder spanning it
+    let main = stmts.last().unwrap().kind.as_main().unwrap();
+    let pipeline = main.kind.as_pipeline().unwrap();
+    assert_eq!(pipeline.exprs.len(), 4);
+    let span = pipeline.exprs[1].span.unwrap();
+    assert_eq!(&prql[span.start..span.end], "filter salary >");
+
+    // the lines after it are parsed and can be resolved
+    let rq = crate::pl_to_rq(stmts).unwrap();
+    let options = sql::Options::default().no_signature().no_format();
+    assert_display_snapshot!(crate::rq_to_sql(rq, options.some()).unwrap(),
+        @"SELECT name, salary * 0.1 AS bonus FROM employees"
+    );
+
+    // queries without errors are parsed as usual
+    let (stmts, diagnostics) = crate::parse_recovering("from employees | take 10");
+    assert!(diagnostics.is_empty());
+    assert_eq!(stmts, parse("from employees | take 10").unwrap());
+}
+
+#[test]
+fn test_check() {
+    use crate::ErrorCode;
+
+    crate::check("from employees | filter salary > 100 | select [name]").unwrap();
+
+    // names are not resolved
+    crate::check("from employees | derive x = unknown_function salary").unwrap();
+
+    let prql = r###"
+from employees
+derive bonus = salary ? 0.1
+select [name ? bonus]
+"###;
+    let diagnostics = crate::check(prql).unwrap_err();
+
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics.iter().all(|d| d.code == ErrorCode::Parse));
+
+    let locations = diagnostics
+        .iter()
+        .map(|d| d.location.as_ref().unwrap().start);
+    assert_eq!(locations.collect::<Vec<_>>(), vec![(2, 22), (3, 13)]);
+}
+
+#[test]
+fn test_cte_materialization() {
+    let query = r###"
+    table big = (from employees | filter salary > 100) @materialized
+    table small = (from departments | take 10) @not_materialized
+    from big
+    join small [==id]
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"WITH big AS MATERIALIZED (SELECT * FROM employees WHERE salary > 100), small AS NOT MATERIALIZED (SELECT * FROM departments LIMIT 10) SELECT big.*, small.* FROM big JOIN small USING(id)"
+    );
+
+    // other targets drop the hints
+    assert_display_snapshot!(compile_for(sql::Target::Generic, query).unwrap(),
+        @"WITH big AS (SELECT * FROM employees WHERE salary > 100), small AS (SELECT * FROM departments LIMIT 10) SELECT big.*, small.* FROM big JOIN small USING(id)"
+    );
+
+    // the hint is added to the definition of the CTE, not to text that looks like it
+    let query = r###"
+    table notes = (from employees | derive note = " big AS (")
+    table big = (from employees | filter salary > 100) @materialized
+    from big
+    join notes [==id]
+    "###;
+    let sql = compile_for(sql::Target::PostgreSql, query).unwrap();
+    assert!(sql.contains("' big AS (' AS note"));
+    assert!(sql.contains("big AS MATERIALIZED (SELECT * FROM employees WHERE salary > 100)"));
+}
+
+#[test]
+fn test_format_options() {
+    let query = r###"
+    from {{ ref('stg_orders') }}
+    aggregate (min order_id)
+    "###;
+    let compile_with = |options: sql::Options| crate::compile(query, options.some()).unwrap();
+
+    // unformatted output is a single line, jinja included
+    let sql = compile_with(sql::Options::default().no_signature().no_format());
+    assert!(!sql.contains('\n'));
+    assert_display_snapshot!(sql, @"SELECT MIN(order_id) FROM {{ ref('stg_orders') }}");
+
+    assert_display_snapshot!(compile_with(sql::Options::default().no_signature().with_indent(4)), @r###"
+    SELECT
+        MIN(order_id)
+    FROM
+        {{ ref('stg_orders') }}
+    "###);
+
+    // keywords of s-strings are passed through, unless asked otherwise
+    let query = "from employees | filter s\"age between 18 and 65\"";
+    let compile_with = |options: sql::Options| crate::compile(query, options.some()).unwrap();
+    assert!(compile_with(sql::Options::default()).contains("between"));
+    assert!(compile_with(sql::Options::default().with_uppercase_keywords()).contains("BETWEEN"));
+}
+
+#[test]
+fn test_sort_by_aggregate_across_split() {
+    // the windowed derive needs `total` as a plain column, so the pipeline
+    // is split after the aggregate, but `total` must still reach ORDER BY
+    let sql = compile(
+        r###"
+    from employees
+    group [dept] (aggregate [total = sum salary])
+    derive running = sum total
+    sort [-total]
+    take 10
+    "###,
+    )
+    .unwrap();
+
+    assert_display_snapshot!(sql, @r###"
+    WITH table_1 AS (
+      SELECT
+        dept,
+        SUM(salary) AS total
+      FROM
+        employees
+      GROUP BY
+        dept
+    )
+    SELECT
+      dept,
+      total,
+      SUM(total) OVER () AS running
+    FROM
+      table_1
+    ORDER BY
+      total DESC
+    LIMIT
+      10
+    "###);
+
+    let dialect = sqlparser::dialect::GenericDialect {};
+    assert!(sqlparser::parser::Parser::parse_sql(&dialect, &sql).is_ok());
+}
+
+#[test]
+fn test_single_atomic_query() {
+    // a query that fits into one SELECT has no CTEs and no generated names
+    let sql = compile_one_line(
+        r###"
+    from employees
+    filter age > 30
+    select [name, age]
+    "###,
+    )
+    .unwrap();
+    assert_eq!(sql.matches("WITH").count(), 0);
+    assert_eq!(sql.matches("table_").count(), 0);
+    assert_display_snapshot!(sql, @"SELECT name, age FROM employees WHERE age > 30");
+
+    // ... while queries that need to be split are unchanged
+    let sql = compile_one_line(
+        r###"
+    from employees
+    derive r = row_number
+    filter r > 1
+    "###,
+    )
+    .unwrap();
+    assert!(sql.starts_with("WITH table_1 AS ("));
+}
+
+#[test]
+fn test_append() {
+    assert_eq!(
+        compile("from employees\nappend managers").unwrap(),
+        compile("from employees\nconcat managers").unwrap()
+    );
+
+    // columns are matched by position
+    let err = compile(
+        r###"
+    from employees
+    select [name, salary]
+    append (from managers | select [name])
+    "###,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("non-matching number of columns"));
+}
+
+#[test]
+fn test_intersect_and_remove() {
+    assert_display_snapshot!(compile_for(sql::Target::Generic, "from employees | intersect managers").unwrap(),
+        @"(SELECT * FROM employees) INTERSECT SELECT * FROM managers"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Generic, "from employees | remove managers").unwrap(),
+        @"(SELECT * FROM employees) EXCEPT SELECT * FROM managers"
+    );
+
+    // MySQL has no EXCEPT, so columns are matched in NOT EXISTS
+    let query = r###"
+    from employees
+    select [name, dept]
+    remove (from managers | select [name, dept])
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::MySql, query).unwrap(),
+        @"WITH table_1 AS (SELECT name, dept FROM managers) SELECT DISTINCT * FROM (SELECT name, dept FROM employees) AS table_3 WHERE NOT EXISTS (SELECT * FROM table_1 AS table_0 WHERE table_3.name = table_0.name AND table_3.dept = table_0.dept)"
+    );
+
+    // ... which requires the columns to be known
+    let err = compile_for(sql::Target::MySql, "from employees | remove managers").unwrap_err();
+    assert_eq!(err.inner[0].code, crate::ErrorCode::Unsupported);
+}
+
+#[test]
+fn test_join_inline_pipeline() {
+    // columns are referenced by the name of the source of the pipeline ...
+    assert_display_snapshot!(compile_one_line(r###"
+    from orders
+    join (from refunds | filter amount > 0) [orders.id == refunds.order_id]
+    "###).unwrap(),
+        @"WITH table_1 AS (SELECT * FROM refunds WHERE amount > 0) SELECT orders.*, table_0.* FROM orders JOIN table_1 AS table_0 ON orders.id = table_0.order_id"
+    );
+
+    // ... or by its alias, which is also used in SQL
+    assert_display_snapshot!(compile_one_line(r###"
+    from orders
+    join r = (from refunds | filter amount > 0) [orders.id == r.order_id]
+    "###).unwrap(),
+        @"WITH table_0 AS (SELECT * FROM refunds WHERE amount > 0) SELECT orders.*, r.* FROM orders JOIN table_0 AS r ON orders.id = r.order_id"
+    );
+
+    // a pipeline that changes the grain
+    assert_display_snapshot!(compile_one_line(r###"
+    from orders
+    join totals = (
+        from refunds
+        group [order_id] (aggregate [refunded = sum amount])
+    ) [orders.id == totals.order_id]
+    "###).unwrap(),
+        @"WITH table_0 AS (SELECT order_id, SUM(amount) AS refunded FROM refunds GROUP BY order_id) SELECT orders.*, totals.order_id, totals.refunded FROM orders JOIN table_0 AS totals ON orders.id = totals.order_id"
+    );
+}
+
+#[test]
+fn test_quantified_comparison() {
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, r###"
+    from employees
+    filter salary > all (from benchmarks | select p99)
+    "###).unwrap(),
+        @"WITH table_1 AS (SELECT p99 FROM benchmarks) SELECT * FROM employees WHERE salary > ALL(SELECT p99 FROM table_1)"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, r###"
+    from employees
+    filter salary <= any (from benchmarks | select p99)
+    "###).unwrap(),
+        @"WITH table_1 AS (SELECT p99 FROM benchmarks) SELECT * FROM employees WHERE salary <= ANY(SELECT p99 FROM table_1)"
+    );
+
+    // SQLite has neither, so ordering comparisons use MIN or MAX
+    assert_display_snapshot!(compile_for(sql::Target::SQLite, r###"
+    from employees
+    filter salary > all (from benchmarks | select p99)
+    "###).unwrap(),
+        @"WITH table_1 AS (SELECT p99 FROM benchmarks) SELECT * FROM employees WHERE salary > (SELECT MAX(p99) FROM table_1)"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::SQLite, r###"
+    from employees
+    filter salary > any (from benchmarks | select p99)
+    "###).unwrap(),
+        @"WITH table_1 AS (SELECT p99 FROM benchmarks) SELECT * FROM employees WHERE salary > (SELECT MIN(p99) FROM table_1)"
+    );
+
+    // ... but equality cannot be expressed that way
+    let err = compile_for(
+        sql::Target::SQLite,
+        "from employees | filter dept == any (from depts | select name)",
+    )
+    .unwrap_err();
+    assert_eq!(err.inner[0].code, crate::ErrorCode::Unsupported);
+
+    // the pipeline must produce one column
+    let err = compile_for(
+        sql::Target::PostgreSql,
+        "from employees | filter salary > all (from benchmarks)",
+    )
+    .unwrap_err();
+    assert_eq!(err.inner[0].code, crate::ErrorCode::Invalid);
+
+    // ... and the help names one of its columns
+    let err = compile_for(
+        sql::Target::PostgreSql,
+        "from employees | filter salary > all (from benchmarks | select [p50, p99])",
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.inner[0].hint.as_deref(),
+        Some("select the compared column, i.e. append `select p50` to the pipeline")
+    );
+}
+
+#[test]
+fn test_strict_standard() {
+    let compile_strict = |target, prql| {
+        let options = (sql::Options::default().no_signature().no_format()).with_target(target);
+        crate::compile(prql, options.with_strict_standard().some())
+    };
+
+    // DISTINCT ON is replaced with ROW_NUMBER
+    let query = "from employees | group department (take 1)";
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT DISTINCT ON (department) * FROM employees"
+    );
+    assert_display_snapshot!(compile_strict(sql::Target::PostgreSql, query).unwrap(),
+        @"WITH table_1 AS (SELECT *, ROW_NUMBER() OVER (PARTITION BY department) AS _expr_0 FROM employees) SELECT * FROM table_1 WHERE _expr_0 <= 1"
+    );
+
+    // TOP is replaced with FETCH FIRST, and identifiers are quoted as the
+    // target quotes them
+    let query = "from employees | select [`first name`] | take 11..20";
+    assert_display_snapshot!(compile_strict(sql::Target::MsSql, query).unwrap(),
+        @"SELECT [first name] FROM employees ORDER BY (SELECT NULL) OFFSET 10 ROWS FETCH FIRST 10 ROWS ONLY"
+    );
+
+    // ... but targets without FETCH FIRST have no standard LIMIT
+    let err = compile_strict(sql::Target::MySql, query)
+        .unwrap_err()
+        .inner
+        .remove(0);
+    assert_eq!(
+        err.reason,
+        "`take` is not supported by sql.mysql with `strict_standard`"
+    );
+    assert_display_snapshot!(compile_strict(sql::Target::MySql, "from employees | select [`first name`]").unwrap(),
+        @"SELECT `first name` FROM employees"
+    );
+
+    // constructs without a standard equivalent are rejected
+    let err = compile_strict(
+        sql::Target::PostgreSql,
+        "from sales | group region rollup:true (aggregate [n = count])",
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "`group` with `rollup` or `cube` is not supported by sql.postgres with `strict_standard`"
+    );
+    assert_eq!(err.code, crate::ErrorCode::Unsupported);
+}
+
+#[test]
+fn test_window_offset_functions() {
+    let derive = |func| format!("from employees | sort hired_at | derive [x = {func}]");
+
+    assert_display_snapshot!(compile_for(sql::Target::Generic, &derive("lag 1 salary")).unwrap(),
+        @"SELECT *, LAG(salary, 1) OVER (ORDER BY hired_at) AS x FROM employees ORDER BY hired_at"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Generic, &derive("lead 2 salary")).unwrap(),
+        @"SELECT *, LEAD(salary, 2) OVER (ORDER BY hired_at) AS x FROM employees ORDER BY hired_at"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Generic, &derive("first salary")).unwrap(),
+        @"SELECT *, FIRST_VALUE(salary) OVER (ORDER BY hired_at ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING) AS x FROM employees ORDER BY hired_at"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Generic, &derive("last salary")).unwrap(),
+        @"SELECT *, LAST_VALUE(salary) OVER (ORDER BY hired_at ROWS BETWEEN UNBOUNDED PRECEDING AND UNBOUNDED FOLLOWING) AS x FROM employees ORDER BY hired_at"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Generic, &derive("row_number")).unwrap(),
+        @"SELECT *, ROW_NUMBER() OVER (ORDER BY hired_at) AS x FROM employees ORDER BY hired_at"
+    );
+
+    // partition comes from an enclosing group
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, "from employees | group department (sort hired_at | derive [x = lag 1 salary])").unwrap(),
+        @"SELECT *, LAG(salary, 1) OVER (PARTITION BY department ORDER BY hired_at) AS x FROM employees"
+    );
+
+    // offset and ranking functions have no frame, even in a `window`
+    assert_display_snapshot!(compile_for(sql::Target::BigQuery, "from employees | sort hired_at | window rolling:3 (derive [x = rank, y = sum salary])").unwrap(),
+        @"SELECT *, RANK() OVER (ORDER BY hired_at) AS x, SUM(salary) OVER (ORDER BY hired_at ROWS BETWEEN 2 PRECEDING AND CURRENT ROW) AS y FROM employees ORDER BY hired_at"
+    );
+
+    // SQL Server requires ORDER BY for these functions
+    let err = compile_for(
+        sql::Target::MsSql,
+        "from employees | derive [x = lag 1 salary]",
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(err.reason, "`lag` needs sorted rows on sql.mssql");
+    assert_eq!(err.code, crate::ErrorCode::Unsupported);
+    assert!(compile_for(
+        sql::Target::Generic,
+        "from employees | derive [x = lag 1 salary]"
+    )
+    .is_ok());
+}
+
+#[test]
+fn test_quote_dangerous_bare_idents() {
+    let prql = "from sessions | select [user, username, started_at]";
+
+    // `user` is the database user on SQL Server and Postgres
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, prql).unwrap(),
+        @"SELECT [user], username, started_at FROM sessions"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, prql).unwrap(),
+        @r###"SELECT "user", username, started_at FROM sessions"###
+    );
+
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, "from t | select [current_schema, system_user]").unwrap(),
+        @r###"SELECT "current_schema", system_user FROM t"###
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, "from t | select [current_schema, system_user]").unwrap(),
+        @"SELECT current_schema, [system_user] FROM t"
+    );
+}
+
+#[test]
+fn test_sort_nulls() {
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, "from t | sort nulls:last [amount]").unwrap(),
+        @"SELECT * FROM t ORDER BY amount NULLS LAST"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, "from t | sort nulls:first [amount]").unwrap(),
+        @"SELECT * FROM t ORDER BY amount NULLS FIRST"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, "from t | sort nulls:last [-amount]").unwrap(),
+        @"SELECT * FROM t ORDER BY amount DESC NULLS LAST"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, "from t | sort nulls:first [-amount, id]").unwrap(),
+        @"SELECT * FROM t ORDER BY amount DESC NULLS FIRST, id NULLS FIRST"
+    );
+
+    // MySQL has no NULLS FIRST / NULLS LAST
+    assert_display_snapshot!(compile_for(sql::Target::MySql, "from t | sort nulls:last [amount]").unwrap(),
+        @"SELECT * FROM t ORDER BY amount IS NULL, amount"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, "from t | sort nulls:first [amount]").unwrap(),
+        @"SELECT * FROM t ORDER BY amount IS NULL DESC, amount"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, "from t | sort nulls:last [-amount]").unwrap(),
+        @"SELECT * FROM t ORDER BY amount IS NULL, amount DESC"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, "from t | sort nulls:first [-amount]").unwrap(),
+        @"SELECT * FROM t ORDER BY amount IS NULL DESC, amount DESC"
+    );
+
+    // without `nulls`, placement is left to the database
+    assert_display_snapshot!(compile_for(sql::Target::MySql, "from t | sort [-amount]").unwrap(),
+        @"SELECT * FROM t ORDER BY amount DESC"
+    );
+
+    let err = compile_for(
+        sql::Target::PostgreSql,
+        "from t | sort nulls:middle [amount]",
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "`nulls` expected first or last, but found middle"
+    );
+}
+
+#[test]
+fn test_take_percent() {
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, "from employees | sort [-salary] | take 10%").unwrap(),
+        @"SELECT TOP (10) PERCENT * FROM employees ORDER BY salary DESC"
+    );
+    // `TOP n PERCENT` is not standard
+    let options = sql::Options::default()
+        .no_signature()
+        .no_format()
+        .with_target(sql::Target::MsSql)
+        .with_strict_standard()
+        .some();
+    let err = crate::compile("from employees | take 5%", options)
+        .unwrap_err()
+        .inner
+        .remove(0);
+    assert_eq!(
+        err.reason,
+        "`take` with a percentage is not supported by sql.mssql with `strict_standard`"
+    );
+
+    // `%` is still the modulo operator
+    assert_display_snapshot!(compile_for(sql::Target::Generic, "from employees | derive [x = 10 % 3, y = 10%3]").unwrap(),
+        @"SELECT *, 10 % 3 AS x, 10 % 3 AS y FROM employees"
+    );
+
+    let err = compile_for(sql::Target::PostgreSql, "from employees | take 10%")
+        .unwrap_err()
+        .inner
+        .remove(0);
+    assert_eq!(
+        err.reason,
+        "`take` with a percentage is not supported by sql.postgres"
+    );
+    assert_eq!(err.code, crate::ErrorCode::Unsupported);
+    assert!(err.location.is_some());
+
+    let err = compile_for(
+        sql::Target::MsSql,
+        "from employees | take 10..20 | take 10%",
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "`take` with a percentage cannot be combined with an offset or another `take`"
+    );
+    assert!(err.location.is_some());
+
+    let err = compile_for(sql::Target::MsSql, "from employees | take 5..10%")
+        .unwrap_err()
+        .inner
+        .remove(0);
+    assert_eq!(err.reason, "`take` with a percentage cannot have an offset");
+    assert!(err.location.is_some());
+}
+
+#[test]
+fn test_sample() {
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, "from events | sample 10%").unwrap(),
+        @"SELECT * FROM events TABLESAMPLE SYSTEM (10)"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::BigQuery, "from events | sample 10% | filter x > 1").unwrap(),
+        @"SELECT * FROM events TABLESAMPLE SYSTEM (10 PERCENT) WHERE x > 1"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, "from events | sample 1000").unwrap(),
+        @"SELECT * FROM events TABLESAMPLE (1000 ROWS)"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Snowflake, "from e = events | sample 1000 | select [e.id]").unwrap(),
+        @"SELECT id FROM events AS e SAMPLE (1000 ROWS)"
+    );
+
+    // `USING SAMPLE` of DuckDB ends the SELECT, so the table is sampled on its own
+    assert_display_snapshot!(compile_for(sql::Target::DuckDb, "from events | sample 10% | sort ts | take 5").unwrap(),
+        @"SELECT * FROM events USING SAMPLE 10 PERCENT ORDER BY ts LIMIT 5"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::DuckDb, "from events | sample 1000 | filter x > 1").unwrap(),
+        @"WITH table_1 AS (SELECT * FROM events USING SAMPLE 1000 ROWS) SELECT * FROM table_1 WHERE x > 1"
+    );
+
+    // Postgres samples a percentage only, so rows are picked at random
+    let sql = compile_for(sql::Target::PostgreSql, "from events | sample 1000").unwrap();
+    assert!(sql.starts_with("WITH "));
+    assert!(sql.contains("RANDOM() AS _expr_0 FROM events ORDER BY _expr_0 LIMIT 1000"));
+    let sql = compile_for(sql::Target::MySql, "from events | sample 1000").unwrap();
+    assert!(sql.contains("RAND() AS _expr_0"));
+
+    let err = compile_for(sql::Target::Generic, "from events | sample 10%")
+        .unwrap_err()
+        .inner
+        .remove(0);
+    assert_eq!(
+        err.reason,
+        "`sample` with a percentage is not supported by sql.generic"
+    );
+    assert_eq!(err.code, crate::ErrorCode::Unsupported);
+    assert!(err.location.is_some());
+
+    let err = compile_for(
+        sql::Target::PostgreSql,
+        "from events | filter x > 1 | sample 10%",
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(
+        err.reason,
+        "`sample` with a percentage must directly follow `from` of a table"
+    );
+
+    let err = compile_for(sql::Target::Generic, "from events | sample 0")
+        .unwrap_err()
+        .inner
+        .remove(0);
+    assert_eq!(
+        err.reason,
+        "`sample` expected a positive number of rows or a percentage, but found 0"
+    );
+}
+
+#[test]
+fn test_join_semi_anti() {
+    assert_display_snapshot!(compile_one_line(r#"
+    from employees
+    join side:semi managers [==id]
+    select [id, name]
+    "#).unwrap(),
+        @"SELECT employees.id, employees.name FROM employees WHERE EXISTS (SELECT 1 FROM managers WHERE employees.id = managers.id)"
+    );
+
+    assert_display_snapshot!(compile_one_line(r#"
+    from employees
+    join side:anti terminations [==id]
+    filter salary > 100 or bonus > 10
+    select [id]
+    "#).unwrap(),
+        @"SELECT employees.id FROM employees WHERE NOT EXISTS (SELECT 1 FROM terminations WHERE employees.id = terminations.id) AND (employees.salary > 100 OR employees.bonus > 10)"
+    );
+
+    // columns of the right relation are not in the frame
+    let err = compile(
+        r#"
+    from employees
+    join side:semi managers [==id]
+    select [managers.level]
+    "#,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(err.code, crate::ErrorCode::UnknownName);
+    assert!(err.reason.contains("managers.level"));
+}
+
+#[test]
+fn test_append_casts() {
+    let compile_with = |options: sql::Options, prql| {
+        crate::compile(prql, options.no_signature().no_format().some())
+    };
+
+    // integers and floats have a common type
+    compile_with(
+        sql::Options::default(),
+        r#"
+    from employees
+    select [a = 1]
+    append (from managers | select [a = 2.5])
+    "#,
+    )
+    .unwrap();
+
+    let query = r#"
+    from employees
+    select [a = 1, b = "x"]
+    append (from managers | select [a = "y", b = 2])
+    "#;
+    let err = compile_with(sql::Options::default(), query)
+        .unwrap_err()
+        .inner
+        .remove(0);
+    assert_eq!(err.code, crate::ErrorCode::Mismatch);
+    assert_display_snapshot!(err.reason,
+        @"column `a` of `append` is integer in one relation and string in the other"
+    );
+    assert!(err.hint.unwrap().contains("append_casts"));
+
+    // the other side of a string is cast
+    let sql = compile_with(sql::Options::default().with_append_casts(), query).unwrap();
+    assert!(sql.contains("CAST(1 AS TEXT)"));
+    assert!(sql.contains("CAST(2 AS TEXT)"));
+
+    let sql = compile_with(
+        sql::Options::default()
+            .with_append_casts()
+            .with_target(sql::Target::BigQuery),
+        query,
+    )
+    .unwrap();
+    assert!(sql.contains("CAST(1 AS STRING)"));
+
+    // no common type and no string to cast to
+    let err = compile_with(
+        sql::Options::default().with_append_casts(),
+        r#"
+    from employees
+    select [a = true]
+    append (from managers | select [b = @2020-01-01])
+    "#,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_display_snapshot!(err.reason,
+        @"column `a` of `append` is bool in one relation and date in the other"
+    );
+}
+
+#[test]
+fn test_join_cross() {
+    // a filter after the join is not folded into the join condition
+    assert_display_snapshot!(compile_one_line(r#"
+    from employees
+    join departments []
+    filter employees.dept_id == departments.id
+    select [employees.name, departments.title]
+    "#).unwrap(),
+        @"SELECT employees.name, departments.title FROM employees CROSS JOIN departments WHERE employees.dept_id = departments.id"
+    );
+
+    assert_eq!(
+        compile_one_line("from a | join b (true)").unwrap(),
+        compile_one_line("from a | join b []").unwrap(),
+    );
+
+    // outer joins keep rows without a match, so they stay as they are
+    assert_display_snapshot!(compile_one_line("from a | join side:left b []").unwrap(),
+        @"SELECT a.*, b.* FROM a LEFT JOIN b ON true"
+    );
+}
+
+#[test]
+fn test_max_identifier_length() {
+    use crate::utils::truncate_ident;
+
+    let base = "customer_orders_with_shipping_addresses_and_billing_details_archive_v2";
+    assert_eq!(base.len(), 70);
+
+    let prql = format!(
+        r#"
+    table {base}_recent = (from {base} | filter year > 2020) @materialized
+    table {base}_refund = (from {base} | filter refunded)
+
+    from {base}_recent
+    join {base}_refund [==id]
+    "#
+    );
+    let compile_with = |options: sql::Options| {
+        let options = options.no_signature().no_format().some();
+        crate::compile_with_notes(&prql, options).unwrap()
+    };
+
+    let postgres = sql::Options::default().with_target(sql::Target::PostgreSql);
+    // i.e. Oracle before 12.2
+    let oracle = sql::Options::default().with_max_identifier_length(30);
+
+    for (options, max_len, hint) in [(postgres, 63, "MATERIALIZED "), (oracle, 30, "")] {
+        let (sql, notes) = compile_with(options);
+
+        // CTE names are truncated, but stay distinct and keep their hints
+        let recent = truncate_ident(format!("{base}_recent"), max_len);
+        let refund = truncate_ident(format!("{base}_refund"), max_len);
+        assert_eq!(recent.len(), max_len);
+        assert_ne!(recent, refund);
+        assert!(sql.contains(&format!("{recent} AS {hint}(")));
+        assert!(sql.contains(&format!("{refund} AS (")));
+        assert!(!sql.contains(&format!("{base}_re")));
+
+        // the name of the table in the database cannot be changed
+        let warning = notes.warnings.iter().find(|w| w.construct == "identifier");
+        assert!(warning.unwrap().message.contains(base));
+    }
+
+    let (sql, notes) = compile_with(sql::Options::default());
+    assert!(sql.contains(&format!("{base}_recent AS (")));
+    assert!(notes.warnings.iter().all(|w| w.construct != "identifier"));
+}
+
+#[test]
+fn test_join_using() {
+    let query = r#"
+    from employees
+    join side:left salaries [==emp_no, ==dept_id]
+    select [employees.emp_no, salaries.dept_id, salaries.salary]
+    "#;
+    assert_display_snapshot!(compile_for(sql::Target::Generic, query).unwrap(),
+        @"SELECT employees.emp_no, salaries.dept_id, salaries.salary FROM employees LEFT JOIN salaries USING(emp_no, dept_id)"
+    );
+
+    // SQL Server has no USING
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, query).unwrap(),
+        @"SELECT employees.emp_no, salaries.dept_id, salaries.salary FROM employees LEFT JOIN salaries ON employees.emp_no = salaries.emp_no AND employees.dept_id = salaries.dept_id"
+    );
+
+    // as with USING, the key of the joined relation is merged into the key
+    // of the left relation, so an unqualified name is not ambiguous
+    assert_display_snapshot!(compile_for(sql::Target::Generic, r#"
+    from employees
+    join side:left salaries [==emp_no]
+    select [emp_no, salaries.salary]
+    "#).unwrap(),
+        @"SELECT employees.emp_no, salaries.salary FROM employees LEFT JOIN salaries USING(emp_no)"
+    );
+
+    // only the first join follows a single table, so later columns could be ambiguous
+    assert_display_snapshot!(compile_for(sql::Target::Generic, r#"
+    from employees
+    join salaries [==emp_no]
+    join titles [==emp_no]
+    select [emp_no, salaries.salary, titles.title]
+    "#).unwrap(),
+        @"SELECT employees.emp_no, salaries.salary, titles.title FROM employees JOIN salaries USING(emp_no) JOIN titles ON employees.emp_no = titles.emp_no"
+    );
+
+    // neither key of a full join can be used for both relations
+    let err = compile_for(
+        sql::Target::Generic,
+        r#"
+    from employees
+    join side:full salaries [==emp_no]
+    select [emp_no]
+    "#,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Ambiguous name"));
+}
+
+#[test]
+fn test_bool_aggregates() {
+    let query = r#"
+    from tests
+    group suite (
+        aggregate [all_passed = every passed, any_failed = any failed]
+    )
+    filter all_passed
+    "#;
+
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT suite, BOOL_AND(passed) AS all_passed, BOOL_OR(failed) AS any_failed FROM tests GROUP BY suite HAVING BOOL_AND(passed)"
+    );
+
+    // SQL Server has no boolean type, so the aggregate is repeated as a
+    // comparison, which is converted into 1 or 0 in the projection
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, query).unwrap(),
+        @"SELECT suite, CASE WHEN MIN(CAST(passed AS INT)) = 1 THEN 1 ELSE 0 END AS all_passed, CASE WHEN MAX(CAST(failed AS INT)) = 1 THEN 1 ELSE 0 END AS any_failed FROM tests GROUP BY suite HAVING MIN(CAST(passed AS INT)) = 1"
+    );
+
+    // MySQL would otherwise reference the alias in HAVING
+    let sql = compile_for(sql::Target::MySql, query).unwrap();
+    assert!(sql.ends_with("HAVING MIN(passed) = 1"));
+
+    let sql = compile_for(
+        sql::Target::MsSql,
+        r#"
+    from tests
+    group suite (aggregate [all_passed = every passed])
+    filter !all_passed
+    "#,
+    )
+    .unwrap();
+    assert!(sql.ends_with("HAVING NOT (MIN(CAST(passed AS INT)) = 1)"));
+}
+
+#[test]
+fn test_loop() {
+    let query = r###"
+    from ([[n], [1]])
+    loop (
+        filter n < 4
+        select n = n + 1
+    )
+    "###;
+
+    // the initial rows and the step are a single recursive CTE
+    let sql = compile_for(sql::Target::PostgreSql, query).unwrap();
+    assert!(sql.starts_with("WITH RECURSIVE "));
+    assert_eq!(sql.matches("UNION ALL").count(), 1);
+    assert!(sql.contains(" UNION ALL SELECT n + 1 AS n FROM table_"));
+    assert!(sql.contains(" WHERE n < 4) SELECT n FROM table_"));
+
+    // SQL Server infers that the CTE is recursive
+    let sql = compile_for(sql::Target::MsSql, query).unwrap();
+    assert!(sql.starts_with("WITH ") && !sql.contains("RECURSIVE"));
+    assert!(sql.contains(" UNION ALL SELECT n + 1 AS n FROM table_"));
+
+    // the CTE cannot be inlined, because it references itself
+    let options = sql::Options::default().no_signature().no_format();
+    let sql = crate::compile(query, options.with_wrap_as("t").some()).unwrap();
+    assert!(sql.starts_with("(WITH RECURSIVE "));
+
+    let error = compile_for(
+        sql::Target::PostgreSql,
+        r###"
+    from ([[n], [1]])
+    loop (
+        select n = n + 1
+        loop (select n = n * 2)
+    )
+    "###,
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert_eq!(error.code, crate::ErrorCode::Unsupported);
+    assert_eq!(
+        error.reason,
+        "`loop` cannot be used in the pipeline of another `loop`"
+    );
+    assert!(error.span.is_some());
+
+    let error = compile_for(
+        sql::Target::PostgreSql,
+        r###"
+    from ([[n], [1]])
+    loop (derive m = n + 1)
+    "###,
+    )
+    .unwrap_err();
+    assert!(error.inner[0].reason.contains("same number of columns"));
+}
+
+#[test]
+fn test_target_override() {
+    use sql::Target;
+
+    // the header names SQLite, but an explicit target wins
+    let prql = r#"
+    prql target:sql.sqlite
+
+    from employees
+    sort age
+    take 10
+    "#;
+    let compile_with_target = |target: Option<Target>| {
+        let mut options = sql::Options::default().no_signature().no_format();
+        options.target = target;
+        crate::compile(prql, options.some()).unwrap()
+    };
+
+    assert_snapshot!(compile_with_target(Some(Target::Generic)), @"SELECT * FROM employees ORDER BY age LIMIT 10");
+    assert_snapshot!(compile_with_target(Some(Target::PostgreSql)), @"SELECT * FROM employees ORDER BY age LIMIT 10");
+    assert_snapshot!(compile_with_target(Some(Target::MsSql)), @"SELECT TOP (10) * FROM employees ORDER BY age");
+    assert_snapshot!(compile_with_target(Some(Target::BigQuery)), @"SELECT * FROM employees ORDER BY age LIMIT 10");
+
+    // without an override, the header is used
+    assert_snapshot!(compile_with_target(None), @"SELECT * FROM employees ORDER BY age LIMIT 10");
+
+    let names = Target::names();
+    assert_eq!(names.len(), Target::all().len());
+    assert!(names.contains(&"sql.mssql".to_string()));
+    for name in names {
+        assert_eq!(Target::from_name(&name).unwrap().to_string(), name);
+    }
+}
+
+#[test]
+fn test_aggregate_of_boolean() {
+    use sql::Target;
+
+    let ratio = r#"
+    from employees
+    aggregate [pct_active = average (status == "active")]
+    "#;
+    assert_snapshot!(compile_for(Target::Generic, ratio).unwrap(),
+        @"SELECT AVG(CASE WHEN status = 'active' THEN 1.0 ELSE 0 END) AS pct_active FROM employees"
+    );
+    assert_snapshot!(compile_for(Target::PostgreSql, ratio).unwrap(),
+        @"SELECT AVG((status = 'active')::int) AS pct_active FROM employees"
+    );
+    assert_snapshot!(compile_for(Target::MySql, ratio).unwrap(),
+        @"SELECT AVG(IF(status = 'active', 1, 0)) AS pct_active FROM employees"
+    );
+    // the ratio is not truncated, so the argument is not cast to FLOAT
+    assert_snapshot!(compile_for(Target::MsSql, ratio).unwrap(),
+        @"SELECT AVG(CASE WHEN status = 'active' THEN 1.0 ELSE 0 END) AS pct_active FROM employees"
+    );
+
+    // a derived boolean column
+    let count = r#"
+    from employees
+    derive is_senior = age > 60
+    aggregate [seniors = sum is_senior]
+    "#;
+    let sql = compile_for(Target::Generic, count).unwrap();
+    assert!(sql.contains("SUM(CASE WHEN "));
+    assert!(sql.contains(" THEN 1 ELSE 0 END) AS seniors"));
+    assert!(compile_for(Target::BigQuery, count)
+        .unwrap()
+        .contains("SUM(IF("));
+
+    // numbers are left alone
+    assert_snapshot!(compile_for(Target::Generic, "from employees\naggregate [s = sum salary]").unwrap(),
+        @"SELECT SUM(salary) AS s FROM employees"
+    );
+}
+
+#[test]
+fn test_ident_quoting() {
+    // a project name with a hyphen is kept in one quoted name
+    let prql = r#"
+    from `my-project.dataset.events`
+    select [`event name`, user_id]
+    "#;
+    assert_display_snapshot!(compile_for(sql::Target::BigQuery, prql).unwrap(),
+        @"SELECT `event name`, user_id FROM `my-project.dataset.events`"
+    );
+
+    let prql = "from `sales.order lines` | select [`unit price`, qty]";
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, prql).unwrap(),
+        @"SELECT [unit price], qty FROM sales.[order lines]"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, prql).unwrap(),
+        @"SELECT `unit price`, qty FROM sales.`order lines`"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, prql).unwrap(),
+        @r###"SELECT "unit price", qty FROM sales."order lines""###
+    );
+
+    // closing brackets are doubled
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, "from t | select [`a]b`]").unwrap(),
+        @"SELECT [a]]b] FROM t"
+    );
+}
+
+#[test]
+fn test_from_aggregated_table() {
+    // the frame of by_day is exactly [day, n], so n is a plain column
+    let sql = compile_one_line(
+        r###"
+    table by_day = (
+        from events
+        group [day] (aggregate [n = count])
+    )
+    from by_day
+    filter n > 100
+    "###,
+    )
+    .unwrap();
+    assert!(sql.starts_with("WITH by_day AS (SELECT day, COUNT(*) AS n FROM events GROUP BY day)"));
+    assert!(sql.ends_with("SELECT day, n FROM by_day WHERE n > 100"));
+    assert!(!sql.contains("HAVING"));
+    assert!(!sql.contains("SELECT *"));
+
+    // the frame has no wildcard, so other columns are not assumed to exist
+    let err = compile_one_line(
+        r###"
+    table by_day = (
+        from events
+        group [day] (aggregate [n = count])
+    )
+    from by_day
+    select [day, user_id]
+    "###,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("Unknown name user_id"));
+}
+
+#[test]
+fn test_format_one_item_per_line() {
+    let compile_select = |columns: &[&str]| {
+        let prql = format!(
+            r###"
+    table recent = (
+        from events
+        filter created_at > @2023-01-01 and kind != "test"
+    )
+    from recent
+    select [{}]
+    "###,
+            columns.join(", ")
+        );
+        crate::compile(&prql, sql::Options::default().no_signature().some()).unwrap()
+    };
+
+    let mut columns = vec!["c0", "c1", "c2", "c3", "c4", "c5", "c6", "c7", "c8", "c9"];
+    let before = compile_select(&columns);
+    columns.insert(5, "added");
+    let after = compile_select(&columns);
+
+    // adding a column adds exactly one line, and leaves the others as they were
+    let before = before.lines().collect::<Vec<_>>();
+    let mut after = after.lines().collect::<Vec<_>>();
+    let added = after.iter().position(|l| l.trim() == "added,").unwrap();
+    after.remove(added);
+    assert_eq!(before, after);
+
+    // conditions and CTEs are on their own lines too
+    assert_display_snapshot!(compile_select(&["c0", "c1"]), @r###"
+    WITH recent AS (
+      SELECT
+        *
+      FROM
+        events
+      WHERE
+        created_at > DATE '2023-01-01'
+        AND kind <> 'test'
+    )
+    SELECT
+      c0,
+      c1
+    FROM
+      recent
+    "###);
+}
+
+#[test]
+fn test_take_range_mssql() {
+    // TOP cannot skip rows
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, "from employees | sort age | take 21..40").unwrap(),
+        @"SELECT * FROM employees ORDER BY age OFFSET 20 ROWS FETCH FIRST 20 ROWS ONLY"
+    );
+
+    // OFFSET needs ORDER BY, even for unsorted rows
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, "from employees | take 21..40").unwrap(),
+        @"SELECT * FROM employees ORDER BY (SELECT NULL) OFFSET 20 ROWS FETCH FIRST 20 ROWS ONLY"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, "from employees | take 21..").unwrap(),
+        @"SELECT * FROM employees ORDER BY (SELECT NULL) OFFSET 20 ROWS"
+    );
+
+    // without an offset, TOP is kept
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, "from employees | sort age | take 20").unwrap(),
+        @"SELECT TOP (20) * FROM employees ORDER BY age"
+    );
+}
+
+#[test]
+fn test_filter_ranking() {
+    let dialect = sqlparser::dialect::GenericDialect {};
+
+    // top 3 of each group
+    let sql = compile_one_line(
+        r###"
+    from employees
+    group department (
+        sort [-salary]
+        filter (row_number) <= 3
+    )
+    "###,
+    )
+    .unwrap();
+    assert!(sql.starts_with("WITH table_1 AS ("));
+    assert!(
+        sql.contains("ROW_NUMBER() OVER (PARTITION BY department ORDER BY salary DESC) AS _expr_0")
+    );
+    assert!(sql.contains("WHERE _expr_0 <= 3"));
+    assert!(!sql.contains("WHERE ROW_NUMBER()"));
+    assert!(sqlparser::parser::Parser::parse_sql(&dialect, &sql).is_ok());
+
+    // the whole table
+    let sql = compile_one_line(
+        r###"
+    from employees
+    sort [-salary]
+    filter (rank) <= 3 and (ntile 4) == 1
+    "###,
+    )
+    .unwrap();
+    assert!(sql.starts_with("WITH table_1 AS ("));
+    assert!(sql.contains("RANK() OVER (ORDER BY salary DESC)"));
+    assert!(sql.contains("NTILE(4) OVER (ORDER BY salary DESC)"));
+    assert!(!sql.contains("WHERE RANK()"));
+    assert!(sqlparser::parser::Parser::parse_sql(&dialect, &sql).is_ok());
+}
+
+#[test]
+fn test_sqlite_joins_and_booleans() {
+    // booleans are integers
+    let prql = "from employees | derive [is_active = true] | filter is_remote == false";
+    assert_display_snapshot!(compile_for(sql::Target::SQLite, prql).unwrap(),
+        @"SELECT *, 1 AS is_active FROM employees WHERE is_remote = 0"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Generic, prql).unwrap(),
+        @"SELECT *, true AS is_active FROM employees WHERE is_remote = false"
+    );
+
+    // a right join is a left join with the relations swapped
+    let prql = r###"
+    from employees
+    join side:right departments [employees.dept_id == departments.id]
+    select [employees.name, departments.title]
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::SQLite, prql).unwrap(),
+        @"SELECT employees.name, departments.title FROM departments LEFT JOIN employees ON employees.dept_id = departments.id"
+    );
+    assert!(compile_for(sql::Target::Generic, prql)
+        .unwrap()
+        .contains("FROM employees RIGHT JOIN departments"));
+
+    // ... which can only be done for the first join
+    let err = compile_for(
+        sql::Target::SQLite,
+        r###"
+    from employees
+    join salaries [==emp_id]
+    join side:right departments [employees.dept_id == departments.id]
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(err.inner[0].code, crate::ErrorCode::Unsupported);
+    assert_eq!(
+        err.inner[0].reason,
+        "`join side:right` is not supported by sql.sqlite"
+    );
+
+    // a full join is a left join, followed by the unmatched rows of the right relation
+    let prql = r###"
+    from employees
+    join side:full departments [employees.dept_id == departments.id]
+    select [employees.name, departments.title]
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::SQLite, prql).unwrap(),
+        @"WITH table_1 AS (SELECT employees.*, departments.* FROM departments LEFT JOIN employees ON employees.dept_id = departments.id WHERE employees.dept_id IS NULL), table_2 AS (SELECT employees.*, departments.* FROM employees LEFT JOIN departments ON employees.dept_id = departments.id UNION ALL SELECT * FROM table_1) SELECT name, title FROM table_2"
+    );
+    assert!(compile_for(sql::Target::Generic, prql)
+        .unwrap()
+        .contains("FROM employees FULL JOIN departments"));
+
+    // ... which can also only be done for the first join
+    let err = compile_for(
+        sql::Target::SQLite,
+        r###"
+    from employees
+    join salaries [==emp_id]
+    join side:full departments [employees.dept_id == departments.id]
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.inner[0].reason,
+        "`join side:full` is not supported by sql.sqlite"
+    );
+}
+
+#[test]
+fn test_clickhouse_limit_by() {
+    let prql = r###"
+    from employees
+    group department (
+        sort [-salary]
+        take 3
+    )
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::ClickHouse, prql).unwrap(),
+        @"SELECT * FROM employees ORDER BY salary DESC LIMIT 3 BY department"
+    );
+    // other targets number the rows
+    assert!(compile_for(sql::Target::Generic, prql)
+        .unwrap()
+        .contains("ROW_NUMBER() OVER"));
+
+    // with an offset, and followed by a take of the whole result
+    let prql = r###"
+    from employees
+    group department (
+        sort [-salary]
+        take 2..3
+    )
+    take 10
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::ClickHouse, prql).unwrap(),
+        @"SELECT * FROM employees ORDER BY salary DESC LIMIT 2 OFFSET 1 BY department LIMIT 10"
+    );
+
+    // a take without an end cannot be expressed with LIMIT BY
+    let prql = "from employees | group department (sort [-salary] | take 3..)";
+    let sql = compile_for(sql::Target::ClickHouse, prql).unwrap();
+    assert!(sql.contains("ROW_NUMBER() OVER"));
+    assert!(!sql.contains("LIMIT"));
+
+    // joins are ALL by default, which matches the semantics of SQL
+    let prql = "from employees | join side:left departments [==dept_id]";
+    assert_display_snapshot!(compile_for(sql::Target::ClickHouse, prql).unwrap(),
+        @"SELECT employees.*, departments.* FROM employees LEFT JOIN departments USING(dept_id)"
+    );
+}
+
+#[test]
+fn test_metadata_trailer() {
+    let prql = "prql target:sql.postgres\nfrom employees | take 10";
+    let options = sql::Options::default().with_metadata_trailer();
+    let sql = crate::compile(prql, options.clone().some()).unwrap();
+
+    let trailer = sql.lines().last().unwrap();
+    let json = trailer.strip_prefix("-- prql: ").unwrap();
+    let json: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(json["dialect"], "postgres");
+    assert_eq!(json["dialect_version"], serde_json::Value::Null);
+    assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(json["input_sha256"].as_str().unwrap().len(), 64);
+
+    assert!(crate::verify(&sql, prql));
+    assert!(!crate::verify(
+        &sql,
+        "prql target:sql.postgres\nfrom employees | take 11"
+    ));
+
+    let versioned = options.clone().with_dialect_version("15").some();
+    let sql = crate::compile(prql, versioned).unwrap();
+    let metadata = crate::Metadata::from_sql(&sql).unwrap();
+    assert_eq!(metadata.dialect_version.unwrap(), "15");
+    assert!(crate::verify(&sql, prql));
+
+    // the trailer stays on its own line without formatting
+    let sql = crate::compile(prql, options.no_format().some()).unwrap();
+    assert!(sql.contains("\n-- prql: {"));
+    assert!(crate::verify(&sql, prql));
+
+    // without the option, there is nothing to verify against
+    let sql = crate::compile(prql, None).unwrap();
+    assert!(!sql.contains("-- prql:"));
+    assert!(!crate::verify(&sql, prql));
+}
+
+#[test]
+fn test_named_windows() {
+    let prql = r###"
+    from employees
+    group dept (
+        sort hired_at
+        derive [rn = row_number, rnk = rank, prev = lag 1 salary]
+    )
+    "###;
+    let sql = compile_for(sql::Target::PostgreSql, prql).unwrap();
+    assert!(sql.contains("ROW_NUMBER() OVER w1 AS rn"));
+    assert!(sql.contains("RANK() OVER w1 AS rnk"));
+    assert!(sql.contains("OVER w1 AS prev"));
+    assert!(sql.ends_with(" FROM employees WINDOW w1 AS (PARTITION BY dept ORDER BY hired_at)"));
+
+    // SQL Server has no WINDOW clause
+    let sql = compile_for(sql::Target::MsSql, prql).unwrap();
+    assert_eq!(
+        sql.matches("OVER (PARTITION BY dept ORDER BY hired_at)")
+            .count(),
+        3
+    );
+    assert!(!sql.contains("WINDOW"));
+
+    // the clause follows WHERE and precedes ORDER BY and LIMIT
+    let prql = r###"
+    from employees
+    filter salary > 0
+    group dept (derive [total = sum salary, top = max salary])
+    "###;
+    let sql = compile_for(sql::Target::PostgreSql, prql).unwrap();
+    assert!(sql.ends_with(" WHERE salary > 0 WINDOW w1 AS (PARTITION BY dept)"));
+
+    let prql = r###"
+    from employees
+    group dept (derive [total = sum salary, top = max salary])
+    sort total
+    take 10
+    "###;
+    let sql = compile_for(sql::Target::PostgreSql, prql).unwrap();
+    assert!(
+        sql.ends_with(" FROM employees WINDOW w1 AS (PARTITION BY dept) ORDER BY total LIMIT 10")
+    );
+
+    // the CTE that the clause follows can still be inlined
+    let prql = r###"
+    from employees
+    take 10
+    group dept (derive [total = sum salary, top = max salary])
+    "###;
+    let options = (sql::Options::default().no_signature().no_format())
+        .with_target(sql::Target::PostgreSql)
+        .with_wrap_as("t");
+    let sql = crate::compile(prql, options.some()).unwrap();
+    assert!(sql.starts_with("(SELECT "));
+    assert!(sql.ends_with(
+        " FROM (SELECT * FROM employees LIMIT 10) AS table_0 WINDOW w1 AS (PARTITION BY dept)) AS t"
+    ));
+
+    // text of expressions is not searched for windows
+    let prql = r###"
+    from employees
+    derive note = "SUM(salary) OVER (PARTITION BY dept)"
+    group dept (derive [total = sum salary, top = max salary])
+    "###;
+    let sql = compile_for(sql::Target::PostgreSql, prql).unwrap();
+    assert!(sql.contains("'SUM(salary) OVER (PARTITION BY dept)' AS note"));
+    assert!(sql.contains("SUM(salary) OVER w1 AS total"));
+
+    // a window that is not shared stays inline
+    let prql = "from employees | group dept (derive [total = sum salary])";
+    let sql = compile_for(sql::Target::PostgreSql, prql).unwrap();
+    assert!(sql.contains("SUM(salary) OVER (PARTITION BY dept) AS total"));
+    assert!(!sql.contains("WINDOW"));
+}
+
+#[test]
+fn test_select_exclude() {
+    // columns of employees are not known, so they are excluded from the star
+    let prql = "from employees | select ![password, ssn]";
+    let sql = compile_for(sql::Target::DuckDb, prql).unwrap();
+    assert_eq!(sql, "SELECT * EXCLUDE (password, ssn) FROM employees");
+
+    let sql = compile_for(sql::Target::BigQuery, prql).unwrap();
+    assert_eq!(sql, "SELECT * EXCEPT (password, ssn) FROM employees");
+
+    let err = compile_for(sql::Target::PostgreSql, prql).unwrap_err();
+    assert_eq!(err.inner[0].code, crate::ErrorCode::Unsupported);
+
+    // known columns are left out of the projection on any target
+    let prql = "from employees | select [id, name, password] | select ![password]";
+    let sql = compile_for(sql::Target::PostgreSql, prql).unwrap();
+    assert_eq!(sql, "SELECT id, name FROM employees");
+
+    // only columns can be excluded
+    let prql = "from employees | select ![salary + 1]";
+    assert!(compile_for(sql::Target::DuckDb, prql).is_err());
+}
+
+#[test]
+fn test_expect_columns() {
+    let compile = |prql: &str| {
+        let options = sql::Options::default().no_signature().no_format().some();
+        crate::compile_with_notes(prql, options)
+    };
+
+    // a matching expectation is silent and does not change the SQL
+    let (sql, notes) = compile(
+        r###"
+    from invoices
+    group customer_id (aggregate [total = sum amount])
+    expect columns [total, customer_id]
+    "###,
+    )
+    .unwrap();
+    assert_eq!(
+        sql,
+        "SELECT customer_id, SUM(amount) AS total FROM invoices GROUP BY customer_id"
+    );
+    assert!(notes.warnings.is_empty());
+
+    // missing column
+    let err = compile(
+        r###"
+    from invoices
+    select [customer_id, total]
+    expect columns [customer_id, total, rank]
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(err.inner[0].code, crate::ErrorCode::Mismatch);
+    assert_eq!(
+        err.inner[0].reason,
+        "columns of the main pipeline do not match `expect columns`: missing `rank`"
+    );
+
+    // extra column
+    let err = compile(
+        r###"
+    from invoices
+    select [customer_id, total, amount]
+    expect columns [customer_id, total]
+    "###,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.inner[0].reason,
+        "columns of the main pipeline do not match `expect columns`: extra `amount`"
+    );
+    assert_eq!(
+        err.inner[0].hint.as_deref(),
+        Some("the pipeline produces [customer_id, total, amount]")
+    );
+
+    // columns of invoices are not known, so there is nothing to compare with
+    let (sql, notes) = compile("from invoices\nexpect columns [customer_id, total]").unwrap();
+    assert_eq!(sql, "SELECT * FROM invoices");
+    assert_eq!(notes.warnings.len(), 1);
+    assert_eq!(notes.warnings[0].construct, "expect");
+
+    // only `expect columns [` ends the pipeline, so columns can be named `expect`
+    let (sql, _) = compile(
+        r###"
+    from checks
+    derive passed = expect > 0
+    select [id, expect, passed]
+    expect columns [id, expect, passed]
+    "###,
+    )
+    .unwrap();
+    assert_eq!(sql, "SELECT id, expect, expect > 0 AS passed FROM checks");
+}
+
+#[test]
+fn test_filter_windowed_qualify() {
+    let query = r###"
+    from employees
+    group department (
+        sort [-salary]
+        derive rn = row_number
+    )
+    filter rn <= 3
+    "###;
+
+    // the filter on the windowed column stays in the same SELECT
+    for target in [sql::Target::Snowflake, sql::Target::DuckDb] {
+        let sql = compile_for(target, query).unwrap();
+        assert!(!sql.contains("WITH"), "{sql}");
+        assert!(sql.contains(
+            " FROM employees QUALIFY ROW_NUMBER() OVER (PARTITION BY department ORDER BY salary DESC) <= 3"
+        ));
+    }
+
+    // other targets filter in a CTE
+    let sql = compile_for(sql::Target::Generic, query).unwrap();
+    assert!(sql.starts_with("WITH table_1 AS"));
+    assert!(sql.contains(" WHERE rn <= 3"));
+    assert!(!sql.contains("QUALIFY"));
+
+    // a WINDOW clause precedes QUALIFY
+    let options = sql::Options::default()
+        .no_signature()
+        .no_format()
+        .with_target(sql::Target::DuckDb);
+    let prql = r###"
+    from employees
+    group department (
+        sort [-salary]
+        derive [rn = row_number, rnk = rank]
+    )
+    filter rn <= 3
+    "###;
+    let sql = crate::compile(prql, options.some()).unwrap();
+    assert!(sql.contains("ROW_NUMBER() OVER w1 AS rn, RANK() OVER w1 AS rnk"));
+    assert!(sql.ends_with(
+        " FROM employees WINDOW w1 AS (PARTITION BY department ORDER BY salary DESC) QUALIFY ROW_NUMBER() OVER (PARTITION BY department ORDER BY salary DESC) <= 3"
+    ));
+}
+
+#[test]
+fn test_date_time_literals() {
+    let prql = r###"
+    from events
+    filter start_date > @2021-01-01
+    filter started_at < @2021-01-01T10:30
+    filter start_time == @09:00
+    "###;
+    assert_display_snapshot!(compile_for(sql::Target::Generic, prql).unwrap(),
+        @"SELECT * FROM events WHERE start_date > DATE '2021-01-01' AND started_at < TIMESTAMP '2021-01-01T10:30' AND start_time = TIME '09:00'"
+    );
+
+    // timestamps without a time zone are DATETIME
+    let sql = compile_for(sql::Target::BigQuery, prql).unwrap();
+    assert!(sql.contains("start_date > DATE '2021-01-01'"));
+    assert!(sql.contains("started_at < DATETIME '2021-01-01T10:30'"));
+    let sql = crate::compile(
+        "prql target:sql.bigquery\nfrom events | filter started_at < @2021-01-01T10:30Z",
+        sql::Options::default().no_signature().no_format().some(),
+    )
+    .unwrap();
+    assert!(sql.contains("started_at < TIMESTAMP '2021-01-01T10:30Z'"));
+
+    // no typed literals
+    let sql = compile_for(sql::Target::MsSql, prql).unwrap();
+    assert!(sql.contains("start_date > CAST('2021-01-01' AS DATE)"));
+    assert!(sql.contains("started_at < CAST('2021-01-01T10:30' AS DATETIME2)"));
+    assert!(sql.contains("start_time = CAST('09:00' AS TIME)"));
+
+    // literals survive a round trip through JSON
+    let rq = crate::prql_to_pl(prql).and_then(crate::pl_to_rq).unwrap();
+    let json = crate::json::from_rq(rq.clone()).unwrap();
+    assert!(json.contains(r#"{"Date":"2021-01-01"}"#));
+    assert!(json.contains(r#"{"Timestamp":"2021-01-01T10:30"}"#));
+    assert!(json.contains(r#"{"Time":"09:00"}"#));
+    assert_eq!(crate::json::to_rq(&json).unwrap(), rq);
+
+    // invalid dates are parse errors, pointing at the literal
+    let err = crate::compile("from events | filter start_date > @2021-02-29", None).unwrap_err();
+    assert_eq!(err.inner[0].code, crate::ErrorCode::Parse);
+    assert_eq!(
+        err.inner[0].reason,
+        "`@2021-02-29` is not a valid date: the day is out of range"
+    );
+    assert_eq!(err.inner[0].span.map(|s| s.start), Some(34));
+}
+
+#[test]
+fn test_compile_timings() {
+    use std::time::Duration;
+
+    let prql = "from employees | take 10 | filter salary > 0";
+
+    let options = sql::Options::default().with_timings();
+    let (sql, timings) = crate::compile_with_timings(prql, options.some()).unwrap();
+
+    // timings are returned separately, the SQL is the same
+    let plain_sql: String = crate::compile(prql, None).unwrap();
+    assert_eq!(sql, plain_sql);
+
+    assert!(timings.total > Duration::ZERO);
+    assert!(timings.parse > Duration::ZERO);
+    assert!(timings.resolve > Duration::ZERO);
+    // a CTE and the main query
+    assert_eq!(timings.atomic_queries.len(), 2);
+    assert!(
+        timings.total
+            >= timings.parse
+                + timings.resolve
+                + timings.split
+                + timings.translate()
+                + timings.format
+    );
+
+    // nothing is measured by default
+    let (_, timings) = crate::compile_with_timings(prql, None).unwrap();
+    assert_eq!(timings, crate::CompileTimings::default());
+}
+
+#[test]
+fn test_in_lists() {
+    // comparisons of the same column are collapsed
+    assert_display_snapshot!(
+        compile_one_line(r#"from tickets | filter status == "a" or status == "b" or status == "c""#).unwrap(),
+        @"SELECT * FROM tickets WHERE status IN ('a', 'b', 'c')"
+    );
+
+    // ... without duplicates
+    assert_display_snapshot!(
+        compile_one_line(r#"from tickets | filter status == "a" or status == "b" or status == "a""#).unwrap(),
+        @"SELECT * FROM tickets WHERE status IN ('a', 'b')"
+    );
+
+    // a chain that compares another column is left alone
+    assert_display_snapshot!(
+        compile_one_line(r#"from tickets | filter status == "a" or status == "b" or priority == 1"#).unwrap(),
+        @"SELECT * FROM tickets WHERE status = 'a' OR status = 'b' OR priority = 1"
+    );
+
+    // ... as is a chain with a comparison that is not `==`
+    assert_display_snapshot!(
+        compile_one_line(r#"from tickets | filter status == "a" or status != "b""#).unwrap(),
+        @"SELECT * FROM tickets WHERE status = 'a' OR status <> 'b'"
+    );
+
+    // explicit lists use the same representation
+    assert_display_snapshot!(
+        compile_one_line(r#"from tickets | filter (status | in ["a", "b"])"#).unwrap(),
+        @"SELECT * FROM tickets WHERE status IN ('a', 'b')"
+    );
+    assert_display_snapshot!(
+        compile_one_line(r#"from tickets | filter (priority + 1 | in [1, 2])"#).unwrap(),
+        @"SELECT * FROM tickets WHERE priority + 1 IN (1, 2)"
+    );
+    assert_display_snapshot!(
+        compile_one_line(r#"from customers | derive is_na = (country | in ["USA", "Canada"])"#).unwrap(),
+        @"SELECT *, country IN ('USA', 'Canada') AS is_na FROM customers"
+    );
+
+    // an empty list matches nothing, instead of being invalid `IN ()`
+    assert_display_snapshot!(
+        compile_one_line("from tickets | filter (status | in [])").unwrap(),
+        @"SELECT * FROM tickets WHERE false"
+    );
+    // ... which is a condition on SQL Server, where booleans are no values
+    let options = (sql::Options::default().no_signature().no_format())
+        .with_target(sql::Target::MsSql)
+        .some();
+    assert_display_snapshot!(
+        crate::compile_one_line("from tickets | filter (status | in [])").unwrap().unwrap(),
+        @"SELECT * FROM tickets WHERE 1 = 0"
+    );
+
+    // ranges open at either end are a single comparison
+    assert_display_snapshot!(
+        compile_one_line("from employees | filter (age | in 18..)").unwrap(),
+        @"SELECT * FROM employees WHERE age >= 18"
+    );
+}
+
+#[test]
+fn test_coalesce_chain() {
+    // a chain of `??` is a single COALESCE
+    assert_display_snapshot!(
+        compile_one_line(r#"from employees | select [name = nickname ?? first_name ?? "unknown"]"#).unwrap(),
+        @"SELECT COALESCE(nickname, first_name, 'unknown') AS name FROM employees"
+    );
+
+    // ... also within aggregates
+    assert_display_snapshot!(
+        compile_one_line("from employees | group dept (aggregate [total = sum (bonus ?? commission ?? 0)])").unwrap(),
+        @"SELECT dept, SUM(COALESCE(bonus, commission, 0)) AS total FROM employees GROUP BY dept"
+    );
+
+    // comparisons with null, on either side
+    assert_display_snapshot!(
+        compile_one_line("from employees | select [no_manager = manager == null, has_manager = null != manager]").unwrap(),
+        @"SELECT manager IS NULL AS no_manager, manager IS NOT NULL AS has_manager FROM employees"
+    );
+}
+
+#[test]
+fn test_unicode_idents() {
+    // letters of any script are idents, and are always quoted
+    assert_display_snapshot!(
+        compile_one_line("from ventas | derive 合計 = precio * cantidad | select [país, café, 合計]").unwrap(),
+        @r###"SELECT "país", "café", precio * cantidad AS "合計" FROM ventas"###
+    );
+
+    // ... with the quoting of the dialect
+    assert_display_snapshot!(
+        compile_one_line("prql target:sql.mssql\nfrom ventas | select [país, 合計]").unwrap(),
+        @"SELECT [país], [合計] FROM ventas"
+    );
+    assert_display_snapshot!(
+        compile_one_line("prql target:sql.mysql\nfrom ventas | select [país, 合計]").unwrap(),
+        @"SELECT `país`, `合計` FROM ventas"
+    );
+
+    // emoji are not letters, so they need backticks
+    assert_eq!(
+        compile_one_line("from reactions | select [`🎉`, n = count_🎉]")
+            .unwrap_err()
+            .inner[0]
+            .code,
+        crate::ErrorCode::Parse
+    );
+    assert_display_snapshot!(
+        compile_one_line("from reactions | select [`🎉`]").unwrap(),
+        @r###"SELECT "🎉" FROM reactions"###
+    );
+
+    // names survive JSON and PRQL round trips
+    let pl = crate::prql_to_pl("from ventas | select [país, 合計, `🎉`]").unwrap();
+    let json = crate::json::from_pl(pl).unwrap();
+    assert!(json.contains("合計"));
+    let prql = crate::pl_to_prql(crate::json::to_pl(&json).unwrap()).unwrap();
+    assert!(prql.contains("`合計`") && prql.contains("`🎉`"));
+    assert_eq!(
+        crate::pl_to_prql(crate::prql_to_pl(&prql).unwrap()).unwrap(),
+        prql
+    );
+
+    let (_, columns) = crate::compile_and_describe(
+        "from ventas | derive 合計 = precio * cantidad | select [país, 合計]",
+        None,
+    )
+    .unwrap();
+    let names: Vec<_> = columns.into_iter().map(|c| c.name.unwrap()).collect();
+    assert_eq!(names, vec!["país", "合計"]);
+}
+
+#[test]
+fn test_sort_and_take_after_append() {
+    let query = "from employees | append managers | sort salary | take 10";
+
+    // ORDER BY and LIMIT of the UNION, instead of its last operand
+    assert_display_snapshot!(compile_for(sql::Target::Generic, query).unwrap(),
+        @"(SELECT * FROM employees) UNION ALL SELECT * FROM managers ORDER BY salary LIMIT 10"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, query).unwrap(),
+        @"(SELECT * FROM employees) UNION ALL (SELECT * FROM managers) ORDER BY salary LIMIT 10"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, query).unwrap(),
+        @"(SELECT * FROM employees) UNION ALL SELECT * FROM managers ORDER BY salary OFFSET 0 ROWS FETCH FIRST 10 ROWS ONLY"
+    );
+
+    // ... also with an offset
+    assert_display_snapshot!(compile_for(sql::Target::Generic, "from employees | append managers | sort [-salary] | take 11..20").unwrap(),
+        @"(SELECT * FROM employees) UNION ALL SELECT * FROM managers ORDER BY salary DESC LIMIT 10 OFFSET 10"
+    );
+}
+
+#[test]
+fn test_case() {
+    assert_display_snapshot!(compile_one_line(r#"
+    from employees
+    derive band = case [
+        salary > 100000 => "high",
+        salary > 50000 => "mid",
+        true => "low",
+    ]
+    "#).unwrap(),
+        @"SELECT *, CASE WHEN salary > 100000 THEN 'high' WHEN salary > 50000 THEN 'mid' ELSE 'low' END AS band FROM employees"
+    );
+
+    // without a `true` condition, the fallback is NULL
+    assert_display_snapshot!(
+        compile_one_line("from employees | derive bonus = case [rating == 5 => salary * 0.1]").unwrap(),
+        @"SELECT *, CASE WHEN rating = 5 THEN salary * 0.1 END AS bonus FROM employees"
+    );
+
+    assert_display_snapshot!(
+        compile_one_line("from employees | group dept (aggregate [seniors = sum (case [age > 60 => 1, true => 0])])").unwrap(),
+        @"SELECT dept, SUM(CASE WHEN age > 60 THEN 1 ELSE 0 END) AS seniors FROM employees GROUP BY dept"
+    );
+
+    assert_display_snapshot!(
+        compile_one_line(r#"from employees | filter (case [dept == "sales" => salary > 50000, true => salary > 80000])"#).unwrap(),
+        @"SELECT * FROM employees WHERE CASE WHEN dept = 'sales' THEN salary > 50000 ELSE salary > 80000 END"
+    );
+
+    // `case` is the same as `switch`
+    assert_eq!(
+        compile_one_line("from employees | derive x = case [a => 1]").unwrap(),
+        compile_one_line("from employees | derive x = switch [a -> 1]").unwrap()
+    );
+}
+
+#[test]
+fn test_policy() {
+    use crate::{ErrorCode, Policy};
+
+    let compile = |prql: &str, policy: Policy| {
+        let options = sql::Options::default().no_signature().no_format();
+        crate::compile(prql, options.with_policy(policy).some())
+    };
+    let denied = |prql: &str, policy: Policy| {
+        let err = compile(prql, policy).unwrap_err().inner.remove(0);
+        assert_eq!(err.code, ErrorCode::Denied);
+        let span = err.span.unwrap();
+        (err.reason, prql[span.start..span.end].to_string())
+    };
+    let tables = Policy::default().with_denied_tables(vec!["salar*".to_string()]);
+
+    // a table read directly
+    assert_eq!(
+        denied("from salaries | take 10", tables.clone()),
+        (
+            "table `salaries` is denied by the `denied_tables` rule of the policy".to_string(),
+            "salaries".to_string()
+        )
+    );
+    assert!(compile("from employees | take 10", tables.clone()).is_ok());
+
+    // ... regardless of the case of its name, which the database ignores
+    let (_, span) = denied("prql target:sql.mssql\nfrom Salaries", tables.clone());
+    assert_eq!(span, "Salaries");
+    let (_, span) = denied("from `SALARIES` | take 10", tables.clone());
+    assert!(span.contains("SALARIES"));
+
+    // ... or within the declaration of another table
+    let (_, span) = denied(
+        "table recent = (from salaries | take 10)\nfrom recent",
+        tables,
+    );
+    assert_eq!(span, "salaries");
+
+    let allowed = Policy::default().with_allowed_tables(vec!["employees".to_string()]);
+    let (reason, span) = denied("from employees | join salaries [==id]", allowed.clone());
+    assert!(reason.contains("`allowed_tables`"));
+    assert_eq!(span, "salaries");
+    assert!(compile("from employees | select [id]", allowed).is_ok());
+
+    // functions
+    let functions = Policy::default().with_denied_functions(vec!["average".to_string()]);
+    let (reason, span) = denied(
+        "from employees | aggregate [avg_salary = average salary]",
+        functions.clone(),
+    );
+    assert_eq!(
+        reason,
+        "function `average` is denied by the `denied_functions` rule of the policy"
+    );
+    assert!(span.contains("average"));
+    assert!(compile("from employees | aggregate [total = sum salary]", functions).is_ok());
+
+    // s-strings, but not f-strings
+    let sstrings = Policy::default().with_deny_sstrings();
+    let (reason, span) = denied(
+        r#"from employees | derive r = s"RANDOM()""#,
+        sstrings.clone(),
+    );
+    assert!(reason.contains("`deny_sstrings`"));
+    assert!(span.contains(r#"s"RANDOM()""#));
+    assert_display_snapshot!(
+        compile(r#"from employees | derive name = f"{first_name} {last_name}""#, sstrings).unwrap(),
+        @"SELECT *, CONCAT(first_name, ' ', last_name) AS name FROM employees"
+    );
+}
+
+#[test]
+fn test_filter_after_select() {
+    // the filter applies to the relation produced by the select
+    let err = compile_one_line("from invoices | select [id, name] | filter amount > 0")
+        .unwrap_err()
+        .inner
+        .remove(0);
+    assert_eq!(err.code, crate::ErrorCode::UnknownName);
+    assert_eq!(err.reason, "Unknown name amount");
+    assert_eq!(
+        err.hint.as_deref(),
+        Some("the relation after `select` does not contain `amount`; use it before the `select`, or include it in the `select`")
+    );
+
+    // ... also when other transforms are in between, where the filter would
+    // otherwise end up in a query separate from the table
+    let err = compile_one_line(
+        "from invoices | select [id, name] | sort id | take 10 | filter amount > 0",
+    )
+    .unwrap_err()
+    .inner
+    .remove(0);
+    assert!(err.hint.unwrap().contains("after `select`"));
+
+    assert_display_snapshot!(
+        compile_one_line("from invoices | filter amount > 0 | select [id, name]").unwrap(),
+        @"SELECT id, name FROM invoices WHERE amount > 0"
+    );
+    assert_display_snapshot!(
+        compile_one_line("from invoices | select [id, name, amount] | filter amount > 0").unwrap(),
+        @"SELECT id, name, amount FROM invoices WHERE amount > 0"
+    );
+}
+
+#[test]
+fn test_string_functions() {
+    let query = r###"
+    from employees
+    derive [
+        name = upper (trim first_name),
+        first_letter = (last_name | substring 1 1),
+    ]
+    filter (length name) > 3
+    select [name, first_letter, email = (lower email | replace "@old.com" "@new.com")]
+    "###;
+
+    assert_display_snapshot!(compile_for(sql::Target::Generic, query).unwrap(),
+        @"SELECT UPPER(TRIM(first_name)) AS name, SUBSTRING(last_name FROM 1 FOR 1) AS first_letter, REPLACE(LOWER(email), '@old.com', '@new.com') AS email FROM employees WHERE LENGTH(UPPER(TRIM(first_name))) > 3"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MsSql, query).unwrap(),
+        @"SELECT UPPER(TRIM(first_name)) AS name, SUBSTRING(last_name, 1, 1) AS first_letter, REPLACE(LOWER(email), '@old.com', '@new.com') AS email FROM employees WHERE LEN(UPPER(TRIM(first_name))) > 3"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::SQLite, query).unwrap(),
+        @"SELECT UPPER(TRIM(first_name)) AS name, SUBSTR(last_name, 1, 1) AS first_letter, REPLACE(LOWER(email), '@old.com', '@new.com') AS email FROM employees WHERE LENGTH(UPPER(TRIM(first_name))) > 3"
+    );
+
+    // wrong number of arguments is reported at the call
+    let arg_count_error = |prql: &str| {
+        let err = compile_for(sql::Target::Generic, prql)
+            .unwrap_err()
+            .inner
+            .remove(0);
+        assert_eq!(err.code, crate::ErrorCode::Mismatch);
+        let span = err.span.unwrap();
+        (err.reason, prql[span.start..span.end].to_string())
+    };
+    let prql = "from employees | derive x = upper first_name last_name";
+    assert_eq!(
+        arg_count_error(prql),
+        (
+            "`upper` expected 1 argument, but found 2".to_string(),
+            "upper first_name last_name".to_string()
+        )
+    );
+    let prql = "from employees | derive x = substring 1 first_name";
+    assert_eq!(
+        arg_count_error(prql),
+        (
+            "`substring` expected 3 arguments, but found 2".to_string(),
+            "substring 1 first_name".to_string()
+        )
+    );
+    let prql = "from employees | derive [x = substring 1 first_name, y = lower email]";
+    assert_eq!(
+        arg_count_error(prql).0,
+        "`substring` expected 3 arguments, but found 2"
+    );
+
+    // columns share names with the functions
+    assert_display_snapshot!(compile_for(sql::Target::Generic, "from film | select [title, length]").unwrap(),
+        @"SELECT title, length FROM film"
+    );
+    let prql = "from film | select [title, length, l = length title, lower = (lower title)]";
+    assert_display_snapshot!(compile_for(sql::Target::Generic, prql).unwrap(),
+        @"SELECT title, length, LENGTH(title) AS l, LOWER(title) AS lower FROM film"
+    );
+}
+
+#[test]
+fn test_regex_search() {
+    let query = r#"from employees | filter last_name ~= "^Smith""#;
+
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, query).unwrap(),
+        @"SELECT * FROM employees WHERE last_name ~ '^Smith'"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, query).unwrap(),
+        @"SELECT * FROM employees WHERE (last_name REGEXP '^Smith')"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::SQLite, query).unwrap(),
+        @"SELECT * FROM employees WHERE (last_name REGEXP '^Smith')"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::BigQuery, query).unwrap(),
+        @"SELECT * FROM employees WHERE REGEXP_CONTAINS(last_name, r'^Smith')"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::Snowflake, query).unwrap(),
+        @"SELECT * FROM employees WHERE (last_name RLIKE '^Smith')"
+    );
+
+    // operands are parenthesized only when they bind weaker than a comparison
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, r#"
+    from employees
+    derive is_smith = (first_name + last_name ~= "Smith$")
+    filter !(title ~= "^Intern") and country == "USA"
+    "#).unwrap(),
+        @"SELECT *, first_name + last_name ~ 'Smith$' AS is_smith FROM employees WHERE NOT title ~ '^Intern' AND country = 'USA'"
+    );
+    let prql = r#"from employees | derive is_smith = (true == (last_name ~= "^Smith"))"#;
+    assert_display_snapshot!(compile_for(sql::Target::PostgreSql, prql).unwrap(),
+        @"SELECT *, true = last_name ~ '^Smith' AS is_smith FROM employees"
+    );
+    assert_display_snapshot!(compile_for(sql::Target::MySql, prql).unwrap(),
+        @"SELECT *, true = (last_name REGEXP '^Smith') AS is_smith FROM employees"
+    );
+
+    // SQL Server has no regular expressions
+    let err = compile_for(sql::Target::MsSql, query)
+        .unwrap_err()
+        .inner
+        .remove(0);
+    assert_eq!(err.code, crate::ErrorCode::Unsupported);
+    assert_eq!(err.reason, "`~=` is not supported by sql.mssql");
+    let span = err.span.unwrap();
+    assert_eq!(&query[span.start..span.end], r#"last_name ~= "^Smith""#);
+
+    // ... and neither has the standard
+    assert!(compile_for(sql::Target::Generic, query).is_err());
+
+    // the operator round-trips through PRQL
+    let pl = crate::prql_to_pl(query).unwrap();
+    assert!(crate::pl_to_prql(pl)
+        .unwrap()
+        .contains(r#"last_name ~= "^Smith""#));
+}
@@ -0,0 +1,78 @@
+//! Durations of compilation phases, for finding out where a slow compile
+//! spends its time.
+//!
+//! Timings are measured only with [crate::sql::Options::timings], so the clock
+//! is not read otherwise. Like deadlines of [crate::Cancellation], they are not
+//! supported on `wasm32-unknown-unknown`, which has no clock, so all durations
+//! stay zero there.
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+#[cfg(not(target_family = "wasm"))]
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+/// Wall-clock durations of the phases of a compilation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompileTimings {
+    /// Parsing PRQL into PL.
+    pub parse: Duration,
+
+    /// Name resolution, type checking and lowering of PL into RQ.
+    pub resolve: Duration,
+
+    /// Preprocessing RQ and splitting it into atomic queries.
+    pub split: Duration,
+
+    /// Translation of each of the atomic queries (the CTEs, followed by the
+    /// main query) into SQL AST.
+    pub atomic_queries: Vec<Duration>,
+
+    /// Rendering SQL AST into a string and formatting it.
+    pub format: Duration,
+
+    /// The whole compilation, including the phases above.
+    pub total: Duration,
+}
+
+impl CompileTimings {
+    /// Duration of the translation of all atomic queries.
+    pub fn translate(&self) -> Duration {
+        self.atomic_queries.iter().sum()
+    }
+}
+
+/// Runs `f` and adds its duration to `duration`, if there is one.
+#[cfg(not(target_family = "wasm"))]
+pub(crate) fn timed<T>(duration: Option<&mut Duration>, f: impl FnOnce() -> T) -> T {
+    let Some(duration) = duration else {
+        return f();
+    };
+
+    let start = Instant::now();
+    let res = f();
+    *duration += start.elapsed();
+    res
+}
+
+/// Runs `f`, leaving `duration` as it is, because there is no clock to read.
+#[cfg(target_family = "wasm")]
+pub(crate) fn timed<T>(_duration: Option<&mut Duration>, f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+impl Display for CompileTimings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "parse {:?}, resolve {:?}, split {:?}, translate {:?} ({} queries), format {:?}, total {:?}",
+            self.parse,
+            self.resolve,
+            self.split,
+            self.translate(),
+            self.atomic_queries.len(),
+            self.format,
+            self.total
+        )
+    }
+}
@@ -69,6 +69,7 @@ impl RqFold for IdLoader {
 pub struct NameGenerator {
     prefix: &'static str,
     id: IdGenerator<usize>,
+    max_len: Option<usize>,
 }
 
 impl NameGenerator {
@@ -76,10 +77,20 @@ impl NameGenerator {
         NameGenerator {
             prefix,
             id: IdGenerator::new(),
+            max_len: None,
         }
     }
 
+    /// Generated names longer than this are truncated, see [super::truncate_ident].
+    pub fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len;
+    }
+
     pub fn gen(&mut self) -> String {
-        format!("{}{}", self.prefix, self.id.gen())
+        let name = format!("{}{}", self.prefix, self.id.gen());
+        match self.max_len {
+            Some(max_len) => super::truncate_ident(name, max_len),
+            None => name,
+        }
     }
 }
@@ -113,3 +113,41 @@ where
             .fold(Some(true), |a, x| a.zip(x).map(|(a, b)| a && b))
     }
 }
+
+/// Line and column of a byte offset within the source, both 0-based. The
+/// column is counted in characters, not bytes.
+pub fn line_col(source: &str, offset: usize) -> Option<(usize, usize)> {
+    let before = source.get(..offset)?;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    let line = before.matches('\n').count();
+    let column = before[line_start..].chars().count();
+    Some((line, column))
+}
+
+/// 64-bit FNV-1a, which is fixed by its spec (unlike
+/// [std::collections::hash_map::DefaultHasher]).
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+/// Shortens an identifier to at most `max_len` bytes, by truncating it and
+/// appending a hash of the whole identifier, so that identifiers with a long
+/// common prefix stay distinct.
+pub fn truncate_ident(ident: String, max_len: usize) -> String {
+    if ident.len() <= max_len {
+        return ident;
+    }
+    let hash = format!("_{:08x}", fnv1a(ident.as_bytes()) as u32);
+
+    let mut prefix_len = max_len.saturating_sub(hash.len());
+    while !ident.is_char_boundary(prefix_len) {
+        prefix_len -= 1;
+    }
+    format!("{}{hash}", &ident[..prefix_len])
+}
@@ -1,4 +1,4 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use itertools::{Itertools, Position};
 
 use crate::ast::pl::Expr;
@@ -28,8 +28,18 @@ where
             // Can't get the debug of the iterator because it's already
             // consumed; is there a way around this? I guess we could show
             // the items after the second, which is kinda weird.
-            Some(Position::First(_)) => Err(anyhow!("Expected only one element, but found more.",)),
-            None => Err(anyhow!("Expected one element, but found none.",)),
+            Some(Position::First(_)) => Err(Error::new(Reason::Expected {
+                who: None,
+                expected: "only one element".to_string(),
+                found: "more".to_string(),
+            })
+            .into()),
+            None => Err(Error::new(Reason::Expected {
+                who: None,
+                expected: "one element".to_string(),
+                found: "none".to_string(),
+            })
+            .into()),
             _ => unreachable!(),
         }
     }
@@ -43,24 +53,17 @@ impl IntoOnlyNode for Vec<Expr> {
     fn into_only_node(mut self, who: &str, occupation: &str) -> Result<Expr, Error> {
         match self.len() {
             1 => Ok(self.remove(0)),
-            0 => Err(Error {
-                reason: Reason::Expected {
-                    who: Some(who.to_string()),
-                    expected: format!("only one {occupation}"),
-                    found: "none".to_string(),
-                },
-                span: None,
-                help: None,
-            }),
-            _ => Err(Error {
-                reason: Reason::Expected {
-                    who: Some(who.to_string()),
-                    expected: format!("only one {occupation}"),
-                    found: "more".to_string(),
-                },
-                span: self[1].span,
-                help: None,
-            }),
+            0 => Err(Error::new(Reason::Expected {
+                who: Some(who.to_string()),
+                expected: format!("only one {occupation}"),
+                found: "none".to_string(),
+            })),
+            _ => Err(Error::new(Reason::Expected {
+                who: Some(who.to_string()),
+                expected: format!("only one {occupation}"),
+                found: "more".to_string(),
+            })
+            .with_span(self[1].span)),
         }
     }
 }
@@ -77,7 +80,12 @@ where
         if self.len() == 1 {
             Ok(self.first().unwrap())
         } else {
-            Err(anyhow!("Expected 1 item, got {}; {:?}", self.len(), self))
+            Err(Error::new(Reason::Expected {
+                who: None,
+                expected: "1 item".to_string(),
+                found: format!("{}; {:?}", self.len(), self),
+            })
+            .into())
         }
     }
 }
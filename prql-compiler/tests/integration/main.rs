@@ -7,6 +7,9 @@ mod tests {
     use std::{fs, path::Path};
 
     use insta::{assert_snapshot, glob};
+    use prql_compiler::ast::pl::Literal;
+    use prql_compiler::ast::rq::{RelationKind, RelationLiteral};
+    use prql_compiler::sql::{Options, Target};
 
     #[test]
     fn test() {
@@ -24,19 +27,52 @@ mod tests {
                 return;
             }
 
-            // compile
-            let sql = prql_compiler::compile(&prql, None).unwrap();
-
-            // save both csv files as same snapshot
-            assert_snapshot!("", sqlite::query_csv(&sqlite_conn, &sql));
-            assert_snapshot!("", duckdb::query_csv(&duckdb_conn, &sql));
+            // compile for the dialect of each of the databases
+            let compile = |target: Target| {
+                let options = Options::default().with_target(target).some();
+                prql_compiler::compile(&prql, options).unwrap()
+            };
+
+            // save all csv files as same snapshot
+            assert_snapshot!(
+                "",
+                sqlite::query_csv(&sqlite_conn, &compile(Target::SQLite))
+            );
+            assert_snapshot!(
+                "",
+                duckdb::query_csv(&duckdb_conn, &compile(Target::DuckDb))
+            );
 
             if let Some(pg_client) = &mut pg_client {
+                let sql = compile(Target::PostgreSql);
                 assert_snapshot!("", postgres::query_csv(pg_client, &sql));
             }
         });
     }
 
+    #[test]
+    fn test_sqlite_date_parts() {
+        // SQLite has no native ISO week, so check the emulation on year boundaries
+        let prql = r#"
+        from invoices
+        take 1
+        select [
+            sunday_of_week_53 = (iso_week s"'2021-01-03'"),
+            monday_of_week_1 = (iso_week s"'2021-01-04'"),
+            monday_of_next_year = (iso_week s"'2024-12-30'"),
+            q = (quarter s"'2021-11-15'"),
+        ]
+        "#;
+        let options = Options::default().with_target(Target::SQLite).some();
+        let sql = prql_compiler::compile(prql, options).unwrap();
+
+        let sqlite_conn = sqlite::connect();
+        assert_eq!(
+            sqlite::query_csv(&sqlite_conn, &sql),
+            "sunday_of_week_53,monday_of_week_1,monday_of_next_year,q\n53,1,1,4"
+        );
+    }
+
     /// Return a path relative to the root `integration` path.
     fn path(relative_path: &str) -> String {
         // Insired by insta's approach to finding a file in a test path.
@@ -55,12 +91,100 @@ mod tests {
         fs::read_to_string(path("data/chinook/schema.sql")).unwrap()
     }
 
+    /// Statements that create a temporary table for each of the relation
+    /// literals in `data/fixtures`, named after its file.
+    fn load_fixtures() -> String {
+        let mut paths: Vec<_> = fs::read_dir(path("data/fixtures"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        paths.sort();
+
+        let mut sql = String::new();
+        for fixture in paths {
+            let name = fixture.file_stem().unwrap().to_str().unwrap();
+            let prql = fs::read_to_string(&fixture).unwrap();
+
+            let pl = prql_compiler::prql_to_pl(&prql).unwrap();
+            let rq = prql_compiler::pl_to_rq(pl).unwrap();
+            let literal = (rq.tables.into_iter())
+                .find_map(|table| match table.relation.kind {
+                    RelationKind::Literal(literal) => Some(literal),
+                    _ => None,
+                })
+                .unwrap_or_else(|| panic!("fixture {name} does not contain a relation literal"));
+
+            let table = create_table(name, &literal)
+                .unwrap_or_else(|e| panic!("cannot load fixture {name}: {e}"));
+            sql += &table;
+        }
+        sql
+    }
+
+    fn create_table(name: &str, literal: &RelationLiteral) -> Result<String, String> {
+        let mut columns = Vec::with_capacity(literal.columns.len());
+        for (index, column) in literal.columns.iter().enumerate() {
+            // type of a column is determined by its first non-null value
+            let value = (literal.rows.iter())
+                .map(|row| &row[index])
+                .find(|value| !matches!(value, Literal::Null));
+            let ty = value.map_or(Ok("VARCHAR"), column_type)?;
+            columns.push(format!("{column} {ty}"));
+        }
+
+        let mut rows = Vec::with_capacity(literal.rows.len());
+        for row in &literal.rows {
+            let values: Vec<_> = row.iter().map(value_to_sql).collect::<Result<_, _>>()?;
+            rows.push(format!("({})", values.join(", ")));
+        }
+
+        let mut sql = format!("CREATE TEMP TABLE {name} ({});\n", columns.join(", "));
+        if !rows.is_empty() {
+            sql += &format!("INSERT INTO {name} VALUES {};\n", rows.join(", "));
+        }
+        Ok(sql)
+    }
+
+    fn column_type(value: &Literal) -> Result<&'static str, String> {
+        Ok(match value {
+            Literal::Null | Literal::String(_) => "VARCHAR",
+            Literal::Integer(_) => "INTEGER",
+            Literal::Float(_) => "DOUBLE",
+            Literal::Boolean(_) => "BOOLEAN",
+            Literal::Date(_) => "DATE",
+            Literal::Time(_) => "TIME",
+            Literal::Timestamp(_) => "TIMESTAMP",
+            Literal::ValueAndUnit(_) | Literal::Relation(_) => {
+                return Err(format!("{value} cannot be stored in a table"))
+            }
+        })
+    }
+
+    fn value_to_sql(value: &Literal) -> Result<String, String> {
+        Ok(match value {
+            Literal::Null => "NULL".to_string(),
+            Literal::Integer(i) => i.to_string(),
+            Literal::Float(f) => f.to_string(),
+            Literal::Boolean(b) => b.to_string().to_uppercase(),
+            // dates and times are cast from strings on insert
+            Literal::String(s) | Literal::Date(s) | Literal::Time(s) | Literal::Timestamp(s) => {
+                format!("'{}'", s.replace('\'', "''"))
+            }
+            Literal::ValueAndUnit(_) | Literal::Relation(_) => {
+                return Err(format!("{value} cannot be stored in a table"))
+            }
+        })
+    }
+
     mod sqlite {
-        use super::path;
+        use super::{load_fixtures, path};
         use rusqlite::{types::ValueRef, Connection};
 
         pub fn connect() -> Connection {
-            Connection::open(path("data/chinook/chinook.db")).unwrap()
+            let conn = Connection::open(path("data/chinook/chinook.db")).unwrap();
+            // temporary, so the database file is not modified
+            conn.execute_batch(&load_fixtures()).unwrap();
+            conn
         }
 
         pub fn query_csv(conn: &Connection, sql: &str) -> String {
@@ -97,8 +221,7 @@ mod tests {
         use chrono::{DateTime, Utc};
         use duckdb::{types::FromSql, types::ValueRef, Connection};
 
-        use super::load_schema;
-        use super::path;
+        use super::{load_fixtures, load_schema, path};
 
         pub fn connect() -> Connection {
             let conn = Connection::open_in_memory().unwrap();
@@ -120,6 +243,7 @@ mod tests {
                 COPY invoice_items FROM '{root}/data/chinook/invoice_items.csv' (AUTO_DETECT TRUE);
             ")
             .as_str()).unwrap();
+            conn.execute_batch(&load_fixtures()).unwrap();
 
             conn
         }
@@ -140,6 +264,7 @@ mod tests {
                             let value = row.get_ref_unwrap(i);
                             match value {
                                 ValueRef::Null => "".to_string(),
+                                ValueRef::Boolean(b) => b.to_string(),
                                 ValueRef::Int(i) => i.to_string(),
                                 ValueRef::TinyInt(i) => i.to_string(),
                                 ValueRef::HugeInt(i) => i.to_string(),
@@ -178,7 +303,9 @@ mod tests {
         pub fn connect() -> Option<Client> {
             let host = std::env::var("POSTGRES_HOST").ok()?;
 
-            let client = Client::connect(&format!("host={} user=postgres", host), NoTls).unwrap();
+            let mut client =
+                Client::connect(&format!("host={} user=postgres", host), NoTls).unwrap();
+            client.batch_execute(&super::load_fixtures()).unwrap();
 
             Some(client)
         }
@@ -92,6 +92,7 @@ impl From<CompileOptions> for prql_compiler::sql::Options {
             format: o.format,
             target: o.target.map(From::from),
             signature_comment: o.signature_comment,
+            ..Default::default()
         }
     }
 }
@@ -87,6 +87,7 @@ impl From<CompileOptions> for prql_compiler::sql::Options {
             format: o.format,
             target: o.target,
             signature_comment: o.signature_comment,
+            ..Default::default()
         }
     }
 }